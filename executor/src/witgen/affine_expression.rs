@@ -7,7 +7,7 @@ use powdr_number::{FieldElement, LargeInt};
 
 use super::global_constraints::RangeConstraintSet;
 use super::range_constraints::RangeConstraint;
-use super::Constraint;
+use super::{to_limbs, Constraint, Endianness};
 use super::{EvalError::*, EvalResult, EvalValue, IncompleteCause};
 
 /// An expression affine in the committed polynomials (or symbolic variables in general).
@@ -341,6 +341,7 @@ where
     /// Tries to assign values to all variables through their bit constraints.
     /// This can also determine if the equation is not satisfiable,
     /// if the bit-constraints do not cover all the bits of the offset.
+    /// Decompositions into limbs are solved directly (see [Self::try_solve_as_limbs]).
     /// Returns an empty vector if it is not able to solve the equation.
     fn try_solve_through_constraints(
         &self,
@@ -360,6 +361,10 @@ where
             )));
         }
 
+        if let Some(assignments) = self.try_solve_as_limbs(&constraints) {
+            return Ok(assignments);
+        }
+
         // Check if they are mutually exclusive and compute assignments.
         let mut covered_bits: <T as FieldElement>::Integer = Zero::zero();
         let mut assignments = EvalValue::complete(vec![]);
@@ -395,6 +400,57 @@ where
             Ok(assignments)
         }
     }
+
+    /// Solves the equation if it decomposes the (negated) offset into limbs of equal width,
+    /// i.e. the coefficients are the limb weights of little- or big-endian limbs in variable
+    /// order and each variable is constrained to the limb width (see [Endianness]).
+    /// Returns None if the equation does not have this form or the limbs do not satisfy the
+    /// range constraints, in which case the general solver determines the cause.
+    fn try_solve_as_limbs(
+        &self,
+        constraints: &[(&K, T, RangeConstraint<T>)],
+    ) -> Option<EvalValue<K, T>> {
+        let count = constraints.len();
+        if count < 2 {
+            return None;
+        }
+        // The coefficient of the second least significant limb determines the limb width.
+        let (endianness, second) = if constraints[0].1.is_one() {
+            (Endianness::Little, constraints[1].1)
+        } else if constraints[count - 1].1.is_one() {
+            (Endianness::Big, constraints[count - 2].1)
+        } else {
+            return None;
+        };
+        let limb_bits = second.to_integer().num_bits() - 1;
+        if limb_bits == 0 || limb_bits * count >= T::BITS as usize {
+            return None;
+        }
+        let limb_mask = *RangeConstraint::<T>::from_max_bit(limb_bits - 1).mask();
+        let is_limb_decomposition =
+            constraints
+                .iter()
+                .enumerate()
+                .all(|(i, (_, coeff, constraint))| {
+                    endianness.limb_weight(i, count, limb_bits) == Some(*coeff)
+                        && (*constraint.mask() & !limb_mask).is_zero()
+                });
+        let value = -self.offset();
+        if !is_limb_decomposition || value.to_integer().num_bits() > limb_bits * count {
+            return None;
+        }
+        let limbs = to_limbs(value, limb_bits, count, endianness);
+        constraints
+            .iter()
+            .zip(limbs)
+            .map(|((i, _, constraint), limb)| {
+                (limb.to_integer() & !*constraint.mask())
+                    .is_zero()
+                    .then_some((**i, Constraint::Assignment(limb)))
+            })
+            .collect::<Option<Vec<_>>>()
+            .map(EvalValue::complete)
+    }
 }
 
 impl<K, T> PartialEq for AffineExpression<K, T>
@@ -600,7 +656,9 @@ mod test {
     use std::collections::BTreeMap;
 
     use super::*;
-    use crate::witgen::{range_constraints::RangeConstraint, EvalError};
+    use crate::witgen::{
+        from_limbs, range_constraints::RangeConstraint, to_limbs, Endianness, EvalError,
+    };
     use powdr_number::{FieldElement, GoldilocksField};
     use pretty_assertions::assert_eq;
     use test_log::test;
@@ -843,6 +901,49 @@ mod test {
         };
     }
 
+    #[test]
+    fn solve_through_constraints_big_endian() {
+        // Decompose into bytes in big-endian order, i.e. the limb with the lowest
+        // variable ID is the most significant one:
+        // 0x12345678 = x1 * 2**24 + x2 * 2**16 + x3 * 2**8 + x4
+        let value: GoldilocksField = 0x12345678u32.into();
+        let expr = (1..=4).fold(AffineExpression::from(value), |expr, i| {
+            expr - AffineExpression::from_variable_id(i)
+                * Endianness::Big.limb_weight(i - 1, 4, 8).unwrap()
+        });
+        let known_constraints: TestRangeConstraints<GoldilocksField> = TestRangeConstraints(
+            (1..=4)
+                .map(|i| (i, RangeConstraint::from_max_bit(7)))
+                .collect(),
+        );
+        let result = expr
+            .solve_with_range_constraints(&known_constraints)
+            .unwrap();
+        assert_eq!(
+            result,
+            EvalValue::complete(vec![
+                (1, Constraint::Assignment(0x12.into())),
+                (2, Constraint::Assignment(0x34.into())),
+                (3, Constraint::Assignment(0x56.into())),
+                (4, Constraint::Assignment(0x78.into()))
+            ])
+        );
+
+        // The solved limbs are the big-endian decomposition of the value and reconstruct it.
+        let limbs = result
+            .constraints
+            .iter()
+            .map(|(_, c)| {
+                let Constraint::Assignment(v) = c else {
+                    unreachable!()
+                };
+                *v
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(limbs, to_limbs(value, 8, 4, Endianness::Big));
+        assert_eq!(from_limbs(&limbs, 8, Endianness::Big), value);
+    }
+
     #[test]
     fn transfer_range_constraints() {
         // x2 * 0x100 + x3 - x1 - 200 = 0,
@@ -0,0 +1,105 @@
+use powdr_ast::analyzed::{
+    AlgebraicBinaryOperation, AlgebraicBinaryOperator, AlgebraicExpression as Expression,
+    AlgebraicUnaryOperation, AlgebraicUnaryOperator, Identity, PolyID,
+};
+use powdr_ast::parsed::visitor::ExpressionVisitable;
+use powdr_number::{BigInt, FieldElement};
+
+use super::rows::RowPair;
+
+/// A bit budget for a group of witness columns: In identities that reference one of the
+/// columns, every intermediate sum or difference, evaluated over the integers instead of
+/// the field, has to be smaller than `2^bits` in absolute value.
+/// Values of columns are interpreted as integers in the range `[0, p)`.
+#[derive(Clone, Debug)]
+pub struct BitBudget {
+    pub columns: Vec<PolyID>,
+    pub bits: usize,
+}
+
+impl BitBudget {
+    pub fn applies_to<T>(&self, identity: &Identity<Expression<T>>) -> bool {
+        identity.expr_any(|expr| match expr {
+            Expression::Reference(poly) => self.columns.contains(&poly.poly_id),
+            _ => false,
+        })
+    }
+
+    /// Checks the intermediate sums of the identity on the given row pair.
+    /// Expressions that reference unknown cells are not checked.
+    pub fn check<T: FieldElement>(
+        &self,
+        identity: &Identity<Expression<T>>,
+        row_pair: &RowPair<'_, '_, T>,
+    ) -> Result<(), String> {
+        identity
+            .left
+            .selector
+            .iter()
+            .chain(&identity.left.expressions)
+            .chain(&identity.right.selector)
+            .chain(&identity.right.expressions)
+            .try_for_each(|expr| self.evaluate(expr, row_pair).map(|_| ()))
+    }
+
+    /// Evaluates the expression over the integers, returning None if it references unknown
+    /// cells and an error if an intermediate sum exceeds the budget.
+    fn evaluate<T: FieldElement>(
+        &self,
+        expr: &Expression<T>,
+        row_pair: &RowPair<'_, '_, T>,
+    ) -> Result<Option<BigInt>, String> {
+        let value = match expr {
+            Expression::BinaryOperation(AlgebraicBinaryOperation { left, op, right }) => {
+                let (Some(left), Some(right)) = (
+                    self.evaluate(left, row_pair)?,
+                    self.evaluate(right, row_pair)?,
+                ) else {
+                    return Ok(None);
+                };
+                match op {
+                    AlgebraicBinaryOperator::Add => self.check_sum(expr, left + right)?,
+                    AlgebraicBinaryOperator::Sub => self.check_sum(expr, left - right)?,
+                    AlgebraicBinaryOperator::Mul => left * right,
+                    AlgebraicBinaryOperator::Pow => {
+                        let exponent = usize::try_from(right)
+                            .map_err(|_| format!("Exponent in {expr} is too large."))?;
+                        left.pow(exponent)
+                    }
+                }
+            }
+            Expression::UnaryOperation(AlgebraicUnaryOperation {
+                op: AlgebraicUnaryOperator::Minus,
+                expr: inner,
+            }) => match self.evaluate(inner, row_pair)? {
+                Some(value) => -value,
+                None => return Ok(None),
+            },
+            _ => match row_pair
+                .evaluate(expr)
+                .ok()
+                .and_then(|v| v.constant_value())
+            {
+                Some(value) => BigInt::from(value.to_arbitrary_integer()),
+                None => return Ok(None),
+            },
+        };
+        Ok(Some(value))
+    }
+
+    fn check_sum<T: FieldElement>(
+        &self,
+        expr: &Expression<T>,
+        value: BigInt,
+    ) -> Result<BigInt, String> {
+        let bound = BigInt::from(1) << self.bits;
+        if value >= bound || value <= -bound.clone() {
+            Err(format!(
+                "The intermediate sum {expr} = {value} exceeds the bit budget of {} bits.",
+                self.bits
+            ))
+        } else {
+            Ok(value)
+        }
+    }
+}
@@ -0,0 +1,16 @@
+/// Fills the blocks of a block machine outside of witness generation, e.g. with optimized
+/// native code for a hash permutation. The arguments are the name of the machine and the
+/// values of the caller's expressions of the connecting identity (None for outputs).
+///
+/// If it returns the values of the block (by column name, one value per row of the block),
+/// they are used instead of solving the identities of the machine. Columns that are not
+/// returned are solved as usual. The block is then validated against the identities, so a
+/// wrong block results in an error. If it returns None, the block is solved as usual.
+pub trait BlockDelegate<T>:
+    Fn(&str, &[Option<T>]) -> Option<Vec<(String, Vec<T>)>> + Send + Sync
+{
+}
+impl<T, F> BlockDelegate<T> for F where
+    F: Fn(&str, &[Option<T>]) -> Option<Vec<(String, Vec<T>)>> + Send + Sync
+{
+}
@@ -0,0 +1,10 @@
+/// Chooses the block index of each call to a block machine, instead of placing the blocks
+/// in the order of the calls. The arguments are the name of the machine, the index of the
+/// call (counting successful calls to that machine) and the values of the caller's
+/// expressions of the connecting identity (None for outputs), which can serve as a key.
+///
+/// Each block index has to be used at most once and the last block of the machine has to
+/// remain free. Blocks are moved as a whole, so machines whose blocks write to rows of the
+/// previous block are not supported.
+pub trait BlockPlacement<T>: Fn(&str, usize, &[Option<T>]) -> usize + Send + Sync {}
+impl<T, F> BlockPlacement<T> for F where F: Fn(&str, usize, &[Option<T>]) -> usize + Send + Sync {}
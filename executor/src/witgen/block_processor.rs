@@ -7,7 +7,8 @@ use powdr_number::FieldElement;
 
 use super::{
     data_structures::finalizable_data::FinalizableData,
-    processor::{OuterQuery, Processor},
+    identity_budget::IdentityBudget,
+    processor::{OuterQuery, Processor, Provenance},
     rows::{RowIndex, UnknownStrategy},
     sequence_iterator::{Action, ProcessingSequenceIterator, SequenceStep},
     EvalError, EvalValue, FixedData, IncompleteCause, MutableState, QueryCallback,
@@ -59,6 +60,30 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> BlockProcessor<'a, 'b, 'c
         Self { processor, ..self }
     }
 
+    pub fn with_identity_budget(self, identity_budget: Option<&'c IdentityBudget>) -> Self {
+        let processor = self.processor.with_identity_budget(identity_budget);
+        Self { processor, ..self }
+    }
+
+    /// Prefixes the log messages of the processor with the name of the given machine.
+    pub fn with_log_scope(self, machine: &'c str) -> Self {
+        let processor = self.processor.with_log_scope(machine);
+        Self { processor, ..self }
+    }
+
+    /// Sets the given cells of the given row, which are then used as inputs by [BlockProcessor::solve].
+    pub fn set_inputs(
+        &mut self,
+        row_index: usize,
+        inputs: &[(PolyID, T)],
+    ) -> Result<(), EvalError<T>> {
+        self.processor
+            .set_cells_if_unset(row_index, inputs, Provenance::Input, || {
+                "block inputs".to_string()
+            })
+            .map(|_| ())
+    }
+
     /// Figures out unknown values.
     /// Returns the assignments to outer query columns.
     pub fn solve(
@@ -69,6 +94,8 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> BlockProcessor<'a, 'b, 'c
 
         while let Some(SequenceStep { row_delta, action }) = sequence_iterator.next() {
             let row_index = (1 + row_delta) as usize;
+            self.processor
+                .set_convergence_iteration(sequence_iterator.round());
             let progress = match action {
                 Action::InternalIdentity(identity_index) => {
                     self.processor
@@ -106,21 +133,37 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> BlockProcessor<'a, 'b, 'c
 
 #[cfg(test)]
 mod tests {
-    use std::collections::BTreeMap;
+    use std::collections::{BTreeMap, HashSet};
+    use std::time::Duration;
 
-    use powdr_ast::analyzed::{PolyID, PolynomialType};
+    use itertools::Itertools;
+    use powdr_ast::analyzed::{
+        AlgebraicExpression as Expression, AlgebraicReference, Identity, IdentityKind, PolyID,
+        PolynomialType,
+    };
     use powdr_number::{FieldElement, GoldilocksField};
     use powdr_pil_analyzer::analyze_string;
 
     use crate::{
         constant_evaluator::generate,
         witgen::{
-            data_structures::finalizable_data::FinalizableData,
+            affine_expression::AffineExpression,
+            column_group::ColumnGroup,
+            data_structures::{
+                copy_constraints::CopyConstraints, finalizable_data::FinalizableData,
+            },
+            global_constraints::set_global_constraints,
             identity_processor::Machines,
             machines::FixedLookup,
-            rows::{Row, RowIndex},
+            processor::{
+                ConflictPolicy, LastRowPolicy, OuterQuery, Processor, ProcessorStats, Provenance,
+            },
+            range_constraints::RangeConstraint,
+            rows::{CellValue, Row, RowIndex, RowPair, UnknownStrategy},
             sequence_iterator::{DefaultSequenceIterator, ProcessingSequenceIterator},
-            unused_query_callback, FixedData, MutableState, QueryCallback,
+            stall::StallCause,
+            unused_query_callback, Constraint, EvalError, FixedData, MutableState,
+            OuterQuerySnapshot, QueryCallback, Scratchpad, SerializedRows, WitgenOptions,
         },
     };
 
@@ -140,6 +183,22 @@ mod tests {
     /// Constructs a processor for a given PIL, then calls a function on it.
     fn do_with_processor<T: FieldElement, Q: QueryCallback<T>, R>(
         src: &str,
+        query_callback: Q,
+        f: impl Fn(BlockProcessor<T, Q>, BTreeMap<String, PolyID>, u64, usize) -> R,
+    ) -> R {
+        do_with_configured_processor(src, |fixed_data| fixed_data, query_callback, f)
+    }
+
+    /// Sets the global range constraints implied by the identities, for tests that rely on them.
+    fn with_global_constraints<T: FieldElement>(fixed_data: FixedData<T>) -> FixedData<T> {
+        let analyzed = fixed_data.analyzed;
+        set_global_constraints(fixed_data, &analyzed.identities).0
+    }
+
+    /// Like [do_with_processor], but configures the fixed data with the given function.
+    fn do_with_configured_processor<T: FieldElement, Q: QueryCallback<T>, R>(
+        src: &str,
+        configure: impl for<'a> FnOnce(FixedData<'a, T>) -> FixedData<'a, T>,
         mut query_callback: Q,
         f: impl Fn(BlockProcessor<T, Q>, BTreeMap<String, PolyID>, u64, usize) -> R,
     ) -> R {
@@ -148,8 +207,13 @@ mod tests {
             .into_iter()
             .map(|(n, c)| (n.to_string(), c))
             .collect::<Vec<_>>();
-        let fixed_data = FixedData::new(&analyzed, &constants, &[], Default::default(), 0);
-
+        let fixed_data = configure(FixedData::new(
+            &analyzed,
+            &constants,
+            &[],
+            Default::default(),
+            0,
+        ));
         // No submachines
         let mut fixed_lookup = FixedLookup::new(fixed_data.global_range_constraints().clone());
         let mut machines = [];
@@ -170,6 +234,8 @@ mod tests {
             fixed_lookup: &mut fixed_lookup,
             machines: Machines::from(machines.iter_mut()),
             query_callback: &mut query_callback,
+            scratchpad: &Scratchpad::default(),
+            tie_breaker: None,
         };
         let row_offset = RowIndex::from_degree(0, fixed_data.degree);
         let identities = analyzed.identities.iter().collect::<Vec<_>>();
@@ -192,6 +258,95 @@ mod tests {
         )
     }
 
+    /// Enables provenance tracking, for tests that inspect it.
+    fn track_provenance<T: FieldElement>(fixed_data: FixedData<T>) -> FixedData<T> {
+        fixed_data.with_options(WitgenOptions {
+            track_provenance: true,
+            ..Default::default()
+        })
+    }
+
+    /// Sets the policy for updates that conflict with the current state of a cell.
+    fn with_conflict_policy<T: FieldElement>(
+        fixed_data: FixedData<T>,
+        conflict_policy: ConflictPolicy,
+    ) -> FixedData<T> {
+        fixed_data.with_options(WitgenOptions {
+            conflict_policy,
+            ..Default::default()
+        })
+    }
+
+    /// Constructs a processor for the witness columns of the `callee` namespace, called
+    /// from a caller row with the given values via all lookups of the PIL, then calls a
+    /// function on it together with the identities that are not lookups.
+    fn do_with_outer_queries<Q: QueryCallback<GoldilocksField>, R>(
+        src: &str,
+        callee: &str,
+        caller_values: &[(&str, u64)],
+        mut query_callback: Q,
+        f: impl for<'a, 'b, 'c> FnOnce(
+            Processor<'a, 'b, 'c, GoldilocksField, Q>,
+            Vec<&'a Identity<Expression<GoldilocksField>>>,
+        ) -> R,
+    ) -> R {
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let constants = generate(&analyzed);
+        let fixed_data = FixedData::new(&analyzed, &constants, &[], Default::default(), 0);
+        let row_offset = RowIndex::from_degree(0, fixed_data.degree);
+
+        let mut caller_row = Row::fresh(&fixed_data, row_offset);
+        for (name, value) in caller_values {
+            caller_row[&fixed_data.try_column_by_name(name).unwrap()].value =
+                CellValue::Known((*value).into());
+        }
+        let caller_next_row = Row::fresh(&fixed_data, row_offset + 1);
+        let caller_rows = RowPair::new(
+            &caller_row,
+            &caller_next_row,
+            row_offset,
+            &fixed_data,
+            UnknownStrategy::Unknown,
+        );
+        let (connecting_identities, identities): (Vec<_>, Vec<_>) = analyzed
+            .identities
+            .iter()
+            .partition(|identity| identity.kind == IdentityKind::Plookup);
+
+        let mut fixed_lookup = FixedLookup::new(fixed_data.global_range_constraints().clone());
+        let mut mutable_state = MutableState {
+            fixed_lookup: &mut fixed_lookup,
+            machines: Machines::from([].iter_mut()),
+            query_callback: &mut query_callback,
+            scratchpad: &Scratchpad::default(),
+            tie_breaker: None,
+        };
+        let witnesses = fixed_data
+            .witness_cols
+            .iter()
+            .filter(|(_, column)| column.poly.name.starts_with(&format!("{callee}.")))
+            .map(|(poly_id, _)| poly_id)
+            .collect();
+        let data = FinalizableData::with_initial_rows_in_progress(
+            &witnesses,
+            (0..fixed_data.degree).map(|i| Row::fresh(&fixed_data, row_offset + i)),
+        );
+        let processor = Processor::new(
+            row_offset,
+            data,
+            &mut mutable_state,
+            &fixed_data,
+            &witnesses,
+        )
+        .with_outer_queries(
+            connecting_identities
+                .iter()
+                .map(|identity| OuterQuery::new(&caller_rows, identity))
+                .collect(),
+        );
+        f(processor, identities)
+    }
+
     fn solve_and_assert<T: FieldElement>(src: &str, asserted_values: &[(usize, &str, u64)]) {
         do_with_processor(
             src,
@@ -236,4 +391,1924 @@ mod tests {
 
         solve_and_assert::<GoldilocksField>(src, &[(7, "Fibonacci.y", 34)]);
     }
+
+    #[test]
+    fn partial_progress_stats() {
+        let src = r#"
+            constant %N = 4;
+
+            namespace Main(%N);
+                col witness x, y;
+                x = y;
+        "#;
+
+        do_with_processor(
+            src,
+            unused_query_callback(),
+            |mut processor: BlockProcessor<GoldilocksField, _>, poly_ids, _, _| {
+                let y = poly_ids["Main.y"];
+                let identity = processor.identities[0];
+                let mut row = processor.processor.row(1).clone();
+                row[&y].value = CellValue::RangeConstraint(RangeConstraint::from_max_bit(7));
+                processor.processor.set_row(1, row);
+
+                // Only the range constraint of `y` can be transferred to `x`.
+                let result = processor
+                    .processor
+                    .process_identity(1, identity, UnknownStrategy::Unknown)
+                    .unwrap();
+                assert!(result.progress && !result.is_complete);
+
+                // Once `y` is known, the identity can be completed.
+                let mut row = processor.processor.row(1).clone();
+                row[&y].value = CellValue::Known(3.into());
+                processor.processor.set_row(1, row);
+                let result = processor
+                    .processor
+                    .process_identity(1, identity, UnknownStrategy::Unknown)
+                    .unwrap();
+                assert!(result.progress && result.is_complete);
+
+                let partial_progress = &processor.processor.stats().partial_progress;
+                assert_eq!(partial_progress.len(), 1);
+                assert_eq!(partial_progress[&identity.id], 1);
+            },
+        );
+    }
+
+    #[test]
+    fn evaluation_cache_stats() {
+        let src = r#"
+            constant %N = 4;
+
+            namespace Main(%N);
+                col witness x, y, z;
+                x = y + z;
+                y = 2 * z;
+        "#;
+
+        do_with_processor(
+            src,
+            unused_query_callback(),
+            |mut processor: BlockProcessor<GoldilocksField, _>, poly_ids, _, _| {
+                let process_all = |processor: &mut BlockProcessor<GoldilocksField, _>| {
+                    let identities = processor.identities;
+                    identities
+                        .iter()
+                        .map(|identity| {
+                            processor
+                                .processor
+                                .process_identity(1, identity, UnknownStrategy::Unknown)
+                                .unwrap()
+                                .progress
+                        })
+                        .collect::<Vec<_>>()
+                };
+
+                // Neither identity can make progress, so retrying them is not necessary.
+                assert_eq!(process_all(&mut processor), vec![false, false]);
+                assert_eq!(process_all(&mut processor), vec![false, false]);
+                let stats = processor.processor.stats();
+                assert_eq!(
+                    (stats.evaluation_cache_hits, stats.evaluation_cache_misses),
+                    (2, 2)
+                );
+
+                // Once `z` is known, both identities have to be evaluated again.
+                let mut row = processor.processor.row(1).clone();
+                row[&poly_ids["Main.z"]].value = CellValue::Known(3.into());
+                processor.processor.set_row(1, row);
+                assert_eq!(process_all(&mut processor), vec![false, true]);
+                assert_eq!(process_all(&mut processor), vec![true, false]);
+                let stats = processor.processor.stats();
+                assert_eq!(
+                    (stats.evaluation_cache_hits, stats.evaluation_cache_misses),
+                    (2, 6)
+                );
+                assert_eq!(
+                    processor.processor.row(1)[&poly_ids["Main.x"]].value,
+                    CellValue::Known(9.into())
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn provenance() {
+        let src = r#"
+            constant %N = 8;
+
+            namespace Fibonacci(%N);
+                col fixed ISFIRST = [1] + [0]*;
+                col fixed ISLAST = [0]* + [1];
+                col witness x, y;
+
+                ISFIRST * (y - 1) = 0;
+                ISFIRST * (x - 1) = 0;
+
+                (1-ISLAST) * (x' - y) = 0;
+                (1-ISLAST) * (y' - (x + y)) = 0;
+        "#;
+
+        do_with_configured_processor(
+            src,
+            track_provenance,
+            unused_query_callback(),
+            |mut processor: BlockProcessor<GoldilocksField, _>,
+             poly_ids,
+             degree,
+             num_identities| {
+                let mut sequence_iterator = ProcessingSequenceIterator::Default(
+                    DefaultSequenceIterator::new(degree as usize - 2, num_identities, None),
+                );
+                processor.solve(&mut sequence_iterator).unwrap();
+
+                let x = poly_ids["Fibonacci.x"];
+                let y = poly_ids["Fibonacci.y"];
+                let identities = processor.identities;
+                let processor = &processor.processor;
+                assert_eq!(
+                    processor.provenance(0, &y),
+                    Some(Provenance::Identity(identities[0].id))
+                );
+                // x in row 1 is copied from y in row 0 via a next reference.
+                assert_eq!(
+                    processor.provenance(1, &x),
+                    Some(Provenance::Identity(identities[2].id))
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn cell_dependencies() {
+        let src = r#"
+            namespace Chain(4);
+                col witness a, b, c;
+
+                a = 5;
+                b = a + 1;
+                c = 2 * b;
+        "#;
+
+        do_with_configured_processor(
+            src,
+            track_provenance,
+            unused_query_callback(),
+            |mut processor: BlockProcessor<GoldilocksField, _>,
+             poly_ids,
+             degree,
+             num_identities| {
+                let mut sequence_iterator = ProcessingSequenceIterator::Default(
+                    DefaultSequenceIterator::new(degree as usize - 2, num_identities, None),
+                );
+                processor.solve(&mut sequence_iterator).unwrap();
+
+                let a = poly_ids["Chain.a"];
+                let b = poly_ids["Chain.b"];
+                let c = poly_ids["Chain.c"];
+                let row = RowIndex::from_degree(1, degree);
+                let processor = &processor.processor;
+                assert_eq!(processor.cell_dependencies(1, &a), HashSet::new());
+                assert_eq!(
+                    processor.cell_dependencies(1, &b),
+                    HashSet::from([(a, row)])
+                );
+                assert_eq!(
+                    processor.cell_dependencies(1, &c),
+                    HashSet::from([(a, row), (b, row)])
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn minimal_inputs() {
+        let src = r#"
+            namespace Chain(4);
+                col witness a, b, c, d, e;
+
+                c = a + 1;
+                d = 2 * c;
+                e = b + 5;
+        "#;
+
+        do_with_configured_processor(
+            src,
+            track_provenance,
+            unused_query_callback(),
+            |mut processor: BlockProcessor<GoldilocksField, _>,
+             poly_ids,
+             degree,
+             num_identities| {
+                let a = poly_ids["Chain.a"];
+                let b = poly_ids["Chain.b"];
+                let c = poly_ids["Chain.c"];
+                let d = poly_ids["Chain.d"];
+                let e = poly_ids["Chain.e"];
+                processor
+                    .set_inputs(1, &[(a, 3.into()), (b, 4.into())])
+                    .unwrap();
+                let mut sequence_iterator = ProcessingSequenceIterator::Default(
+                    DefaultSequenceIterator::new(degree as usize - 2, num_identities, None),
+                );
+                processor.solve(&mut sequence_iterator).unwrap();
+
+                let row = RowIndex::from_degree(1, degree);
+                // `b` is an input, but not needed to determine `d`.
+                assert_eq!(
+                    processor.processor.minimal_inputs_for(1, &d),
+                    vec![(a, row)]
+                );
+                assert_eq!(
+                    processor.processor.minimal_inputs_for(1, &c),
+                    vec![(a, row)]
+                );
+                assert_eq!(
+                    processor.processor.minimal_inputs_for(1, &e),
+                    vec![(b, row)]
+                );
+
+                // The inputs are reported when an identity fails.
+                let identities = processor.identities.to_vec();
+                let mut row = processor.processor.row(1).clone();
+                row[&c].value = CellValue::Known(10.into());
+                processor.processor.set_row(1, row);
+                let Err(EvalError::IdentityFailed(failure)) = processor.processor.process_identity(
+                    1,
+                    identities[1],
+                    UnknownStrategy::Unknown,
+                ) else {
+                    panic!("Expected an identity failure");
+                };
+                let provenance = failure.provenance.unwrap();
+                assert!(
+                    provenance.contains(&format!(
+                        "Chain.d: Identity({}) (derived from 2 cells, inputs: Chain.a (row 1))",
+                        identities[1].id
+                    )),
+                    "{provenance}"
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn enumerate_assignments() {
+        let src = r#"
+            namespace Bits(4);
+                col witness a, b, c;
+
+                a * (1 - a) = 0;
+                b * (1 - b) = 0;
+                c = a + 2 * b;
+        "#;
+
+        do_with_configured_processor(
+            src,
+            with_global_constraints,
+            unused_query_callback(),
+            |mut processor: BlockProcessor<GoldilocksField, _>, poly_ids, _, _| {
+                let identities = processor.identities;
+                let processor = &mut processor.processor;
+                let assignments = processor.enumerate_assignments(0, identities, 10);
+
+                let (a, b, c) = (poly_ids["Bits.a"], poly_ids["Bits.b"], poly_ids["Bits.c"]);
+                let expected = [(0, 0), (0, 1), (1, 0), (1, 1)]
+                    .into_iter()
+                    .map(|(a_value, b_value)| {
+                        vec![
+                            (a, a_value.into()),
+                            (b, b_value.into()),
+                            (c, (a_value + 2 * b_value).into()),
+                        ]
+                    })
+                    .collect::<Vec<_>>();
+                assert_eq!(assignments, expected);
+                // The row is restored.
+                assert!(!processor.row(0)[&a].value.is_known());
+
+                assert_eq!(processor.enumerate_assignments(0, identities, 3).len(), 3);
+            },
+        );
+    }
+
+    #[test]
+    fn contradicting_range_constraints() {
+        let src = r#"
+            namespace Bit(4);
+                col witness x;
+
+                x * (1 - x) = 0;
+                x = 5;
+        "#;
+
+        do_with_configured_processor(
+            src,
+            with_global_constraints,
+            unused_query_callback(),
+            |mut processor: BlockProcessor<GoldilocksField, _>, _, degree, num_identities| {
+                let mut sequence_iterator = ProcessingSequenceIterator::Default(
+                    DefaultSequenceIterator::new(degree as usize - 2, num_identities, None),
+                );
+                let err = processor.solve(&mut sequence_iterator).unwrap_err();
+                let message = err.to_string();
+                assert!(message.contains("Contradicting range constraints for Bit.x"));
+                assert!(message.contains("Bit.x = 5 (from Bit.x = 5;)"));
+                assert!(message.contains("incompatible with the range constraint [0, 1]"));
+            },
+        );
+    }
+
+    #[test]
+    fn row_affine_system() {
+        let src = r#"
+            namespace Affine(4);
+                col fixed SEVEN = [7]*;
+                col witness a, b, c;
+
+                a + 2 * b = SEVEN;
+                c = 3 * b + a;
+        "#;
+
+        do_with_processor(
+            src,
+            unused_query_callback(),
+            |mut processor: BlockProcessor<GoldilocksField, _>, poly_ids, _, _| {
+                let identities = processor.identities;
+                let processor = &mut processor.processor;
+                // Renders the expressions with small signed coefficients.
+                let render =
+                    |system: Vec<AffineExpression<&AlgebraicReference, GoldilocksField>>| {
+                        system
+                            .iter()
+                            .map(|expr| {
+                                expr.nonzero_coefficients()
+                                    .map(|(poly, c)| {
+                                        format!("{} * {}", c.try_into_i32().unwrap(), poly)
+                                    })
+                                    .chain([expr.offset().try_into_i32().unwrap().to_string()])
+                                    .collect::<Vec<_>>()
+                                    .join(" + ")
+                            })
+                            .collect::<Vec<_>>()
+                    };
+                assert_eq!(
+                    render(processor.row_affine_system(0, identities)),
+                    vec![
+                        "1 * Affine.a + 2 * Affine.b + -7",
+                        "-1 * Affine.a + -3 * Affine.b + 1 * Affine.c + 0"
+                    ]
+                );
+
+                // The processor needs an expression that outlives it.
+                let b = Box::leak(Box::new(Expression::Reference(AlgebraicReference {
+                    name: "Affine.b".to_string(),
+                    poly_id: poly_ids["Affine.b"],
+                    next: false,
+                })));
+                processor
+                    .set_value(0, b, 1.into(), Provenance::Machine, || "b".to_string())
+                    .unwrap();
+                assert_eq!(
+                    render(processor.row_affine_system(0, identities)),
+                    vec!["1 * Affine.a + -5", "-1 * Affine.a + 1 * Affine.c + -3"]
+                );
+            },
+        );
+    }
+
+    /// Processes `y = x' + 1` on the last row, with `x = 5` in the row after it.
+    fn process_last_row(last_row_policy: LastRowPolicy) -> Result<Option<u64>, String> {
+        let src = r#"
+            namespace Last(4);
+                col witness x, y;
+                y = x' + 1;
+        "#;
+        do_with_configured_processor(
+            src,
+            |fixed_data| {
+                fixed_data.with_options(WitgenOptions {
+                    last_row_policy,
+                    ..Default::default()
+                })
+            },
+            unused_query_callback(),
+            |mut processor: BlockProcessor<GoldilocksField, _>, poly_ids, degree, _| {
+                let identity = processor.identities[0];
+                let processor = &mut processor.processor;
+                let mut row = processor.row(0).clone();
+                row[&poly_ids["Last.x"]].value = CellValue::Known(5.into());
+                processor.set_row(degree as usize, row);
+
+                let last_row = degree as usize - 1;
+                processor
+                    .process_identity(last_row, identity, UnknownStrategy::Unknown)
+                    .map_err(|e| e.to_string())?;
+                Ok(Option::<GoldilocksField>::from(
+                    processor.row(last_row)[&poly_ids["Last.y"]].value.clone(),
+                )
+                .map(|y| y.to_degree()))
+            },
+        )
+    }
+
+    #[test]
+    fn last_row_policy_cyclic() {
+        assert_eq!(process_last_row(LastRowPolicy::Cyclic), Ok(Some(6)));
+    }
+
+    #[test]
+    fn last_row_policy_zero() {
+        assert_eq!(process_last_row(LastRowPolicy::Zero), Ok(Some(1)));
+    }
+
+    #[test]
+    fn last_row_policy_error() {
+        let error = process_last_row(LastRowPolicy::Error).unwrap_err();
+        assert!(error.contains("not allowed on the last row (3)"), "{error}");
+    }
+
+    fn stall_cause(src: &str) -> Option<StallCause> {
+        do_with_processor(
+            src,
+            unused_query_callback(),
+            |processor: BlockProcessor<GoldilocksField, _>, _, _, _| {
+                processor.processor.stall_cause(0, processor.identities)
+            },
+        )
+    }
+
+    #[test]
+    fn stall_causes() {
+        let cycle = stall_cause(
+            r#"
+            namespace Cycle(4);
+                col witness x, y;
+                x + y = 1;
+                x - y = 3;
+        "#,
+        )
+        .unwrap();
+        assert_eq!(
+            cycle,
+            StallCause::Cycle {
+                identities: vec![0, 1],
+                cells: vec!["Cycle.x".to_string(), "Cycle.y".to_string()]
+            }
+        );
+        assert_eq!(
+            cycle.to_string(),
+            "Identities wait on each other: identity 0 -> Cycle.x -> identity 1 -> Cycle.y -> identity 0"
+        );
+
+        let missing_inputs = stall_cause(
+            r#"
+            namespace Missing(4);
+                col witness x, y, z;
+                x = y + z;
+        "#,
+        );
+        assert_eq!(
+            missing_inputs,
+            Some(StallCause::MissingInputs {
+                cells: vec![
+                    "Missing.x".to_string(),
+                    "Missing.y".to_string(),
+                    "Missing.z".to_string()
+                ]
+            })
+        );
+    }
+
+    /// Applies the assignments to `Bit.x` in row 0 as a single sequence of updates and returns
+    /// the result, as well as the final value of the cell.
+    fn assign_bit(
+        conflict_policy: ConflictPolicy,
+        values: &[u64],
+    ) -> (Result<bool, String>, Option<GoldilocksField>) {
+        let src = r#"
+            namespace Bit(4);
+                col witness x;
+                x * (1 - x) = 0;
+        "#;
+        do_with_configured_processor(
+            src,
+            |fixed_data| with_conflict_policy(with_global_constraints(fixed_data), conflict_policy),
+            unused_query_callback(),
+            |mut processor: BlockProcessor<GoldilocksField, _>, poly_ids, _, _| {
+                let x = poly_ids["Bit.x"];
+                let processor = &mut processor.processor;
+                let values = values
+                    .iter()
+                    .map(|value| (x, (*value).into()))
+                    .collect::<Vec<_>>();
+                let result = processor
+                    .set_cells_if_unset(0, &values, Provenance::Machine, || "test".to_string())
+                    .map_err(|e| e.to_string());
+                (result, processor.row(0)[&x].value.clone().into())
+            },
+        )
+    }
+
+    #[test]
+    fn conflict_policy_strict() {
+        let (result, value) = assign_bit(ConflictPolicy::Strict, &[1]);
+        assert_eq!(result, Ok(true));
+        assert_eq!(value, Some(1.into()));
+
+        let (result, _) = assign_bit(ConflictPolicy::Strict, &[1, 1]);
+        assert!(result
+            .unwrap_err()
+            .contains("but the value was already set to 1"));
+
+        let (result, value) = assign_bit(ConflictPolicy::Strict, &[5]);
+        assert!(result
+            .unwrap_err()
+            .contains("Contradicting range constraints for Bit.x"));
+        assert_eq!(value, None);
+    }
+
+    #[test]
+    fn conflict_policy_allow_equal() {
+        let (result, value) = assign_bit(ConflictPolicy::AllowEqual, &[1, 1]);
+        assert_eq!(result, Ok(true));
+        assert_eq!(value, Some(1.into()));
+
+        let (result, _) = assign_bit(ConflictPolicy::AllowEqual, &[1, 0]);
+        assert!(result
+            .unwrap_err()
+            .contains("but the value was already set to 1"));
+
+        let (result, _) = assign_bit(ConflictPolicy::AllowEqual, &[5]);
+        assert!(result
+            .unwrap_err()
+            .contains("Contradicting range constraints for Bit.x"));
+    }
+
+    #[test]
+    fn conflict_policy_override_range_with_value() {
+        let (result, value) = assign_bit(ConflictPolicy::OverrideRangeWithValue, &[5]);
+        assert_eq!(result, Ok(true));
+        assert_eq!(value, Some(5.into()));
+
+        let (result, _) = assign_bit(ConflictPolicy::OverrideRangeWithValue, &[5, 5]);
+        assert!(result
+            .unwrap_err()
+            .contains("but the value was already set to 5"));
+    }
+
+    #[test]
+    fn eager_range_checks() {
+        let src = r#"
+            namespace Bit(4);
+                col witness x;
+                x * (1 - x) = 0;
+        "#;
+        do_with_configured_processor(
+            src,
+            |fixed_data| {
+                with_global_constraints(fixed_data).with_options(WitgenOptions {
+                    conflict_policy: ConflictPolicy::OverrideRangeWithValue,
+                    eager_range_checks: true,
+                    ..Default::default()
+                })
+            },
+            unused_query_callback(),
+            |mut processor: BlockProcessor<GoldilocksField, _>, poly_ids, _, _| {
+                let x = poly_ids["Bit.x"];
+                let processor = &mut processor.processor;
+                let result = processor
+                    .set_cells_if_unset(0, &[(x, 5.into())], Provenance::Machine, || {
+                        "test".to_string()
+                    })
+                    .unwrap_err()
+                    .to_string();
+                assert!(result.starts_with("Range check failed for Bit.x (Row 0):\n    Bit.x = 5"));
+                let value: Option<GoldilocksField> = processor.row(0)[&x].value.clone().into();
+                assert_eq!(value, None);
+            },
+        )
+    }
+
+    #[test]
+    fn finalize_with_copy_constraints() {
+        let src = r#"
+            namespace Copy(8);
+                col fixed FIRST = [1] + [0]*;
+                col witness x, y, z;
+                FIRST * (x - 1) = 0;
+                y = x + 1;
+        "#;
+        do_with_configured_processor(
+            src,
+            |fixed_data| {
+                // y is copied to x in the next row, z is a copy of y in the next row.
+                let cell = |name, row| {
+                    (
+                        fixed_data.try_column_by_name(name).unwrap(),
+                        RowIndex::from_degree(row, fixed_data.degree),
+                    )
+                };
+                let copy_constraints = CopyConstraints::new(
+                    &(0..6)
+                        .flat_map(|row| {
+                            [
+                                (cell("Copy.y", row), cell("Copy.x", row + 1)),
+                                (cell("Copy.z", row), cell("Copy.y", row + 1)),
+                            ]
+                        })
+                        .collect::<Vec<_>>(),
+                );
+                fixed_data.with_copy_constraints(copy_constraints)
+            },
+            unused_query_callback(),
+            |mut processor: BlockProcessor<GoldilocksField, _>, poly_ids, _, _| {
+                for row_index in 0..7 {
+                    for identity in processor.identities {
+                        processor
+                            .processor
+                            .process_identity(row_index, identity, UnknownStrategy::Unknown)
+                            .unwrap();
+                    }
+                    // The row is only finalized in the next iteration, once z is known.
+                    // Otherwise, propagating the value of y in the next row would fail.
+                    processor.processor.finalize_range(row_index..row_index + 1);
+                }
+
+                let mut data = processor.finish();
+                assert_eq!(data.rows_in_progress(), 1);
+                let columns = data.take_transposed().collect::<BTreeMap<_, _>>();
+                let values = |name: &str, rows: usize| {
+                    columns[&poly_ids[name]].0[..rows]
+                        .iter()
+                        .map(|v| v.to_degree())
+                        .collect::<Vec<_>>()
+                };
+                assert_eq!(values("Copy.x", 7), (1..=7).collect::<Vec<_>>());
+                assert_eq!(values("Copy.y", 7), (2..=8).collect::<Vec<_>>());
+                assert_eq!(values("Copy.z", 6), (3..=8).collect::<Vec<_>>());
+            },
+        );
+    }
+
+    #[test]
+    fn propagate_range_constraints_along_copy_constraints() {
+        let src = r#"
+            namespace Copy(4);
+                col fixed FOUR = [4]*;
+                col witness x, y, z;
+                x * (1 - x) = 0;
+                y = FOUR * x;
+        "#;
+        do_with_configured_processor(
+            src,
+            |fixed_data| {
+                // y is copied to z in the next row, which has no range constraint of its own.
+                let cell = |name, row| {
+                    (
+                        fixed_data.try_column_by_name(name).unwrap(),
+                        RowIndex::from_degree(row, fixed_data.degree),
+                    )
+                };
+                let copy_constraints =
+                    CopyConstraints::new(&[(cell("Copy.y", 0), cell("Copy.z", 1))]);
+                with_global_constraints(fixed_data).with_copy_constraints(copy_constraints)
+            },
+            unused_query_callback(),
+            |mut processor: BlockProcessor<GoldilocksField, _>, poly_ids, _, _| {
+                let (y, z) = (poly_ids["Copy.y"], poly_ids["Copy.z"]);
+                assert_eq!(processor.processor.row(0)[&y].value, CellValue::Unknown);
+                assert_eq!(processor.processor.row(1)[&z].value, CellValue::Unknown);
+                // Processing the identities twice does not change anything anymore.
+                for _ in 0..2 {
+                    for identity in processor.identities {
+                        processor
+                            .processor
+                            .process_identity(0, identity, UnknownStrategy::Unknown)
+                            .unwrap();
+                    }
+                }
+                assert_eq!(
+                    processor.processor.row(1)[&z].value,
+                    CellValue::RangeConstraint(RangeConstraint::from_mask(4u32))
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn copy_constraints_to_fixed_columns() {
+        let run = |value: u64| {
+            let src = format!(
+                r#"
+            namespace Copy(4);
+                col fixed FOUR = [4]*;
+                col witness x;
+                x = {value};
+        "#
+            );
+            do_with_configured_processor(
+                &src,
+                |fixed_data| {
+                    let cell = |name, row| {
+                        (
+                            fixed_data.try_column_by_name(name).unwrap(),
+                            RowIndex::from_degree(row, fixed_data.degree),
+                        )
+                    };
+                    let copy_constraints =
+                        CopyConstraints::new(&[(cell("Copy.x", 0), cell("Copy.FOUR", 1))]);
+                    fixed_data.with_copy_constraints(copy_constraints)
+                },
+                unused_query_callback(),
+                |mut processor: BlockProcessor<GoldilocksField, _>, _, _, _| {
+                    processor
+                        .processor
+                        .process_identity(0, processor.identities[0], UnknownStrategy::Unknown)
+                        .map(|_| ())
+                        .map_err(|e| e.to_string())
+                },
+            )
+        };
+        assert_eq!(run(4), Ok(()));
+        let error = run(5).unwrap_err();
+        assert!(
+            error.contains("Copy constraint violated: Copy.FOUR (Row 1) is fixed to 4"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn conflicting_copy_assignment() {
+        let src = r#"
+            namespace Copy(4);
+                col witness x, y;
+                x = 4;
+        "#;
+        do_with_configured_processor(
+            src,
+            |fixed_data| {
+                let cell = |name, row| {
+                    (
+                        fixed_data.try_column_by_name(name).unwrap(),
+                        RowIndex::from_degree(row, fixed_data.degree),
+                    )
+                };
+                let copy_constraints =
+                    CopyConstraints::new(&[(cell("Copy.x", 0), cell("Copy.y", 1))]);
+                fixed_data.with_copy_constraints(copy_constraints)
+            },
+            unused_query_callback(),
+            |mut processor: BlockProcessor<GoldilocksField, _>, poly_ids, _, _| {
+                // `y` is already known in row 1, but to a different value than `x` in row 0.
+                let mut row = processor.processor.row(1).clone();
+                row[&poly_ids["Copy.y"]].value = CellValue::Known(7.into());
+                processor.processor.set_row(1, row);
+
+                let error = processor
+                    .processor
+                    .process_identity(0, processor.identities[0], UnknownStrategy::Unknown)
+                    .err()
+                    .unwrap()
+                    .to_string();
+                assert!(
+                    error.contains(
+                        "Could not set Copy.y (Row 1) to 4: Linear constraint is not satisfiable"
+                    ),
+                    "{error}"
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn conflicting_copy_class() {
+        let src = r#"
+            namespace Copy(4);
+                col witness x, y, z;
+                x = 4;
+        "#;
+        do_with_configured_processor(
+            src,
+            |fixed_data| {
+                let cell = |name, row| {
+                    (
+                        fixed_data.try_column_by_name(name).unwrap(),
+                        RowIndex::from_degree(row, fixed_data.degree),
+                    )
+                };
+                let copy_constraints = CopyConstraints::new(&[
+                    (cell("Copy.x", 0), cell("Copy.y", 1)),
+                    (cell("Copy.y", 1), cell("Copy.z", 2)),
+                ]);
+                fixed_data.with_copy_constraints(copy_constraints)
+            },
+            unused_query_callback(),
+            |mut processor: BlockProcessor<GoldilocksField, _>, poly_ids, _, _| {
+                // `z` is already known in row 2, but to a different value than `x` in row 0.
+                let mut row = processor.processor.row(2).clone();
+                row[&poly_ids["Copy.z"]].value = CellValue::Known(7.into());
+                processor.processor.set_row(2, row);
+
+                let Err(error) = processor.processor.process_identity(
+                    0,
+                    processor.identities[0],
+                    UnknownStrategy::Unknown,
+                ) else {
+                    panic!("Expected the identity to fail");
+                };
+                let EvalError::CopyConstraintConflict {
+                    value,
+                    class,
+                    cause,
+                } = &error
+                else {
+                    panic!("Expected a copy constraint conflict: {error}");
+                };
+                assert_eq!(*value, 4.into());
+                let members = class
+                    .iter()
+                    .map(|(column, row, _)| (column.as_str(), *row))
+                    .sorted()
+                    .collect::<Vec<_>>();
+                assert_eq!(members, [("Copy.x", 0), ("Copy.y", 1), ("Copy.z", 2)]);
+                let conflicting = class
+                    .iter()
+                    .find(|(column, _, _)| column == "Copy.z")
+                    .unwrap();
+                assert_eq!(conflicting.2, Some(7.into()));
+                assert!(matches!(**cause, EvalError::SetValueFailed { .. }));
+                let error = error.to_string();
+                assert!(error.contains("    Copy.z (Row 2) = 7\n"), "{error}");
+            },
+        );
+    }
+
+    #[test]
+    fn debug_snapshot() {
+        let src = r#"
+            namespace Main(4);
+                col witness x, y;
+                x = 3;
+                y = x + 1;
+        "#;
+        do_with_processor(
+            src,
+            unused_query_callback(),
+            |mut processor: BlockProcessor<GoldilocksField, _>, _, _, _| {
+                let identities = processor.identities.to_vec();
+                processor
+                    .processor
+                    .process_identity(1, identities[0], UnknownStrategy::Unknown)
+                    .unwrap();
+                let snapshot = processor.processor.debug_snapshot();
+                assert_eq!(snapshot.len(), 4);
+                assert_eq!(snapshot.value("Main.x", 1), Some(3.into()));
+                assert!(!snapshot.is_known("Main.y", 1));
+                assert_eq!(snapshot.known_cells()[1], vec!["Main.x"]);
+                assert!(snapshot.outer_queries.is_empty());
+                assert!(snapshot.inputs.is_empty());
+
+                // Later progress does not change the snapshot.
+                processor
+                    .processor
+                    .process_identity(1, identities[1], UnknownStrategy::Unknown)
+                    .unwrap();
+                assert!(!snapshot.is_known("Main.y", 1));
+                let snapshot = processor.processor.debug_snapshot();
+                assert_eq!(snapshot.value("Main.y", 1), Some(4.into()));
+            },
+        );
+    }
+
+    #[test]
+    fn witness_snapshot_round_trip() {
+        let src = r#"
+            namespace Main(4);
+                col witness x, y;
+                x = 3;
+                y = x + 1;
+        "#;
+        do_with_processor(
+            src,
+            unused_query_callback(),
+            |mut processor: BlockProcessor<GoldilocksField, _>, poly_ids, _, _| {
+                let identities = processor.identities.to_vec();
+                processor
+                    .processor
+                    .process_identity(1, identities[0], UnknownStrategy::Unknown)
+                    .unwrap();
+                let mut row = processor.processor.row(2).clone();
+                row[&poly_ids["Main.y"]].value =
+                    CellValue::RangeConstraint(RangeConstraint::from_range(2.into(), 9.into()));
+                processor.processor.set_row(2, row);
+                processor.processor.finalize_range(0..1);
+
+                let snapshot = processor.processor.witness_snapshot();
+                let state = processor.processor.debug_snapshot();
+                processor
+                    .processor
+                    .process_identity(1, identities[1], UnknownStrategy::Unknown)
+                    .unwrap();
+                assert_ne!(processor.processor.debug_snapshot(), state);
+
+                let restored = SerializedRows::from_bytes(snapshot.as_bytes().to_vec());
+                processor
+                    .processor
+                    .restore_from_snapshot(&restored)
+                    .unwrap();
+                assert_eq!(processor.processor.debug_snapshot(), state);
+                assert_eq!(processor.processor.witness_snapshot(), snapshot);
+                let cells = |row: usize| {
+                    let row = processor.processor.row(row);
+                    (
+                        row[&poly_ids["Main.x"]].value.clone(),
+                        row[&poly_ids["Main.y"]].value.clone(),
+                    )
+                };
+                assert_eq!(cells(1), (CellValue::Known(3.into()), CellValue::Unknown));
+                assert_eq!(
+                    cells(2).1,
+                    CellValue::RangeConstraint(RangeConstraint::from_range(2.into(), 9.into()))
+                );
+
+                let truncated = SerializedRows::from_bytes(snapshot.as_bytes()[..20].to_vec());
+                assert_eq!(
+                    processor.processor.restore_from_snapshot(&truncated),
+                    Err("Unexpected end of witness snapshot.".to_string())
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn resume_from_witness_snapshot() {
+        let src = r#"
+            constant %N = 8;
+
+            namespace Fibonacci(%N);
+                col fixed ISFIRST = [1] + [0]*;
+                col fixed ISLAST = [0]* + [1];
+                col witness x, y;
+
+                ISFIRST * (y - 1) = 0;
+                ISFIRST * (x - 1) = 0;
+
+                (1-ISLAST) * (x' - y) = 0;
+                (1-ISLAST) * (y' - (x + y)) = 0;
+        "#;
+        let solve = |processor: &mut BlockProcessor<GoldilocksField, _>, degree, num_identities| {
+            let mut sequence_iterator = ProcessingSequenceIterator::Default(
+                DefaultSequenceIterator::new(degree as usize - 2, num_identities, None),
+            );
+            processor.solve(&mut sequence_iterator).unwrap();
+        };
+        let uninterrupted = do_with_processor(
+            src,
+            unused_query_callback(),
+            |mut processor, _, degree, num_identities| {
+                solve(&mut processor, degree, num_identities);
+                processor.processor.debug_snapshot()
+            },
+        );
+
+        // Process the first half of the block, then take a snapshot.
+        let snapshot = do_with_processor(
+            src,
+            unused_query_callback(),
+            |mut processor: BlockProcessor<GoldilocksField, _>, _, _, _| {
+                let identities = processor.identities.to_vec();
+                for row_index in 0..4 {
+                    for identity in &identities {
+                        processor
+                            .processor
+                            .process_identity(row_index, identity, UnknownStrategy::Unknown)
+                            .unwrap();
+                    }
+                }
+                let state = processor.processor.debug_snapshot();
+                assert_eq!(state.value("Fibonacci.y", 3), Some(5.into()));
+                assert!(!state.is_known("Fibonacci.y", 5));
+                processor.processor.witness_snapshot()
+            },
+        );
+
+        // Resume in a new processor.
+        let resumed = do_with_processor(
+            src,
+            unused_query_callback(),
+            |mut processor, _, degree, num_identities| {
+                processor
+                    .processor
+                    .restore_from_snapshot(&snapshot)
+                    .unwrap();
+                solve(&mut processor, degree, num_identities);
+                processor.processor.debug_snapshot()
+            },
+        );
+        assert_eq!(resumed, uninterrupted);
+        assert_eq!(resumed.value("Fibonacci.y", 7), Some(34.into()));
+    }
+
+    #[test]
+    fn preview_identity() {
+        let src = r#"
+            namespace Main(4);
+                col witness x, y;
+                x = 3;
+                y = x + 1;
+        "#;
+        do_with_processor(
+            src,
+            unused_query_callback(),
+            |mut processor: BlockProcessor<GoldilocksField, _>, poly_ids, _, _| {
+                let identities = processor.identities.to_vec();
+                let updates = processor
+                    .processor
+                    .preview_identity(1, identities[0], UnknownStrategy::Unknown)
+                    .unwrap();
+                assert!(updates.is_complete());
+                let [(poly, Constraint::Assignment(value))] = &updates.constraints[..] else {
+                    panic!("Expected a single assignment: {updates:?}");
+                };
+                assert_eq!((poly.name.as_str(), *value), ("Main.x", 3.into()));
+                // Nothing was applied, so `y` cannot be determined yet.
+                assert!(!processor.processor.row(1)[&poly_ids["Main.x"]]
+                    .value
+                    .is_known());
+                let updates = processor
+                    .processor
+                    .preview_identity(1, identities[1], UnknownStrategy::Unknown)
+                    .unwrap();
+                assert!(updates.constraints.is_empty() && !updates.is_complete());
+
+                // Errors are reported like by `process_identity`.
+                let mut row = processor.processor.row(1).clone();
+                row[&poly_ids["Main.x"]].value = CellValue::Known(5.into());
+                processor.processor.set_row(1, row);
+                let error = processor
+                    .processor
+                    .preview_identity(1, identities[0], UnknownStrategy::Unknown)
+                    .unwrap_err()
+                    .to_string();
+                assert!(
+                    error.starts_with("Error in identity: Main.x = 3"),
+                    "{error}"
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn structured_identity_error() {
+        let src = r#"
+            namespace Main(4);
+                col witness x, y;
+                x = 3;
+                y = x' + 1;
+        "#;
+        do_with_processor(
+            src,
+            unused_query_callback(),
+            |mut processor: BlockProcessor<GoldilocksField, _>, poly_ids, _, _| {
+                let identities = processor.identities.to_vec();
+                let mut row = processor.processor.row(1).clone();
+                row[&poly_ids["Main.x"]].value = CellValue::Known(5.into());
+                row[&poly_ids["Main.y"]].value = CellValue::Known(0.into());
+                processor.processor.set_row(1, row);
+                let mut row = processor.processor.row(2).clone();
+                row[&poly_ids["Main.x"]].value = CellValue::Known(7.into());
+                processor.processor.set_row(2, row);
+
+                let Err(error) = processor.processor.process_identity(
+                    1,
+                    identities[0],
+                    UnknownStrategy::Unknown,
+                ) else {
+                    panic!("Expected an error");
+                };
+                let EvalError::IdentityFailed(failure) = &error else {
+                    panic!("Expected a structured error: {error}");
+                };
+                assert_eq!(failure.identity_id, identities[0].id);
+                assert_eq!(failure.identity, "Main.x = 3;");
+                assert_eq!((failure.local_row, failure.global_row), (1, 1));
+                // Nonzero values first.
+                assert_eq!(
+                    failure.current_row,
+                    vec![
+                        ("Main.x".to_string(), 5.into()),
+                        ("Main.y".to_string(), 0.into())
+                    ]
+                );
+                assert_eq!(failure.next_row, None);
+                assert!(matches!(
+                    failure.cause,
+                    EvalError::ConstraintUnsatisfiable(_)
+                ));
+                // The text rendering is the default.
+                assert!(error
+                    .to_string()
+                    .starts_with("Error in identity: Main.x = 3;\nKnown values in current row (local: 1, global 1):\n    Main.x = 5\n    Main.y = 0\n"));
+
+                let Err(error) = processor.processor.process_identity(
+                    1,
+                    identities[1],
+                    UnknownStrategy::Unknown,
+                ) else {
+                    panic!("Expected an error");
+                };
+                let EvalError::IdentityFailed(failure) = &error else {
+                    panic!("Expected a structured error: {error}");
+                };
+                assert_eq!(
+                    failure.next_row,
+                    Some(vec![("Main.x".to_string(), 7.into())])
+                );
+                let json = failure.to_json();
+                assert!(json.starts_with(&format!(
+                    "{{\"identity_id\":{},\"identity\":\"Main.y = Main.x' + 1;\",\"local_row\":1,\"global_row\":1,\"current_row\":{{\"Main.x\":\"5\",\"Main.y\":\"0\"}},\"next_row\":{{\"Main.x\":\"7\"}},",
+                    identities[1].id
+                )), "{json}");
+            },
+        );
+    }
+
+    #[test]
+    fn random_unknown_strategy() {
+        let src = r#"
+            namespace Main(4);
+                col witness x, y;
+                x = 3;
+                // `y` can be 0 or 1.
+                x * (y * y - y) = 0;
+        "#;
+        do_with_processor(
+            src,
+            unused_query_callback(),
+            |mut processor: BlockProcessor<GoldilocksField, _>, poly_ids, _, _| {
+                let identities = processor.identities.to_vec();
+                let mut row = processor.processor.row(1).clone();
+                row[&poly_ids["Main.x"]].value = CellValue::Known(3.into());
+                processor.processor.set_row(1, row.clone());
+
+                let mut process = |strategy| {
+                    processor
+                        .processor
+                        .process_identity(1, identities[1], strategy)
+                        .is_ok()
+                };
+                assert!(process(UnknownStrategy::Unknown));
+                assert!(process(UnknownStrategy::Zero));
+                assert!(!process(UnknownStrategy::Random(1)));
+
+                let mut check = |strategy| {
+                    processor
+                        .processor
+                        .check_row_pair(1, &row, identities[1], false, strategy)
+                };
+                assert!(check(UnknownStrategy::Zero));
+                assert!(!check(UnknownStrategy::Random(1)));
+                assert!(!check(UnknownStrategy::Random(2)));
+            },
+        );
+    }
+
+    #[test]
+    fn unsolved_cells() {
+        let src = r#"
+            namespace Main(4);
+                col fixed BYTE(i) { i & 0xff };
+                col witness x, y, b;
+                x = 3;
+                // Not solvable, because it is not affine.
+                y * y = x * y;
+                { b } in { BYTE };
+        "#;
+        do_with_configured_processor(
+            src,
+            with_global_constraints,
+            unused_query_callback(),
+            |mut processor: BlockProcessor<GoldilocksField, _>,
+             poly_ids,
+             degree,
+             num_identities| {
+                let mut sequence_iterator = ProcessingSequenceIterator::Default(
+                    DefaultSequenceIterator::new(degree as usize - 2, num_identities, None),
+                );
+                processor.solve(&mut sequence_iterator).unwrap();
+                // The last row is not processed.
+                let mut row = processor.processor.row(3).clone();
+                row[&poly_ids["Main.x"]].value = CellValue::Known(3.into());
+                processor.processor.set_row(3, row);
+
+                let cells = |column: &str| {
+                    (0..degree)
+                        .map(|row| (poly_ids[column], RowIndex::from_degree(row, degree)))
+                        .collect::<Vec<_>>()
+                };
+                // `b` is only range-constrained.
+                assert_eq!(processor.processor.unsolved_cells(false), cells("Main.y"));
+                let mut unsolved = processor.processor.unsolved_cells(true);
+                unsolved.sort();
+                let mut expected = [cells("Main.y"), cells("Main.b")].concat();
+                expected.sort();
+                assert_eq!(unsolved, expected);
+            },
+        );
+    }
+
+    #[test]
+    fn transaction() {
+        let src = r#"
+            namespace Main(4);
+                col fixed BYTE(i) { i & 0xff };
+                col witness x, y, b;
+                x = 3;
+                y = x + 1;
+                { b } in { BYTE };
+        "#;
+        do_with_processor(
+            src,
+            unused_query_callback(),
+            |mut processor: BlockProcessor<GoldilocksField, _>,
+             poly_ids,
+             degree,
+             num_identities| {
+                let values = |processor: &BlockProcessor<GoldilocksField, _>| {
+                    (0..degree as usize)
+                        .flat_map(|row| {
+                            ["Main.x", "Main.y", "Main.b"].map(|column| {
+                                processor.processor.row(row)[&poly_ids[column]]
+                                    .value
+                                    .clone()
+                            })
+                        })
+                        .collect::<Vec<_>>()
+                };
+                let solve = |processor: &mut BlockProcessor<GoldilocksField, _>| {
+                    let mut sequence_iterator = ProcessingSequenceIterator::Default(
+                        DefaultSequenceIterator::new(degree as usize - 2, num_identities, None),
+                    );
+                    processor.solve(&mut sequence_iterator).unwrap();
+                };
+                let initial = values(&processor);
+
+                processor.processor.begin_transaction();
+                solve(&mut processor);
+                assert_ne!(values(&processor), initial);
+                processor.processor.rollback();
+                assert_eq!(values(&processor), initial);
+            },
+        );
+    }
+
+    #[test]
+    fn rollback_range_propagation_and_outer_query() {
+        let src = r#"
+            namespace Copy(4);
+                col fixed FOUR = [4]*;
+                col witness x, y, z;
+                x * (1 - x) = 0;
+                y = FOUR * x;
+        "#;
+        do_with_configured_processor(
+            src,
+            |fixed_data| {
+                let cell = |name, row| {
+                    (
+                        fixed_data.try_column_by_name(name).unwrap(),
+                        RowIndex::from_degree(row, fixed_data.degree),
+                    )
+                };
+                let copy_constraints =
+                    CopyConstraints::new(&[(cell("Copy.y", 0), cell("Copy.z", 1))]);
+                with_global_constraints(fixed_data).with_copy_constraints(copy_constraints)
+            },
+            unused_query_callback(),
+            |mut processor: BlockProcessor<GoldilocksField, _>, poly_ids, _, _| {
+                let (y, z) = (poly_ids["Copy.y"], poly_ids["Copy.z"]);
+                processor.processor.begin_transaction();
+                for identity in processor.identities {
+                    processor
+                        .processor
+                        .process_identity(0, identity, UnknownStrategy::Unknown)
+                        .unwrap();
+                }
+                assert!(matches!(
+                    processor.processor.row(1)[&z].value,
+                    CellValue::RangeConstraint(_)
+                ));
+                processor.processor.rollback();
+                assert_eq!(processor.processor.row(0)[&y].value, CellValue::Unknown);
+                assert_eq!(processor.processor.row(1)[&z].value, CellValue::Unknown);
+            },
+        );
+
+        let src = r#"
+            namespace Main(4);
+                col witness a, b;
+            namespace Sub(4);
+                col fixed LATCH = [0, 1]*;
+                col witness x, y;
+                y = x + 1;
+                {Main.a, Main.b} in Sub.LATCH {Sub.x, Sub.y};
+        "#;
+        do_with_outer_queries(
+            src,
+            "Sub",
+            &[("Main.a", 2)],
+            unused_query_callback(),
+            |mut processor, identities| {
+                assert!(processor.set_inputs_if_unset(1).unwrap());
+                processor
+                    .process_identity(1, identities[0], UnknownStrategy::Unknown)
+                    .unwrap();
+                assert!(!processor.finished_outer_query());
+
+                processor.begin_transaction();
+                let (progress, _) = processor.process_outer_query(1).unwrap();
+                assert!(progress);
+                assert!(processor.finished_outer_query());
+                processor.rollback();
+                assert!(!processor.finished_outer_query());
+            },
+        );
+    }
+
+    #[test]
+    fn outer_query_does_not_write_caller_columns() {
+        let src = r#"
+            namespace Main(4);
+                col witness a, b;
+            namespace Sub(4);
+                col fixed LATCH = [0, 1]*;
+                col witness x, y;
+                {Main.a, Main.b} in Sub.LATCH {Sub.x, Sub.y};
+        "#;
+        do_with_outer_queries(
+            src,
+            "Sub",
+            &[],
+            unused_query_callback(),
+            |mut processor, _| {
+                let [a, b, x, y] = [0, 1, 2, 3].map(|id| PolyID {
+                    id,
+                    ptype: PolynomialType::Committed,
+                });
+                let range_constraint = RangeConstraint::from_max_bit(3);
+                let mut row = processor.row(1).clone();
+                row[&x].value = CellValue::Known(2.into());
+                row[&y].value = CellValue::RangeConstraint(range_constraint.clone());
+                processor.set_row(1, row);
+                let known_columns = processor.data().known_columns().collect::<Vec<_>>();
+
+                // The callee determines an assignment and a range constraint for the caller.
+                let (_, outer_assignments) = processor.process_outer_query(1).unwrap();
+                let mut outer_assignments = outer_assignments
+                    .into_iter()
+                    .map(|(poly, constraint)| (poly.poly_id, constraint))
+                    .collect::<Vec<_>>();
+                outer_assignments.sort();
+                assert_eq!(
+                    outer_assignments,
+                    vec![
+                        (a, Constraint::Assignment(2.into())),
+                        (b, Constraint::RangeConstraint(range_constraint))
+                    ]
+                );
+                for poly_id in [a, b] {
+                    assert_eq!(processor.row(1)[&poly_id].value, CellValue::Unknown);
+                }
+
+                processor.finalize_range(0..4);
+                assert_eq!(
+                    processor.data().known_columns().collect::<Vec<_>>(),
+                    known_columns
+                );
+                assert_eq!(
+                    processor
+                        .finish()
+                        .known_columns()
+                        .map(|(poly_id, _)| poly_id)
+                        .collect::<Vec<_>>(),
+                    vec![x, y]
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn identity_latency() {
+        // A synthetic workload: identity 1 is usually fast, but slow on a single row.
+        let mut stats = ProcessorStats::default();
+        let micros = Duration::from_micros;
+        for _ in 0..1000 {
+            for (id, latency) in [(0, micros(10)), (1, micros(1)), (2, micros(100))] {
+                stats
+                    .identity_latencies
+                    .entry(id)
+                    .or_default()
+                    .record(latency);
+            }
+        }
+        stats
+            .identity_latencies
+            .get_mut(&1)
+            .unwrap()
+            .record(micros(5000));
+        assert_eq!(stats.slowest_identity(), Some((1, micros(5000))));
+        let latencies = &stats.identity_latencies[&1];
+        assert_eq!(latencies.count(), 1001);
+        // Quantiles are accurate up to 1/8.
+        assert!(latencies.p50() >= micros(1) && latencies.p50() <= micros(1) * 9 / 8);
+        assert!(latencies.p99() <= micros(1) * 9 / 8);
+        let latencies = &stats.identity_latencies[&2];
+        assert!(latencies.p99() >= micros(100) && latencies.p99() <= micros(100) * 9 / 8);
+        assert_eq!(latencies.max(), micros(100));
+
+        let src = r#"
+            namespace Main(4);
+                col witness x, y;
+                x = 1;
+                y = x + 2;
+        "#;
+        do_with_configured_processor(
+            src,
+            |fixed_data| {
+                fixed_data.with_options(WitgenOptions {
+                    measure_identity_latency: true,
+                    ..Default::default()
+                })
+            },
+            unused_query_callback(),
+            |mut processor: BlockProcessor<GoldilocksField, _>, _, degree, num_identities| {
+                let mut sequence_iterator = ProcessingSequenceIterator::Default(
+                    DefaultSequenceIterator::new(degree as usize - 2, num_identities, None),
+                );
+                processor.solve(&mut sequence_iterator).unwrap();
+                let stats = processor.processor.stats();
+                assert_eq!(stats.identity_latencies.len(), num_identities);
+                assert!(stats.identity_latencies.values().all(|l| l.count() > 0));
+                assert!(stats.slowest_identity().is_some());
+            },
+        );
+    }
+
+    #[test]
+    fn redundant_identities() {
+        let src = r#"
+            namespace Main(4);
+                col witness x, y, z;
+                x = 1;
+                y = x + 2;
+                z = y - x;
+                // Implied by the identities above.
+                z = 2;
+        "#;
+        do_with_configured_processor(
+            src,
+            track_provenance,
+            unused_query_callback(),
+            |mut processor: BlockProcessor<GoldilocksField, _>,
+             poly_ids,
+             degree,
+             num_identities| {
+                let mut sequence_iterator = ProcessingSequenceIterator::Default(
+                    DefaultSequenceIterator::new(degree as usize - 2, num_identities, None),
+                );
+                processor.solve(&mut sequence_iterator).unwrap();
+                // The last row is not processed.
+                let mut row = processor.processor.row(3).clone();
+                for (name, value) in [("Main.x", 1), ("Main.y", 3), ("Main.z", 2)] {
+                    row[&poly_ids[name]].value = CellValue::Known(value.into());
+                }
+                processor.processor.set_row(3, row);
+
+                let identities = processor.identities;
+                assert_eq!(
+                    processor.processor.redundant_identities(identities),
+                    vec![identities[3].id]
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn column_solved_ratio() {
+        let src = r#"
+            namespace Main(4);
+                col witness x, y;
+                x = 1;
+                // Not solvable, because it is not affine.
+                y * y = x * y;
+        "#;
+        do_with_processor(
+            src,
+            unused_query_callback(),
+            |mut processor: BlockProcessor<GoldilocksField, _>, _, degree, num_identities| {
+                assert_eq!(processor.processor.column_solved_ratio(), 0.0);
+                let mut sequence_iterator = ProcessingSequenceIterator::Default(
+                    DefaultSequenceIterator::new(degree as usize - 2, num_identities, None),
+                );
+                processor.solve(&mut sequence_iterator).unwrap();
+                // `x` is known in all rows but the last one, which is not processed.
+                let ratio = processor.processor.column_solved_ratio();
+                assert!(0.0 < ratio && ratio < 1.0);
+                assert_eq!(ratio, 3.0 / 8.0);
+            },
+        );
+    }
+
+    #[test]
+    fn input_validator() {
+        let src = r#"
+            namespace std::prover(4);
+                enum Query {
+                    Input(int),
+                    None,
+                }
+
+            namespace Main(4);
+                col witness byte(i) query std::prover::Query::Input(i);
+        "#;
+        let query_callback = |query: &str| -> Result<Option<GoldilocksField>, String> {
+            let index = query
+                .strip_prefix("Input(")
+                .and_then(|query| query.strip_suffix(')'))
+                .ok_or_else(|| format!("Unsupported query: {query}"))?;
+            let inputs = [1, 2, 300, 4];
+            Ok(Some(inputs[index.parse::<usize>().unwrap()].into()))
+        };
+        fn is_byte(value: GoldilocksField) -> bool {
+            value.to_degree() < 256
+        }
+        do_with_configured_processor(
+            src,
+            |fixed_data| {
+                fixed_data.with_options(WitgenOptions {
+                    input_validators: vec![("Main.byte".to_string(), &is_byte)],
+                    ..Default::default()
+                })
+            },
+            query_callback,
+            |mut processor: BlockProcessor<GoldilocksField, _>, poly_ids, _, _| {
+                assert!(processor.processor.process_queries(1).unwrap());
+                assert_eq!(
+                    processor.processor.row(1)[&poly_ids["Main.byte"]].value,
+                    CellValue::Known(2.into())
+                );
+                let error = processor.processor.process_queries(2).unwrap_err();
+                assert_eq!(
+                    error,
+                    EvalError::InvalidInput {
+                        column: "Main.byte".to_string(),
+                        row: 2,
+                        value: 300.into()
+                    }
+                );
+                assert!(!processor.processor.row(2)[&poly_ids["Main.byte"]]
+                    .value
+                    .is_known());
+            },
+        );
+    }
+
+    #[test]
+    fn progress_stats() {
+        let src = r#"
+            namespace Main(4);
+                col fixed FIRST = [1, 0, 0, 0];
+                col witness x, y, z;
+                x = 1;
+                FIRST * (y - 2) = 0;
+                z = x + 2;
+        "#;
+        do_with_configured_processor(
+            src,
+            |fixed_data| {
+                fixed_data.with_options(WitgenOptions {
+                    count_progress: true,
+                    ..Default::default()
+                })
+            },
+            unused_query_callback(),
+            |mut processor: BlockProcessor<GoldilocksField, _>,
+             poly_ids,
+             degree,
+             num_identities| {
+                let mut sequence_iterator = ProcessingSequenceIterator::Default(
+                    DefaultSequenceIterator::new(degree as usize - 2, num_identities, None),
+                );
+                processor.solve(&mut sequence_iterator).unwrap();
+                // The last row is not processed, `y` is only determined in the first row.
+                assert_eq!(
+                    processor.processor.progress_stats(),
+                    BTreeMap::from([
+                        (poly_ids["Main.x"], 3),
+                        (poly_ids["Main.y"], 1),
+                        (poly_ids["Main.z"], 3)
+                    ])
+                );
+            },
+        );
+        // Nothing is counted if disabled.
+        do_with_processor(
+            src,
+            unused_query_callback(),
+            |mut processor: BlockProcessor<GoldilocksField, _>, _, degree, num_identities| {
+                let mut sequence_iterator = ProcessingSequenceIterator::Default(
+                    DefaultSequenceIterator::new(degree as usize - 2, num_identities, None),
+                );
+                processor.solve(&mut sequence_iterator).unwrap();
+                assert!(processor.processor.progress_stats().is_empty());
+            },
+        );
+    }
+
+    #[test]
+    fn fill_levels() {
+        let src = r#"
+            namespace Main(4);
+                col witness x, y, z, a, b;
+                x = 1;
+                y = x + 1;
+                z = y + 1;
+                a = 2 * z;
+                b = z + 3;
+        "#;
+        do_with_configured_processor(
+            src,
+            track_provenance,
+            unused_query_callback(),
+            |mut processor: BlockProcessor<GoldilocksField, _>, poly_ids, degree, _| {
+                for identity in processor.identities.iter().copied() {
+                    processor
+                        .processor
+                        .process_identity(0, identity, UnknownStrategy::Unknown)
+                        .unwrap();
+                }
+                let row = RowIndex::from_degree(0, degree);
+                let cells = |names: &[&str]| {
+                    names
+                        .iter()
+                        .map(|name| (poly_ids[*name], row))
+                        .collect::<Vec<_>>()
+                };
+                assert_eq!(
+                    processor.processor.fill_levels(),
+                    vec![
+                        cells(&["Main.x"]),
+                        cells(&["Main.y"]),
+                        cells(&["Main.z"]),
+                        cells(&["Main.a", "Main.b"]),
+                    ]
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn column_group() {
+        let src = r#"
+            namespace Regs(4);
+                col witness regs[3];
+                regs[0] = 1;
+                regs[1] = 2;
+                regs[2] = regs[0] + regs[1];
+        "#;
+        let group = std::cell::OnceCell::new();
+        do_with_configured_processor(
+            src,
+            |fixed_data| {
+                assert_eq!(ColumnGroup::all(&fixed_data).len(), 1);
+                group
+                    .set(ColumnGroup::by_name(&fixed_data, "Regs.regs").unwrap())
+                    .unwrap();
+                fixed_data
+            },
+            unused_query_callback(),
+            |mut processor: BlockProcessor<GoldilocksField, _>, _, _, _| {
+                let group = group.get().unwrap();
+                assert_eq!(group.columns.len(), 3);
+                assert_eq!(processor.processor.read_group(0, group), None);
+                for identity in processor.identities {
+                    processor
+                        .processor
+                        .process_identity(0, identity, UnknownStrategy::Unknown)
+                        .unwrap();
+                }
+                assert_eq!(
+                    processor.processor.read_group(0, group),
+                    Some(vec![1.into(), 2.into(), 3.into()])
+                );
+
+                // Writing a group is all or nothing.
+                let source_name = || "test".to_string();
+                let values = [4.into(), 5.into(), 6.into()];
+                processor
+                    .processor
+                    .write_group(0, group, &values, Provenance::Input, source_name)
+                    .unwrap_err();
+                assert_eq!(processor.processor.read_group(1, group), None);
+                processor
+                    .processor
+                    .write_group(1, group, &values, Provenance::Input, source_name)
+                    .unwrap();
+                assert_eq!(
+                    processor.processor.read_group(1, group),
+                    Some(values.to_vec())
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn process_identities_batched() {
+        let src = r#"
+            namespace Batch(4);
+                col witness a, b, c, d, e;
+                a = 1;
+                b = 2;
+                c = 3;
+                d = 4;
+                e = a + b;
+        "#;
+        // Processes the identities of row 0 until they are all complete and returns
+        // the values of the row and the number of row pairs that were constructed,
+        // i.e. one per batch or per identity processed on its own.
+        let run = |batched: bool| {
+            do_with_processor(
+                src,
+                unused_query_callback(),
+                |mut processor: BlockProcessor<GoldilocksField, _>, poly_ids, _, _| {
+                    let identities = processor.identities;
+                    let mut passes = vec![];
+                    let mut row_pairs = 0;
+                    loop {
+                        let is_complete = if batched {
+                            let result = processor
+                                .processor
+                                .process_identities_batched(0, identities, UnknownStrategy::Unknown)
+                                .unwrap();
+                            row_pairs += 1;
+                            assert!(result.aggregate.progress);
+                            assert_eq!(
+                                result.aggregate.is_complete,
+                                result.is_complete.iter().all(|c| *c)
+                            );
+                            result.is_complete
+                        } else {
+                            row_pairs += identities.len();
+                            identities
+                                .iter()
+                                .map(|identity| {
+                                    processor
+                                        .processor
+                                        .process_identity(0, identity, UnknownStrategy::Unknown)
+                                        .unwrap()
+                                        .is_complete
+                                })
+                                .collect()
+                        };
+                        let done = is_complete.iter().all(|c| *c);
+                        passes.push(is_complete);
+                        if done {
+                            break;
+                        }
+                    }
+                    let values = ["a", "b", "c", "d", "e"].map(|name| {
+                        processor.processor.row(0)[&poly_ids[&format!("Batch.{name}")]]
+                            .value
+                            .clone()
+                    });
+                    (values, passes, row_pairs)
+                },
+            )
+        };
+
+        let (values, passes, row_pairs) = run(false);
+        assert_eq!(passes.len(), 1);
+        assert_eq!(row_pairs, 5);
+
+        // In the first batch, `e` does not see the values of `a` and `b` yet.
+        let (batched_values, batched_passes, batched_row_pairs) = run(true);
+        assert_eq!(batched_values, values);
+        assert_eq!(
+            batched_passes,
+            vec![vec![true, true, true, true, false], vec![true; 5]]
+        );
+        assert_eq!(batched_row_pairs, 2);
+    }
+
+    #[test]
+    fn multiple_outer_queries() {
+        let src = r#"
+            namespace Main(4);
+                col witness a, b, c, d;
+            namespace Sub(4);
+                col fixed LATCH = [0, 1]*;
+                col witness x, y, z;
+                z = x + y;
+                {Main.a, Main.c} in Sub.LATCH {Sub.x, Sub.z};
+                {Main.b, Main.d} in Sub.LATCH {Sub.y, Sub.z};
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let constants = generate(&analyzed);
+        let fixed_data = FixedData::new(&analyzed, &constants, &[], Default::default(), 0);
+        let row_offset = RowIndex::from_degree(0, fixed_data.degree);
+
+        // The caller passes `a` and `b` and receives `c` and `d`.
+        let mut caller_row = Row::fresh(&fixed_data, row_offset);
+        for (name, value) in [("Main.a", 2), ("Main.b", 3)] {
+            caller_row[&fixed_data.try_column_by_name(name).unwrap()].value =
+                CellValue::Known(value.into());
+        }
+        let caller_next_row = Row::fresh(&fixed_data, row_offset + 1);
+        let caller_rows = RowPair::new(
+            &caller_row,
+            &caller_next_row,
+            row_offset,
+            &fixed_data,
+            UnknownStrategy::Unknown,
+        );
+        let (connecting_identities, identities): (Vec<_>, Vec<_>) = analyzed
+            .identities
+            .iter()
+            .partition(|identity| identity.kind == IdentityKind::Plookup);
+
+        let mut fixed_lookup = FixedLookup::new(fixed_data.global_range_constraints().clone());
+        let mut query_callback = unused_query_callback();
+        let mut mutable_state = MutableState {
+            fixed_lookup: &mut fixed_lookup,
+            machines: Machines::from([].iter_mut()),
+            query_callback: &mut query_callback,
+            scratchpad: &Scratchpad::default(),
+            tie_breaker: None,
+        };
+        let witnesses = ["Sub.x", "Sub.y", "Sub.z"]
+            .into_iter()
+            .map(|name| fixed_data.try_column_by_name(name).unwrap())
+            .collect();
+        let data = FinalizableData::with_initial_rows_in_progress(
+            &witnesses,
+            (0..4).map(|i| Row::fresh(&fixed_data, row_offset + i)),
+        );
+        let mut processor = Processor::new(
+            row_offset,
+            data,
+            &mut mutable_state,
+            &fixed_data,
+            &witnesses,
+        )
+        .with_outer_queries(
+            connecting_identities
+                .iter()
+                .map(|identity| OuterQuery::new(&caller_rows, identity))
+                .collect(),
+        );
+
+        // The inputs of both lookups are extracted, the block ends in row 1.
+        assert!(processor.set_inputs_if_unset(1).unwrap());
+        processor
+            .process_identity(1, identities[0], UnknownStrategy::Unknown)
+            .unwrap();
+        assert!(!processor.finished_outer_query());
+        let (progress, outer_assignments) = processor.process_outer_query(1).unwrap();
+        assert!(progress);
+        let mut outer_assignments = outer_assignments
+            .into_iter()
+            .map(|(poly, constraint)| (poly.name.clone(), constraint))
+            .collect::<Vec<_>>();
+        outer_assignments.sort();
+        assert_eq!(
+            outer_assignments,
+            vec![
+                ("Main.c".to_string(), Constraint::Assignment(5.into())),
+                ("Main.d".to_string(), Constraint::Assignment(5.into()))
+            ]
+        );
+        assert!(processor.finished_outer_query());
+
+        // Both lookups share the latch and are part of the snapshot.
+        assert_eq!(
+            processor.latch_values(0..4),
+            [false, true, false, true].map(Some)
+        );
+        let snapshot = processor.debug_snapshot();
+        assert_eq!(
+            snapshot.outer_queries,
+            vec![
+                OuterQuerySnapshot {
+                    identity: connecting_identities[0].to_string(),
+                    left: vec![Some(2.into()), Some(5.into())],
+                },
+                OuterQuerySnapshot {
+                    identity: connecting_identities[1].to_string(),
+                    left: vec![Some(3.into()), Some(5.into())],
+                },
+            ]
+        );
+    }
 }
@@ -0,0 +1,56 @@
+use powdr_ast::analyzed::{Analyzed, IdentityKind};
+use powdr_number::{DegreeType, FieldElement};
+
+use super::data_structures::finalizable_data::FinalizableData;
+use super::identity_processor::Machines;
+use super::machines::FixedLookup;
+use super::processor::{Processor, RowCheckFailure};
+use super::rows::{Row, RowIndex};
+use super::{identities_for_stage, unused_query_callback, FixedData, MutableState, Scratchpad};
+
+/// Changes the value of a single cell of a generated witness and reports which polynomial
+/// identities do not hold anymore, without re-running witness generation.
+/// As in [super::constraint_residuals], the last row is followed by the first one.
+/// Lookups and permutations are not checked.
+pub fn override_and_check<T: FieldElement>(
+    analyzed: &Analyzed<T>,
+    fixed_col_values: &[(String, Vec<T>)],
+    witness: &[(String, Vec<T>)],
+    row: DegreeType,
+    column: &str,
+    value: T,
+) -> Result<(), Vec<RowCheckFailure<T>>> {
+    let fixed_data = FixedData::new(analyzed, fixed_col_values, witness, Default::default(), 0);
+    let poly_id = fixed_data
+        .try_column_by_name(column)
+        .unwrap_or_else(|| panic!("Column {column} not found."));
+    let identities = identities_for_stage(analyzed, 0)
+        .into_iter()
+        .filter(|identity| identity.kind == IdentityKind::Polynomial)
+        .collect::<Vec<_>>();
+    let identities = identities.iter().collect::<Vec<_>>();
+
+    let witness_cols = fixed_data.witness_cols.keys().collect();
+    let data = FinalizableData::with_initial_rows_in_progress(
+        &witness_cols,
+        (0..fixed_data.degree)
+            .map(|i| Row::fresh(&fixed_data, RowIndex::from_degree(i, fixed_data.degree))),
+    );
+    let mut fixed_lookup = FixedLookup::new(fixed_data.global_range_constraints().clone());
+    let mut query_callback = unused_query_callback();
+    let mut mutable_state = MutableState {
+        fixed_lookup: &mut fixed_lookup,
+        machines: Machines::from([].iter_mut()),
+        query_callback: &mut query_callback,
+        scratchpad: &Scratchpad::default(),
+        tie_breaker: None,
+    };
+    let mut processor = Processor::new(
+        RowIndex::from_degree(0, fixed_data.degree),
+        data,
+        &mut mutable_state,
+        &fixed_data,
+        &witness_cols,
+    );
+    processor.override_and_check(row as usize, &poly_id, value, &identities)
+}
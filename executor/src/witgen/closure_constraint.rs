@@ -0,0 +1,55 @@
+use powdr_number::FieldElement;
+
+use super::rows::RowPair;
+use super::{EvalResult, Scratchpad};
+
+type ConstraintFunction<'b, T> = dyn for<'row, 'a> Fn(&RowPair<'row, 'a, T>, &Scratchpad) -> EvalResult<'a, T>
+    + Send
+    + Sync
+    + 'b;
+
+/// A constraint of the main machine given as a Rust function instead of a PIL identity,
+/// e.g. to experiment with a constraint before adding it to the PIL.
+/// The function is called on the current and the next row and returns the updates it
+/// can derive, just like the processing of an identity. It is processed together with
+/// the identities that do not reference the next row.
+/// The returned constraints have to refer to columns of the main machine, see
+/// [RowPair::witness_reference].
+pub struct ClosureConstraint<'b, T: FieldElement> {
+    pub name: String,
+    function: Box<ConstraintFunction<'b, T>>,
+}
+
+impl<'b, T: FieldElement> ClosureConstraint<'b, T> {
+    pub fn new(
+        name: impl Into<String>,
+        function: impl for<'row, 'a> Fn(&RowPair<'row, 'a, T>) -> EvalResult<'a, T> + Send + Sync + 'b,
+    ) -> Self {
+        Self::with_scratchpad(name, move |row_pair, _| function(row_pair))
+    }
+
+    /// Like [ClosureConstraint::new], but the function also receives the [Scratchpad] of
+    /// the run, e.g. to carry state from one row to the next.
+    /// Since the function can be called multiple times on the same row, it should only
+    /// update the scratchpad once per row.
+    pub fn with_scratchpad(
+        name: impl Into<String>,
+        function: impl for<'row, 'a> Fn(&RowPair<'row, 'a, T>, &Scratchpad) -> EvalResult<'a, T>
+            + Send
+            + Sync
+            + 'b,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            function: Box::new(function),
+        }
+    }
+
+    pub fn evaluate<'a>(
+        &self,
+        row_pair: &RowPair<'_, 'a, T>,
+        scratchpad: &Scratchpad,
+    ) -> EvalResult<'a, T> {
+        (self.function)(row_pair, scratchpad)
+    }
+}
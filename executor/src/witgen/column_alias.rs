@@ -0,0 +1,42 @@
+use std::collections::BTreeMap;
+
+use powdr_ast::analyzed::{
+    Analyzed, Expression, FunctionValueDefinition, PolyID, Reference, SymbolKind, TypedExpression,
+};
+
+/// Returns, for each name that is defined as a reference to a (non-array) column, e.g.
+/// `let alias = x;`, the name and ID of the referenced column. Aliases of aliases are
+/// resolved to the underlying column.
+pub fn column_aliases<T>(analyzed: &Analyzed<T>) -> BTreeMap<String, (String, PolyID)> {
+    let mut aliases = BTreeMap::new();
+    loop {
+        let mut progress = false;
+        for (name, (_, definition)) in &analyzed.definitions {
+            if aliases.contains_key(name) {
+                continue;
+            }
+            let Some(FunctionValueDefinition::Expression(TypedExpression {
+                e: Expression::Reference(_, Reference::Poly(reference)),
+                ..
+            })) = definition
+            else {
+                continue;
+            };
+            let column = match analyzed.definitions.get(&reference.name) {
+                Some((symbol, _))
+                    if matches!(symbol.kind, SymbolKind::Poly(_)) && !symbol.is_array() =>
+                {
+                    Some((symbol.absolute_name.clone(), symbol.into()))
+                }
+                _ => aliases.get(&reference.name).cloned(),
+            };
+            if let Some(column) = column {
+                aliases.insert(name.clone(), column);
+                progress = true;
+            }
+        }
+        if !progress {
+            return aliases;
+        }
+    }
+}
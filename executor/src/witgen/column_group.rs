@@ -0,0 +1,50 @@
+use powdr_ast::analyzed::{PolyID, PolynomialType, SymbolKind};
+use powdr_number::FieldElement;
+
+use super::FixedData;
+
+/// A group of witness columns that is read and written as a unit, e.g. the columns of a
+/// register file. Groups are declared in PIL as arrays of witness columns, i.e. the
+/// columns of `col witness regs[3];` form the group `regs`, or formed by the columns that
+/// a hook determines together, e.g. those of an [super::InstructionReplay].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ColumnGroup {
+    /// The (absolute) name of the array.
+    pub name: String,
+    /// The columns of the group, in the order of the array elements.
+    pub columns: Vec<PolyID>,
+}
+
+impl ColumnGroup {
+    /// Returns the group formed by the array of witness columns with the given name,
+    /// or None if there is no such array.
+    pub fn by_name<T: FieldElement>(fixed_data: &FixedData<T>, name: &str) -> Option<Self> {
+        let (symbol, _) = fixed_data.analyzed.definitions.get(name)?;
+        (symbol.kind == SymbolKind::Poly(PolynomialType::Committed) && symbol.is_array()).then(
+            || ColumnGroup {
+                name: symbol.absolute_name.clone(),
+                columns: symbol
+                    .array_elements()
+                    .map(|(_, poly_id)| poly_id)
+                    .collect(),
+            },
+        )
+    }
+
+    /// Returns all groups, in source order.
+    pub fn all<T: FieldElement>(fixed_data: &FixedData<T>) -> Vec<Self> {
+        fixed_data
+            .analyzed
+            .committed_polys_in_source_order()
+            .iter()
+            .filter(|(symbol, _)| symbol.is_array())
+            .map(|(symbol, _)| ColumnGroup {
+                name: symbol.absolute_name.clone(),
+                columns: symbol
+                    .array_elements()
+                    .map(|(_, poly_id)| poly_id)
+                    .collect(),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,36 @@
+use std::sync::Mutex;
+
+use powdr_ast::analyzed::PolyID;
+use powdr_number::{DegreeType, FieldElement};
+
+type WriteCell<'b, T> = dyn FnMut(PolyID, DegreeType, T) + Send + 'b;
+
+/// Receives the cells of rows as soon as they are finalized during witness generation,
+/// e.g. to write them directly into the column buffers of a backend. Only rows that are
+/// finalized while processing (see [super::WitgenOptions::row_window]) are passed,
+/// the remaining rows are part of the generated witness as usual.
+pub struct ColumnSink<'b, T: FieldElement> {
+    write: Mutex<Box<WriteCell<'b, T>>>,
+}
+
+impl<'b, T: FieldElement> ColumnSink<'b, T> {
+    /// Creates a sink that calls `write` with the column, (global) row and value of each
+    /// finalized cell. The rows finalized at once are passed in column-major order.
+    pub fn new(write: impl FnMut(PolyID, DegreeType, T) + Send + 'b) -> Self {
+        Self {
+            write: Mutex::new(Box::new(write)),
+        }
+    }
+}
+
+/// Cell writing, independent of the lifetime of [ColumnSink]'s write function.
+pub trait WriteCells<T>: Sync {
+    /// Calls `cells` with a function that writes a cell (column, global row, value).
+    fn write_cells(&self, cells: &mut dyn FnMut(&mut WriteCell<'_, T>));
+}
+
+impl<'b, T: FieldElement> WriteCells<T> for ColumnSink<'b, T> {
+    fn write_cells(&self, cells: &mut dyn FnMut(&mut WriteCell<'_, T>)) {
+        cells(&mut **self.write.lock().unwrap())
+    }
+}
@@ -0,0 +1,27 @@
+use std::collections::BTreeMap;
+
+use powdr_ast::analyzed::{Analyzed, PolyID};
+use powdr_number::FieldElement;
+
+/// Computes, for each witness column of a generated witness, the fraction of its cells
+/// that are zero. Columns with a high ratio are candidates for sparse storage.
+/// Columns that are not part of the witness (e.g. of later stages) are skipped.
+pub fn column_sparsity<T: FieldElement>(
+    analyzed: &Analyzed<T>,
+    witness: &[(String, Vec<T>)],
+) -> BTreeMap<PolyID, f64> {
+    let witness = witness
+        .iter()
+        .map(|(name, values)| (name.as_str(), values))
+        .collect::<BTreeMap<_, _>>();
+    analyzed
+        .committed_polys_in_source_order()
+        .into_iter()
+        .flat_map(|(symbol, _)| symbol.array_elements())
+        .filter_map(|(name, poly_id)| {
+            let values = witness.get(name.as_str()).filter(|v| !v.is_empty())?;
+            let zeros = values.iter().filter(|value| value.is_zero()).count();
+            Some((poly_id, zeros as f64 / values.len() as f64))
+        })
+        .collect()
+}
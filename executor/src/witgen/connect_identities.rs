@@ -0,0 +1,191 @@
+use std::collections::HashSet;
+
+use powdr_ast::analyzed::{
+    AlgebraicExpression as Expression, AlgebraicReference, IdentityKind, PolyID, PolynomialType,
+};
+use powdr_number::{DegreeType, FieldElement, LargeInt};
+
+use super::rows::RowIndex;
+use super::FixedData;
+
+type Cell = (PolyID, RowIndex);
+
+/// Derives copy constraints from the connect identities of the PIL, i.e. identities of the form
+/// `{ x_0, ..., x_n } connect { s_0, ..., s_n };`, where the `x_i` are (possibly shifted)
+/// columns and the `s_i` are fixed columns.
+///
+/// Cell `j` of `x_i` (i.e. row `j + 1` of the column for `x_i'`) has the index `i * degree + j`.
+/// The value of `s_i` in row `j` is the index of the cell that cell `j` of `x_i` is equal to.
+pub fn copy_constraints_from_connect_identities<T: FieldElement>(
+    fixed_data: &FixedData<T>,
+) -> Result<Vec<(Cell, Cell)>, String> {
+    let degree = fixed_data.degree;
+    let mut pairs = vec![];
+    for identity in &fixed_data.analyzed.identities {
+        if identity.kind != IdentityKind::Connect {
+            continue;
+        }
+        if identity.left.selector.is_some() || identity.right.selector.is_some() {
+            return Err(format!("Selectors are not supported: {identity}"));
+        }
+        let columns = identity
+            .left
+            .expressions
+            .iter()
+            .map(|e| match e {
+                Expression::Reference(r) => Ok(r),
+                _ => Err(format!("Expected a column, but got {e}: {identity}")),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let connections = identity
+            .right
+            .expressions
+            .iter()
+            .map(|e| match e {
+                Expression::Reference(r @ AlgebraicReference { next: false, .. })
+                    if r.poly_id.ptype == PolynomialType::Constant =>
+                {
+                    Ok(fixed_data.fixed_cols[&r.poly_id].values())
+                }
+                _ => Err(format!("Expected a fixed column, but got {e}: {identity}")),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let cell = |index: DegreeType| {
+            let column = columns[(index / degree) as usize];
+            let row = (index % degree + DegreeType::from(column.next)) % degree;
+            (column.poly_id, RowIndex::from_degree(row, degree))
+        };
+        let cell_count = columns.len() as u64 * degree;
+        for (i, values) in connections.iter().enumerate() {
+            for (j, value) in values.iter().enumerate() {
+                let index = value
+                    .to_integer()
+                    .try_into_u64()
+                    .filter(|index| *index < cell_count)
+                    .ok_or_else(|| {
+                        format!(
+                            "Invalid cell index {value} in row {j} of {}: {identity}",
+                            identity.right.expressions[i]
+                        )
+                    })?;
+                pairs.push((cell(i as u64 * degree + j as u64), cell(index)));
+            }
+        }
+    }
+    Ok(pairs)
+}
+
+/// Checks that the copy constraints of the fixed data only connect witness cells of the
+/// same machine, given the witness columns of each machine. Cells of fixed columns can be
+/// copy-constrained to any witness cell.
+pub fn check_copy_constraints_within_machines<T: FieldElement>(
+    fixed_data: &FixedData<T>,
+    machine_witnesses: &[HashSet<PolyID>],
+) -> Result<(), String> {
+    let machine_of = |poly_id: &PolyID| {
+        machine_witnesses
+            .iter()
+            .position(|witnesses| witnesses.contains(poly_id))
+    };
+    for cell @ (poly_id, row) in fixed_data.copy_constraints.cells() {
+        let (next_poly_id, next_row) = fixed_data.copy_constraints.next(cell);
+        if poly_id.ptype != PolynomialType::Committed
+            || next_poly_id.ptype != PolynomialType::Committed
+        {
+            continue;
+        }
+        if machine_of(&poly_id) != machine_of(&next_poly_id) {
+            return Err(format!(
+                "Copy constraint between {} (Row {}) and {} (Row {}), which are not part of the same machine.",
+                fixed_data.column_name(&poly_id),
+                DegreeType::from(row),
+                fixed_data.column_name(&next_poly_id),
+                DegreeType::from(next_row),
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use powdr_number::GoldilocksField;
+    use powdr_pil_analyzer::analyze_string;
+
+    use crate::constant_evaluator::generate;
+    use crate::witgen::data_structures::copy_constraints::CopyConstraints;
+
+    use super::*;
+
+    #[test]
+    fn load_copy_constraints() {
+        // The cells are x[0..4] (indices 0..4) and y'[0..4], i.e. y[1], y[2], y[3], y[0]
+        // (indices 4..8). The connections are x[0] = x[1] = y'[2] and x[3] = y'[3].
+        let src = r#"
+            namespace Copy(4);
+                col fixed SX = [1, 6, 2, 7];
+                col fixed SY = [4, 5, 0, 3];
+                col witness x, y;
+                { x, y' } connect { SX, SY };
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let constants = generate(&analyzed)
+            .into_iter()
+            .map(|(n, c)| (n.to_string(), c))
+            .collect::<Vec<_>>();
+        let fixed_data = FixedData::new(&analyzed, &constants, &[], Default::default(), 0);
+        let pairs = copy_constraints_from_connect_identities(&fixed_data).unwrap();
+        let classes = CopyConstraints::new(&pairs).non_trivial_equivalence_classes();
+
+        let cell = |name, row| {
+            (
+                fixed_data.try_column_by_name(name).unwrap(),
+                RowIndex::from_degree(row, 4),
+            )
+        };
+        let expected: BTreeSet<BTreeSet<_>> = [
+            [cell("Copy.x", 0), cell("Copy.x", 1), cell("Copy.y", 3)]
+                .into_iter()
+                .collect(),
+            [cell("Copy.x", 3), cell("Copy.y", 0)].into_iter().collect(),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(classes, expected);
+    }
+
+    #[test]
+    fn copy_constraints_across_machines() {
+        let src = r#"
+            namespace Copy(4);
+                col fixed SX = [1, 4, 2, 3];
+                col fixed SY = [0, 5, 6, 7];
+                col witness x, y;
+                { x, y } connect { SX, SY };
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let constants = generate(&analyzed)
+            .into_iter()
+            .map(|(n, c)| (n.to_string(), c))
+            .collect::<Vec<_>>();
+        let fixed_data = FixedData::new(&analyzed, &constants, &[], Default::default(), 0);
+        let pairs = copy_constraints_from_connect_identities(&fixed_data).unwrap();
+        let fixed_data = fixed_data.with_copy_constraints(CopyConstraints::new(&pairs));
+        let x = fixed_data.try_column_by_name("Copy.x").unwrap();
+        let y = fixed_data.try_column_by_name("Copy.y").unwrap();
+
+        let same_machine = [[x, y].into_iter().collect()];
+        assert_eq!(
+            check_copy_constraints_within_machines(&fixed_data, &same_machine),
+            Ok(())
+        );
+        // x[1] = y[0] connects the two machines.
+        let different_machines = [[x].into_iter().collect(), [y].into_iter().collect()];
+        assert_eq!(
+            check_copy_constraints_within_machines(&fixed_data, &different_machines),
+            Err("Copy constraint between Copy.x (Row 1) and Copy.y (Row 0), which are not part of the same machine.".to_string())
+        );
+    }
+}
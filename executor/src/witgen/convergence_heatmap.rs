@@ -0,0 +1,52 @@
+use itertools::Itertools;
+use powdr_ast::analyzed::PolyID;
+
+/// Counts, for each fixpoint iteration and witness column, how many cells of the column
+/// became known in that iteration, summed over all rows (see
+/// [super::WitgenOptions::convergence_heatmap]). Iterations are counted from the
+/// start of each fixpoint loop, i.e. per row of a VM and per row of a block, so cells
+/// counted in late iterations show where witness generation converges slowly.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConvergenceHeatmap {
+    /// The names of the witness columns, in the order of the counts of an iteration.
+    pub columns: Vec<String>,
+    /// For each iteration, the number of cells of each column that became known in it.
+    pub iterations: Vec<Vec<usize>>,
+}
+
+impl ConvergenceHeatmap {
+    /// Creates an empty heatmap for the given witness columns, ordered by their IDs.
+    pub fn new(columns: Vec<String>) -> Self {
+        Self {
+            columns,
+            iterations: vec![],
+        }
+    }
+
+    /// Records that a cell of the given witness column became known in the given iteration.
+    pub fn record(&mut self, iteration: usize, poly_id: &PolyID) {
+        if self.iterations.len() <= iteration {
+            self.iterations
+                .resize(iteration + 1, vec![0; self.columns.len()]);
+        }
+        self.iterations[iteration][poly_id.id as usize] += 1;
+    }
+
+    /// Returns the number of cells of the given column that became known in the given
+    /// iteration, or None if there is no such column or iteration.
+    pub fn count(&self, iteration: usize, column: &str) -> Option<usize> {
+        let index = self.columns.iter().position(|c| c == column)?;
+        Some(self.iterations.get(iteration)?[index])
+    }
+
+    /// Renders the heatmap as CSV, with a header and one line per iteration.
+    pub fn to_csv(&self) -> String {
+        let header = format!("iteration,{}", self.columns.iter().format(","));
+        let lines = self
+            .iterations
+            .iter()
+            .enumerate()
+            .map(|(i, counts)| format!("{i},{}", counts.iter().format(",")));
+        std::iter::once(header).chain(lines).join("\n") + "\n"
+    }
+}
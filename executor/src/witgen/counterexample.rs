@@ -0,0 +1,71 @@
+use powdr_number::FieldElement;
+
+use super::residuals::ResidualReport;
+
+/// A reduced set of inputs for which the witness still fails validation,
+/// see [super::WitnessGenerator::reduce_counterexample].
+#[derive(Clone, Debug)]
+pub struct Counterexample<T> {
+    /// The reduced inputs, in their original order.
+    pub inputs: Vec<T>,
+    /// The witness generated from the reduced inputs, truncated after the row pair
+    /// of the first failure.
+    pub trace: Vec<(String, Vec<T>)>,
+    /// The validation failures of the (full) witness generated from the reduced inputs.
+    pub report: ResidualReport<T>,
+}
+
+/// Reduces `inputs` to a 1-minimal subsequence for which `fails` still holds, using the
+/// delta debugging algorithm ("ddmin"), i.e. removing any single remaining input makes
+/// `fails` return false. `fails` is assumed to hold for `inputs`.
+pub fn delta_debug<I: Clone>(inputs: &[I], fails: impl Fn(&[I]) -> bool) -> Vec<I> {
+    let mut inputs = inputs.to_vec();
+    let mut granularity = 2;
+    while inputs.len() >= 2 {
+        let chunk_size = inputs.len().div_ceil(granularity);
+        let chunks = (0..inputs.len())
+            .step_by(chunk_size)
+            .map(|start| start..(start + chunk_size).min(inputs.len()))
+            .collect::<Vec<_>>();
+        let mut subsets = chunks.iter().map(|chunk| inputs[chunk.clone()].to_vec());
+        let mut complements = chunks.iter().map(|chunk| {
+            inputs[..chunk.start]
+                .iter()
+                .chain(&inputs[chunk.end..])
+                .cloned()
+                .collect::<Vec<_>>()
+        });
+        if let Some(subset) = subsets.find(|subset| fails(subset)) {
+            inputs = subset;
+            granularity = 2;
+        } else if let Some(complement) = complements.find(|c| fails(c)) {
+            inputs = complement;
+            granularity = (granularity - 1).max(2);
+        } else if granularity < inputs.len() {
+            granularity = (granularity * 2).min(inputs.len());
+        } else {
+            break;
+        }
+    }
+    inputs
+}
+
+/// Truncates the witness after the row pair of the first failure of the report.
+pub fn failure_prefix<T: FieldElement>(
+    witness: &[(String, Vec<T>)],
+    report: &ResidualReport<T>,
+) -> Vec<(String, Vec<T>)> {
+    let first_row = report
+        .failures
+        .iter()
+        .map(|failure| failure.row)
+        .min()
+        .unwrap_or_default();
+    witness
+        .iter()
+        .map(|(name, values)| {
+            let len = (first_row as usize + 2).min(values.len());
+            (name.clone(), values[..len].to_vec())
+        })
+        .collect()
+}
@@ -90,6 +90,10 @@ impl<V, T: PolynomialTypeTrait> ColumnMap<V, T> {
         self.values.iter()
     }
 
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut V> {
+        self.values.iter_mut()
+    }
+
     pub fn len(&self) -> usize {
         self.values.len()
     }
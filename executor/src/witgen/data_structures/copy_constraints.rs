@@ -2,7 +2,6 @@ use std::collections::BTreeMap;
 #[cfg(test)]
 use std::collections::BTreeSet;
 
-#[cfg(test)]
 use itertools::Itertools;
 
 /// Global copy constraints, represented as a graph on all cells. Each node has one outgoing edge
@@ -22,7 +21,6 @@ impl<CellId: Ord + Copy> Default for CopyConstraints<CellId> {
 
 impl<CellId: Ord + Copy> CopyConstraints<CellId> {
     /// Creates a new set of copy constraints from a list of pairs of cells that should be equal.
-    #[cfg(test)]
     pub fn new(constraint_pairs: &[(CellId, CellId)]) -> Self {
         let mut copy_constraints = CopyConstraints::default();
         for &(a, b) in constraint_pairs {
@@ -31,7 +29,6 @@ impl<CellId: Ord + Copy> CopyConstraints<CellId> {
         copy_constraints
     }
 
-    #[cfg(test)]
     fn add_copy_constraint(&mut self, a: CellId, b: CellId) {
         if self.are_connected(a, b) {
             // The algorithm below does not work if the two cells are already connected.
@@ -59,7 +56,11 @@ impl<CellId: Ord + Copy> CopyConstraints<CellId> {
         self.edges.is_empty()
     }
 
-    #[cfg(test)]
+    /// Returns all cells that are copy-constrained to at least one other cell.
+    pub fn cells(&self) -> impl Iterator<Item = CellId> + '_ {
+        self.edges.keys().copied()
+    }
+
     fn are_connected(&self, a: CellId, b: CellId) -> bool {
         self.iter_equivalence_class(a).contains(&b)
     }
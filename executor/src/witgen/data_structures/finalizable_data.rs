@@ -1,13 +1,17 @@
 use std::{
-    collections::HashSet,
+    collections::{BTreeMap, HashSet},
     ops::{Index, IndexMut},
 };
 
 use bit_vec::BitVec;
-use powdr_ast::analyzed::PolyID;
+use powdr_ast::analyzed::{PolyID, PolynomialType};
 use powdr_number::FieldElement;
 
-use crate::witgen::rows::Row;
+use crate::witgen::data_structures::column_map::WitnessColumnMap;
+use crate::witgen::processor::Provenance;
+use crate::witgen::rows::{Cell, CellValue, Row};
+use crate::witgen::witness_snapshot::{SnapshotReader, SnapshotWriter};
+use crate::witgen::FixedData;
 
 /// A row entry in [FinalizableData].
 #[derive(Clone)]
@@ -18,6 +22,9 @@ enum Entry<'a, T: FieldElement> {
     /// stored in [FinalizableData]) and a bit vector indicating which cells are known.
     /// The value of unknown cells should be ignored.
     Finalized(Vec<T>, BitVec),
+    /// A finalized row in which all cells are known, with the values stored in
+    /// [FinalizableData::compacted_values], starting at the given index.
+    Compacted(usize),
 }
 
 /// A data structure that stores rows of a witness table, and behaves much like a `Vec<Row<T>>`.
@@ -31,6 +38,15 @@ pub struct FinalizableData<'a, T: FieldElement> {
     data: Vec<Entry<'a, T>>,
     /// The list of column IDs (in sorted order), used to index finalized rows.
     column_ids: Vec<PolyID>,
+    /// The number of rows that are still in progress.
+    rows_in_progress: usize,
+    /// The values of all compacted rows (see [FinalizableData::compact_known_rows]).
+    compacted_values: Vec<T>,
+    /// The number of compacted rows.
+    compacted_rows: usize,
+    /// How the cells were determined, by row index and column, if provenance is tracked.
+    /// Kept when rows are finalized.
+    provenance: BTreeMap<usize, BTreeMap<PolyID, Provenance>>,
 }
 
 impl<'a, T: FieldElement> FinalizableData<'a, T> {
@@ -45,7 +61,14 @@ impl<'a, T: FieldElement> FinalizableData<'a, T> {
         let mut column_ids = column_ids.iter().cloned().collect::<Vec<_>>();
         column_ids.sort();
         let data = rows.map(Entry::InProgress).collect::<Vec<_>>();
-        Self { data, column_ids }
+        Self {
+            rows_in_progress: data.len(),
+            data,
+            column_ids,
+            compacted_values: vec![],
+            compacted_rows: 0,
+            provenance: BTreeMap::new(),
+        }
     }
 
     pub fn len(&self) -> usize {
@@ -56,44 +79,154 @@ impl<'a, T: FieldElement> FinalizableData<'a, T> {
         self.data.is_empty()
     }
 
+    /// Returns the number of rows that have not been finalized yet.
+    pub fn rows_in_progress(&self) -> usize {
+        self.rows_in_progress
+    }
+
+    /// Estimates the number of bytes used by the rows, not counting heap allocations
+    /// of range constraints.
+    pub fn estimated_bytes(&self) -> usize {
+        let columns = self.column_ids.len();
+        let finalized_rows = self.data.len() - self.rows_in_progress - self.compacted_rows;
+        self.rows_in_progress * columns * std::mem::size_of::<Cell<'a, T>>()
+            + finalized_rows * (columns * std::mem::size_of::<T>() + columns.div_ceil(8))
+            + self.compacted_values.len() * std::mem::size_of::<T>()
+            + self.data.len() * std::mem::size_of::<Entry<'a, T>>()
+    }
+
     pub fn push(&mut self, row: Row<'a, T>) {
         self.data.push(Entry::InProgress(row));
+        self.rows_in_progress += 1;
     }
 
     pub fn pop(&mut self) -> Option<Row<'a, T>> {
+        if let Some(last) = self.data.len().checked_sub(1) {
+            self.provenance.remove(&last);
+        }
         match self.data.pop() {
-            Some(Entry::InProgress(row)) => Some(row),
-            Some(Entry::Finalized(_, _)) => panic!("Row already finalized."),
+            Some(Entry::InProgress(row)) => {
+                self.rows_in_progress -= 1;
+                Some(row)
+            }
+            Some(Entry::Finalized(..) | Entry::Compacted(_)) => panic!("Row already finalized."),
             None => None,
         }
     }
 
     pub fn extend(&mut self, other: Self) {
-        self.data.extend(other.data);
+        let row_offset = self.data.len();
+        self.provenance.extend(
+            other
+                .provenance
+                .into_iter()
+                .map(|(i, provenance)| (row_offset + i, provenance)),
+        );
+        let offset = self.compacted_values.len();
+        self.data
+            .extend(other.data.into_iter().map(|entry| match entry {
+                Entry::Compacted(start) => Entry::Compacted(offset + start),
+                entry => entry,
+            }));
+        self.compacted_values.extend(other.compacted_values);
+        self.rows_in_progress += other.rows_in_progress;
+        self.compacted_rows += other.compacted_rows;
     }
 
     pub fn remove(&mut self, i: usize) -> Row<'a, T> {
+        let following = self.provenance.split_off(&i);
+        self.provenance.extend(
+            following
+                .into_iter()
+                .filter(|(row, _)| *row != i)
+                .map(|(row, provenance)| (row - 1, provenance)),
+        );
         match self.data.remove(i) {
-            Entry::InProgress(row) => row,
-            Entry::Finalized(_, _) => panic!("Row {i} already finalized."),
+            Entry::InProgress(row) => {
+                self.rows_in_progress -= 1;
+                row
+            }
+            Entry::Finalized(..) | Entry::Compacted(_) => panic!("Row {i} already finalized."),
         }
     }
 
+    /// Removes all rows from index `len` on, including the values of removed compacted rows.
     pub fn truncate(&mut self, len: usize) {
+        if len >= self.data.len() {
+            return;
+        }
+        self.rows_in_progress -= self.data[len..]
+            .iter()
+            .filter(|entry| matches!(entry, Entry::InProgress(_)))
+            .count();
+        let removed_compacted_rows = self.data[len..]
+            .iter()
+            .filter(|entry| matches!(entry, Entry::Compacted(_)))
+            .count();
         self.data.truncate(len);
+        self.provenance.split_off(&len);
+        if removed_compacted_rows > 0 {
+            self.compacted_rows -= removed_compacted_rows;
+            self.repack_compacted_values();
+        }
+    }
+
+    /// Rebuilds [FinalizableData::compacted_values] from the values of the remaining
+    /// compacted rows, dropping the values of rows that were removed.
+    fn repack_compacted_values(&mut self) {
+        let columns = self.column_ids.len();
+        let mut compacted_values = Vec::with_capacity(self.compacted_rows * columns);
+        for entry in &mut self.data {
+            if let Entry::Compacted(start) = entry {
+                let new_start = compacted_values.len();
+                compacted_values
+                    .extend_from_slice(&self.compacted_values[*start..*start + columns]);
+                *start = new_start;
+            }
+        }
+        self.compacted_values = compacted_values;
+    }
+
+    /// Records how the given cell was determined. The provenance is kept when the row
+    /// is finalized and can be retrieved with [FinalizableData::take_provenance].
+    pub fn set_provenance(&mut self, i: usize, poly_id: PolyID, provenance: Provenance) {
+        self.provenance
+            .entry(i)
+            .or_default()
+            .insert(poly_id, provenance);
+    }
+
+    /// Returns how the given cell was determined, if it was recorded with
+    /// [FinalizableData::set_provenance].
+    pub fn provenance(&self, i: usize, poly_id: &PolyID) -> Option<Provenance> {
+        self.provenance.get(&i)?.get(poly_id).copied()
+    }
+
+    /// Takes the provenance of the cells recorded with [FinalizableData::set_provenance],
+    /// by row index and column.
+    pub fn take_provenance(&mut self) -> BTreeMap<(usize, PolyID), Provenance> {
+        std::mem::take(&mut self.provenance)
+            .into_iter()
+            .flat_map(|(i, row)| {
+                row.into_iter()
+                    .map(move |(poly_id, provenance)| ((i, poly_id), provenance))
+            })
+            .collect()
     }
 
     pub fn get_mut(&mut self, i: usize) -> Option<&mut Row<'a, T>> {
         match &mut self.data[i] {
             Entry::InProgress(row) => Some(row),
-            Entry::Finalized(_, _) => panic!("Row {i} already finalized."),
+            Entry::Finalized(..) | Entry::Compacted(_) => panic!("Row {i} already finalized."),
         }
     }
 
     pub fn last(&self) -> Option<&Row<'a, T>> {
         match self.data.last() {
             Some(Entry::InProgress(row)) => Some(row),
-            Some(Entry::Finalized(_, _)) => panic!("Last row already finalized."),
+            Some(Entry::Finalized(..) | Entry::Compacted(_)) => {
+                panic!("Last row already finalized.")
+            }
             None => None,
         }
     }
@@ -116,6 +249,7 @@ impl<'a, T: FieldElement> FinalizableData<'a, T> {
                 .map(|c| (row[c].value.unwrap_or_default(), row[c].value.is_known()))
                 .unzip();
             self.data[i] = Entry::Finalized(values, known_cells);
+            self.rows_in_progress -= 1;
             true
         } else {
             false
@@ -128,6 +262,183 @@ impl<'a, T: FieldElement> FinalizableData<'a, T> {
         }
     }
 
+    /// Compacts those of the given rows that are finalized and fully known: Their values
+    /// are moved into a single buffer shared by all compacted rows, which frees the memory
+    /// of the row and of the information which cells are known. Compacted rows can still
+    /// be read (e.g. by [FinalizableData::known_columns]), but not modified.
+    /// Rows in progress and rows with unknown cells are left unchanged.
+    pub fn compact_known_rows(&mut self, range: impl Iterator<Item = usize>) {
+        for i in range {
+            if let Entry::Finalized(values, known_cells) = &self.data[i] {
+                if known_cells.all() {
+                    let start = self.compacted_values.len();
+                    self.compacted_values.extend_from_slice(values);
+                    self.data[i] = Entry::Compacted(start);
+                    self.compacted_rows += 1;
+                }
+            }
+        }
+    }
+
+    /// Returns the value of the cell in the given column (index into [FinalizableData::column_ids])
+    /// of the given row, or None if the cell is not known.
+    fn known_value(&self, i: usize, column_index: usize) -> Option<T> {
+        match &self.data[i] {
+            Entry::InProgress(row) => match row[&self.column_ids[column_index]].value {
+                CellValue::Known(value) => Some(value),
+                _ => None,
+            },
+            Entry::Finalized(values, known_cells) => known_cells
+                .get(column_index)
+                .unwrap()
+                .then_some(values[column_index]),
+            Entry::Compacted(start) => Some(self.compacted_values[start + column_index]),
+        }
+    }
+
+    /// Like [FinalizableData::finalize_range], but also passes the values of the rows that
+    /// were newly finalized to `sink` (column ID, row, value), in column-major order.
+    /// Unknown cells are passed as zero.
+    pub fn finalize_range_into(
+        &mut self,
+        range: impl Iterator<Item = usize>,
+        mut sink: impl FnMut(PolyID, usize, T),
+    ) {
+        let rows = range.filter(|i| self.finalize(*i)).collect::<Vec<_>>();
+        for (column_index, poly_id) in self.column_ids.iter().enumerate() {
+            for &i in &rows {
+                sink(
+                    *poly_id,
+                    i,
+                    self.known_value(i, column_index).unwrap_or_default(),
+                );
+            }
+        }
+    }
+
+    /// Returns the values of all rows (finalized or not) as a list of columns,
+    /// with None for unknown cells.
+    pub fn known_columns(&self) -> impl Iterator<Item = (PolyID, Vec<Option<T>>)> + '_ {
+        self.column_ids
+            .iter()
+            .enumerate()
+            .map(move |(column_index, poly_id)| {
+                let values = (0..self.data.len())
+                    .map(|i| self.known_value(i, column_index))
+                    .collect();
+                (*poly_id, values)
+            })
+    }
+
+    /// Returns the (row, column) of all cells that are not known, in row-major order.
+    /// Range-constrained cells of rows in progress are only included if
+    /// `include_range_constrained` is set. Finalized rows do not keep range constraints,
+    /// so all their unknown cells are included.
+    pub fn unknown_cells(
+        &self,
+        include_range_constrained: bool,
+    ) -> impl Iterator<Item = (usize, PolyID)> + '_ {
+        self.data.iter().enumerate().flat_map(move |(i, entry)| {
+            self.column_ids
+                .iter()
+                .enumerate()
+                .filter(move |(column_index, poly_id)| match entry {
+                    Entry::InProgress(row) => match row[poly_id].value {
+                        CellValue::Known(_) => false,
+                        CellValue::RangeConstraint(_) => include_range_constrained,
+                        CellValue::Unknown => true,
+                    },
+                    Entry::Finalized(_, known_cells) => !known_cells[*column_index],
+                    Entry::Compacted(_) => false,
+                })
+                .map(move |(_, poly_id)| (i, *poly_id))
+        })
+    }
+
+    /// Writes the column IDs and all rows. Rows in progress are written with all their
+    /// cells (i.e. all witness columns), including range constraints.
+    pub fn write_snapshot(&self, writer: &mut SnapshotWriter) {
+        writer.write_u64(self.column_ids.len() as u64);
+        for poly_id in &self.column_ids {
+            writer.write_u64(poly_id.id);
+        }
+        writer.write_u64(self.data.len() as u64);
+        for entry in &self.data {
+            match entry {
+                Entry::InProgress(row) => {
+                    writer.write_u8(0);
+                    for cell in row.values() {
+                        writer.write_cell_value(&cell.value);
+                    }
+                }
+                Entry::Finalized(values, known_cells) => {
+                    writer.write_u8(1);
+                    writer.write_bits(known_cells);
+                    for value in values {
+                        writer.write_field(*value);
+                    }
+                }
+                // Compacted rows are read back as finalized rows.
+                Entry::Compacted(start) => {
+                    writer.write_u8(1);
+                    writer.write_bits(&BitVec::from_elem(self.column_ids.len(), true));
+                    for value in &self.compacted_values[*start..*start + self.column_ids.len()] {
+                        writer.write_field(*value);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Reads data written by [FinalizableData::write_snapshot].
+    pub fn read_snapshot(
+        reader: &mut SnapshotReader,
+        fixed_data: &'a FixedData<'a, T>,
+    ) -> Result<Self, String> {
+        let column_ids = (0..reader.read_u64()?)
+            .map(|_| {
+                Ok(PolyID {
+                    id: reader.read_u64()?,
+                    ptype: PolynomialType::Committed,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let mut rows_in_progress = 0;
+        let data = (0..reader.read_u64()?)
+            .map(|_| match reader.read_u8()? {
+                0 => {
+                    rows_in_progress += 1;
+                    let cells = fixed_data
+                        .witness_cols
+                        .keys()
+                        .map(|poly_id| {
+                            let value = reader.read_cell_value()?;
+                            Ok(Cell::new(fixed_data.column_name(&poly_id), value))
+                        })
+                        .collect::<Result<Vec<_>, String>>()?;
+                    Ok(Entry::InProgress(WitnessColumnMap::from(cells.into_iter())))
+                }
+                1 => {
+                    let known_cells = reader.read_bits(column_ids.len())?;
+                    let values = column_ids
+                        .iter()
+                        .map(|_| reader.read_field())
+                        .collect::<Result<Vec<_>, String>>()?;
+                    Ok(Entry::Finalized(values, known_cells))
+                }
+                tag => Err(format!("Invalid row tag {tag} in witness snapshot.")),
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(Self {
+            data,
+            column_ids,
+            rows_in_progress,
+            compacted_values: vec![],
+            compacted_rows: 0,
+            provenance: BTreeMap::new(),
+        })
+    }
+
     /// Takes all data out of the [FinalizableData] and returns it as a list of columns.
     /// Columns are represented as a tuple of:
     /// - A list of values
@@ -151,6 +462,8 @@ impl<'a, T: FieldElement> FinalizableData<'a, T> {
         let mut columns = vec![Vec::with_capacity(self.data.len()); self.column_ids.len()];
         let mut known_cells_col =
             vec![BitVec::with_capacity(self.data.len()); self.column_ids.len()];
+        let compacted_values = std::mem::take(&mut self.compacted_values);
+        self.compacted_rows = 0;
         for row in std::mem::take(&mut self.data) {
             match row {
                 Entry::InProgress(_) => unreachable!(),
@@ -162,6 +475,12 @@ impl<'a, T: FieldElement> FinalizableData<'a, T> {
                         columns[col_index].push(value);
                     }
                 }
+                Entry::Compacted(start) => {
+                    for (col_index, column) in columns.iter_mut().enumerate() {
+                        known_cells_col[col_index].push(true);
+                        column.push(compacted_values[start + col_index]);
+                    }
+                }
             }
         }
 
@@ -183,7 +502,9 @@ impl<'a, T: FieldElement> Index<usize> for FinalizableData<'a, T> {
     fn index(&self, index: usize) -> &Self::Output {
         match &self.data[index] {
             Entry::InProgress(row) => row,
-            Entry::Finalized(_, _) => panic!("Row {index} already finalized."),
+            Entry::Finalized(..) | Entry::Compacted(_) => {
+                panic!("Row {index} already finalized.")
+            }
         }
     }
 }
@@ -192,7 +513,119 @@ impl<'a, T: FieldElement> IndexMut<usize> for FinalizableData<'a, T> {
     fn index_mut(&mut self, index: usize) -> &mut Self::Output {
         match &mut self.data[index] {
             Entry::InProgress(row) => row,
-            Entry::Finalized(_, _) => panic!("Row {index} already finalized."),
+            Entry::Finalized(..) | Entry::Compacted(_) => {
+                panic!("Row {index} already finalized.")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use powdr_number::GoldilocksField;
+    use powdr_pil_analyzer::analyze_string;
+
+    use crate::constant_evaluator::generate;
+    use crate::witgen::rows::RowIndex;
+
+    use super::*;
+
+    fn with_data(f: impl Fn(FinalizableData<GoldilocksField>)) {
+        let analyzed = analyze_string("namespace main(4); col witness x, y;");
+        let constants = generate(&analyzed);
+        let fixed_data = FixedData::new(&analyzed, &constants, &[], Default::default(), 0);
+        let columns = fixed_data.witness_cols.keys().collect::<Vec<_>>();
+        let mut data = FinalizableData::with_initial_rows_in_progress(
+            &columns.iter().copied().collect(),
+            (0..4).map(|i| Row::fresh(&fixed_data, RowIndex::from_degree(i, 4))),
+        );
+        // All cells are known, except for `y` in the last row.
+        for i in 0..4 {
+            data[i][&columns[0]].value = CellValue::Known(GoldilocksField::from(i as u64));
+            if i < 3 {
+                data[i][&columns[1]].value = CellValue::Known(GoldilocksField::from(10 * i as u64));
+            }
         }
+        data.finalize_range(0..4);
+        f(data)
+    }
+
+    #[test]
+    fn compact_known_rows() {
+        with_data(|mut data| {
+            let values = data.known_columns().collect::<Vec<_>>();
+            let bytes = data.estimated_bytes();
+
+            data.compact_known_rows(0..4);
+            assert_eq!(data.compacted_rows, 3);
+            assert!(data.estimated_bytes() < bytes);
+            assert_eq!(data.known_columns().collect::<Vec<_>>(), values);
+            assert_eq!(data.unknown_cells(true).count(), 1);
+
+            for ((poly_id, (column, known_cells)), (expected_poly_id, expected)) in
+                data.take_transposed().zip(values)
+            {
+                assert_eq!(poly_id, expected_poly_id);
+                let column = column
+                    .into_iter()
+                    .zip(&known_cells)
+                    .map(|(value, is_known)| is_known.then_some(value))
+                    .collect::<Vec<_>>();
+                assert_eq!(column, expected);
+            }
+        });
+    }
+
+    #[test]
+    fn truncate_compacted_rows() {
+        with_data(|mut data| {
+            let values = data.known_columns().collect::<Vec<_>>();
+            // Compact out of order, so that the values of row 2 are not at the end.
+            data.compact_known_rows([2, 0, 1].into_iter());
+            assert_eq!(data.compacted_values.len(), 3 * data.column_ids.len());
+
+            data.truncate(2);
+            assert_eq!(data.len(), 2);
+            assert_eq!(data.compacted_rows, 2);
+            assert_eq!(data.compacted_values.len(), 2 * data.column_ids.len());
+            let truncated = values
+                .into_iter()
+                .map(|(poly_id, column)| (poly_id, column[..2].to_vec()))
+                .collect::<Vec<_>>();
+            assert_eq!(data.known_columns().collect::<Vec<_>>(), truncated);
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "Row 1 already finalized.")]
+    fn compacted_rows_cannot_be_modified() {
+        with_data(|mut data| {
+            data.compact_known_rows(0..4);
+            data.get_mut(1);
+        });
+    }
+
+    #[test]
+    fn provenance_is_kept() {
+        with_data(|mut data| {
+            let x = data.column_ids[0];
+            data.set_provenance(1, x, Provenance::Query);
+            data.set_provenance(3, x, Provenance::Input);
+            data.compact_known_rows(0..4);
+
+            // Extending re-indexes the provenance of the appended rows.
+            data.extend(data.clone());
+            data.truncate(6);
+            assert_eq!(data.provenance(1, &x), Some(Provenance::Query));
+            assert_eq!(data.provenance(5, &x), Some(Provenance::Query));
+            assert_eq!(
+                data.take_provenance().into_iter().collect::<Vec<_>>(),
+                vec![
+                    ((1, x), Provenance::Query),
+                    ((3, x), Provenance::Input),
+                    ((5, x), Provenance::Query)
+                ]
+            );
+        });
     }
 }
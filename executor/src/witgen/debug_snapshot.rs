@@ -0,0 +1,73 @@
+use std::collections::BTreeMap;
+
+use powdr_number::{DegreeType, FieldElement};
+
+/// A read-only copy of the state of a processor, taken for inspection while debugging
+/// (see `Processor::debug_snapshot`). Unlike the processor itself, it does not borrow
+/// anything, so it can be kept around and explored after processing continued.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DebugSnapshot<T> {
+    /// The (global) row of the first row of the snapshot.
+    pub row_offset: DegreeType,
+    /// The values of the columns of the machine, None for unknown cells.
+    pub columns: BTreeMap<String, Vec<Option<T>>>,
+    /// The state of the outer queries answered together, if any.
+    pub outer_queries: Vec<OuterQuerySnapshot<T>>,
+    /// The inputs set on the processor.
+    pub inputs: Vec<(String, T)>,
+}
+
+/// Called with a [DebugSnapshot] of the processor of a VM machine in which witness
+/// generation fails, before panicking (see [super::WitgenOptions::failure_inspector]),
+/// e.g. to explore the state in a REPL.
+pub trait FailureInspector<T>: Fn(&DebugSnapshot<T>) + Send + Sync {}
+impl<T, F> FailureInspector<T> for F where F: Fn(&DebugSnapshot<T>) + Send + Sync {}
+
+/// The state of one of the outer queries in a [DebugSnapshot].
+#[derive(Clone, Debug, PartialEq)]
+pub struct OuterQuerySnapshot<T> {
+    /// The connecting identity.
+    pub identity: String,
+    /// The values of the left side of the connecting identity, None if not known yet.
+    pub left: Vec<Option<T>>,
+}
+
+impl<T: FieldElement> DebugSnapshot<T> {
+    /// Returns the number of rows of the snapshot.
+    pub fn len(&self) -> usize {
+        self.columns
+            .values()
+            .next()
+            .map_or(0, |values| values.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the value of the given column in the given (global) row, or None if it is
+    /// unknown or not part of the snapshot.
+    pub fn value(&self, column: &str, row: DegreeType) -> Option<T> {
+        let values = self.columns.get(column)?;
+        let index = row.checked_sub(self.row_offset)?;
+        values.get(usize::try_from(index).ok()?).copied().flatten()
+    }
+
+    /// Returns whether the value of the given column in the given (global) row is known.
+    pub fn is_known(&self, column: &str, row: DegreeType) -> bool {
+        self.value(column, row).is_some()
+    }
+
+    /// Returns, for each row, the names of the columns that are known in that row.
+    pub fn known_cells(&self) -> Vec<Vec<&str>> {
+        (0..self.len())
+            .map(|i| {
+                self.columns
+                    .iter()
+                    .filter(|(_, values)| values[i].is_some())
+                    .map(|(name, _)| name.as_str())
+                    .collect()
+            })
+            .collect()
+    }
+}
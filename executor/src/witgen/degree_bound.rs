@@ -0,0 +1,145 @@
+use std::fmt::{self, Display};
+
+use powdr_ast::analyzed::{
+    AlgebraicBinaryOperation, AlgebraicBinaryOperator, AlgebraicExpression as Expression,
+    AlgebraicUnaryOperation, Analyzed, Identity, IdentityKind,
+};
+use powdr_ast::parsed::SelectedExpressions;
+use powdr_number::{DegreeType, FieldElement};
+
+use super::identities_for_stage;
+
+/// A reason why a witness cannot be handed to a backend that supports constraints up to
+/// a maximum degree.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DegreeBoundViolation {
+    /// The trace degree is not a power of two.
+    TraceDegree { degree: DegreeType },
+    /// A witness column does not have one value per row.
+    ColumnLength {
+        column: String,
+        length: usize,
+        degree: DegreeType,
+    },
+    /// An identity has a higher degree than supported.
+    ConstraintDegree {
+        identity_id: u64,
+        identity: String,
+        degree: usize,
+        max_degree: usize,
+    },
+}
+
+impl Display for DegreeBoundViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DegreeBoundViolation::TraceDegree { degree } => write!(
+                f,
+                "The trace degree {degree} is not a power of two. \
+                 Change the degree of the namespaces to {}.",
+                degree.next_power_of_two()
+            ),
+            DegreeBoundViolation::ColumnLength {
+                column,
+                length,
+                degree,
+            } => write!(
+                f,
+                "Column {column} has {length} values, but the trace degree is {degree}."
+            ),
+            DegreeBoundViolation::ConstraintDegree {
+                identity_id,
+                identity,
+                degree,
+                max_degree,
+            } => write!(
+                f,
+                "Identity {identity_id} has degree {degree}, but the maximum constraint \
+                 degree is {max_degree}: {identity}\n\
+                 Introduce intermediate witness columns to reduce its degree."
+            ),
+        }
+    }
+}
+
+/// Checks that a generated witness is compatible with a backend that supports
+/// constraints up to the given degree: The trace degree has to be a power of two,
+/// all witness columns have to be of that length and no identity (of the first stage)
+/// can exceed the maximum constraint degree.
+/// @returns the first violation found.
+pub fn check_degree_bound<T: FieldElement>(
+    analyzed: &Analyzed<T>,
+    witness: &[(String, Vec<T>)],
+    max_degree: usize,
+) -> Result<(), DegreeBoundViolation> {
+    let degree = analyzed.degree();
+    if !degree.is_power_of_two() {
+        return Err(DegreeBoundViolation::TraceDegree { degree });
+    }
+    if let Some((column, values)) = witness
+        .iter()
+        .find(|(_, values)| values.len() as DegreeType != degree)
+    {
+        return Err(DegreeBoundViolation::ColumnLength {
+            column: column.clone(),
+            length: values.len(),
+            degree,
+        });
+    }
+    identities_for_stage(analyzed, 0)
+        .iter()
+        .map(|identity| (identity, identity_degree(identity)))
+        .find(|(_, degree)| *degree > max_degree)
+        .map_or(Ok(()), |(identity, degree)| {
+            Err(DegreeBoundViolation::ConstraintDegree {
+                identity_id: identity.id,
+                identity: identity.to_string(),
+                degree,
+                max_degree,
+            })
+        })
+}
+
+/// Returns the degree of the identity, i.e. the maximum degree of its (selected) expressions.
+fn identity_degree<T: FieldElement>(identity: &Identity<Expression<T>>) -> usize {
+    match identity.kind {
+        IdentityKind::Polynomial => expression_degree(identity.expression_for_poly_id()),
+        _ => std::cmp::max(
+            selected_expressions_degree(&identity.left),
+            selected_expressions_degree(&identity.right),
+        ),
+    }
+}
+
+fn selected_expressions_degree<T: FieldElement>(
+    selected: &SelectedExpressions<Expression<T>>,
+) -> usize {
+    let selector_degree = selected.selector.as_ref().map_or(0, expression_degree);
+    selected
+        .expressions
+        .iter()
+        .map(|expression| selector_degree + expression_degree(expression))
+        .max()
+        .unwrap_or(selector_degree)
+}
+
+fn expression_degree<T: FieldElement>(expression: &Expression<T>) -> usize {
+    match expression {
+        Expression::Reference(_) => 1,
+        Expression::PublicReference(_) | Expression::Challenge(_) | Expression::Number(_) => 0,
+        Expression::BinaryOperation(AlgebraicBinaryOperation { left, op, right }) => {
+            let left_degree = expression_degree(left);
+            match op {
+                AlgebraicBinaryOperator::Add | AlgebraicBinaryOperator::Sub => {
+                    std::cmp::max(left_degree, expression_degree(right))
+                }
+                AlgebraicBinaryOperator::Mul => left_degree + expression_degree(right),
+                AlgebraicBinaryOperator::Pow => match right.as_ref() {
+                    Expression::Number(exponent) => left_degree * exponent.to_degree() as usize,
+                    _ => unreachable!("Exponents have to be constant."),
+                },
+            }
+        }
+        Expression::UnaryOperation(AlgebraicUnaryOperation { expr, .. }) => expression_degree(expr),
+    }
+}
@@ -0,0 +1,119 @@
+use bit_vec::BitVec;
+use powdr_ast::analyzed::PolyID;
+use powdr_number::{DegreeType, FieldElement};
+
+use super::{FixedData, TieBreaker};
+
+/// Declares the cells of a witness column as "don't care", i.e. they are not expected to
+/// be determined during witness generation. If a selector (a fixed column) is given,
+/// only cells in rows where the selector is nonzero are "don't care".
+#[derive(Clone, Debug)]
+pub struct DontCare {
+    pub column: PolyID,
+    pub selector: Option<PolyID>,
+}
+
+impl DontCare {
+    fn applies_to<T: FieldElement>(
+        &self,
+        fixed_data: &FixedData<T>,
+        column: &PolyID,
+        row: DegreeType,
+    ) -> bool {
+        self.column == *column
+            && self
+                .selector
+                .map(|selector| !fixed_data.fixed_cols[&selector].value(row).is_zero())
+                .unwrap_or(true)
+    }
+}
+
+/// Sets the cells of a finalized column that were never written and are "don't care"
+/// to the configured default value, or to the value chosen by the tie breaker, if given.
+/// @returns the number of cells that were never written and are not "don't care".
+pub fn fill_unwritten_cells<T: FieldElement>(
+    fixed_data: &FixedData<T>,
+    column: &PolyID,
+    values: &mut [T],
+    known_cells: &BitVec,
+    tie_breaker: Option<&TieBreaker>,
+) -> usize {
+    let mut unwritten = 0;
+    for (row, (value, is_known)) in values.iter_mut().zip(known_cells).enumerate() {
+        if is_known {
+            continue;
+        }
+        let row = row as DegreeType;
+        if fixed_data
+            .dont_care
+            .iter()
+            .any(|dont_care| dont_care.applies_to(fixed_data, column, row))
+        {
+            *value = match tie_breaker {
+                Some(tie_breaker) => tie_breaker.choose(column, row),
+                None => fixed_data.dont_care_value,
+            };
+        } else {
+            unwritten += 1;
+        }
+    }
+    unwritten
+}
+
+#[cfg(test)]
+mod test {
+    use powdr_number::GoldilocksField;
+    use powdr_pil_analyzer::analyze_string;
+
+    use super::*;
+
+    #[test]
+    fn only_unwritten_dont_care_cells_are_filled() {
+        let src = r#"
+            namespace main(4);
+                col fixed SEL = [0, 1, 1, 0];
+                col witness x, y;
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let constants = vec![(
+            "main.SEL".to_string(),
+            [0, 1, 1, 0]
+                .into_iter()
+                .map(GoldilocksField::from)
+                .collect(),
+        )];
+        let fixed_data = FixedData::new(&analyzed, &constants, &[], Default::default(), 0);
+        let x = fixed_data.try_column_by_name("main.x").unwrap();
+        let y = fixed_data.try_column_by_name("main.y").unwrap();
+        let selector = fixed_data.try_column_by_name("main.SEL");
+        let fixed_data = fixed_data.with_dont_care(
+            vec![
+                DontCare {
+                    column: x,
+                    selector: None,
+                },
+                DontCare {
+                    column: y,
+                    selector,
+                },
+            ],
+            9.into(),
+        );
+        let known_cells = BitVec::from_fn(4, |row| row == 0);
+        let fill = |column| {
+            let mut values = vec![GoldilocksField::from(5); 4];
+            let unwritten =
+                fill_unwritten_cells(&fixed_data, &column, &mut values, &known_cells, None);
+            (
+                values
+                    .into_iter()
+                    .map(|v| v.to_degree())
+                    .collect::<Vec<_>>(),
+                unwritten,
+            )
+        };
+
+        assert_eq!(fill(x), (vec![5, 9, 9, 9], 0));
+        assert_eq!(fill(y), (vec![5, 9, 9, 5], 1));
+    }
+}
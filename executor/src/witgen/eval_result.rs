@@ -1,9 +1,11 @@
 use std::fmt::{self, Debug};
 
+use lazy_static::lazy_static;
 use powdr_ast::analyzed::AlgebraicReference;
-use powdr_number::FieldElement;
+use powdr_number::{DegreeType, FieldElement};
 
 use super::range_constraints::RangeConstraint;
+use super::util::json_string;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum IncompleteCause<K = usize> {
@@ -166,6 +168,8 @@ pub type EvalResult<'a, T, K = &'a AlgebraicReference> = Result<EvalValue<K, T>,
 pub enum EvalError<T: FieldElement> {
     /// We ran out of rows
     RowsExhausted(String),
+    /// The machine with the given name processed more identities than its budget allows.
+    BudgetExceeded(String),
     /// A constraint that cannot be satisfied (i.e. 2 = 1).
     ConstraintUnsatisfiable(String),
     /// Conflicting bit- or range constraints in an equation, i.e. for X = 0x100, where X is known to be at most 0xff.
@@ -174,12 +178,149 @@ pub enum EvalError<T: FieldElement> {
     InvalidDivision,
     /// Fixed lookup failed
     FixedLookupFailed(Vec<(String, T)>),
+    /// A value propagated along a copy constraint does not match the value of the
+    /// fixed column cell it is copy-constrained to.
+    FixedCopyConstraintViolated {
+        column: String,
+        row: DegreeType,
+        fixed_value: T,
+        value: T,
+    },
+    /// A value propagated along a copy constraint conflicts with a cell of the
+    /// equivalence class, e.g. because the cell is already known to have another value.
+    CopyConstraintConflict {
+        value: T,
+        /// All cells (column, row) of the equivalence class with their values, if known.
+        class: Vec<(String, DegreeType, Option<T>)>,
+        cause: Box<EvalError<T>>,
+    },
+    /// An expression could not be set to the given value, e.g. because it is already
+    /// known to have a different value.
+    SetValueFailed {
+        expression: String,
+        row: DegreeType,
+        value: T,
+        cause: Box<EvalError<T>>,
+    },
+    /// Processing an identity on a row failed.
+    IdentityFailed(Box<IdentityFailure<T>>),
     /// Error getting information from the prover.
     ProverQueryError(String),
+    /// The query callback returned a value for a cell that its input validator rejects.
+    InvalidInput {
+        column: String,
+        row: DegreeType,
+        value: T,
+    },
+    /// The outer assignment observer rejected a value assigned to a cell of the caller.
+    OuterAssignmentRejected {
+        column: String,
+        row: DegreeType,
+        value: T,
+        reason: String,
+    },
     Generic(String),
     Multiple(Vec<EvalError<T>>),
 }
 
+/// The environment variable that switches the rendering of [IdentityFailure]s to JSON
+/// if it is set to `json`. It is read once, when the first failure is rendered.
+pub const DIAGNOSTICS_FORMAT_VAR: &str = "POWDR_WITGEN_DIAGNOSTICS";
+
+lazy_static! {
+    static ref JSON_DIAGNOSTICS: bool =
+        std::env::var(DIAGNOSTICS_FORMAT_VAR).is_ok_and(|format| format == "json");
+}
+
+/// An error that occurred while processing an identity, together with the known values
+/// of the rows it was processed on, e.g. for tooling that highlights the offending cells.
+/// By default, it is rendered as text, see [DIAGNOSTICS_FORMAT_VAR] and
+/// [IdentityFailure::to_json] for JSON.
+#[derive(Clone, PartialEq)]
+pub struct IdentityFailure<T: FieldElement> {
+    pub identity_id: u64,
+    pub identity: String,
+    /// The index of the row within the machine's rows being processed.
+    pub local_row: usize,
+    pub global_row: DegreeType,
+    /// The known values of the current row, nonzero values first.
+    pub current_row: Vec<(String, T)>,
+    /// The known values of the next row, if the identity references it.
+    pub next_row: Option<Vec<(String, T)>>,
+    /// The rendered provenance of the known values of the current row, if it is tracked.
+    pub provenance: Option<String>,
+    pub cause: EvalError<T>,
+}
+
+impl<T: FieldElement> IdentityFailure<T> {
+    /// Renders the failure as a JSON object. Values are rendered as strings, so that
+    /// large field elements are not rounded.
+    pub fn to_json(&self) -> String {
+        let values = |values: &[(String, T)]| {
+            let entries = values
+                .iter()
+                .map(|(name, value)| format!("{}:\"{value}\"", json_string(name)))
+                .collect::<Vec<_>>();
+            format!("{{{}}}", entries.join(","))
+        };
+        format!(
+            "{{\"identity_id\":{},\"identity\":{},\"local_row\":{},\"global_row\":{},\"current_row\":{},\"next_row\":{},\"provenance\":{},\"cause\":{}}}",
+            self.identity_id,
+            json_string(&self.identity),
+            self.local_row,
+            self.global_row,
+            values(&self.current_row),
+            self.next_row.as_deref().map_or("null".to_string(), values),
+            self.provenance.as_deref().map_or("null".to_string(), json_string),
+            json_string(&self.cause.to_string())
+        )
+    }
+
+    fn render_text(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let values = |values: &[(String, T)]| {
+            values
+                .iter()
+                .map(|(name, value)| format!("    {name} = {value}"))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        writeln!(f, "Error in identity: {}", self.identity)?;
+        writeln!(
+            f,
+            "Known values in current row (local: {}, global {}):\n{}",
+            self.local_row,
+            self.global_row,
+            values(&self.current_row)
+        )?;
+        if let Some(next_row) = &self.next_row {
+            writeln!(
+                f,
+                "Known values in next row (local: {}, global {}):\n{}",
+                self.local_row + 1,
+                self.global_row + 1,
+                values(next_row)
+            )?;
+        }
+        if let Some(provenance) = &self.provenance {
+            writeln!(
+                f,
+                "Provenance of known values in current row:\n{provenance}"
+            )?;
+        }
+        write!(f, "   => Error: {}", self.cause)
+    }
+}
+
+impl<T: FieldElement> fmt::Display for IdentityFailure<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if *JSON_DIAGNOSTICS {
+            f.write_str(&self.to_json())
+        } else {
+            self.render_text(f)
+        }
+    }
+}
+
 impl<T: FieldElement> Debug for EvalError<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         <Self as fmt::Display>::fmt(self, f)
@@ -227,6 +368,9 @@ impl<T: FieldElement> fmt::Display for EvalError<T> {
             EvalError::RowsExhausted(machine_name) => {
                 write!(f, "Table rows exhausted for machine {machine_name}")
             }
+            EvalError::BudgetExceeded(machine_name) => {
+                write!(f, "Identity processing budget exceeded for machine {machine_name}")
+            }
             EvalError::FixedLookupFailed(input_assignment) => {
                 let query = input_assignment
                     .iter()
@@ -238,9 +382,55 @@ impl<T: FieldElement> fmt::Display for EvalError<T> {
                     "Lookup into fixed columns failed: no match for query: {query}"
                 )
             }
+            EvalError::FixedCopyConstraintViolated {
+                column,
+                row,
+                fixed_value,
+                value,
+            } => write!(
+                f,
+                "Copy constraint violated: {column} (Row {row}) is fixed to {fixed_value}, but the copied value is {value}"
+            ),
+            EvalError::CopyConstraintConflict {
+                value,
+                class,
+                cause,
+            } => {
+                writeln!(
+                    f,
+                    "Copy constraint conflict: Could not propagate {value} to all cells of the equivalence class:"
+                )?;
+                for (column, row, value) in class {
+                    match value {
+                        Some(value) => writeln!(f, "    {column} (Row {row}) = {value}")?,
+                        None => writeln!(f, "    {column} (Row {row}) = ?")?,
+                    }
+                }
+                write!(f, "   => {cause}")
+            }
+            EvalError::SetValueFailed {
+                expression,
+                row,
+                value,
+                cause,
+            } => write!(f, "Could not set {expression} (Row {row}) to {value}: {cause}"),
+            EvalError::IdentityFailed(failure) => write!(f, "{failure}"),
             EvalError::ProverQueryError(s) => {
                 write!(f, "Error getting external information from the prover: {s}")
             }
+            EvalError::InvalidInput { column, row, value } => write!(
+                f,
+                "Invalid input for {column} (Row {row}): {value} is rejected by the input validator"
+            ),
+            EvalError::OuterAssignmentRejected {
+                column,
+                row,
+                value,
+                reason,
+            } => write!(
+                f,
+                "Outer assignment {column} = {value} (Row {row}) was rejected: {reason}"
+            ),
             EvalError::Generic(s) => write!(f, "{s}"),
         }
     }
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::marker::PhantomData;
 
 use powdr_ast::analyzed::{
@@ -7,7 +8,16 @@ use powdr_ast::analyzed::{
 
 use powdr_number::FieldElement;
 
-use super::{affine_expression::AffineResult, IncompleteCause};
+use super::{
+    affine_expression::{AffineExpression, AffineResult},
+    field_backend::{CpuBackend, FieldBackend},
+    IncompleteCause,
+};
+
+/// Results of evaluated expressions, by the address of the expression
+/// (see [ExpressionEvaluator::evaluate_cached]).
+pub type EvaluationCache<'a, T> =
+    HashMap<*const Expression<T>, AffineExpression<&'a AlgebraicReference, T>>;
 
 pub trait SymbolicVariables<T> {
     /// Value of a polynomial (fixed or witness).
@@ -20,8 +30,9 @@ pub trait SymbolicVariables<T> {
     }
 }
 
-pub struct ExpressionEvaluator<T, SV> {
+pub struct ExpressionEvaluator<T, SV, B = CpuBackend> {
     variables: SV,
+    backend: B,
     marker: PhantomData<T>,
 }
 
@@ -33,23 +44,69 @@ where
     pub fn new(variables: SV) -> Self {
         Self {
             variables,
+            backend: CpuBackend,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T, SV, B> ExpressionEvaluator<T, SV, B>
+where
+    SV: SymbolicVariables<T>,
+    T: FieldElement,
+    B: FieldBackend<T>,
+{
+    /// Performs the arithmetic using the given backend instead of the [CpuBackend].
+    pub fn with_backend<B2: FieldBackend<T>>(self, backend: B2) -> ExpressionEvaluator<T, SV, B2> {
+        ExpressionEvaluator {
+            variables: self.variables,
+            backend,
             marker: PhantomData,
         }
     }
+
     /// Tries to evaluate the expression to an expression affine in the witness polynomials,
     /// taking current values of polynomials into account.
     /// @returns an expression affine in the witness polynomials
     pub fn evaluate<'a>(&self, expr: &'a Expression<T>) -> AffineResult<&'a AlgebraicReference, T> {
-        // @TODO if we iterate on processing the constraints in the same row,
-        // we could store the simplified values.
+        self.evaluate_with(expr, &mut |e| self.evaluate(e))
+    }
+
+    /// Like [ExpressionEvaluator::evaluate], but reuses the results of operations stored in
+    /// the cache (by the address of the expression) and stores the new ones. The cache must
+    /// only be reused as long as the values of the variables do not change.
+    pub fn evaluate_cached<'a>(
+        &self,
+        expr: &'a Expression<T>,
+        cache: &mut EvaluationCache<'a, T>,
+    ) -> AffineResult<&'a AlgebraicReference, T> {
+        if let Expression::Reference(_) | Expression::Number(_) = expr {
+            // Not worth caching.
+            return self.evaluate(expr);
+        }
+        let key = expr as *const Expression<T>;
+        if let Some(result) = cache.get(&key) {
+            return Ok(result.clone());
+        }
+        let result = self.evaluate_with(expr, &mut |e| self.evaluate_cached(e, cache))?;
+        cache.insert(key, result.clone());
+        Ok(result)
+    }
+
+    /// Evaluates the expression, using `evaluate` for its sub-expressions.
+    fn evaluate_with<'a>(
+        &self,
+        expr: &'a Expression<T>,
+        evaluate: &mut impl FnMut(&'a Expression<T>) -> AffineResult<&'a AlgebraicReference, T>,
+    ) -> AffineResult<&'a AlgebraicReference, T> {
         match expr {
             Expression::Reference(poly) => self.variables.value(poly),
             Expression::Number(n) => Ok((*n).into()),
             Expression::BinaryOperation(AlgebraicBinaryOperation { left, op, right }) => {
-                self.evaluate_binary_operation(left, op, right)
+                self.evaluate_binary_operation(left, op, right, evaluate)
             }
             Expression::UnaryOperation(AlgebraicUnaryOperation { op, expr }) => {
-                self.evaluate_unary_operation(op, expr)
+                self.evaluate_unary_operation(op, expr, evaluate)
             }
             Expression::Challenge(challenge) => self.variables.challenge(challenge),
             e => unimplemented!("Unexpected expression: {}", e),
@@ -61,41 +118,42 @@ where
         left: &'a Expression<T>,
         op: &AlgebraicBinaryOperator,
         right: &'a Expression<T>,
+        evaluate: &mut impl FnMut(&'a Expression<T>) -> AffineResult<&'a AlgebraicReference, T>,
     ) -> AffineResult<&'a AlgebraicReference, T> {
         match op {
             AlgebraicBinaryOperator::Add => {
-                let left_expr = self.evaluate(left)?;
+                let left_expr = evaluate(left)?;
                 if left_expr.is_zero() {
-                    return self.evaluate(right);
+                    return evaluate(right);
                 }
-                let right_expr = self.evaluate(right)?;
+                let right_expr = evaluate(right)?;
                 if right_expr.is_zero() {
                     return Ok(left_expr);
                 }
-                Ok(left_expr + right_expr)
+                Ok(self.backend.add(left_expr, right_expr))
             }
-            AlgebraicBinaryOperator::Sub => Ok(self.evaluate(left)? - self.evaluate(right)?),
+            AlgebraicBinaryOperator::Sub => Ok(self.backend.sub(evaluate(left)?, evaluate(right)?)),
             AlgebraicBinaryOperator::Mul => {
                 // don't short circuit on err as rhs might still be 0
-                let left_res = self.evaluate(left);
+                let left_res = evaluate(left);
                 match left_res {
                     Ok(left_expr) if left_expr.is_zero() => Ok(left_expr),
-                    Ok(left_expr) if left_expr.is_one() => self.evaluate(right),
+                    Ok(left_expr) if left_expr.is_one() => evaluate(right),
                     Ok(left_expr) => {
-                        let right_expr = self.evaluate(right)?;
+                        let right_expr = evaluate(right)?;
                         if let Some(n) = left_expr.constant_value() {
-                            return Ok(right_expr * n);
+                            return Ok(self.backend.mul(right_expr, n));
                         }
                         // lhs not a constant
                         match right_expr.constant_value() {
                             Some(r) if r.is_zero() => Ok(right_expr),
                             Some(r) if r.is_one() => Ok(left_expr),
-                            Some(r) => Ok(left_expr * r),
+                            Some(r) => Ok(self.backend.mul(left_expr, r)),
                             None => Err(IncompleteCause::QuadraticTerm),
                         }
                     }
                     // Err on lhs is ok if rhs is zero
-                    Err(left_err) => match self.evaluate(right) {
+                    Err(left_err) => match evaluate(right) {
                         Ok(right_expr) => {
                             if let Some(n) = right_expr.constant_value() {
                                 if n.is_zero() {
@@ -110,12 +168,12 @@ where
             }
             AlgebraicBinaryOperator::Pow => {
                 if let (Some(l), r) = (
-                    self.evaluate(left)?.constant_value(),
-                    self.evaluate(right)?
+                    evaluate(left)?.constant_value(),
+                    evaluate(right)?
                         .constant_value()
                         .expect("non-constant exponent should be caught earlier"),
                 ) {
-                    Ok(l.pow(r.to_integer()).into())
+                    Ok(self.backend.pow(l, r).into())
                 } else {
                     Err(IncompleteCause::ExponentiationTerm)
                 }
@@ -127,9 +185,10 @@ where
         &self,
         op: &AlgebraicUnaryOperator,
         expr: &'a Expression<T>,
+        evaluate: &mut impl FnMut(&'a Expression<T>) -> AffineResult<&'a AlgebraicReference, T>,
     ) -> AffineResult<&'a AlgebraicReference, T> {
-        self.evaluate(expr).map(|v| match op {
-            AlgebraicUnaryOperator::Minus => -v,
+        evaluate(expr).map(|v| match op {
+            AlgebraicUnaryOperator::Minus => self.backend.neg(v),
         })
     }
 }
@@ -0,0 +1,91 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fmt::{self, Display};
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+
+use itertools::Itertools;
+use powdr_number::{DegreeType, FieldElement};
+
+use super::data_structures::finalizable_data::FinalizableData;
+use super::FixedData;
+
+/// Everything needed to reproduce a failure of witness generation, written to a single
+/// file if enabled (see [super::WitgenOptions::failure_bundle]).
+pub struct FailureBundle<T> {
+    /// The (global) row that failed.
+    pub row: DegreeType,
+    pub errors: Vec<String>,
+    /// A hash of all identities, to check that a bundle is reproduced with the same PIL.
+    pub identities_hash: u64,
+    /// The externally provided witness values.
+    pub inputs: Vec<(String, Vec<T>)>,
+    /// The (global) row of the first row of the partial trace.
+    pub first_row: DegreeType,
+    /// The partial trace of the failing machine, None for unknown cells.
+    pub trace: Vec<(String, Vec<Option<T>>)>,
+}
+
+impl<T: FieldElement> FailureBundle<T> {
+    pub fn new(
+        fixed_data: &FixedData<T>,
+        row: DegreeType,
+        errors: Vec<String>,
+        first_row: DegreeType,
+        data: &FinalizableData<T>,
+    ) -> Self {
+        let mut hasher = DefaultHasher::new();
+        for identity in &fixed_data.analyzed.identities {
+            identity.to_string().hash(&mut hasher);
+        }
+        Self {
+            row,
+            errors,
+            identities_hash: hasher.finish(),
+            inputs: fixed_data
+                .witness_cols
+                .values()
+                .filter_map(|column| {
+                    let values = column.external_values?;
+                    Some((column.poly.name.clone(), values.clone()))
+                })
+                .collect(),
+            first_row,
+            trace: data
+                .known_columns()
+                .map(|(poly_id, values)| (fixed_data.column_name(&poly_id).to_string(), values))
+                .collect(),
+        }
+    }
+
+    /// Writes the bundle to the given file, logging (instead of returning) any error,
+    /// so that the original failure is still reported.
+    pub fn write(&self, path: &Path) {
+        match std::fs::write(path, self.to_string()) {
+            Ok(()) => log::error!("Wrote failure bundle to {}.", path.display()),
+            Err(e) => log::error!("Could not write failure bundle to {}: {e}", path.display()),
+        }
+    }
+}
+
+impl<T: FieldElement> Display for FailureBundle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "row: {}", self.row)?;
+        for error in &self.errors {
+            writeln!(f, "error: {}", error.replace('\n', "\n  "))?;
+        }
+        writeln!(f, "identities_hash: {:016x}", self.identities_hash)?;
+        writeln!(f, "inputs:")?;
+        for (name, values) in &self.inputs {
+            writeln!(f, "  {name}: {}", values.iter().format(", "))?;
+        }
+        writeln!(f, "trace (from row {}):", self.first_row)?;
+        for (name, values) in &self.trace {
+            let values = values.iter().map(|v| match v {
+                Some(v) => v.to_string(),
+                None => "?".to_string(),
+            });
+            writeln!(f, "  {name}: {}", values.format(", "))?;
+        }
+        Ok(())
+    }
+}
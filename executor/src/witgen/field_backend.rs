@@ -0,0 +1,328 @@
+use powdr_ast::analyzed::{AlgebraicExpression as Expression, AlgebraicReference, Identity};
+use powdr_number::FieldElement;
+
+use super::affine_expression::AffineExpression;
+use super::residuals::identity_residual;
+use super::rows::RowPair;
+
+/// An expression affine in the witness columns, as produced when evaluating identities.
+pub type AffineRowExpression<'a, T> = AffineExpression<&'a AlgebraicReference, T>;
+
+/// Performs the field arithmetic needed to evaluate expressions on rows and to check
+/// identities on complete witnesses.
+/// The default [CpuBackend] computes everything directly; other implementations
+/// (e.g. on a GPU) can offload the work, but have to produce the same results.
+pub trait FieldBackend<T: FieldElement>: Send + Sync {
+    fn add<'a>(
+        &self,
+        left: AffineRowExpression<'a, T>,
+        right: AffineRowExpression<'a, T>,
+    ) -> AffineRowExpression<'a, T>;
+
+    fn sub<'a>(
+        &self,
+        left: AffineRowExpression<'a, T>,
+        right: AffineRowExpression<'a, T>,
+    ) -> AffineRowExpression<'a, T>;
+
+    fn neg<'a>(&self, expr: AffineRowExpression<'a, T>) -> AffineRowExpression<'a, T>;
+
+    /// Multiplies an affine expression by a constant factor.
+    fn mul<'a>(&self, expr: AffineRowExpression<'a, T>, factor: T) -> AffineRowExpression<'a, T>;
+
+    fn pow(&self, base: T, exponent: T) -> T;
+
+    /// Evaluates `left - right` of the identities on each of the row pairs, e.g. to check
+    /// a complete witness, and returns the residuals by row pair and then by identity.
+    /// The residual is None if the identity is not a polynomial identity or references
+    /// unknown cells. By default, the identities are evaluated one by one on the row pairs.
+    fn identity_residuals(
+        &self,
+        identities: &[Identity<Expression<T>>],
+        row_pairs: &[RowPair<'_, '_, T>],
+    ) -> Vec<Vec<Option<T>>> {
+        row_pairs
+            .iter()
+            .map(|row_pair| {
+                identities
+                    .iter()
+                    .map(|identity| identity_residual(identity, row_pair))
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+impl<T: FieldElement, B: FieldBackend<T> + ?Sized> FieldBackend<T> for &B {
+    fn add<'a>(
+        &self,
+        left: AffineRowExpression<'a, T>,
+        right: AffineRowExpression<'a, T>,
+    ) -> AffineRowExpression<'a, T> {
+        (**self).add(left, right)
+    }
+
+    fn sub<'a>(
+        &self,
+        left: AffineRowExpression<'a, T>,
+        right: AffineRowExpression<'a, T>,
+    ) -> AffineRowExpression<'a, T> {
+        (**self).sub(left, right)
+    }
+
+    fn neg<'a>(&self, expr: AffineRowExpression<'a, T>) -> AffineRowExpression<'a, T> {
+        (**self).neg(expr)
+    }
+
+    fn mul<'a>(&self, expr: AffineRowExpression<'a, T>, factor: T) -> AffineRowExpression<'a, T> {
+        (**self).mul(expr, factor)
+    }
+
+    fn pow(&self, base: T, exponent: T) -> T {
+        (**self).pow(base, exponent)
+    }
+
+    fn identity_residuals(
+        &self,
+        identities: &[Identity<Expression<T>>],
+        row_pairs: &[RowPair<'_, '_, T>],
+    ) -> Vec<Vec<Option<T>>> {
+        (**self).identity_residuals(identities, row_pairs)
+    }
+}
+
+/// Evaluates all arithmetic directly on the CPU.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CpuBackend;
+
+impl<T: FieldElement> FieldBackend<T> for CpuBackend {
+    fn add<'a>(
+        &self,
+        left: AffineRowExpression<'a, T>,
+        right: AffineRowExpression<'a, T>,
+    ) -> AffineRowExpression<'a, T> {
+        left + right
+    }
+
+    fn sub<'a>(
+        &self,
+        left: AffineRowExpression<'a, T>,
+        right: AffineRowExpression<'a, T>,
+    ) -> AffineRowExpression<'a, T> {
+        left - right
+    }
+
+    fn neg<'a>(&self, expr: AffineRowExpression<'a, T>) -> AffineRowExpression<'a, T> {
+        -expr
+    }
+
+    fn mul<'a>(&self, expr: AffineRowExpression<'a, T>, factor: T) -> AffineRowExpression<'a, T> {
+        expr * factor
+    }
+
+    fn pow(&self, base: T, exponent: T) -> T {
+        base.pow(exponent.to_integer())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use powdr_number::GoldilocksField;
+    use powdr_pil_analyzer::analyze_string;
+
+    use crate::{
+        constant_evaluator::generate,
+        witgen::{
+            constraint_residuals,
+            rows::{CellValue, Row, RowIndex, UnknownStrategy},
+            FixedData, WitgenOptions,
+        },
+    };
+
+    use super::*;
+
+    /// Counts the operations and forwards them to the [CpuBackend].
+    #[derive(Default)]
+    struct CountingBackend {
+        additive: AtomicUsize,
+        multiplicative: AtomicUsize,
+        residual_batches: AtomicUsize,
+    }
+
+    impl<T: FieldElement> FieldBackend<T> for CountingBackend {
+        fn add<'a>(
+            &self,
+            left: AffineRowExpression<'a, T>,
+            right: AffineRowExpression<'a, T>,
+        ) -> AffineRowExpression<'a, T> {
+            self.additive.fetch_add(1, Ordering::Relaxed);
+            CpuBackend.add(left, right)
+        }
+
+        fn sub<'a>(
+            &self,
+            left: AffineRowExpression<'a, T>,
+            right: AffineRowExpression<'a, T>,
+        ) -> AffineRowExpression<'a, T> {
+            self.additive.fetch_add(1, Ordering::Relaxed);
+            CpuBackend.sub(left, right)
+        }
+
+        fn neg<'a>(&self, expr: AffineRowExpression<'a, T>) -> AffineRowExpression<'a, T> {
+            self.additive.fetch_add(1, Ordering::Relaxed);
+            CpuBackend.neg(expr)
+        }
+
+        fn mul<'a>(
+            &self,
+            expr: AffineRowExpression<'a, T>,
+            factor: T,
+        ) -> AffineRowExpression<'a, T> {
+            self.multiplicative.fetch_add(1, Ordering::Relaxed);
+            CpuBackend.mul(expr, factor)
+        }
+
+        fn pow(&self, base: T, exponent: T) -> T {
+            self.multiplicative.fetch_add(1, Ordering::Relaxed);
+            CpuBackend.pow(base, exponent)
+        }
+
+        fn identity_residuals(
+            &self,
+            identities: &[Identity<Expression<T>>],
+            row_pairs: &[RowPair<'_, '_, T>],
+        ) -> Vec<Vec<Option<T>>> {
+            self.residual_batches.fetch_add(1, Ordering::Relaxed);
+            CpuBackend.identity_residuals(identities, row_pairs)
+        }
+    }
+
+    #[test]
+    fn row_pair_evaluation_uses_backend() {
+        let src = r#"
+            namespace main(4);
+                col fixed TWO = [2]*;
+                col witness x, y;
+                x * 3 + y = TWO ** 3;
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let constants = generate(&analyzed);
+        let backend = CountingBackend::default();
+        let fixed_data = FixedData::new(&analyzed, &constants, &[], Default::default(), 0)
+            .with_options(WitgenOptions {
+                field_backend: &backend,
+                ..Default::default()
+            });
+        let row = Row::fresh(&fixed_data, RowIndex::from_degree(0, 4));
+        let row_pair = RowPair::from_single_row(
+            &row,
+            RowIndex::from_degree(0, 4),
+            &fixed_data,
+            UnknownStrategy::Unknown,
+        );
+
+        let identity = &analyzed.identities[0];
+        let result = row_pair
+            .evaluate(identity.expression_for_poly_id())
+            .unwrap();
+        let cpu_fixed_data = FixedData::new(&analyzed, &constants, &[], Default::default(), 0);
+        let cpu_row_pair = RowPair::from_single_row(
+            &row,
+            RowIndex::from_degree(0, 4),
+            &cpu_fixed_data,
+            UnknownStrategy::Unknown,
+        );
+        assert_eq!(
+            result,
+            cpu_row_pair
+                .evaluate(identity.expression_for_poly_id())
+                .unwrap()
+        );
+
+        // `+` and `-`, as well as `* 3` and `** 3`.
+        assert_eq!(backend.additive.load(Ordering::Relaxed), 2);
+        assert_eq!(backend.multiplicative.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn cached_row_pair_evaluation() {
+        let src = r#"
+            namespace main(4);
+                col fixed TWO = [2]*;
+                col witness x, y;
+                (x + TWO) * (x - 1) * 3 + (y + 5) * 7 - (x + y) * 11 = TWO ** 3;
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let constants = generate(&analyzed);
+        let backend = CountingBackend::default();
+        let fixed_data = FixedData::new(&analyzed, &constants, &[], Default::default(), 0)
+            .with_options(WitgenOptions {
+                field_backend: &backend,
+                ..Default::default()
+            });
+        let mut row = Row::fresh(&fixed_data, RowIndex::from_degree(0, 4));
+        row[&fixed_data.try_column_by_name("main.x").unwrap()].value = CellValue::Known(4.into());
+        let row_pair = RowPair::from_single_row(
+            &row,
+            RowIndex::from_degree(0, 4),
+            &fixed_data,
+            UnknownStrategy::Unknown,
+        );
+        let expression = analyzed.identities[0].expression_for_poly_id();
+        let operations = || {
+            backend.additive.load(Ordering::Relaxed)
+                + backend.multiplicative.load(Ordering::Relaxed)
+        };
+
+        let uncached = row_pair.evaluate(expression).unwrap();
+        let uncached_operations = operations();
+        let mut cache = HashMap::new();
+        for _ in 0..3 {
+            assert_eq!(
+                row_pair.evaluate_cached(expression, &mut cache).unwrap(),
+                uncached
+            );
+        }
+        // Only the first evaluation does any arithmetic.
+        assert_eq!(operations(), 2 * uncached_operations);
+        assert_eq!(cache[&(expression as *const _)], uncached);
+    }
+
+    #[test]
+    fn residuals_use_backend() {
+        let src = r#"
+            namespace main(4);
+                col witness x, y;
+                y = x * 3;
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let constants = generate(&analyzed);
+        let witness = vec![
+            (
+                "main.x".to_string(),
+                [1, 2, 3, 4].map(GoldilocksField::from).to_vec(),
+            ),
+            (
+                "main.y".to_string(),
+                [3, 6, 10, 12].map(GoldilocksField::from).to_vec(),
+            ),
+        ];
+        let backend = CountingBackend::default();
+
+        let report = constraint_residuals(&analyzed, &constants, &witness, &backend);
+        assert_eq!(
+            report,
+            constraint_residuals(&analyzed, &constants, &witness, &CpuBackend)
+        );
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].row, 2);
+        // All rows are checked in a single batch, whose arithmetic also goes through the
+        // backend: `-` in each row, `* 3` in all rows except the first, where x is 1.
+        assert_eq!(backend.residual_batches.load(Ordering::Relaxed), 1);
+        assert_eq!(backend.additive.load(Ordering::Relaxed), 4);
+        assert_eq!(backend.multiplicative.load(Ordering::Relaxed), 3);
+    }
+}
@@ -0,0 +1,59 @@
+use std::fmt::{self, Display};
+use std::ops::Range;
+
+use powdr_number::{DegreeType, FieldElement};
+
+/// A row in which a witness column differs from the fixed column it has to agree with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FixedColumnMismatch<T> {
+    pub witness_column: String,
+    pub fixed_column: String,
+    pub row: DegreeType,
+    pub witness_value: T,
+    pub fixed_value: T,
+}
+
+impl<T: Display> Display for FixedColumnMismatch<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (Row {}) is {}, but {} is {}",
+            self.witness_column, self.row, self.witness_value, self.fixed_column, self.fixed_value
+        )
+    }
+}
+
+/// Checks that a witness column of a generated witness agrees with a fixed column in the
+/// given rows, e.g. in a "boot" region in which the witness is initialized from the fixed
+/// column. Returns all rows in which they differ, i.e. an empty list if they agree.
+/// Panics if one of the columns is not found or the rows are out of range.
+pub fn verify_agrees_with_fixed<T: FieldElement>(
+    witness: &[(String, Vec<T>)],
+    fixed: &[(String, Vec<T>)],
+    witness_column: &str,
+    fixed_column: &str,
+    rows: Range<DegreeType>,
+) -> Vec<FixedColumnMismatch<T>> {
+    let rows_of = |values: &[T]| values[rows.start as usize..rows.end as usize].to_vec();
+    let witness_values = rows_of(find_column(witness, witness_column));
+    let fixed_values = rows_of(find_column(fixed, fixed_column));
+    rows.clone()
+        .zip(witness_values.into_iter().zip(fixed_values))
+        .filter(|(_, (witness_value, fixed_value))| witness_value != fixed_value)
+        .map(|(row, (witness_value, fixed_value))| FixedColumnMismatch {
+            witness_column: witness_column.to_string(),
+            fixed_column: fixed_column.to_string(),
+            row,
+            witness_value,
+            fixed_value,
+        })
+        .collect()
+}
+
+fn find_column<'a, T>(columns: &'a [(String, Vec<T>)], column: &str) -> &'a [T] {
+    columns
+        .iter()
+        .find(|(name, _)| name == column)
+        .map(|(_, values)| values.as_slice())
+        .unwrap_or_else(|| panic!("Column {column} not found."))
+}
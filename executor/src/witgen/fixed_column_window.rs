@@ -0,0 +1,141 @@
+use std::ops::Range;
+use std::sync::Mutex;
+
+use powdr_number::DegreeType;
+
+/// Computes the values of fixed columns on demand, so that they do not have to be
+/// materialized in full before witness generation.
+pub trait FixedColumnSource<T>: Send + Sync {
+    /// Returns the values of the fixed column with the given name in the given rows.
+    fn values(&self, column: &str, rows: Range<DegreeType>) -> Vec<T>;
+}
+
+/// The values of a fixed column that are materialized in windows of a fixed size.
+/// Only the window containing the most recently accessed row is kept, older windows
+/// are discarded.
+pub struct FixedColumnWindow<'a, T> {
+    source: &'a dyn FixedColumnSource<T>,
+    window_size: DegreeType,
+    degree: DegreeType,
+    /// The first row and the values of the current window.
+    window: Mutex<(DegreeType, Vec<T>)>,
+}
+
+impl<'a, T: Copy> FixedColumnWindow<'a, T> {
+    pub fn new(
+        source: &'a dyn FixedColumnSource<T>,
+        window_size: DegreeType,
+        degree: DegreeType,
+    ) -> Self {
+        assert!(window_size > 0, "The window size must be positive.");
+        Self {
+            source,
+            window_size,
+            degree,
+            window: Mutex::new((0, vec![])),
+        }
+    }
+
+    /// Returns the value in the given row, materializing the window containing the
+    /// row (and discarding the current one) if needed.
+    pub fn value(&self, name: &str, row: DegreeType) -> T {
+        let mut window = self.window.lock().unwrap();
+        let (start, values) = &mut *window;
+        if !(*start..*start + values.len() as DegreeType).contains(&row) {
+            *start = row - row % self.window_size;
+            let end = (*start + self.window_size).min(self.degree);
+            *values = self.source.values(name, *start..end);
+            assert_eq!(
+                values.len() as DegreeType,
+                end - *start,
+                "Fixed column source returned the wrong number of values for {name}."
+            );
+        }
+        values[(row - *start) as usize]
+    }
+
+    /// Returns the rows that are currently materialized.
+    pub fn materialized_rows(&self) -> Range<DegreeType> {
+        let window = self.window.lock().unwrap();
+        window.0..window.0 + window.1.len() as DegreeType
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use powdr_number::GoldilocksField;
+    use powdr_pil_analyzer::analyze_string;
+
+    use crate::witgen::{
+        rows::{Row, RowIndex, RowPair, UnknownStrategy},
+        Constraint, FixedData, WitgenOptions,
+    };
+
+    use super::*;
+
+    /// Computes `STEP(i) = i` and records the requested ranges.
+    #[derive(Default)]
+    struct StepSource {
+        requests: Mutex<Vec<Range<DegreeType>>>,
+    }
+
+    impl FixedColumnSource<GoldilocksField> for StepSource {
+        fn values(&self, column: &str, rows: Range<DegreeType>) -> Vec<GoldilocksField> {
+            assert_eq!(column, "main.STEP");
+            self.requests.lock().unwrap().push(rows.clone());
+            rows.map(GoldilocksField::from).collect()
+        }
+    }
+
+    #[test]
+    fn only_active_window_is_materialized() {
+        let src = r#"
+            namespace main(16);
+                col fixed STEP(i) { i };
+                col witness x;
+                x = STEP * 2;
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let source = StepSource::default();
+        let fixed_data =
+            FixedData::with_lazy_fixed_columns(&analyzed, &[], &[], Default::default(), 0)
+                .with_options(WitgenOptions {
+                    fixed_column_windows: Some((&source, 4)),
+                    ..Default::default()
+                });
+        let step = fixed_data.try_column_by_name("main.STEP").unwrap();
+        let identity = analyzed.identities[0].expression_for_poly_id();
+
+        for (row, expected_window) in [(1, 0..4), (2, 0..4), (5, 4..8), (15, 12..16)] {
+            let row_index = RowIndex::from_degree(row, 16);
+            let current = Row::fresh(&fixed_data, row_index);
+            let row_pair = RowPair::from_single_row(
+                &current,
+                row_index,
+                &fixed_data,
+                UnknownStrategy::Unknown,
+            );
+            let constraints = row_pair
+                .evaluate(identity)
+                .unwrap()
+                .solve()
+                .unwrap()
+                .constraints;
+            assert_eq!(constraints[0].1, Constraint::Assignment((2 * row).into()));
+            assert_eq!(
+                fixed_data.fixed_cols[&step].materialized_rows(),
+                Some(expected_window)
+            );
+        }
+        assert_eq!(*source.requests.lock().unwrap(), vec![0..4, 4..8, 12..16]);
+    }
+
+    #[test]
+    #[should_panic(expected = "The values of the fixed columns main.STEP were not provided.")]
+    fn missing_fixed_column_without_windows() {
+        let analyzed = analyze_string::<GoldilocksField>(
+            "namespace main(16); col fixed STEP(i) { i }; col witness x; x = STEP * 2;",
+        );
+        FixedData::new(&analyzed, &[], &[], Default::default(), 0);
+    }
+}
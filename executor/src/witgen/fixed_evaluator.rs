@@ -2,7 +2,7 @@ use super::affine_expression::AffineResult;
 use super::expression_evaluator::SymbolicVariables;
 use super::FixedData;
 use powdr_ast::analyzed::AlgebraicReference;
-use powdr_number::FieldElement;
+use powdr_number::{DegreeType, FieldElement};
 
 /// Evaluates only fixed columns on a specific row.
 pub struct FixedEvaluator<'a, T: FieldElement> {
@@ -23,13 +23,14 @@ impl<'a, T: FieldElement> SymbolicVariables<T> for FixedEvaluator<'a, T> {
             poly.is_fixed(),
             "Can only access fixed columns in the fixed evaluator."
         );
-        let col_data = self.fixed_data.fixed_cols[&poly.poly_id].values;
-        let degree = col_data.len();
+        let degree = self.fixed_data.degree as usize;
         let row = if poly.next {
             (self.row + 1) % degree
         } else {
             self.row
         };
-        Ok(col_data[row].into())
+        Ok(self.fixed_data.fixed_cols[&poly.poly_id]
+            .value(row as DegreeType)
+            .into())
     }
 }
@@ -6,17 +6,22 @@ use std::collections::{BTreeMap, HashMap, HashSet};
 
 use crate::witgen::data_structures::finalizable_data::FinalizableData;
 use crate::witgen::machines::profiling::{record_end, record_start};
-use crate::witgen::processor::OuterQuery;
+use crate::witgen::processor::{OuterQuery, Provenance};
 use crate::witgen::rows::CellValue;
 use crate::witgen::EvalValue;
 
 use super::block_processor::BlockProcessor;
 use super::data_structures::column_map::WitnessColumnMap;
+use super::dont_care::fill_unwritten_cells;
+use super::identity_budget::IdentityBudget;
+use super::instruction_replay::ResolvedInstructionReplay;
 use super::machines::{FixedLookup, Machine};
 use super::rows::{Row, RowIndex, RowPair};
 use super::sequence_iterator::{DefaultSequenceIterator, ProcessingSequenceIterator};
 use super::vm_processor::VmProcessor;
-use super::{EvalResult, FixedData, MutableState, QueryCallback};
+use super::{
+    EvalResult, FixedData, MutableState, QueryCallback, Scratchpad, TieBreaker, WitnessProvenance,
+};
 
 struct ProcessResult<'a, T: FieldElement> {
     eval_value: EvalValue<&'a AlgebraicReference, T>,
@@ -31,6 +36,11 @@ pub struct Generator<'a, T: FieldElement> {
     data: FinalizableData<'a, T>,
     latch: Option<Expression<T>>,
     name: String,
+    instruction_replay: Option<&'a ResolvedInstructionReplay<'a>>,
+    identity_budget: Option<IdentityBudget>,
+    /// The provenance of the cells, by row and column, taken from the data together with
+    /// the witness columns.
+    provenance: BTreeMap<(usize, PolyID), Provenance>,
 }
 
 impl<'a, T: FieldElement> Machine<'a, T> for Generator<'a, T> {
@@ -85,6 +95,8 @@ impl<'a, T: FieldElement> Machine<'a, T> for Generator<'a, T> {
         &mut self,
         fixed_lookup: &'b mut FixedLookup<T>,
         query_callback: &'b mut Q,
+        scratchpad: &'b Scratchpad,
+        tie_breaker: Option<&'b TieBreaker>,
     ) -> HashMap<String, Vec<T>> {
         log::debug!("Finalizing VM: {}", self.name());
 
@@ -93,14 +105,42 @@ impl<'a, T: FieldElement> Machine<'a, T> for Generator<'a, T> {
             fixed_lookup,
             machines: [].into_iter().into(),
             query_callback,
+            scratchpad,
+            tie_breaker,
         };
 
         self.fill_remaining_rows(&mut mutable_state_no_machines);
         self.fix_first_row();
 
+        self.provenance = self.data.take_provenance();
         self.data
             .take_transposed()
-            .map(|(id, (values, _))| (self.fixed_data.column_name(&id).to_string(), values))
+            .map(|(id, (mut values, known_cells))| {
+                let name = self.fixed_data.column_name(&id).to_string();
+                let unwritten = fill_unwritten_cells(
+                    self.fixed_data,
+                    &id,
+                    &mut values,
+                    &known_cells,
+                    tie_breaker,
+                );
+                if unwritten > 0 {
+                    log::warn!(
+                        "{unwritten} cells of column {name} were never written, setting them to 0."
+                    );
+                }
+                (name, values)
+            })
+            .collect()
+    }
+
+    fn take_provenance(&mut self) -> WitnessProvenance {
+        std::mem::take(&mut self.provenance)
+            .into_iter()
+            .map(|((row, poly_id), provenance)| {
+                let name = self.fixed_data.column_name(&poly_id).to_string();
+                ((name, row as DegreeType), provenance)
+            })
             .collect()
     }
 }
@@ -115,6 +155,10 @@ impl<'a, T: FieldElement> Generator<'a, T> {
         latch: Option<Expression<T>>,
     ) -> Self {
         let data = FinalizableData::new(&witnesses);
+        let identity_budget = fixed_data
+            .options
+            .identity_budget
+            .map(|budget| IdentityBudget::new(&name, budget));
         Self {
             connecting_identities: connecting_identities.clone(),
             name,
@@ -123,6 +167,20 @@ impl<'a, T: FieldElement> Generator<'a, T> {
             witnesses,
             data,
             latch,
+            instruction_replay: None,
+            identity_budget,
+            provenance: BTreeMap::new(),
+        }
+    }
+
+    /// Uses the given instruction replay to determine the control flow of this machine.
+    pub fn with_instruction_replay(
+        self,
+        instruction_replay: &'a ResolvedInstructionReplay<'a>,
+    ) -> Self {
+        Self {
+            instruction_replay: Some(instruction_replay),
+            ..self
         }
     }
 
@@ -199,7 +257,9 @@ impl<'a, T: FieldElement> Generator<'a, T> {
             &identities_with_next_reference,
             self.fixed_data,
             &self.witnesses,
-        );
+        )
+        .with_log_scope(&self.name)
+        .with_identity_budget(self.identity_budget.as_ref());
         let mut sequence_iterator = ProcessingSequenceIterator::Default(
             DefaultSequenceIterator::new(0, identities_with_next_reference.len(), None),
         );
@@ -231,10 +291,15 @@ impl<'a, T: FieldElement> Generator<'a, T> {
             &self.witnesses,
             data,
             mutable_state,
-        );
+        )
+        .with_log_scope(&self.name)
+        .with_identity_budget(self.identity_budget.as_ref());
         if let Some(outer_query) = outer_query {
             processor = processor.with_outer_query(outer_query);
         }
+        if let Some(instruction_replay) = self.instruction_replay {
+            processor = processor.with_instruction_replay(instruction_replay);
+        }
         let eval_value = processor.run(is_main_run);
         let block = processor.finish();
         ProcessResult { eval_value, block }
@@ -117,7 +117,10 @@ pub fn set_global_constraints<'a, T: FieldElement>(
     // It allows us to completely remove some lookups.
     let mut full_span = BTreeSet::new();
     for (poly_id, col) in fixed_data.fixed_cols.iter() {
-        if let Some((cons, full)) = process_fixed_column(col.values) {
+        let Some(values) = col.try_values() else {
+            continue;
+        };
+        if let Some((cons, full)) = process_fixed_column(values) {
             assert!(known_constraints.insert(poly_id, cons).is_none());
             if full {
                 full_span.insert(poly_id);
@@ -0,0 +1,32 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use powdr_number::FieldElement;
+
+use super::EvalError;
+
+/// Limits the number of identities a machine processes over the whole witness generation
+/// (see [super::WitgenOptions::identity_budget]).
+pub struct IdentityBudget {
+    machine: String,
+    remaining: AtomicU64,
+}
+
+impl IdentityBudget {
+    pub fn new(machine: &str, budget: u64) -> Self {
+        Self {
+            machine: machine.to_string(),
+            remaining: AtomicU64::new(budget),
+        }
+    }
+
+    /// Spends the budget for processing `count` identities.
+    /// Fails if not enough budget is left.
+    pub fn spend<T: FieldElement>(&self, count: u64) -> Result<(), EvalError<T>> {
+        self.remaining
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |remaining| {
+                remaining.checked_sub(count)
+            })
+            .map(|_| ())
+            .map_err(|_| EvalError::BudgetExceeded(self.machine.clone()))
+    }
+}
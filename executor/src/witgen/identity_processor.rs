@@ -16,7 +16,8 @@ use super::{
     machines::{FixedLookup, KnownMachine},
     processor::OuterQuery,
     rows::RowPair,
-    EvalResult, EvalValue, FixedData, IncompleteCause, MutableState, QueryCallback,
+    EvalResult, EvalValue, FixedData, IncompleteCause, MutableState, QueryCallback, Scratchpad,
+    TieBreaker,
 };
 
 /// A list of mutable references to machines.
@@ -63,6 +64,8 @@ impl<'a, 'b, T: FieldElement> Machines<'a, 'b, T> {
         caller_rows: &RowPair<'_, 'a, T>,
         fixed_lookup: &mut FixedLookup<T>,
         query_callback: &mut Q,
+        scratchpad: &Scratchpad,
+        tie_breaker: Option<&TieBreaker>,
     ) -> EvalResult<'a, T> {
         let machine_index = *self
             .identity_to_machine_index
@@ -74,6 +77,8 @@ impl<'a, 'b, T: FieldElement> Machines<'a, 'b, T> {
             fixed_lookup,
             machines: others,
             query_callback,
+            scratchpad,
+            tie_breaker,
         };
 
         current.process_plookup_timed(&mut mutable_state, identity_id, caller_rows)
@@ -99,6 +104,18 @@ where
     }
 }
 
+/// Tries to figure out additional values / range constraints from a polynomial identity
+/// on the given row pair. In contrast to lookups, this does not need any mutable state.
+pub fn process_polynomial_identity<'a, T: FieldElement>(
+    identity: &'a Identity<Expression<T>>,
+    rows: &RowPair<T>,
+) -> EvalResult<'a, T> {
+    match rows.evaluate(identity.expression_for_poly_id()) {
+        Err(incomplete_cause) => Ok(EvalValue::incomplete(incomplete_cause)),
+        Ok(evaluated) => evaluated.solve_with_range_constraints(rows),
+    }
+}
+
 /// Computes (value or range constraint) updates given a [RowPair] and [Identity].
 /// The lifetimes mean the following:
 /// - `'a`: The duration of the entire witness generation (e.g. references to identities)
@@ -130,7 +147,7 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> IdentityProcessor<'a, 'b,
         rows: &RowPair<'_, 'a, T>,
     ) -> EvalResult<'a, T> {
         let result = match identity.kind {
-            IdentityKind::Polynomial => self.process_polynomial_identity(identity, rows),
+            IdentityKind::Polynomial => process_polynomial_identity(identity, rows),
             IdentityKind::Plookup | IdentityKind::Permutation => {
                 self.process_plookup(identity, rows)
             }
@@ -146,17 +163,6 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> IdentityProcessor<'a, 'b,
         result
     }
 
-    fn process_polynomial_identity(
-        &self,
-        identity: &'a Identity<Expression<T>>,
-        rows: &RowPair<T>,
-    ) -> EvalResult<'a, T> {
-        match rows.evaluate(identity.expression_for_poly_id()) {
-            Err(incomplete_cause) => Ok(EvalValue::incomplete(incomplete_cause)),
-            Ok(evaluated) => evaluated.solve_with_range_constraints(rows),
-        }
-    }
-
     fn process_plookup(
         &mut self,
         identity: &'a Identity<Expression<T>>,
@@ -207,6 +213,8 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> IdentityProcessor<'a, 'b,
             rows,
             self.mutable_state.fixed_lookup,
             self.mutable_state.query_callback,
+            self.mutable_state.scratchpad,
+            self.mutable_state.tie_breaker,
         )
     }
 
@@ -0,0 +1,71 @@
+use powdr_ast::analyzed::{Analyzed, IdentityKind};
+use powdr_number::{DegreeType, FieldElement};
+
+use super::data_structures::finalizable_data::FinalizableData;
+use super::identity_processor::Machines;
+use super::machines::FixedLookup;
+use super::processor::Processor;
+use super::rows::{Row, RowIndex};
+use super::{identities_for_stage, unused_query_callback, FixedData, MutableState, Scratchpad};
+
+/// The result of validating a witness against a single identity.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IdentityValidation {
+    pub identity_id: u64,
+    pub identity: String,
+    /// The (global) rows on which the identity does not hold. For identities that reference
+    /// the next row, this is the first row of the row pair.
+    pub failing_rows: Vec<DegreeType>,
+}
+
+impl IdentityValidation {
+    pub fn holds(&self) -> bool {
+        self.failing_rows.is_empty()
+    }
+}
+
+/// Validates a complete witness against each polynomial identity separately, in the order
+/// of the identities, e.g. to see which newly added constraints an existing witness
+/// already satisfies. Lookups and permutations are not checked.
+pub fn validate_identities<T: FieldElement>(
+    analyzed: &Analyzed<T>,
+    fixed_col_values: &[(String, Vec<T>)],
+    witness: &[(String, Vec<T>)],
+) -> Vec<IdentityValidation> {
+    let fixed_data = FixedData::new(analyzed, fixed_col_values, witness, Default::default(), 0);
+    let identities = identities_for_stage(analyzed, 0)
+        .into_iter()
+        .filter(|identity| identity.kind == IdentityKind::Polynomial)
+        .collect::<Vec<_>>();
+
+    let witness_cols = fixed_data.witness_cols.keys().collect();
+    let data = FinalizableData::with_initial_rows_in_progress(
+        &witness_cols,
+        (0..fixed_data.degree)
+            .map(|i| Row::fresh(&fixed_data, RowIndex::from_degree(i, fixed_data.degree))),
+    );
+    let mut fixed_lookup = FixedLookup::new(fixed_data.global_range_constraints().clone());
+    let mut query_callback = unused_query_callback();
+    let mut mutable_state = MutableState {
+        fixed_lookup: &mut fixed_lookup,
+        machines: Machines::from([].iter_mut()),
+        query_callback: &mut query_callback,
+        scratchpad: &Scratchpad::default(),
+        tie_breaker: None,
+    };
+    let mut processor = Processor::new(
+        RowIndex::from_degree(0, fixed_data.degree),
+        data,
+        &mut mutable_state,
+        &fixed_data,
+        &witness_cols,
+    );
+    identities
+        .iter()
+        .map(|identity| IdentityValidation {
+            identity_id: identity.id,
+            identity: identity.to_string(),
+            failing_rows: processor.failing_rows(identity),
+        })
+        .collect()
+}
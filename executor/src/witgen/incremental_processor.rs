@@ -0,0 +1,141 @@
+use std::collections::HashSet;
+
+use powdr_ast::analyzed::{AlgebraicExpression as Expression, Identity, PolyID};
+use powdr_number::{DegreeType, FieldElement};
+
+use super::{
+    data_structures::finalizable_data::FinalizableData,
+    processor::{Processor, Provenance},
+    rows::{CellValue, Row, RowIndex, UnknownStrategy},
+    EvalError, FixedData, MutableState, QueryCallback,
+};
+
+/// A processor that extends a trace as new input rows are appended.
+/// Rows are assumed to only depend on earlier rows (and on the inputs), so that appending
+/// rows only requires solving the new rows, starting from the last row of the previous batch.
+/// Rows that are no longer needed are finalized.
+pub struct IncrementalProcessor<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> {
+    processor: Processor<'a, 'b, 'c, T, Q>,
+    identities: &'c [&'a Identity<Expression<T>>],
+    fixed_data: &'a FixedData<'a, T>,
+    /// The witness columns of the trace, in sorted order.
+    witness_cols: Vec<PolyID>,
+    /// The number of rows that have been solved. The processor additionally holds the
+    /// row after them, which might already be partially known.
+    solved_rows: usize,
+    /// The first row that has not been finalized yet.
+    finalize_start: usize,
+}
+
+impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> IncrementalProcessor<'a, 'b, 'c, T, Q> {
+    pub fn new(
+        mutable_state: &'c mut MutableState<'a, 'b, T, Q>,
+        identities: &'c [&'a Identity<Expression<T>>],
+        fixed_data: &'a FixedData<'a, T>,
+        witness_cols: &'c HashSet<PolyID>,
+    ) -> Self {
+        let row_offset = RowIndex::from_degree(0, fixed_data.degree);
+        let data = FinalizableData::with_initial_rows_in_progress(
+            witness_cols,
+            [Row::fresh(fixed_data, row_offset)].into_iter(),
+        );
+        let mut sorted_witness_cols = witness_cols.iter().cloned().collect::<Vec<_>>();
+        sorted_witness_cols.sort();
+        Self {
+            processor: Processor::new(row_offset, data, mutable_state, fixed_data, witness_cols),
+            identities,
+            fixed_data,
+            witness_cols: sorted_witness_cols,
+            solved_rows: 0,
+            finalize_start: 0,
+        }
+    }
+
+    /// Appends rows in which the given columns have the given values (all of the same
+    /// length) and solves the remaining cells of these rows.
+    /// Fails if one of the new cells cannot be determined.
+    /// @returns the values of the new rows, for each witness column (sorted by ID).
+    pub fn append_rows(
+        &mut self,
+        inputs: &[(PolyID, Vec<T>)],
+    ) -> Result<Vec<(PolyID, Vec<T>)>, EvalError<T>> {
+        let count = inputs.first().map(|(_, values)| values.len()).unwrap_or(0);
+        assert!(
+            inputs.iter().all(|(_, values)| values.len() == count),
+            "All input columns need to have the same number of rows."
+        );
+        let start = self.solved_rows;
+        let end = start + count;
+        assert!(
+            end as DegreeType <= self.fixed_data.degree,
+            "Cannot extend the trace to {end} rows, the degree is {}.",
+            self.fixed_data.degree
+        );
+
+        let row_offset = RowIndex::from_degree(0, self.fixed_data.degree);
+        for (i, row_index) in (start..end).enumerate() {
+            self.processor.set_row(
+                row_index + 1,
+                Row::fresh(self.fixed_data, row_offset + row_index + 1),
+            );
+            let values = inputs
+                .iter()
+                .map(|(poly_id, values)| (*poly_id, values[i]))
+                .collect::<Vec<_>>();
+            self.processor
+                .set_cells_if_unset(row_index, &values, Provenance::Input, || {
+                    "streamed inputs".to_string()
+                })?;
+        }
+
+        // The last solved row is processed again, because identities on it
+        // might reference the first new row.
+        loop {
+            let mut progress = false;
+            for row_index in start.saturating_sub(1)..end {
+                for identity in self.identities {
+                    progress |= self
+                        .processor
+                        .process_identity(row_index, identity, UnknownStrategy::Unknown)?
+                        .progress;
+                }
+                if row_index >= start {
+                    progress |= self.processor.process_queries(row_index)?;
+                }
+            }
+            if !progress {
+                break;
+            }
+        }
+
+        let new_rows = self
+            .witness_cols
+            .iter()
+            .map(|poly_id| {
+                let values = (start..end)
+                    .map(
+                        |row_index| match self.processor.row(row_index)[poly_id].value {
+                            CellValue::Known(value) => Ok(value),
+                            _ => Err(format!(
+                                "Could not determine {} in row {}.",
+                                self.fixed_data.column_name(poly_id),
+                                row_index
+                            )),
+                        },
+                    )
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok((*poly_id, values))
+            })
+            .collect::<Result<Vec<_>, EvalError<T>>>()?;
+
+        // The last solved row and the row after it are still needed for the next batch.
+        let finalize_end = end.saturating_sub(1);
+        if finalize_end > self.finalize_start {
+            self.processor
+                .finalize_range(self.finalize_start..finalize_end);
+            self.finalize_start = finalize_end;
+        }
+        self.solved_rows = end;
+        Ok(new_rows)
+    }
+}
@@ -0,0 +1,14 @@
+/// Maps the inputs of a call to a block machine to a canonical form, so that calls that are
+/// semantically identical share a cached result, e.g. by sorting the arguments of a
+/// commutative operation. The arguments are the name of the machine and the values of the
+/// caller's expressions of the connecting identity (None for outputs).
+///
+/// If a call has the same canonical inputs as an earlier successful call, its outputs are
+/// taken from that call. The call still gets its own block, so that the connecting identity
+/// holds for the caller's values, but the block is computed with the outputs already known.
+/// This fails if the canonicalization changes the outputs.
+pub trait InputCanonicalization<T>: Fn(&str, &[Option<T>]) -> Vec<Option<T>> + Send + Sync {}
+impl<T, F> InputCanonicalization<T> for F where
+    F: Fn(&str, &[Option<T>]) -> Vec<Option<T>> + Send + Sync
+{
+}
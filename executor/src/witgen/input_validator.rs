@@ -0,0 +1,6 @@
+/// A predicate that each value the query callback returns for a column has to satisfy,
+/// e.g. that it is a byte (see [super::WitgenOptions::input_validators]).
+/// Values are checked as they are fetched, so a malformed input is reported with the
+/// cell where it is consumed.
+pub trait InputValidator<T>: Fn(T) -> bool + Send + Sync {}
+impl<T, F> InputValidator<T> for F where F: Fn(T) -> bool + Send + Sync {}
@@ -0,0 +1,74 @@
+use powdr_number::FieldElement;
+
+use super::{AffineExpression, ClosureConstraint, EvalError, EvalValue, IncompleteCause};
+
+/// Returns a closure constraint that copies values between witness columns of the same
+/// row depending on the instruction of the row. Each copy is given as
+/// `(selector, source, target)` and requires `source = target` in all rows where the
+/// (decoded) instruction flag `selector` is non-zero, e.g. `("main.instr_mov_a_b",
+/// "main.a", "main.b")` for a move instruction. Values are propagated in both directions.
+///
+/// In contrast to [super::WitgenOptions::copy_constraints], the copied cells do
+/// not have to be known in advance: They are determined on each row once the selectors
+/// are known, so the copy constraints can depend on the executed program.
+pub fn instruction_copies<'b, T: FieldElement>(
+    copies: &[(&str, &str, &str)],
+) -> ClosureConstraint<'b, T> {
+    let copies = copies
+        .iter()
+        .map(|(selector, source, target)| {
+            (selector.to_string(), source.to_string(), target.to_string())
+        })
+        .collect::<Vec<_>>();
+    let name = format!(
+        "instruction copies: {}",
+        copies
+            .iter()
+            .map(|(selector, source, target)| format!("{selector} => {target} = {source}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    ClosureConstraint::<T>::new(name, move |row_pair| {
+        let mut result = EvalValue::complete(vec![]);
+        for (selector, source, target) in &copies {
+            let selector_ref = row_pair
+                .witness_reference(selector)
+                .ok_or_else(|| format!("Witness column {selector} not found."))?;
+            let source_ref = row_pair
+                .witness_reference(source)
+                .ok_or_else(|| format!("Witness column {source} not found."))?;
+            let target_ref = row_pair
+                .witness_reference(target)
+                .ok_or_else(|| format!("Witness column {target} not found."))?;
+            match row_pair.get_value(selector_ref) {
+                None => {
+                    result.combine(EvalValue::incomplete(IncompleteCause::DataNotYetAvailable));
+                    continue;
+                }
+                Some(flag) if flag.is_zero() => continue,
+                Some(_) => {}
+            }
+            result.combine(
+                match (
+                    row_pair.get_value(source_ref),
+                    row_pair.get_value(target_ref),
+                ) {
+                    (Some(value), Some(copy)) if value == copy => EvalValue::complete(vec![]),
+                    (Some(value), Some(copy)) => {
+                        return Err(EvalError::ConstraintUnsatisfiable(format!(
+                            "{selector} is set, but {source} is {value} and {target} is {copy}"
+                        )))
+                    }
+                    (Some(value), None) => {
+                        (AffineExpression::from_variable_id(target_ref) - value.into()).solve()?
+                    }
+                    (None, Some(copy)) => {
+                        (AffineExpression::from_variable_id(source_ref) - copy.into()).solve()?
+                    }
+                    (None, None) => EvalValue::incomplete(IncompleteCause::DataNotYetAvailable),
+                },
+            );
+        }
+        Ok(result)
+    })
+}
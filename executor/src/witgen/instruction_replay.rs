@@ -0,0 +1,82 @@
+use powdr_number::{DegreeType, FieldElement};
+
+use super::{ColumnGroup, EvalError, FixedData};
+
+/// A recorded execution of the main VM. During witness generation, it determines the
+/// program counter and the instruction flags of each row, so that only the remaining
+/// columns (the "datapath") have to be derived from the identities.
+#[derive(Clone, Debug, Default)]
+pub struct InstructionReplay {
+    /// The name of the program counter column.
+    pub pc: String,
+    /// The names of the instruction flag columns.
+    pub instruction_flags: Vec<String>,
+    /// For each program counter value, the index (into `instruction_flags`) of the
+    /// instruction at that address.
+    pub program: Vec<usize>,
+    /// The executed program counter values, one per row. Jumps and loops show up as
+    /// non-consecutive or repeated values.
+    pub executed_pcs: Vec<u64>,
+}
+
+/// An [InstructionReplay] with column names resolved to polynomial IDs.
+pub struct ResolvedInstructionReplay<'a> {
+    /// The program counter followed by the instruction flags, which are
+    /// always set together.
+    columns: ColumnGroup,
+    replay: &'a InstructionReplay,
+}
+
+impl<'a> ResolvedInstructionReplay<'a> {
+    pub fn new<T: FieldElement>(replay: &'a InstructionReplay, fixed_data: &FixedData<T>) -> Self {
+        let resolve = |name: &str| {
+            fixed_data
+                .try_column_by_name(name)
+                .unwrap_or_else(|| panic!("Column {name} of the instruction replay not found."))
+        };
+        Self {
+            columns: ColumnGroup {
+                name: "instruction replay".to_string(),
+                columns: std::iter::once(&replay.pc)
+                    .chain(&replay.instruction_flags)
+                    .map(|name| resolve(name))
+                    .collect(),
+            },
+            replay,
+        }
+    }
+
+    /// Returns the program counter and instruction flag columns, in the order of
+    /// the values returned by [Self::row_values].
+    pub fn columns(&self) -> &ColumnGroup {
+        &self.columns
+    }
+
+    /// Returns whether the replay determines the values of the given (global) row.
+    pub fn covers_row(&self, row: DegreeType) -> bool {
+        row < self.replay.executed_pcs.len() as DegreeType
+    }
+
+    /// Returns the values of the program counter and instruction flag columns (see
+    /// [Self::columns]) in the given (global) row, or None if the row is not covered
+    /// by the replay. Fails if the replayed program counter is outside the program.
+    pub fn row_values<T: FieldElement>(
+        &self,
+        row: DegreeType,
+    ) -> Result<Option<Vec<T>>, EvalError<T>> {
+        let Some(&pc) = self.replay.executed_pcs.get(row as usize) else {
+            return Ok(None);
+        };
+        let instruction = *self.replay.program.get(pc as usize).ok_or_else(|| {
+            EvalError::Generic(format!(
+                "Replayed pc {pc} in row {row} is outside the program of length {}.",
+                self.replay.program.len()
+            ))
+        })?;
+        Ok(Some(
+            std::iter::once(T::from(pc))
+                .chain((0..self.replay.instruction_flags.len()).map(|i| T::from(i == instruction)))
+                .collect(),
+        ))
+    }
+}
@@ -0,0 +1,35 @@
+use powdr_number::FieldElement;
+
+use super::QueryCallback;
+
+/// Answers queries of the form `Inverse(x)` (see `std::prover::Query::Inverse`) with the
+/// inverse of `x`, or with `zero_inverse` if `x` is zero. All other queries are passed on
+/// to `query_callback`.
+///
+/// Since the inverse of zero is not constrained, the column receiving the inverse should be
+/// accompanied by an "is zero" flag column, constrained via
+/// ```text
+/// is_zero = 1 - x * inv;
+/// is_zero * x = 0;
+/// ```
+/// Witness generation then derives the flag from the queried inverse, which makes both
+/// columns consistent regardless of the designated value (for zero, the flag is 1).
+pub fn inverse_query_callback<T: FieldElement>(
+    query_callback: impl QueryCallback<T>,
+    zero_inverse: T,
+) -> impl QueryCallback<T> {
+    move |query: &str| -> Result<Option<T>, String> {
+        let Some(value) = query
+            .strip_prefix("Inverse(")
+            .and_then(|query| query.strip_suffix(')'))
+        else {
+            return query_callback(query);
+        };
+        let value = T::from_str_radix(value.trim(), 10)
+            .map_err(|e| format!("Error parsing the argument of query {query}: {e}"))?;
+        Ok(Some(match value.is_zero() {
+            true => zero_inverse,
+            false => T::one() / value,
+        }))
+    }
+}
@@ -0,0 +1,19 @@
+/// An external store for lookups into fixed columns, e.g. for tables that are too large
+/// to be indexed in memory. It is consulted by the fixed lookup machine before the
+/// in-memory index of the fixed columns (see [super::WitgenOptions::key_value_table]).
+pub trait KeyValueTable<T>: Send + Sync {
+    /// Returns the values of `output_columns` in a row where `input_columns` have the values
+    /// `key`, or None if there is no such row. The columns are given by their full names,
+    /// in the order of the lookup.
+    fn get(&self, input_columns: &[&str], key: &[T], output_columns: &[&str]) -> Option<Vec<T>>;
+}
+
+/// Determines how a lookup that is not found in the [KeyValueTable] is handled.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KeyValueMiss {
+    /// The lookup is resolved against the fixed columns.
+    #[default]
+    FixedColumns,
+    /// The lookup fails.
+    Error,
+}
@@ -0,0 +1,78 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// The number of buckets per power of two. Quantiles are accurate up to a relative
+/// error of `1 / SUB_BUCKETS`.
+const SUB_BUCKETS: u64 = 8;
+const SUB_BUCKET_BITS: u32 = SUB_BUCKETS.trailing_zeros();
+
+/// A streaming estimator of latency quantiles: Latencies are counted in logarithmic
+/// buckets, so memory use only depends on the range of the latencies, not their number.
+/// The maximum is tracked exactly.
+#[derive(Clone, Debug, Default)]
+pub struct LatencyHistogram {
+    /// The number of latencies per bucket, see [bucket].
+    buckets: BTreeMap<u64, u64>,
+    count: u64,
+    max: Duration,
+}
+
+/// Returns the bucket of a latency in nanoseconds. Latencies below `SUB_BUCKETS` have
+/// their own bucket, larger ones are split into `SUB_BUCKETS` buckets per power of two.
+fn bucket(nanos: u64) -> u64 {
+    if nanos < SUB_BUCKETS {
+        return nanos;
+    }
+    let exponent = 63 - nanos.leading_zeros();
+    let mantissa = (nanos >> (exponent - SUB_BUCKET_BITS)) & (SUB_BUCKETS - 1);
+    exponent as u64 * SUB_BUCKETS + mantissa
+}
+
+/// Returns the largest latency in nanoseconds that falls into the given bucket.
+fn bucket_upper_bound(bucket: u64) -> u64 {
+    if bucket < SUB_BUCKETS {
+        return bucket;
+    }
+    let (exponent, mantissa) = (bucket / SUB_BUCKETS, bucket % SUB_BUCKETS);
+    ((SUB_BUCKETS + mantissa + 1) << (exponent - SUB_BUCKET_BITS as u64)) - 1
+}
+
+impl LatencyHistogram {
+    pub fn record(&mut self, latency: Duration) {
+        let nanos = u64::try_from(latency.as_nanos()).unwrap_or(u64::MAX);
+        *self.buckets.entry(bucket(nanos)).or_default() += 1;
+        self.count += 1;
+        self.max = self.max.max(latency);
+    }
+
+    /// The number of recorded latencies.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns an upper bound of the `q`-quantile (for `q` between 0 and 1) of the
+    /// recorded latencies, or zero if nothing was recorded.
+    pub fn quantile(&self, q: f64) -> Duration {
+        let rank = ((q * self.count as f64).ceil() as u64).clamp(1, self.count.max(1));
+        let mut seen = 0;
+        for (bucket, count) in &self.buckets {
+            seen += count;
+            if seen >= rank {
+                return Duration::from_nanos(bucket_upper_bound(*bucket)).min(self.max);
+            }
+        }
+        Duration::ZERO
+    }
+
+    pub fn p50(&self) -> Duration {
+        self.quantile(0.5)
+    }
+
+    pub fn p99(&self) -> Duration {
+        self.quantile(0.99)
+    }
+
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+}
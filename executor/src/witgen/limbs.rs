@@ -0,0 +1,116 @@
+use powdr_number::{FieldElement, LargeInt};
+
+/// The order of the limbs of a value that is decomposed into limbs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Endianness {
+    /// The first limb is the least significant one.
+    #[default]
+    Little,
+    /// The first limb is the most significant one.
+    Big,
+}
+
+impl Endianness {
+    /// The factor of the limb with the given index in the reconstruction of a value
+    /// from `count` limbs of `limb_bits` bits each, or None if the limb starts beyond
+    /// the bits of the field.
+    pub fn limb_weight<T: FieldElement>(
+        self,
+        index: usize,
+        count: usize,
+        limb_bits: usize,
+    ) -> Option<T> {
+        let position = match self {
+            Endianness::Little => index,
+            Endianness::Big => count - 1 - index,
+        };
+        let shift = position.checked_mul(limb_bits)?;
+        (shift < T::BITS as usize).then(|| T::from(T::Integer::one() << shift))
+    }
+}
+
+/// Decomposes a value into `count` limbs of `limb_bits` bits each, in the given order.
+/// Panics if the value does not fit into the limbs.
+pub fn to_limbs<T: FieldElement>(
+    value: T,
+    limb_bits: usize,
+    count: usize,
+    endianness: Endianness,
+) -> Vec<T> {
+    let integer = value.to_integer();
+    assert!(
+        integer.num_bits() <= limb_bits * count,
+        "Value {value} does not fit into {count} limbs of {limb_bits} bits."
+    );
+    let mask = (0..limb_bits.min(T::Integer::NUM_BITS)).fold(T::Integer::from(0), |mask, bit| {
+        mask | (T::Integer::one() << bit)
+    });
+    let mut limbs = (0..count)
+        .map(|i| match i * limb_bits {
+            shift if shift < T::Integer::NUM_BITS => T::from((integer >> shift) & mask),
+            // The value fits, so the limbs beyond the bits of the integer are zero.
+            _ => T::zero(),
+        })
+        .collect::<Vec<_>>();
+    if endianness == Endianness::Big {
+        limbs.reverse();
+    }
+    limbs
+}
+
+/// Reconstructs a value from limbs of `limb_bits` bits each, in the given order.
+/// Panics if a nonzero limb starts beyond the bits of the field.
+pub fn from_limbs<T: FieldElement>(limbs: &[T], limb_bits: usize, endianness: Endianness) -> T {
+    limbs.iter().enumerate().fold(T::zero(), |acc, (i, limb)| {
+        if limb.is_zero() {
+            return acc;
+        }
+        let weight = endianness
+            .limb_weight::<T>(i, limbs.len(), limb_bits)
+            .unwrap_or_else(|| panic!("Limb {i} of {limb_bits} bits does not fit into the field."));
+        acc + weight * *limb
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use powdr_number::GoldilocksField;
+
+    use super::*;
+
+    #[test]
+    fn limb_order() {
+        let value = GoldilocksField::from(0x12345678u32);
+        let little = to_limbs(value, 8, 4, Endianness::Little);
+        let big = to_limbs(value, 8, 4, Endianness::Big);
+        assert_eq!(
+            little,
+            [0x78u32, 0x56, 0x34, 0x12].map(GoldilocksField::from)
+        );
+        assert_eq!(big, [0x12u32, 0x34, 0x56, 0x78].map(GoldilocksField::from));
+        assert_eq!(from_limbs(&little, 8, Endianness::Little), value);
+        assert_eq!(from_limbs(&big, 8, Endianness::Big), value);
+    }
+
+    #[test]
+    fn limb_weight_beyond_field() {
+        let weight = |index| Endianness::Big.limb_weight::<GoldilocksField>(index, 9, 8);
+        assert_eq!(weight(8), Some(1.into()));
+        assert_eq!(weight(1), Some(GoldilocksField::from(1u64 << 56)));
+        assert_eq!(weight(0), None);
+        assert_eq!(
+            Endianness::Little.limb_weight::<GoldilocksField>(1, 2, usize::MAX),
+            None
+        );
+        // Leading zero limbs do not matter.
+        let limbs = to_limbs(GoldilocksField::from(0x1234u32), 8, 9, Endianness::Big);
+        assert_eq!(limbs[7..], [0x12u32, 0x34].map(GoldilocksField::from));
+        assert_eq!(from_limbs(&limbs, 8, Endianness::Big), 0x1234u32.into());
+    }
+
+    #[test]
+    #[should_panic = "does not fit into 2 limbs of 8 bits"]
+    fn value_too_large() {
+        to_limbs(GoldilocksField::from(0x10000u32), 8, 2, Endianness::Big);
+    }
+}
@@ -0,0 +1,26 @@
+use std::fmt::{self, Display};
+
+/// The scope of the log messages of a [super::processor::Processor], i.e. the name of the
+/// machine it belongs to. Messages are prefixed with the scope, so that the trace output
+/// of interleaved machines can be told apart: `log::trace!("{}...", self.log_scope)`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LogScope<'s> {
+    machine: Option<&'s str>,
+}
+
+impl<'s> LogScope<'s> {
+    pub fn new(machine: &'s str) -> Self {
+        Self {
+            machine: Some(machine),
+        }
+    }
+}
+
+impl Display for LogScope<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.machine {
+            Some(machine) => write!(f, "[{machine}] "),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::BTreeMap;
+
+use powdr_ast::analyzed::{AlgebraicExpression as Expression, Analyzed, IdentityKind};
+use powdr_ast::parsed::SelectedExpressions;
+use powdr_number::FieldElement;
+
+use super::rows::{Row, RowIndex, RowPair, UnknownStrategy};
+use super::{identities_for_stage, FixedData};
+
+/// Computes, for a lookup or permutation with the given identity ID, how many times
+/// each tuple of the right-hand side (the table) is used by the left-hand side in a
+/// generated witness. Tuples of the table that are never used are included with a count of 0.
+/// Panics if the identity does not exist or the witness is incomplete.
+pub fn lookup_histogram<T: FieldElement>(
+    analyzed: &Analyzed<T>,
+    fixed_col_values: &[(String, Vec<T>)],
+    witness: &[(String, Vec<T>)],
+    identity_id: u64,
+) -> BTreeMap<Vec<T>, usize> {
+    let identities = identities_for_stage(analyzed, 0);
+    let identity = identities
+        .iter()
+        .find(|identity| identity.id == identity_id)
+        .unwrap_or_else(|| panic!("Identity {identity_id} not found."));
+    assert!(
+        matches!(
+            identity.kind,
+            IdentityKind::Plookup | IdentityKind::Permutation
+        ),
+        "Identity {identity_id} is not a lookup or permutation: {identity}"
+    );
+
+    let fixed_data = FixedData::new(analyzed, fixed_col_values, witness, Default::default(), 0);
+    let degree = fixed_data.degree;
+    let mut histogram = BTreeMap::new();
+    let mut used = vec![];
+    for row in 0..degree {
+        let current = Row::fresh(&fixed_data, RowIndex::from_degree(row, degree));
+        let next = Row::fresh(&fixed_data, RowIndex::from_degree(row, degree) + 1);
+        let row_pair = RowPair::new(
+            &current,
+            &next,
+            RowIndex::from_degree(row, degree),
+            &fixed_data,
+            UnknownStrategy::Unknown,
+        );
+        if let Some(tuple) = selected_tuple(&row_pair, &identity.right) {
+            histogram.insert(tuple, 0);
+        }
+        used.extend(selected_tuple(&row_pair, &identity.left));
+    }
+    for tuple in used {
+        if let Some(count) = histogram.get_mut(&tuple) {
+            *count += 1;
+        }
+    }
+    histogram
+}
+
+/// Evaluates the expressions of the selected expressions on the given row pair,
+/// or returns None if the selector is zero.
+fn selected_tuple<T: FieldElement>(
+    row_pair: &RowPair<'_, '_, T>,
+    selected_expressions: &SelectedExpressions<Expression<T>>,
+) -> Option<Vec<T>> {
+    let evaluate = |expr| {
+        row_pair
+            .evaluate(expr)
+            .ok()
+            .and_then(|value| value.constant_value())
+            .unwrap_or_else(|| panic!("Could not evaluate {expr} on a complete witness."))
+    };
+    let is_selected = selected_expressions
+        .selector
+        .as_ref()
+        .map(|selector| !evaluate(selector).is_zero())
+        .unwrap_or(true);
+    is_selected.then(|| {
+        selected_expressions
+            .expressions
+            .iter()
+            .map(evaluate)
+            .collect()
+    })
+}
@@ -6,15 +6,17 @@ use super::{EvalResult, FixedData, FixedLookup};
 
 use crate::witgen::block_processor::BlockProcessor;
 use crate::witgen::data_structures::finalizable_data::FinalizableData;
+use crate::witgen::identity_budget::IdentityBudget;
 use crate::witgen::identity_processor::IdentityProcessor;
-use crate::witgen::processor::{OuterQuery, Processor};
+use crate::witgen::pending_calls::PendingCall;
+use crate::witgen::processor::{OuterQuery, Processor, Provenance};
 use crate::witgen::rows::{CellValue, Row, RowIndex, RowPair, UnknownStrategy};
 use crate::witgen::sequence_iterator::{
     DefaultSequenceIterator, ProcessingSequenceCache, ProcessingSequenceIterator,
 };
 use crate::witgen::util::try_to_simple_poly;
-use crate::witgen::{machines::Machine, EvalError, EvalValue, IncompleteCause};
-use crate::witgen::{MutableState, QueryCallback};
+use crate::witgen::{machines::Machine, Constraint, EvalError, EvalValue, IncompleteCause};
+use crate::witgen::{MutableState, QueryCallback, Scratchpad, TieBreaker, WitnessProvenance};
 use itertools::Itertools;
 use powdr_ast::analyzed::{
     AlgebraicExpression as Expression, AlgebraicReference, Identity, IdentityKind, PolyID,
@@ -117,6 +119,19 @@ pub struct BlockMachine<'a, T: FieldElement> {
     processing_sequence_cache: ProcessingSequenceCache,
     fixed_data: &'a FixedData<'a, T>,
     name: String,
+    /// The block index of each block (in the order of the calls), if a block placement
+    /// strategy is used.
+    block_indices: Vec<usize>,
+    /// The calls (connecting identity ID and caller row) that were answered as incomplete
+    /// and have not been completed since.
+    pending_calls: BTreeSet<(u64, DegreeType)>,
+    identity_budget: Option<IdentityBudget>,
+    /// The left values of successful calls, by connecting identity ID and canonical inputs
+    /// (see [crate::witgen::InputCanonicalization]).
+    output_cache: BTreeMap<(u64, Vec<Option<T>>), Vec<T>>,
+    /// The provenance of the cells, by row (including the dummy block) and column, taken
+    /// from the data together with the witness columns.
+    provenance: BTreeMap<(usize, PolyID), Provenance>,
 }
 
 impl<'a, T: FieldElement> BlockMachine<'a, T> {
@@ -154,6 +169,10 @@ impl<'a, T: FieldElement> BlockMachine<'a, T> {
             witness_cols,
             (0..block_size).map(|i| Row::fresh(fixed_data, start_index + i)),
         );
+        let identity_budget = fixed_data
+            .options
+            .identity_budget
+            .map(|budget| IdentityBudget::new(&name, budget));
         Some(BlockMachine {
             name,
             block_size,
@@ -169,6 +188,11 @@ impl<'a, T: FieldElement> BlockMachine<'a, T> {
                 identities.len(),
             ),
             fixed_data,
+            block_indices: vec![],
+            pending_calls: BTreeSet::new(),
+            identity_budget,
+            output_cache: BTreeMap::new(),
+            provenance: BTreeMap::new(),
         })
     }
 }
@@ -246,7 +270,7 @@ fn try_to_period<T: FieldElement>(
                 return None;
             }
 
-            let values = fixed_data.fixed_cols[&poly.poly_id].values;
+            let values = fixed_data.fixed_cols[&poly.poly_id].values();
 
             let offset = values.iter().position(|v| v.is_one())?;
             let period = 1 + values.iter().skip(offset + 1).position(|v| v.is_one())?;
@@ -286,14 +310,43 @@ impl<'a, T: FieldElement> Machine<'a, T> for BlockMachine<'a, T> {
         let previous_len = self.data.len();
         let result = self.process_plookup_internal(mutable_state, identity_id, caller_rows);
         if let Ok(assignments) = &result {
-            if !assignments.is_complete() {
+            let call = (identity_id, caller_rows.current_row_index.into());
+            if assignments.is_complete() {
+                self.pending_calls.remove(&call);
+            } else {
                 // rollback the changes.
                 self.data.truncate(previous_len);
+                self.pending_calls.insert(call);
             }
         }
         result
     }
 
+    fn take_provenance(&mut self) -> WitnessProvenance {
+        std::mem::take(&mut self.provenance)
+            .into_iter()
+            .filter_map(|((i, poly_id), provenance)| {
+                // Rows of the dummy block are not part of the witness.
+                let row = self.placed_row(i.checked_sub(self.block_size)?)?;
+                (row < self.fixed_data.degree as usize).then(|| {
+                    let name = self.fixed_data.column_name(&poly_id).to_string();
+                    ((name, row as DegreeType), provenance)
+                })
+            })
+            .collect()
+    }
+
+    fn pending_calls(&self) -> Vec<PendingCall> {
+        self.pending_calls
+            .iter()
+            .map(|(identity_id, caller_row)| PendingCall {
+                machine: self.name.clone(),
+                identity_id: *identity_id,
+                caller_row: *caller_row,
+            })
+            .collect()
+    }
+
     fn name(&self) -> &str {
         &self.name
     }
@@ -302,6 +355,8 @@ impl<'a, T: FieldElement> Machine<'a, T> for BlockMachine<'a, T> {
         &mut self,
         fixed_lookup: &'b mut FixedLookup<T>,
         query_callback: &'b mut Q,
+        scratchpad: &'b Scratchpad,
+        tie_breaker: Option<&'b TieBreaker>,
     ) -> HashMap<String, Vec<T>> {
         if self.data.len() < 2 * self.block_size {
             log::warn!(
@@ -329,6 +384,8 @@ impl<'a, T: FieldElement> Machine<'a, T> for BlockMachine<'a, T> {
                 fixed_lookup,
                 machines: vec![].into_iter().into(),
                 query_callback,
+                scratchpad,
+                tie_breaker,
             };
             let mut processor = Processor::new(
                 row_offset,
@@ -336,7 +393,8 @@ impl<'a, T: FieldElement> Machine<'a, T> for BlockMachine<'a, T> {
                 &mut mutable_state,
                 self.fixed_data,
                 &self.witness_cols,
-            );
+            )
+            .with_log_scope(&self.name);
 
             // Set all selectors to 0
             for id in self.connecting_identities.values() {
@@ -345,6 +403,7 @@ impl<'a, T: FieldElement> Machine<'a, T> for BlockMachine<'a, T> {
                         self.latch_row + 1,
                         id.right.selector.as_ref().unwrap(),
                         T::zero(),
+                        Provenance::Machine,
                         || "Zero selectors".to_string(),
                     )
                     .unwrap();
@@ -365,6 +424,7 @@ impl<'a, T: FieldElement> Machine<'a, T> for BlockMachine<'a, T> {
             }
         }
 
+        self.provenance = self.data.take_provenance();
         let mut data = self
             .data
             .take_transposed()
@@ -411,7 +471,8 @@ impl<'a, T: FieldElement> Machine<'a, T> for BlockMachine<'a, T> {
                     })
                     .collect::<Vec<_>>();
 
-                let values = values
+                let values = self
+                    .place_blocks(values)
                     .into_iter()
                     .enumerate()
                     .map(|(i, v)| v.unwrap_or(default_block[i % self.block_size]))
@@ -472,12 +533,13 @@ impl<'a, T: FieldElement> BlockMachine<'a, T> {
         identity_id: u64,
         caller_rows: &'b RowPair<'b, 'a, T>,
     ) -> EvalResult<'a, T> {
-        let outer_query = OuterQuery::new(caller_rows, self.connecting_identities[&identity_id]);
+        let mut outer_query =
+            OuterQuery::new(caller_rows, self.connecting_identities[&identity_id]);
 
         log::trace!("Start processing block machine '{}'", self.name());
         log::trace!("Left values of lookup:");
         for l in &outer_query.left {
-            log::trace!("  {}", l);
+            log::trace!("  {l}");
         }
 
         // First check if we already store the value.
@@ -512,6 +574,33 @@ impl<'a, T: FieldElement> BlockMachine<'a, T> {
             }
         }
 
+        // If a call with the same canonical inputs succeeded before, use its outputs.
+        let cache_key = self
+            .fixed_data
+            .options
+            .input_canonicalization
+            .map(|canonicalize| {
+                let values = outer_query
+                    .left
+                    .iter()
+                    .map(|l| l.constant_value())
+                    .collect::<Vec<_>>();
+                (identity_id, canonicalize(&self.name, &values))
+            });
+        let mut cached_updates = EvalValue::complete(vec![]);
+        if let Some(cached) = cache_key
+            .as_ref()
+            .and_then(|key| self.output_cache.get(key))
+        {
+            log::trace!("Using cached outputs for block machine '{}'", self.name());
+            for (l, value) in outer_query.left.iter_mut().zip(cached) {
+                if !l.is_constant() {
+                    cached_updates.combine((l.clone() - (*value).into()).solve()?);
+                    *l = (*value).into();
+                }
+            }
+        }
+
         // TODO this assumes we are always using the same lookup for this machine.
         let mut sequence_iterator = self
             .processing_sequence_cache
@@ -532,8 +621,38 @@ impl<'a, T: FieldElement> BlockMachine<'a, T> {
             return Err(EvalError::RowsExhausted(self.name.clone()));
         }
 
-        let process_result =
-            self.process(mutable_state, &mut sequence_iterator, outer_query.clone())?;
+        let block_index = self
+            .fixed_data
+            .options
+            .block_placement
+            .map(|block_placement| {
+                let key = outer_query
+                    .left
+                    .iter()
+                    .map(|l| l.constant_value())
+                    .collect::<Vec<_>>();
+                let call_index = self.rows() as usize / self.block_size;
+                block_placement(&self.name, call_index, &key)
+            });
+        if let Some(block_index) = block_index {
+            self.check_block_index(block_index)?;
+        }
+
+        let delegated_block = self.fixed_data.options.block_delegate.and_then(|delegate| {
+            let values = outer_query
+                .left
+                .iter()
+                .map(|l| l.constant_value())
+                .collect::<Vec<_>>();
+            delegate(&self.name, &values)
+        });
+
+        let process_result = self.process(
+            mutable_state,
+            &mut sequence_iterator,
+            outer_query.clone(),
+            delegated_block.as_deref(),
+        )?;
 
         let process_result = if sequence_iterator.is_cached() && !process_result.is_success() {
             log::debug!("The cached sequence did not complete the block machine. \
@@ -542,18 +661,36 @@ impl<'a, T: FieldElement> BlockMachine<'a, T> {
             let mut sequence_iterator = self
                 .processing_sequence_cache
                 .get_default_sequence_iterator();
-            self.process(mutable_state, &mut sequence_iterator, outer_query.clone())?
+            self.process(
+                mutable_state,
+                &mut sequence_iterator,
+                outer_query.clone(),
+                delegated_block.as_deref(),
+            )?
         } else {
             process_result
         };
 
         match process_result {
-            ProcessResult::Success(new_block, updates) => {
+            ProcessResult::Success(new_block, mut updates) => {
                 log::trace!(
                     "End processing block machine '{}' (successfully)",
                     self.name()
                 );
+                if let Some(key) = cache_key {
+                    let mut left = outer_query.left.clone();
+                    for (poly, c) in &updates.constraints {
+                        if let Constraint::Assignment(value) = c {
+                            left.iter_mut().for_each(|l| l.assign(poly, *value));
+                        }
+                    }
+                    if let Some(values) = left.iter().map(|l| l.constant_value()).collect() {
+                        self.output_cache.entry(key).or_insert(values);
+                    }
+                }
+                updates.combine(cached_updates);
                 self.append_block(new_block)?;
+                self.block_indices.extend(block_index);
 
                 // TODO: This would be the right thing to do, but currently leads to failing tests
                 // due to #1385 ("Witgen: Block machines "forget" that they already completed a block"):
@@ -565,32 +702,81 @@ impl<'a, T: FieldElement> BlockMachine<'a, T> {
                     .report_processing_sequence(&outer_query.left, sequence_iterator);
                 Ok(updates)
             }
-            ProcessResult::Incomplete(updates) => {
+            ProcessResult::Incomplete(mut updates) => {
                 log::trace!(
                     "End processing block machine '{}' (incomplete)",
                     self.name()
                 );
                 self.processing_sequence_cache
                     .report_incomplete(&outer_query.left);
+                updates.combine(cached_updates);
                 Ok(updates)
             }
         }
     }
 
+    /// Checks that a block index chosen by the block placement strategy is free and
+    /// leaves the last block free, like the sequential placement does.
+    fn check_block_index(&self, block_index: usize) -> Result<(), EvalError<T>> {
+        if (block_index + 1) * self.block_size >= self.fixed_data.degree as usize {
+            return Err(EvalError::Generic(format!(
+                "Block index {block_index} chosen for machine {} is out of range.",
+                self.name
+            )));
+        }
+        if self.block_indices.contains(&block_index) {
+            return Err(EvalError::Generic(format!(
+                "Block index {block_index} chosen for machine {} is already used.",
+                self.name
+            )));
+        }
+        Ok(())
+    }
+
+    /// Moves the blocks of a column (without the dummy block) from the order of the calls
+    /// to the block indices chosen by the block placement strategy.
+    /// Returns the row at which the given row (not counting the dummy block) is placed by
+    /// [BlockMachine::place_blocks], or None if it is not part of a placed block.
+    fn placed_row(&self, row: usize) -> Option<usize> {
+        if self.block_indices.is_empty() {
+            return Some(row);
+        }
+        let block_index = self.block_indices.get(row / self.block_size)?;
+        Some(block_index * self.block_size + row % self.block_size)
+    }
+
+    fn place_blocks(&self, values: Vec<Option<T>>) -> Vec<Option<T>> {
+        if self.block_indices.is_empty() {
+            return values;
+        }
+        let mut placed = vec![None; values.len()];
+        for (block, block_index) in self.block_indices.iter().enumerate() {
+            let source = block * self.block_size;
+            let target = block_index * self.block_size;
+            placed[target..target + self.block_size]
+                .copy_from_slice(&values[source..source + self.block_size]);
+        }
+        placed
+    }
+
     fn process<'b, Q: QueryCallback<T>>(
         &self,
         mutable_state: &mut MutableState<'a, 'b, T, Q>,
         sequence_iterator: &mut ProcessingSequenceIterator,
         outer_query: OuterQuery<'a, 'b, T>,
+        delegated_block: Option<&[(String, Vec<T>)]>,
     ) -> Result<ProcessResult<'a, T>, EvalError<T>> {
         // We start at the last row of the previous block.
         let row_offset = self.last_row_index();
         // Make the block two rows larger than the block size, it includes the last row of the previous block
         // and the first row of the next block.
-        let block = FinalizableData::with_initial_rows_in_progress(
+        let mut block = FinalizableData::with_initial_rows_in_progress(
             &self.witness_cols,
             (0..(self.block_size + 2)).map(|i| Row::fresh(self.fixed_data, row_offset + i)),
         );
+        if let Some(delegated_block) = delegated_block {
+            self.fill_delegated_block(&mut block, delegated_block)?;
+        }
         let mut processor = BlockProcessor::new(
             row_offset,
             block,
@@ -599,14 +785,98 @@ impl<'a, T: FieldElement> BlockMachine<'a, T> {
             self.fixed_data,
             &self.witness_cols,
         )
-        .with_outer_query(outer_query);
-
-        let outer_assignments = processor.solve(sequence_iterator)?;
+        .with_log_scope(&self.name)
+        .with_outer_query(outer_query)
+        .with_identity_budget(self.identity_budget.as_ref());
+
+        // For a delegated block, solving validates the block against the identities.
+        let outer_assignments =
+            processor
+                .solve(sequence_iterator)
+                .map_err(|e| match delegated_block {
+                    Some(_) => EvalError::Generic(format!(
+                        "Delegated block of machine {} is invalid: {e}",
+                        self.name
+                    )),
+                    None => e,
+                })?;
         let new_block = processor.finish();
 
         Ok(ProcessResult::new(new_block, outer_assignments))
     }
 
+    /// Sets the values returned by the [crate::witgen::BlockDelegate] in the rows of the
+    /// block (excluding the last row of the previous block and the first row of the next).
+    fn fill_delegated_block(
+        &self,
+        block: &mut FinalizableData<'a, T>,
+        delegated_block: &[(String, Vec<T>)],
+    ) -> Result<(), EvalError<T>> {
+        for (name, values) in delegated_block {
+            let poly_id = self
+                .fixed_data
+                .try_column_by_name(name)
+                .filter(|poly_id| self.witness_cols.contains(poly_id))
+                .ok_or_else(|| {
+                    EvalError::Generic(format!(
+                        "Delegated block of machine {} sets {name}, which is not a witness column of the machine.",
+                        self.name
+                    ))
+                })?;
+            if values.len() != self.block_size {
+                return Err(EvalError::Generic(format!(
+                    "Delegated block of machine {} has {} values for {name}, but the block size is {}.",
+                    self.name,
+                    values.len(),
+                    self.block_size
+                )));
+            }
+            for (i, value) in values.iter().enumerate() {
+                block[i + 1][&poly_id].value = CellValue::Known(*value);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn witness_cols(&self) -> &HashSet<PolyID> {
+        &self.witness_cols
+    }
+
+    /// Processes a single block in which the latch row has the given input values,
+    /// independently of any caller and of the blocks processed so far.
+    /// The block starts at row 0, so that it sees the same fixed values as the first block.
+    /// @returns the rows of the block.
+    pub fn process_single_block<'b, Q: QueryCallback<T>>(
+        &self,
+        mutable_state: &mut MutableState<'a, 'b, T, Q>,
+        inputs: &[(PolyID, T)],
+    ) -> Result<Vec<Row<'a, T>>, EvalError<T>> {
+        // Like in `process`, the block includes the last row of the previous block
+        // and the first row of the next block.
+        let row_offset = RowIndex::from_i64(-1, self.fixed_data.degree);
+        let block = FinalizableData::with_initial_rows_in_progress(
+            &self.witness_cols,
+            (0..(self.block_size + 2)).map(|i| Row::fresh(self.fixed_data, row_offset + i)),
+        );
+        let mut processor = BlockProcessor::new(
+            row_offset,
+            block,
+            mutable_state,
+            &self.identities,
+            self.fixed_data,
+            &self.witness_cols,
+        )
+        .with_log_scope(&self.name)
+        .with_identity_budget(self.identity_budget.as_ref());
+        processor.set_inputs(1 + self.latch_row, inputs)?;
+        let mut sequence_iterator = ProcessingSequenceIterator::Default(
+            DefaultSequenceIterator::new(self.block_size, self.identities.len(), None),
+        );
+        processor.solve(&mut sequence_iterator)?;
+        let block = processor.finish();
+        Ok((1..=self.block_size).map(|i| block[i].clone()).collect())
+    }
+
     /// Takes a block of rows, which contains the last row of its previous block
     /// and the first row of its next block. The first row of its next block is ignored,
     /// the last row of its previous block is merged with the one we have already.
@@ -637,7 +907,14 @@ impl<'a, T: FieldElement> BlockMachine<'a, T> {
             }
         }
 
-        // 3. Remove the last row of the previous block from data
+        // 3. Remove the last row of the previous block from data, keeping the provenance
+        // of its cells.
+        let last_row = self.data.len() - 1;
+        for poly_id in &self.witness_cols {
+            if let Some(provenance) = self.data.provenance(last_row, poly_id) {
+                new_block.set_provenance(0, *poly_id, provenance);
+            }
+        }
         self.data.pop();
 
         // 4. Finalize most of the block (unless it's the dummy block)
@@ -6,7 +6,7 @@ use itertools::Itertools;
 use super::{FixedLookup, Machine};
 use crate::witgen::rows::RowPair;
 use crate::witgen::util::try_to_simple_poly;
-use crate::witgen::{EvalResult, FixedData, MutableState, QueryCallback};
+use crate::witgen::{EvalResult, FixedData, MutableState, QueryCallback, Scratchpad, TieBreaker};
 use crate::witgen::{EvalValue, IncompleteCause};
 use powdr_number::{DegreeType, FieldElement};
 
@@ -201,6 +201,8 @@ impl<'a, T: FieldElement> Machine<'a, T> for DoubleSortedWitnesses<'a, T> {
         &mut self,
         _fixed_lookup: &'b mut FixedLookup<T>,
         _query_callback: &'b mut Q,
+        _scratchpad: &'b Scratchpad,
+        _tie_breaker: Option<&'b TieBreaker>,
     ) -> HashMap<String, Vec<T>> {
         let mut addr = vec![];
         let mut step = vec![];
@@ -400,11 +402,7 @@ impl<'a, T: FieldElement> DoubleSortedWitnesses<'a, T> {
                 }
             };
 
-            log::trace!(
-                "Memory write: addr={:x}, step={step}, value={:x}",
-                addr,
-                value
-            );
+            log::trace!("Memory write: addr={addr:x}, step={step}, value={value:x}");
             self.data.insert(addr, value);
             self.trace
                 .insert(
@@ -419,11 +417,7 @@ impl<'a, T: FieldElement> DoubleSortedWitnesses<'a, T> {
                 .is_none()
         } else {
             let value = self.data.entry(addr).or_default();
-            log::trace!(
-                "Memory read: addr={:x}, step={step}, value={:x}",
-                addr,
-                value
-            );
+            log::trace!("Memory read: addr={addr:x}, step={step}, value={value:x}");
             let ass =
                 (value_expr.clone() - (*value).into()).solve_with_range_constraints(caller_rows)?;
             assignments.combine(ass);
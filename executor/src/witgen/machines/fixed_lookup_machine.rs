@@ -8,7 +8,7 @@ use powdr_ast::analyzed::{
     AlgebraicExpression as Expression, AlgebraicReference, IdentityKind, PolyID, PolynomialType,
 };
 use powdr_ast::parsed::SelectedExpressions;
-use powdr_number::FieldElement;
+use powdr_number::{DegreeType, FieldElement};
 
 use crate::witgen::affine_expression::AffineExpression;
 use crate::witgen::global_constraints::{GlobalConstraints, RangeConstraintSet};
@@ -16,7 +16,7 @@ use crate::witgen::machines::record_start;
 use crate::witgen::range_constraints::RangeConstraint;
 use crate::witgen::rows::RowPair;
 use crate::witgen::util::try_to_simple_poly_ref;
-use crate::witgen::{EvalError, EvalValue, IncompleteCause};
+use crate::witgen::{EvalError, EvalValue, IncompleteCause, KeyValueMiss};
 use crate::witgen::{EvalResult, FixedData};
 
 use super::record_end;
@@ -101,12 +101,12 @@ impl<T: FieldElement> IndexedColumns<T> {
         // get all values for the columns to be indexed
         let input_column_values = sorted_input_fixed_columns
             .iter()
-            .map(|id| fixed_data.fixed_cols[id].values)
+            .map(|id| fixed_data.fixed_cols[id].values())
             .collect::<Vec<_>>();
 
         let output_column_values = sorted_output_fixed_columns
             .iter()
-            .map(|id| fixed_data.fixed_cols[id].values)
+            .map(|id| fixed_data.fixed_cols[id].values())
             .collect::<Vec<_>>();
 
         let index: BTreeMap<Vec<T>, IndexValue> = (0..fixed_data.degree as usize)
@@ -250,39 +250,70 @@ impl<T: FieldElement> FixedLookup<T> {
             }
         });
 
-        let input_assignment_with_ids = input_assignment
-            .iter()
-            .map(|(poly_ref, v)| (poly_ref.poly_id, *v))
-            .collect();
-        let index_value = self
-            .indices
-            .get_match(
-                fixed_data,
-                input_assignment_with_ids,
-                output_columns.clone(),
-            )
-            .ok_or_else(|| {
-                let input_assignment = input_assignment
-                    .into_iter()
-                    .map(|(poly_ref, v)| (poly_ref.name.clone(), v))
-                    .collect();
-                EvalError::FixedLookupFailed(input_assignment)
-            })?;
+        let lookup_failed = || {
+            let input_assignment = input_assignment
+                .iter()
+                .map(|(poly_ref, v)| (poly_ref.name.clone(), *v))
+                .collect();
+            EvalError::FixedLookupFailed(input_assignment)
+        };
 
-        let row = match index_value.row() {
-            // a single match, we continue
-            Some(row) => row,
-            // multiple matches, we stop and learnt nothing
-            None => {
-                return Ok(EvalValue::incomplete(
-                    IncompleteCause::MultipleLookupMatches,
-                ))
+        let external_output = match fixed_data.options.key_value_table {
+            Some((table, on_miss)) => {
+                let input_columns = input_assignment
+                    .iter()
+                    .map(|(poly_ref, _)| poly_ref.name.as_str())
+                    .collect::<Vec<_>>();
+                let key = input_assignment.iter().map(|(_, v)| *v).collect::<Vec<_>>();
+                let output_names = output_columns
+                    .iter()
+                    .map(|column| fixed_data.column_name(column))
+                    .collect::<Vec<_>>();
+                match table.get(&input_columns, &key, &output_names) {
+                    Some(output) => {
+                        assert_eq!(output.len(), output_columns.len());
+                        Some(output)
+                    }
+                    None if on_miss == KeyValueMiss::Error => return Err(lookup_failed()),
+                    None => None,
+                }
             }
+            None => None,
         };
 
-        let output = output_columns
-            .iter()
-            .map(|column| fixed_data.fixed_cols[column].values[row]);
+        let output = match external_output {
+            Some(output) => output,
+            None => {
+                let input_assignment_with_ids = input_assignment
+                    .iter()
+                    .map(|(poly_ref, v)| (poly_ref.poly_id, *v))
+                    .collect();
+                let index_value = self
+                    .indices
+                    .get_match(
+                        fixed_data,
+                        input_assignment_with_ids,
+                        output_columns.clone(),
+                    )
+                    .ok_or_else(lookup_failed)?;
+
+                let row = match index_value.row() {
+                    // a single match, we continue
+                    Some(row) => row,
+                    // multiple matches, we stop and learnt nothing
+                    None => {
+                        return Ok(EvalValue::incomplete(
+                            IncompleteCause::MultipleLookupMatches,
+                        ))
+                    }
+                };
+
+                output_columns
+                    .iter()
+                    .map(|column| fixed_data.fixed_cols[column].value(row as DegreeType))
+                    .collect()
+            }
+        };
 
         let mut result = EvalValue::complete(vec![]);
         for (l, r) in output_expressions.into_iter().zip(output) {
@@ -7,8 +7,10 @@ use super::fixed_lookup_machine::FixedLookup;
 use super::sorted_witness_machine::SortedWitnesses;
 use super::FixedData;
 use super::KnownMachine;
+use super::Machine;
 use crate::witgen::generator::Generator;
 use crate::witgen::machines::write_once_memory::WriteOnceMemory;
+use crate::witgen::plan::MachinePlan;
 use itertools::Itertools;
 use powdr_ast::analyzed::{AlgebraicExpression as Expression, Identity, IdentityKind, PolyID};
 use powdr_ast::parsed::visitor::ExpressionVisitable;
@@ -18,6 +20,8 @@ use powdr_number::FieldElement;
 pub struct ExtractionOutput<'a, T: FieldElement> {
     pub fixed_lookup: FixedLookup<T>,
     pub machines: Vec<KnownMachine<'a, T>>,
+    /// For each machine (in the same order as `machines`), a description of its parts.
+    pub machine_plans: Vec<MachinePlan>,
     pub base_identities: Vec<&'a Identity<Expression<T>>>,
     pub base_witnesses: HashSet<PolyID>,
 }
@@ -32,6 +36,7 @@ pub fn split_out_machines<'a, T: FieldElement>(
     let fixed_lookup = FixedLookup::new(fixed.global_range_constraints().clone());
 
     let mut machines: Vec<KnownMachine<T>> = vec![];
+    let mut machine_plans = vec![];
 
     let all_witnesses = fixed.witness_cols.keys().collect::<HashSet<_>>();
     let mut remaining_witnesses = all_witnesses.clone();
@@ -171,15 +176,23 @@ pub fn split_out_machines<'a, T: FieldElement>(
                 name_with_type("Vm"),
                 fixed,
                 &connecting_identities,
-                machine_identities,
-                machine_witnesses,
+                machine_identities.clone(),
+                machine_witnesses.clone(),
                 Some(latch),
             )));
         }
+        machine_plans.push(MachinePlan::new(
+            machines.last().unwrap().name().to_string(),
+            fixed,
+            &machine_witnesses,
+            &machine_identities,
+            connecting_identities.keys().copied(),
+        ));
     }
     ExtractionOutput {
         fixed_lookup,
         machines,
+        machine_plans,
         base_identities,
         base_witnesses: remaining_witnesses,
     }
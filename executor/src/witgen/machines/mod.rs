@@ -11,11 +11,13 @@ use self::sorted_witness_machine::SortedWitnesses;
 use self::write_once_memory::WriteOnceMemory;
 
 use super::generator::Generator;
+use super::pending_calls::PendingCall;
 use super::rows::RowPair;
 use super::EvalResult;
 use super::FixedData;
 use super::MutableState;
 use super::QueryCallback;
+use super::{Scratchpad, TieBreaker, WitnessProvenance};
 
 mod block_machine;
 mod double_sorted_witness_machine;
@@ -59,10 +61,25 @@ pub trait Machine<'a, T: FieldElement>: Send + Sync {
         &mut self,
         fixed_lookup: &'b mut FixedLookup<T>,
         query_callback: &'b mut Q,
+        scratchpad: &'b Scratchpad,
+        tie_breaker: Option<&'b TieBreaker>,
     ) -> HashMap<String, Vec<T>>;
 
     /// Returns the identity IDs that this machine is responsible for.
     fn identity_ids(&self) -> Vec<u64>;
+
+    /// Returns the calls to this machine that were answered as incomplete and have not
+    /// been completed since.
+    fn pending_calls(&self) -> Vec<PendingCall> {
+        vec![]
+    }
+
+    /// Returns how the cells of the witness columns were determined, by column name and
+    /// row, if provenance tracking is enabled. Has to be called after
+    /// [Machine::take_witness_col_values].
+    fn take_provenance(&mut self) -> WitnessProvenance {
+        WitnessProvenance::new()
+    }
 }
 
 /// All known implementations of [Machine].
@@ -114,21 +131,25 @@ impl<'a, T: FieldElement> Machine<'a, T> for KnownMachine<'a, T> {
         &mut self,
         fixed_lookup: &'b mut FixedLookup<T>,
         query_callback: &'b mut Q,
+        scratchpad: &'b Scratchpad,
+        tie_breaker: Option<&'b TieBreaker>,
     ) -> HashMap<String, Vec<T>> {
         match self {
             KnownMachine::SortedWitnesses(m) => {
-                m.take_witness_col_values(fixed_lookup, query_callback)
+                m.take_witness_col_values(fixed_lookup, query_callback, scratchpad, tie_breaker)
             }
             KnownMachine::DoubleSortedWitnesses(m) => {
-                m.take_witness_col_values(fixed_lookup, query_callback)
+                m.take_witness_col_values(fixed_lookup, query_callback, scratchpad, tie_breaker)
             }
             KnownMachine::WriteOnceMemory(m) => {
-                m.take_witness_col_values(fixed_lookup, query_callback)
+                m.take_witness_col_values(fixed_lookup, query_callback, scratchpad, tie_breaker)
             }
             KnownMachine::BlockMachine(m) => {
-                m.take_witness_col_values(fixed_lookup, query_callback)
+                m.take_witness_col_values(fixed_lookup, query_callback, scratchpad, tie_breaker)
+            }
+            KnownMachine::Vm(m) => {
+                m.take_witness_col_values(fixed_lookup, query_callback, scratchpad, tie_breaker)
             }
-            KnownMachine::Vm(m) => m.take_witness_col_values(fixed_lookup, query_callback),
         }
     }
 
@@ -141,4 +162,24 @@ impl<'a, T: FieldElement> Machine<'a, T> for KnownMachine<'a, T> {
             KnownMachine::Vm(m) => m.identity_ids(),
         }
     }
+
+    fn pending_calls(&self) -> Vec<PendingCall> {
+        match self {
+            KnownMachine::SortedWitnesses(m) => m.pending_calls(),
+            KnownMachine::DoubleSortedWitnesses(m) => m.pending_calls(),
+            KnownMachine::WriteOnceMemory(m) => m.pending_calls(),
+            KnownMachine::BlockMachine(m) => m.pending_calls(),
+            KnownMachine::Vm(m) => m.pending_calls(),
+        }
+    }
+
+    fn take_provenance(&mut self) -> WitnessProvenance {
+        match self {
+            KnownMachine::SortedWitnesses(m) => m.take_provenance(),
+            KnownMachine::DoubleSortedWitnesses(m) => m.take_provenance(),
+            KnownMachine::WriteOnceMemory(m) => m.take_provenance(),
+            KnownMachine::BlockMachine(m) => m.take_provenance(),
+            KnownMachine::Vm(m) => m.take_provenance(),
+        }
+    }
 }
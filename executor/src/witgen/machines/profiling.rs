@@ -4,6 +4,8 @@ use std::{
     time::{Duration, Instant},
 };
 
+use crate::witgen::util::json_string;
+
 #[derive(PartialEq, Debug, Copy, Clone)]
 enum Event {
     Start,
@@ -42,19 +44,34 @@ pub fn record_end(name: &str) {
     EVENT_LOG.with(|s| s.borrow_mut().push((Event::End, id, Instant::now())));
 }
 
-pub fn reset_and_print_profile_summary() {
-    EVENT_LOG.with(|event_log| {
-        let id_to_name = NAME_TO_ID.with(|name_to_id| {
-            let name_to_id = name_to_id.borrow();
-            name_to_id
-                .iter()
-                .map(|(name, id)| (*id, name.clone()))
-                .collect::<BTreeMap<_, _>>()
-        });
-
-        // Taking the events out is actually important, because there might be
-        // multiple (consecutive) runs of witgen in the same thread.
-        let event_log = std::mem::take(&mut (*event_log.borrow_mut()));
+/// The events recorded during a run of witgen, see [take_profile].
+pub struct Profile {
+    event_log: Vec<(Event, usize, Instant)>,
+    id_to_name: BTreeMap<usize, String>,
+}
+
+/// Takes the events recorded so far in this thread.
+pub fn take_profile() -> Profile {
+    let id_to_name = NAME_TO_ID.with(|name_to_id| {
+        let name_to_id = name_to_id.borrow();
+        name_to_id
+            .iter()
+            .map(|(name, id)| (*id, name.clone()))
+            .collect::<BTreeMap<_, _>>()
+    });
+
+    // Taking the events out is actually important, because there might be
+    // multiple (consecutive) runs of witgen in the same thread.
+    let event_log = EVENT_LOG.with(|event_log| std::mem::take(&mut (*event_log.borrow_mut())));
+    Profile {
+        event_log,
+        id_to_name,
+    }
+}
+
+impl Profile {
+    pub fn print_summary(&self) {
+        let event_log = &self.event_log;
         log::debug!("\n == Witgen profile ({} events)", event_log.len());
 
         // Aggregate time spent in each machine.
@@ -112,11 +129,36 @@ pub fn reset_and_print_profile_summary() {
                 "  {:>5.1}% ({:>8.1?}): {}",
                 percentage,
                 duration,
-                id_to_name[&id]
+                self.id_to_name[&id]
             );
         }
         log::debug!("  ---------------------------");
-        log::debug!("    ==> Total: {:?}", total_time);
+        log::debug!("    ==> Total: {total_time:?}");
         log::debug!("\n");
-    });
+    }
+
+    /// Renders the events as nested duration events in the Chrome tracing format
+    /// (to be opened in `chrome://tracing` or Perfetto), one event per line.
+    /// Timestamps are in microseconds since the first event.
+    pub fn to_chrome_trace(&self) -> String {
+        let Some((_, _, start)) = self.event_log.first() else {
+            return "{\"traceEvents\":[]}\n".to_string();
+        };
+        let events = self
+            .event_log
+            .iter()
+            .map(|(event, id, time)| {
+                let phase = match event {
+                    Event::Start => "B",
+                    Event::End => "E",
+                };
+                let timestamp = time.duration_since(*start).as_secs_f64() * 1_000_000.0;
+                format!(
+                    r#"{{"name":{},"cat":"witgen","ph":"{phase}","ts":{timestamp:.3},"pid":1,"tid":1}}"#,
+                    json_string(&self.id_to_name[id])
+                )
+            })
+            .collect::<Vec<_>>();
+        format!("{{\"traceEvents\":[\n{}\n]}}\n", events.join(",\n"))
+    }
 }
@@ -10,7 +10,9 @@ use crate::witgen::{
     expression_evaluator::ExpressionEvaluator, fixed_evaluator::FixedEvaluator,
     symbolic_evaluator::SymbolicEvaluator,
 };
-use crate::witgen::{EvalValue, IncompleteCause, MutableState, QueryCallback};
+use crate::witgen::{
+    EvalValue, IncompleteCause, MutableState, QueryCallback, Scratchpad, TieBreaker,
+};
 use powdr_ast::analyzed::{
     AlgebraicExpression as Expression, AlgebraicReference, Identity, IdentityKind, PolyID,
 };
@@ -181,6 +183,8 @@ impl<'a, T: FieldElement> Machine<'a, T> for SortedWitnesses<'a, T> {
         &mut self,
         _fixed_lookup: &'b mut FixedLookup<T>,
         _query_callback: &'b mut Q,
+        _scratchpad: &'b Scratchpad,
+        _tie_breaker: Option<&'b TieBreaker>,
     ) -> HashMap<String, Vec<T>> {
         let mut result = HashMap::new();
 
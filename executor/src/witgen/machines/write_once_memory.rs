@@ -9,7 +9,7 @@ use powdr_number::{DegreeType, FieldElement};
 
 use crate::witgen::{
     rows::RowPair, util::try_to_simple_poly, EvalError, EvalResult, EvalValue, FixedData,
-    IncompleteCause, MutableState, QueryCallback,
+    IncompleteCause, MutableState, QueryCallback, Scratchpad, TieBreaker,
 };
 
 use super::{FixedLookup, Machine};
@@ -102,7 +102,7 @@ impl<'a, T: FieldElement> WriteOnceMemory<'a, T> {
         for row in 0..fixed_data.degree {
             let key = key_polys
                 .iter()
-                .map(|k| fixed_data.fixed_cols[k].values[row as usize])
+                .map(|k| fixed_data.fixed_cols[k].value(row))
                 .collect::<Vec<_>>();
             if key_to_index.insert(key, row).is_some() {
                 // Duplicate keys, can't be a write-once memory
@@ -151,8 +151,8 @@ impl<'a, T: FieldElement> WriteOnceMemory<'a, T> {
             .map(|v| v.constant_value())
             .collect::<Vec<_>>();
 
-        log::trace!("Key: {:?}", key);
-        log::trace!("Value: {:?}", value);
+        log::trace!("Key: {key:?}");
+        log::trace!("Value: {value:?}");
 
         let Some(key) = key else {
             return Ok(EvalValue::incomplete(
@@ -241,6 +241,8 @@ impl<'a, T: FieldElement> Machine<'a, T> for WriteOnceMemory<'a, T> {
         &mut self,
         _fixed_lookup: &'b mut FixedLookup<T>,
         _query_callback: &'b mut Q,
+        _scratchpad: &'b Scratchpad,
+        _tie_breaker: Option<&'b TieBreaker>,
     ) -> HashMap<String, Vec<T>> {
         self.value_polys
             .iter()
@@ -1,46 +1,164 @@
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ops::Range;
 use std::rc::Rc;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
+use itertools::Itertools;
 use powdr_ast::analyzed::{
-    AlgebraicExpression, AlgebraicReference, Analyzed, Expression, FunctionValueDefinition, PolyID,
-    PolynomialType, SymbolKind, TypedExpression,
+    AlgebraicExpression, AlgebraicReference, Analyzed, Expression, FunctionValueDefinition,
+    Identity, PolyID, PolynomialType, SymbolKind, TypedExpression,
 };
 use powdr_ast::parsed::visitor::ExpressionVisitable;
 use powdr_ast::parsed::{FunctionKind, LambdaExpression};
 use powdr_number::{DegreeType, FieldElement};
 
+pub use self::affine_expression::AffineExpression;
+use self::bit_budget::BitBudget;
+pub use self::block_delegate::BlockDelegate;
+pub use self::block_placement::BlockPlacement;
+pub use self::cell_override::override_and_check;
+pub use self::closure_constraint::ClosureConstraint;
+use self::column_alias::column_aliases;
+pub use self::column_group::ColumnGroup;
+pub use self::column_sink::ColumnSink;
+pub use self::column_sparsity::column_sparsity;
+use self::connect_identities::{
+    check_copy_constraints_within_machines, copy_constraints_from_connect_identities,
+};
+pub use self::convergence_heatmap::ConvergenceHeatmap;
+pub use self::counterexample::Counterexample;
 use self::data_structures::column_map::{FixedColumnMap, WitnessColumnMap};
+use self::data_structures::copy_constraints::CopyConstraints;
+pub use self::debug_snapshot::{DebugSnapshot, FailureInspector, OuterQuerySnapshot};
+pub use self::degree_bound::{check_degree_bound, DegreeBoundViolation};
+use self::dont_care::DontCare;
 pub use self::eval_result::{
-    Constraint, Constraints, EvalError, EvalResult, EvalStatus, EvalValue, IncompleteCause,
+    Constraint, Constraints, EvalError, EvalResult, EvalStatus, EvalValue, IdentityFailure,
+    IncompleteCause, DIAGNOSTICS_FORMAT_VAR,
 };
+pub use self::field_backend::{AffineRowExpression, CpuBackend, FieldBackend};
+pub use self::fixed_agreement::{verify_agrees_with_fixed, FixedColumnMismatch};
+pub use self::fixed_column_window::FixedColumnSource;
+use self::fixed_column_window::FixedColumnWindow;
 use self::generator::Generator;
+pub use self::identity_validation::{validate_identities, IdentityValidation};
+use self::incremental_processor::IncrementalProcessor;
+pub use self::input_canonicalization::InputCanonicalization;
+pub use self::input_validator::InputValidator;
+pub use self::instruction_copies::instruction_copies;
+pub use self::instruction_replay::InstructionReplay;
+pub use self::inverse_query::inverse_query_callback;
+pub use self::key_value_table::{KeyValueMiss, KeyValueTable};
+pub use self::limbs::{from_limbs, to_limbs, Endianness};
+pub use self::lookup_histogram::lookup_histogram;
+pub use self::monotonic::{verify_monotonic, MonotonicityViolation};
+use self::optional_identities::optional_identities;
+pub use self::options::WitgenOptions;
+pub use self::outer_assignment_observer::OuterAssignmentObserver;
+pub use self::pending_calls::PendingCall;
+pub use self::pil_fingerprint::pil_fingerprint;
+pub use self::plan::{plan_report, MachinePlan, WitgenPlan};
+pub use self::processor::{
+    ConflictPolicy, LastRowPolicy, OptionalIdentityPolicy, Provenance, RowCheckFailure,
+};
+use self::query_scheduler::scheduled_query_callback;
+pub use self::query_scheduler::{QueryScheduler, RandomDelays};
+pub use self::reference_witness::{compare_with_reference, CellDifference};
+pub use self::residuals::{
+    constraint_residuals, machine_constraint_residuals, residual_magnitude, ResidualReport,
+};
+pub use self::row_tagger::RowTagger;
+use self::rows::RowIndex;
+pub use self::rows::{RowPair, RowValue};
+pub use self::running_aggregate::running_aggregate;
+pub use self::scratchpad::Scratchpad;
+pub use self::selector_summary::selector_summary;
+pub use self::tie_breaker::TieBreaker;
+pub use self::tower_field::{
+    from_bit_sliced, to_bit_sliced, BitSlicedColumn, TowerFieldDecomposition,
+};
+pub use self::vm_processor::{NextRowPolicy, ScheduleStrategy};
+pub use self::witness_snapshot::SerializedRows;
+pub use self::witness_template::WitnessTemplate;
 
 use self::global_constraints::GlobalConstraints;
 use self::identity_processor::Machines;
-use self::machines::machine_extractor::ExtractionOutput;
-use self::machines::profiling::{record_end, record_start, reset_and_print_profile_summary};
-use self::machines::{FixedLookup, Machine};
+use self::instruction_replay::ResolvedInstructionReplay;
+use self::machines::machine_extractor::{refs_in_identity, ExtractionOutput};
+use self::machines::profiling::{record_end, record_start, take_profile};
+use self::machines::{FixedLookup, KnownMachine, Machine};
+use self::reference_witness::compare_witnesses;
 
 mod affine_expression;
+mod bit_budget;
+mod block_delegate;
+mod block_placement;
 mod block_processor;
+mod cell_override;
+mod closure_constraint;
+mod column_alias;
+mod column_group;
+mod column_sink;
+mod column_sparsity;
+mod connect_identities;
+mod convergence_heatmap;
+mod counterexample;
 mod data_structures;
+mod debug_snapshot;
+mod degree_bound;
+mod dont_care;
 mod eval_result;
 mod expression_evaluator;
+mod failure_bundle;
+mod field_backend;
+mod fixed_agreement;
+mod fixed_column_window;
 pub mod fixed_evaluator;
 mod generator;
 mod global_constraints;
+mod identity_budget;
 mod identity_processor;
+mod identity_validation;
+mod incremental_processor;
+mod input_canonicalization;
+mod input_validator;
+mod instruction_copies;
+mod instruction_replay;
+mod inverse_query;
+mod key_value_table;
+mod latency_histogram;
+mod limbs;
+mod log_scope;
+mod lookup_histogram;
 mod machines;
+mod monotonic;
+mod optional_identities;
+mod options;
+mod outer_assignment_observer;
+mod pending_calls;
+mod pil_fingerprint;
+mod plan;
 mod processor;
 mod query_processor;
+mod query_scheduler;
 mod range_constraints;
+mod reference_witness;
+mod residuals;
+mod row_tagger;
 mod rows;
+mod running_aggregate;
+mod scratchpad;
+mod selector_summary;
 mod sequence_iterator;
+mod stall;
 pub mod symbolic_evaluator;
 mod symbolic_witness_evaluator;
+mod tie_breaker;
+mod tower_field;
 mod util;
 mod vm_processor;
+mod witness_snapshot;
+mod witness_template;
 
 static OUTER_CODE_NAME: &str = "witgen (outer code)";
 
@@ -103,8 +221,16 @@ pub struct MutableState<'a, 'b, T: FieldElement, Q: QueryCallback<T>> {
     pub fixed_lookup: &'b mut FixedLookup<T>,
     pub machines: Machines<'a, 'b, T>,
     pub query_callback: &'b mut Q,
+    pub scratchpad: &'b Scratchpad,
+    /// Chooses the values of cells that may take any value, if set.
+    pub tie_breaker: Option<&'b TieBreaker>,
 }
 
+/// How the cells of the witness were determined, by column name and row
+/// (see [WitnessGenerator::generate_with_provenance]).
+pub type WitnessProvenance = BTreeMap<(String, DegreeType), Provenance>;
+
+#[derive(Clone)]
 pub struct WitnessGenerator<'a, 'b, T: FieldElement> {
     analyzed: &'a Analyzed<T>,
     fixed_col_values: &'b [(String, Vec<T>)],
@@ -112,6 +238,9 @@ pub struct WitnessGenerator<'a, 'b, T: FieldElement> {
     external_witness_values: &'b [(String, Vec<T>)],
     stage: u8,
     challenges: BTreeMap<u64, T>,
+    instruction_replay: Option<&'b InstructionReplay>,
+    options: WitgenOptions<'b, T>,
+    fixed_column_overrides: &'b [(String, Vec<T>)],
 }
 
 impl<'a, 'b, T: FieldElement> WitnessGenerator<'a, 'b, T> {
@@ -127,6 +256,9 @@ impl<'a, 'b, T: FieldElement> WitnessGenerator<'a, 'b, T> {
             external_witness_values: &[],
             stage: 0,
             challenges: BTreeMap::new(),
+            instruction_replay: None,
+            options: WitgenOptions::default(),
+            fixed_column_overrides: &[],
         }
     }
 
@@ -148,38 +280,176 @@ impl<'a, 'b, T: FieldElement> WitnessGenerator<'a, 'b, T> {
         }
     }
 
+    /// Sets the program counter and instruction flags of the main machine from a
+    /// recorded execution, leaving only the remaining columns to the identities.
+    pub fn with_instruction_replay(self, instruction_replay: &'b InstructionReplay) -> Self {
+        WitnessGenerator {
+            instruction_replay: Some(instruction_replay),
+            ..self
+        }
+    }
+
+    /// Sets the options that control how the witness is generated (see [WitgenOptions]).
+    pub fn with_options(self, options: WitgenOptions<'b, T>) -> Self {
+        WitnessGenerator { options, ..self }
+    }
+
+    /// Uses the given values for the fixed columns of the same names instead of the values
+    /// passed to [WitnessGenerator::new], e.g. to try out a different lookup table.
+    /// Each column needs to have as many values as the degree.
+    pub fn with_fixed_column_overrides(
+        self,
+        fixed_column_overrides: &'b [(String, Vec<T>)],
+    ) -> Self {
+        WitnessGenerator {
+            fixed_column_overrides,
+            ..self
+        }
+    }
+
+    /// Generates the witness once with each of the two schedule strategies and checks that
+    /// the results are identical. The solved values do not depend on the order in which
+    /// identities are processed, so a difference points to a bug in one of the strategies.
+    /// @returns the witness, panics (listing the first differing cells) if the results differ.
+    pub fn compare_schedule_strategies(
+        self,
+        first: ScheduleStrategy,
+        second: ScheduleStrategy,
+    ) -> Vec<(String, Vec<T>)> {
+        let with_strategy = |schedule_strategy| {
+            self.clone().with_options(WitgenOptions {
+                schedule_strategy,
+                ..self.options.clone()
+            })
+        };
+        let expected = with_strategy(first).generate();
+        let actual = with_strategy(second).generate();
+        let differences = compare_witnesses(&actual, &expected, 10);
+        assert!(
+            differences.is_empty(),
+            "Schedule strategies {first:?} and {second:?} produced different witnesses:\n{}",
+            differences.iter().map(|d| d.to_string()).join("\n")
+        );
+        actual
+    }
+
+    /// Generates the witness `runs` times, invoking each query callback on a separate
+    /// thread after a delay chosen by the scheduler, and checks that the results are
+    /// identical. This guards against witness generation depending on the timing of
+    /// (potentially concurrent) query callbacks.
+    /// @returns the witness, panics (listing the first differing cells) if the results differ.
+    pub fn check_query_determinism(
+        self,
+        runs: usize,
+        scheduler: &dyn QueryScheduler,
+    ) -> Vec<(String, Vec<T>)> {
+        let generate_run = |run| {
+            let query_callback = scheduled_query_callback(self.query_callback, scheduler, run);
+            WitnessGenerator {
+                query_callback: &query_callback,
+                ..self.clone()
+            }
+            .generate()
+        };
+        let expected = generate_run(0);
+        for run in 1..runs {
+            let differences = compare_witnesses(&generate_run(run), &expected, 10);
+            assert!(
+                differences.is_empty(),
+                "Run {run} produced a different witness than the first run:\n{}",
+                differences.iter().map(|d| d.to_string()).join("\n")
+            );
+        }
+        expected
+    }
+
+    /// Generates the witness `runs` times, each time on a fresh thread, and checks that
+    /// the results are identical. The hashers of `HashSet`s and `HashMap`s are seeded
+    /// randomly per thread, so each run iterates them in a different order, and a
+    /// difference points to a result depending on that order (e.g. of the witness columns).
+    /// @returns the witness, panics (listing the first differing cells) if the results differ.
+    pub fn check_hash_order_determinism(self, runs: usize) -> Vec<(String, Vec<T>)> {
+        let generate_run = || {
+            std::thread::scope(|scope| {
+                scope
+                    .spawn(|| self.clone().generate())
+                    .join()
+                    .unwrap_or_else(|e| std::panic::resume_unwind(e))
+            })
+        };
+        let expected = generate_run();
+        for run in 1..runs {
+            let differences = compare_witnesses(&generate_run(), &expected, 10);
+            assert!(
+                differences.is_empty(),
+                "Run {run} iterated hash sets in a different order and produced a different witness than the first run:\n{}",
+                differences.iter().map(|d| d.to_string()).join("\n")
+            );
+        }
+        expected
+    }
+
+    /// Finds a small counterexample for a witness that fails validation: Using delta
+    /// debugging, `inputs` is reduced to a (1-minimal) subsequence for which the witness
+    /// generated with the query callback returned by `query_callback` still fails
+    /// `validate`. Runs in which witness generation itself fails do not count as failures.
+    /// This generates the witness many times, so it is only meant to be run on demand.
+    /// @returns None if the witness for the full inputs does not fail validation.
+    pub fn reduce_counterexample<Q: QueryCallback<T>>(
+        &self,
+        inputs: &[T],
+        query_callback: impl Fn(Vec<T>) -> Q,
+        validate: impl Fn(&[(String, Vec<T>)]) -> ResidualReport<T>,
+    ) -> Option<Counterexample<T>> {
+        let run = |inputs: &[T]| {
+            let query_callback = query_callback(inputs.to_vec());
+            let witness = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                WitnessGenerator {
+                    query_callback: &query_callback,
+                    ..self.clone()
+                }
+                .generate()
+            }))
+            .ok()?;
+            let report = validate(&witness);
+            (!report.failures.is_empty()).then_some((witness, report))
+        };
+        run(inputs)?;
+        let inputs = counterexample::delta_debug(inputs, |inputs| run(inputs).is_some());
+        let (witness, report) = run(&inputs).unwrap();
+        Some(Counterexample {
+            trace: counterexample::failure_prefix(&witness, &report),
+            inputs,
+            report,
+        })
+    }
+
     /// Generates the committed polynomial values
     /// @returns the values (in source order) and the degree of the polynomials.
     pub fn generate(self) -> Vec<(String, Vec<T>)> {
+        self.generate_witness_and_provenance().0
+    }
+
+    /// Like [WitnessGenerator::generate], but also tracks how each cell was determined
+    /// (see [WitgenOptions::track_provenance]).
+    /// @returns the values (in source order) and the provenance of the cells of VM and
+    /// block machines, by column name and row. Cells that were never written or that
+    /// were filled in after solving (e.g. unused rows of block machines) have no provenance.
+    pub fn generate_with_provenance(self) -> (Vec<(String, Vec<T>)>, WitnessProvenance) {
+        WitnessGenerator {
+            options: WitgenOptions {
+                track_provenance: true,
+                ..self.options.clone()
+            },
+            ..self
+        }
+        .generate_witness_and_provenance()
+    }
+
+    fn generate_witness_and_provenance(self) -> (Vec<(String, Vec<T>)>, WitnessProvenance) {
         record_start(OUTER_CODE_NAME);
-        let fixed = FixedData::new(
-            self.analyzed,
-            self.fixed_col_values,
-            self.external_witness_values,
-            self.challenges,
-            self.stage,
-        );
-        let identities = self
-            .analyzed
-            .identities_with_inlined_intermediate_polynomials()
-            .into_iter()
-            .filter(|identity| {
-                let discard = identity.expr_any(|expr| {
-                    if let AlgebraicExpression::Challenge(challenge) = expr {
-                        challenge.stage >= self.stage.into()
-                    } else {
-                        false
-                    }
-                });
-                if discard {
-                    log::debug!(
-                        "Skipping identity that references challenge of later stage: {}",
-                        identity
-                    );
-                }
-                !discard
-            })
-            .collect::<Vec<_>>();
+        let fixed = self.fixed_data();
+        let identities = identities_for_stage(self.analyzed, self.stage);
 
         // Removes identities like X * (X - 1) = 0 or { A } in { BYTES }
         // These are already captured in the range constraints.
@@ -188,14 +458,36 @@ impl<'a, 'b, T: FieldElement> WitnessGenerator<'a, 'b, T> {
         let ExtractionOutput {
             mut fixed_lookup,
             mut machines,
+            machine_plans,
             base_identities,
             base_witnesses,
         } = machines::machine_extractor::split_out_machines(&fixed, retained_identities);
+        let machine_witnesses = machine_plans
+            .iter()
+            .map(|plan| {
+                plan.witness_columns
+                    .iter()
+                    .map(|name| fixed.try_column_by_name(name).unwrap())
+                    .collect()
+            })
+            .chain(std::iter::once(base_witnesses.clone()))
+            .collect::<Vec<_>>();
+        check_copy_constraints_within_machines(&fixed, &machine_witnesses)
+            .unwrap_or_else(|e| panic!("Invalid copy constraint: {e}"));
+        let instruction_replay = self
+            .instruction_replay
+            .map(|replay| ResolvedInstructionReplay::new(replay, &fixed));
         let mut query_callback = self.query_callback;
+        let default_scratchpad = Scratchpad::default();
+        let scratchpad = self.options.scratchpad.unwrap_or(&default_scratchpad);
+        scratchpad.clear();
+        let tie_breaker = self.options.tie_breaking_seed.map(TieBreaker::new);
         let mut mutable_state = MutableState {
             fixed_lookup: &mut fixed_lookup,
             machines: Machines::from(machines.iter_mut()),
             query_callback: &mut query_callback,
+            scratchpad,
+            tie_breaker: tie_breaker.as_ref(),
         };
         let mut generator = Generator::new(
             "Main Machine".to_string(),
@@ -208,24 +500,73 @@ impl<'a, 'b, T: FieldElement> WitnessGenerator<'a, 'b, T> {
             // infinite loop after the first return.
             None,
         );
+        if let Some(instruction_replay) = &instruction_replay {
+            generator = generator.with_instruction_replay(instruction_replay);
+        }
 
         generator.run(&mut mutable_state);
 
         // Get columns from machines
-        let main_columns = generator
-            .take_witness_col_values(mutable_state.fixed_lookup, mutable_state.query_callback);
+        let main_columns = generator.take_witness_col_values(
+            mutable_state.fixed_lookup,
+            mutable_state.query_callback,
+            scratchpad,
+            tie_breaker.as_ref(),
+        );
         let mut columns = mutable_state
             .machines
             .iter_mut()
             .flat_map(|m| {
-                m.take_witness_col_values(mutable_state.fixed_lookup, mutable_state.query_callback)
-                    .into_iter()
+                m.take_witness_col_values(
+                    mutable_state.fixed_lookup,
+                    mutable_state.query_callback,
+                    scratchpad,
+                    tie_breaker.as_ref(),
+                )
+                .into_iter()
             })
             .chain(main_columns)
             .collect::<BTreeMap<_, _>>();
+        let provenance = machines
+            .iter_mut()
+            .flat_map(|m| m.take_provenance())
+            .chain(generator.take_provenance())
+            .collect();
+
+        if self.options.check_pending_calls {
+            let pending_calls = machines
+                .iter()
+                .flat_map(|m| m.pending_calls())
+                .collect::<Vec<_>>();
+            assert!(
+                pending_calls.is_empty(),
+                "Some calls to machines were never resolved:\n{}",
+                pending_calls
+                    .iter()
+                    .map(|call| format!("  {call}"))
+                    .join("\n")
+            );
+        }
 
         record_end(OUTER_CODE_NAME);
-        reset_and_print_profile_summary();
+        let profile = take_profile();
+        profile.print_summary();
+        if let Some(path) = self.options.chrome_trace {
+            if let Err(e) = std::fs::write(path, profile.to_chrome_trace()) {
+                log::error!("Could not write Chrome trace to {}: {e}", path.display());
+            }
+        }
+        if let (Some(path), Some(heatmap)) =
+            (self.options.convergence_heatmap, &fixed.convergence_heatmap)
+        {
+            let csv = heatmap.lock().unwrap().to_csv();
+            if let Err(e) = std::fs::write(path, csv) {
+                log::error!(
+                    "Could not write convergence heatmap to {}: {e}",
+                    path.display()
+                );
+            }
+        }
 
         // Order columns according to the order of declaration.
         let witness_cols = self
@@ -245,8 +586,381 @@ impl<'a, 'b, T: FieldElement> WitnessGenerator<'a, 'b, T> {
         for (name, value) in extract_publics(&witness_cols, self.analyzed) {
             log::debug!("  {name:>30}: {value}");
         }
+        if let Some(max_degree) = self.options.max_constraint_degree {
+            if let Err(violation) = check_degree_bound(self.analyzed, &witness_cols, max_degree) {
+                panic!("Witness is not compatible with the degree bound: {violation}");
+            }
+        }
+        (witness_cols, provenance)
+    }
+
+    /// Generates the committed polynomial values batch by batch, e.g. while the inputs are
+    /// still being streamed in. Each batch provides the values of some witness columns for
+    /// the rows following the previous batch. The remaining cells of these rows are solved
+    /// and passed to `on_rows`, in source order.
+    /// Rows may only depend on earlier rows, since rows of previous batches are not solved
+    /// again. Sub-machines are not supported.
+    /// @returns the values of all generated rows (in source order).
+    pub fn generate_incrementally(
+        self,
+        input_batches: impl IntoIterator<Item = Vec<(String, Vec<T>)>>,
+        mut on_rows: impl FnMut(&[(String, Vec<T>)]),
+    ) -> Vec<(String, Vec<T>)> {
+        let fixed = self.fixed_data();
+        let identities = identities_for_stage(self.analyzed, self.stage);
+        let (fixed, retained_identities) =
+            global_constraints::set_global_constraints(fixed, &identities);
+        let ExtractionOutput {
+            mut fixed_lookup,
+            mut machines,
+            base_identities,
+            base_witnesses,
+            ..
+        } = machines::machine_extractor::split_out_machines(&fixed, retained_identities);
+        assert!(
+            machines.is_empty(),
+            "Incremental witness generation does not support sub-machines."
+        );
+        let mut query_callback = self.query_callback;
+        let default_scratchpad = Scratchpad::default();
+        let scratchpad = self.options.scratchpad.unwrap_or(&default_scratchpad);
+        scratchpad.clear();
+        let tie_breaker = self.options.tie_breaking_seed.map(TieBreaker::new);
+        let mut mutable_state = MutableState {
+            fixed_lookup: &mut fixed_lookup,
+            machines: Machines::from(machines.iter_mut()),
+            query_callback: &mut query_callback,
+            scratchpad,
+            tie_breaker: tie_breaker.as_ref(),
+        };
+        let mut processor = IncrementalProcessor::new(
+            &mut mutable_state,
+            &base_identities,
+            &fixed,
+            &base_witnesses,
+        );
+
+        let mut witness_cols: Vec<(String, Vec<T>)> = vec![];
+        for batch in input_batches {
+            let inputs = batch
+                .into_iter()
+                .map(|(name, values)| {
+                    let poly_id = fixed
+                        .try_column_by_name(&name)
+                        .unwrap_or_else(|| panic!("Input column {name} not found."));
+                    (poly_id, values)
+                })
+                .collect::<Vec<_>>();
+            let new_rows = processor
+                .append_rows(&inputs)
+                .unwrap_or_else(|e| {
+                    log::error!("\nError: Could not append rows:\n{e}");
+                    panic!("Witness generation failed.");
+                })
+                .into_iter()
+                .map(|(poly_id, values)| (fixed.column_name(&poly_id).to_string(), values))
+                .collect::<Vec<_>>();
+            on_rows(&new_rows);
+            if witness_cols.is_empty() {
+                witness_cols = new_rows;
+            } else {
+                for ((_, column), (_, values)) in witness_cols.iter_mut().zip(new_rows) {
+                    column.extend(values);
+                }
+            }
+        }
         witness_cols
     }
+
+    /// Generates the witness of the main machine, leaving the given cells (column name and
+    /// row) as placeholders, identified by a tag. Cells that depend on placeholders stay
+    /// unknown as well. The template can then be instantiated with different values for the
+    /// placeholders by [WitnessGenerator::instantiate_template], which only solves the cells
+    /// that are still unknown. Sub-machines are not supported.
+    /// Fails if a placeholder is determined by the constraints.
+    pub fn generate_template(
+        self,
+        placeholders: Vec<((String, DegreeType), String)>,
+    ) -> Result<WitnessTemplate, EvalError<T>> {
+        let (rows, witness) = self.solve_main_machine(None, &[])?;
+        for ((name, row), tag) in &placeholders {
+            let (_, values) = witness
+                .iter()
+                .find(|(column, _)| column == name)
+                .ok_or_else(|| format!("Witness column {name} not found."))?;
+            if let Some(value) = values[*row as usize] {
+                return Err(format!(
+                    "Placeholder {tag} ({name} in row {row}) is determined to be {value}."
+                )
+                .into());
+            }
+        }
+        let mut tags = BTreeMap::<String, Vec<(String, DegreeType)>>::new();
+        for (cell, tag) in placeholders {
+            tags.entry(tag).or_default().push(cell);
+        }
+        Ok(WitnessTemplate {
+            placeholders: tags,
+            rows,
+        })
+    }
+
+    /// Fills the placeholders of a template created by [WitnessGenerator::generate_template]
+    /// (with the same PIL and options) with the given values by tag, and solves the cells
+    /// that depend on them.
+    /// @returns the values of all witness columns, in source order.
+    pub fn instantiate_template(
+        self,
+        template: &WitnessTemplate,
+        values: &BTreeMap<String, T>,
+    ) -> Result<Vec<(String, Vec<T>)>, EvalError<T>> {
+        let cells = template
+            .placeholders
+            .iter()
+            .map(|(tag, cells)| {
+                let value = *values
+                    .get(tag)
+                    .ok_or_else(|| format!("No value given for placeholder {tag}."))?;
+                Ok(cells.iter().map(move |cell| (cell.clone(), value)))
+            })
+            .flatten_ok()
+            .collect::<Result<Vec<_>, EvalError<T>>>()?;
+        let (_, witness) = self.solve_main_machine(Some(&template.rows), &cells)?;
+        witness
+            .into_iter()
+            .map(|(name, values)| {
+                let values = values
+                    .into_iter()
+                    .enumerate()
+                    .map(|(row, value)| {
+                        value.ok_or_else(|| format!("Could not determine {name} in row {row}."))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok((name, values))
+            })
+            .collect()
+    }
+
+    /// Solves the main machine on all rows, starting from the given rows (fresh ones if None)
+    /// with the given cells set.
+    /// @returns the solved rows and the values of all witness cells in source order,
+    /// None where they are unknown.
+    #[allow(clippy::type_complexity)]
+    fn solve_main_machine(
+        self,
+        rows: Option<&SerializedRows>,
+        cells: &[((String, DegreeType), T)],
+    ) -> Result<(SerializedRows, Vec<(String, Vec<Option<T>>)>), EvalError<T>> {
+        let fixed = self.fixed_data();
+        let identities = identities_for_stage(self.analyzed, self.stage);
+        let (fixed, retained_identities) =
+            global_constraints::set_global_constraints(fixed, &identities);
+        let ExtractionOutput {
+            mut fixed_lookup,
+            mut machines,
+            base_identities,
+            base_witnesses,
+            ..
+        } = machines::machine_extractor::split_out_machines(&fixed, retained_identities);
+        assert!(
+            machines.is_empty(),
+            "Witness templates do not support sub-machines."
+        );
+        let mut query_callback = self.query_callback;
+        let default_scratchpad = Scratchpad::default();
+        let scratchpad = self.options.scratchpad.unwrap_or(&default_scratchpad);
+        let tie_breaker = self.options.tie_breaking_seed.map(TieBreaker::new);
+        let mut mutable_state = MutableState {
+            fixed_lookup: &mut fixed_lookup,
+            machines: Machines::from(machines.iter_mut()),
+            query_callback: &mut query_callback,
+            scratchpad,
+            tie_breaker: tie_breaker.as_ref(),
+        };
+
+        let cells = cells
+            .iter()
+            .map(|((name, row), value)| {
+                let poly_id = fixed
+                    .try_column_by_name(name)
+                    .ok_or_else(|| format!("Witness column {name} not found."))?;
+                Ok((poly_id, *row, *value))
+            })
+            .collect::<Result<Vec<_>, EvalError<T>>>()?;
+        let (rows, mut values) = witness_template::solve_main_machine(
+            &mut mutable_state,
+            &base_identities,
+            &fixed,
+            &base_witnesses,
+            rows,
+            &cells,
+        )?;
+
+        let witness = self
+            .analyzed
+            .committed_polys_in_source_order()
+            .into_iter()
+            .flat_map(|(p, _)| p.array_elements())
+            .filter_map(|(name, poly_id)| Some((name, values.remove(&poly_id)?)))
+            .collect();
+        Ok((rows, witness))
+    }
+
+    /// Computes the witness of a single block of the given block machine (by its name as in
+    /// [plan_report]), in which the latch row has the given input values. This allows testing
+    /// a block machine in isolation, without a caller.
+    /// @returns the values of the block's rows for each witness column of the machine,
+    /// in source order.
+    pub fn generate_single_block(
+        self,
+        machine_name: &str,
+        inputs: &[(String, T)],
+    ) -> Result<Vec<(String, Vec<T>)>, EvalError<T>> {
+        let fixed = self.fixed_data();
+        let identities = identities_for_stage(self.analyzed, self.stage);
+        let (fixed, retained_identities) =
+            global_constraints::set_global_constraints(fixed, &identities);
+        let ExtractionOutput {
+            mut fixed_lookup,
+            mut machines,
+            ..
+        } = machines::machine_extractor::split_out_machines(&fixed, retained_identities);
+        let index = machines
+            .iter()
+            .position(|machine| machine.name() == machine_name)
+            .unwrap_or_else(|| panic!("Machine {machine_name} not found."));
+        let inputs = inputs
+            .iter()
+            .map(|(name, value)| {
+                let poly_id = fixed
+                    .try_column_by_name(name)
+                    .unwrap_or_else(|| panic!("Column {name} not found."));
+                (poly_id, *value)
+            })
+            .collect::<Vec<_>>();
+
+        let mut query_callback = self.query_callback;
+        let mut machines = Machines::from(machines.iter_mut());
+        let (machine, other_machines) = machines.split(index);
+        let scratchpad = Scratchpad::default();
+        let tie_breaker = self.options.tie_breaking_seed.map(TieBreaker::new);
+        let mut mutable_state = MutableState {
+            fixed_lookup: &mut fixed_lookup,
+            machines: other_machines,
+            query_callback: &mut query_callback,
+            scratchpad: self.options.scratchpad.unwrap_or(&scratchpad),
+            tie_breaker: tie_breaker.as_ref(),
+        };
+        let KnownMachine::BlockMachine(machine) = machine else {
+            panic!("Machine {machine_name} is not a block machine.");
+        };
+        let rows = machine.process_single_block(&mut mutable_state, &inputs)?;
+
+        let witness_cols = machine.witness_cols();
+        self.analyzed
+            .committed_polys_in_source_order()
+            .into_iter()
+            .flat_map(|(p, _)| p.array_elements())
+            .filter_map(|(name, poly_id)| {
+                witness_cols.contains(&poly_id).then_some((name, poly_id))
+            })
+            .map(|(name, poly_id)| {
+                let values = rows
+                    .iter()
+                    .enumerate()
+                    .map(|(row, r)| match r[&poly_id].value {
+                        rows::CellValue::Known(value) => Ok(value),
+                        _ => Err(format!("Could not determine {name} in row {row}.").into()),
+                    })
+                    .collect::<Result<Vec<_>, EvalError<T>>>()?;
+                Ok((name, values))
+            })
+            .collect()
+    }
+
+    /// Creates the fixed data, including all options, for witness generation.
+    fn fixed_data(&self) -> FixedData<'b, T>
+    where
+        'a: 'b,
+    {
+        let fixed = FixedData::with_lazy_fixed_columns(
+            self.analyzed,
+            self.fixed_col_values,
+            self.external_witness_values,
+            self.challenges.clone(),
+            self.stage,
+        )
+        .with_fixed_column_overrides(self.fixed_column_overrides)
+        .with_options(self.options.clone());
+        let dont_care = self
+            .options
+            .dont_care_columns
+            .iter()
+            .map(|(column, selector)| {
+                let column_by_name = |name: &String| {
+                    fixed
+                        .try_column_by_name(name)
+                        .unwrap_or_else(|| panic!("Column {name} not found."))
+                };
+                DontCare {
+                    column: column_by_name(column),
+                    selector: selector.as_ref().map(column_by_name),
+                }
+            })
+            .collect();
+        let mut copy_constraint_pairs = self
+            .options
+            .copy_constraints
+            .iter()
+            .map(|(a, b)| {
+                let cell = |(name, row): &(String, DegreeType)| {
+                    let poly_id = fixed
+                        .try_column_by_name(name)
+                        .unwrap_or_else(|| panic!("Column {name} not found."));
+                    (poly_id, RowIndex::from_degree(*row, fixed.degree))
+                };
+                (cell(a), cell(b))
+            })
+            .collect::<Vec<_>>();
+        if self.options.copy_constraints_from_pil {
+            copy_constraint_pairs.extend(
+                copy_constraints_from_connect_identities(&fixed)
+                    .unwrap_or_else(|e| panic!("Invalid connect identity: {e}")),
+            );
+        }
+        let copy_constraints = CopyConstraints::new(&copy_constraint_pairs);
+        fixed
+            .with_dont_care(dont_care, self.options.dont_care_value)
+            .with_copy_constraints(copy_constraints)
+    }
+}
+
+/// Returns the identities (with inlined intermediate polynomials) that are relevant
+/// for the witness generation of the given stage, i.e. those that do not reference
+/// challenges of the same or a later stage.
+fn identities_for_stage<T: FieldElement>(
+    analyzed: &Analyzed<T>,
+    stage: u8,
+) -> Vec<Identity<AlgebraicExpression<T>>> {
+    analyzed
+        .identities_with_inlined_intermediate_polynomials()
+        .into_iter()
+        .filter(|identity| {
+            let discard = identity.expr_any(|expr| {
+                if let AlgebraicExpression::Challenge(challenge) = expr {
+                    challenge.stage >= stage.into()
+                } else {
+                    false
+                }
+            });
+            if discard {
+                log::debug!(
+                    "Skipping identity that references challenge of later stage: {identity}"
+                );
+            }
+            !discard
+        })
+        .collect()
 }
 
 pub fn extract_publics<T: FieldElement>(
@@ -277,9 +991,26 @@ pub struct FixedData<'a, T: FieldElement> {
     column_by_name: HashMap<String, PolyID>,
     challenges: BTreeMap<u64, T>,
     global_range_constraints: GlobalConstraints<T>,
+    options: WitgenOptions<'a, T>,
+    /// The witness columns of [WitgenOptions::monotonic_columns].
+    monotonic_columns: Vec<PolyID>,
+    /// The bit budgets of [WitgenOptions::bit_budgets].
+    bit_budgets: Vec<BitBudget>,
+    /// The validators of the values returned by the query callback for each column.
+    input_validators: Vec<(PolyID, &'a dyn InputValidator<T>)>,
+    /// The IDs of the identities that are skipped if they cannot be solved.
+    optional_identities: BTreeSet<u64>,
+    /// If set, processors record in which fixpoint iteration cells became known.
+    convergence_heatmap: Option<Mutex<ConvergenceHeatmap>>,
+    /// Cells that are not expected to be determined, see [DontCare].
+    dont_care: Vec<DontCare>,
+    /// The value of "don't care" cells that were never written.
+    dont_care_value: T,
+    copy_constraints: CopyConstraints<(PolyID, RowIndex)>,
 }
 
 impl<'a, T: FieldElement> FixedData<'a, T> {
+    /// Creates the fixed data. Panics if the values of a fixed column are not provided.
     pub fn new(
         analyzed: &'a Analyzed<T>,
         fixed_col_values: &'a [(String, Vec<T>)],
@@ -287,17 +1018,49 @@ impl<'a, T: FieldElement> FixedData<'a, T> {
         challenges: BTreeMap<u64, T>,
         stage: u8,
     ) -> Self {
-        let mut external_witness_values = external_witness_values
-            .iter()
-            .map(|(name, values)| (name.clone(), values))
-            .collect::<BTreeMap<_, _>>();
+        let fixed_data = Self::with_lazy_fixed_columns(
+            analyzed,
+            fixed_col_values,
+            external_witness_values,
+            challenges,
+            stage,
+        );
+        fixed_data.assert_fixed_columns_provided();
+        fixed_data
+    }
+
+    /// Like [FixedData::new], but the values of fixed columns may be missing. They have
+    /// to be provided by fixed column overrides or computed in windows (see
+    /// [WitgenOptions::fixed_column_windows]), which is checked when the options are applied.
+    pub fn with_lazy_fixed_columns(
+        analyzed: &'a Analyzed<T>,
+        fixed_col_values: &'a [(String, Vec<T>)],
+        external_witness_values: &'a [(String, Vec<T>)],
+        challenges: BTreeMap<u64, T>,
+        stage: u8,
+    ) -> Self {
+        // Aliases of columns refer to the same column (and PolyID) as the column itself.
+        let aliases = column_aliases(analyzed);
+        let mut external_values_by_name = BTreeMap::new();
+        for (name, values) in external_witness_values {
+            let name = aliases
+                .get(name)
+                .map(|(column, _)| column.clone())
+                .unwrap_or_else(|| name.clone());
+            if external_values_by_name
+                .insert(name.clone(), values)
+                .is_some()
+            {
+                panic!("External witness values for column {name} provided more than once.");
+            }
+        }
 
         let witness_cols =
             WitnessColumnMap::from(analyzed.committed_polys_in_source_order().iter().flat_map(
                 |(poly, value)| {
                     poly.array_elements()
                         .map(|(name, poly_id)| {
-                            let external_values = external_witness_values.remove(name.as_str());
+                            let external_values = external_values_by_name.remove(name.as_str());
                             if let Some(external_values) = &external_values {
                                 if external_values.len() != analyzed.degree() as usize {
                                     log::debug!(
@@ -318,20 +1081,32 @@ impl<'a, T: FieldElement> FixedData<'a, T> {
                 },
         ));
 
-        if !external_witness_values.is_empty() {
+        if !external_values_by_name.is_empty() {
             let available_columns = witness_cols
                 .iter()
                 .map(|(_, witness)| &witness.poly.name)
                 .collect::<Vec<_>>();
             panic!(
                 "External witness values for non-existent columns: {:?}\nAvailable columns: {:?}",
-                external_witness_values.keys(),
+                external_values_by_name.keys(),
                 available_columns
             );
         }
 
-        let fixed_cols =
-            FixedColumnMap::from(fixed_col_values.iter().map(|(n, v)| FixedColumn::new(n, v)));
+        let mut fixed_col_values = fixed_col_values
+            .iter()
+            .map(|(name, values)| (name.as_str(), values))
+            .collect::<BTreeMap<_, _>>();
+        let fixed_cols = FixedColumnMap::from(
+            analyzed
+                .constant_polys_in_source_order()
+                .iter()
+                .flat_map(|(poly, _)| poly.array_elements())
+                .map(|(name, _)| {
+                    let values = fixed_col_values.remove(name.as_str());
+                    FixedColumn::new(&name, values)
+                }),
+        );
 
         // The global range constraints are not set yet.
         let global_range_constraints = GlobalConstraints {
@@ -349,52 +1124,219 @@ impl<'a, T: FieldElement> FixedData<'a, T> {
                 .iter()
                 .filter(|(_, (symbol, _))| matches!(symbol.kind, SymbolKind::Poly(_)))
                 .map(|(name, (symbol, _))| (name.clone(), symbol.into()))
+                .chain(
+                    aliases
+                        .into_iter()
+                        .map(|(alias, (_, poly_id))| (alias, poly_id)),
+                )
                 .collect(),
             challenges,
             global_range_constraints,
+            options: WitgenOptions::default(),
+            monotonic_columns: vec![],
+            bit_budgets: vec![],
+            input_validators: vec![],
+            optional_identities: BTreeSet::new(),
+            convergence_heatmap: None,
+            dont_care: vec![],
+            dont_care_value: T::zero(),
+            copy_constraints: Default::default(),
         }
     }
 
-    pub fn with_global_range_constraints(
+    pub fn with_copy_constraints(
         self,
-        global_range_constraints: GlobalConstraints<T>,
+        copy_constraints: CopyConstraints<(PolyID, RowIndex)>,
     ) -> Self {
-        assert!(
-            self.global_range_constraints
-                .witness_constraints
-                .values()
-                .chain(self.global_range_constraints.fixed_constraints.values())
-                .all(|c| c.is_none()),
-            "range constraints already set"
-        );
-
         Self {
-            global_range_constraints,
+            copy_constraints,
             ..self
         }
     }
 
-    pub fn global_range_constraints(&self) -> &GlobalConstraints<T> {
-        &self.global_range_constraints
-    }
-
-    fn witness_map_with<V: Clone>(&self, initial_value: V) -> WitnessColumnMap<V> {
-        WitnessColumnMap::new(initial_value, self.witness_cols.len())
-    }
-
-    fn column_name(&self, poly_id: &PolyID) -> &str {
-        match poly_id.ptype {
-            PolynomialType::Committed => &self.witness_cols[poly_id].poly.name,
-            PolynomialType::Constant => &self.fixed_cols[poly_id].name,
-            PolynomialType::Intermediate => unimplemented!(),
+    pub fn with_dont_care(self, dont_care: Vec<DontCare>, dont_care_value: T) -> Self {
+        Self {
+            dont_care,
+            dont_care_value,
+            ..self
         }
     }
 
-    pub fn try_column_by_name(&self, name: &str) -> Option<PolyID> {
-        self.column_by_name.get(name).cloned()
+    /// Uses the given values for the fixed columns of the same names instead of the
+    /// values computed from their definitions.
+    pub fn with_fixed_column_overrides(mut self, overrides: &'a [(String, Vec<T>)]) -> Self {
+        for (name, values) in overrides {
+            let poly_id = self
+                .column_by_name
+                .get(name)
+                .filter(|poly_id| poly_id.ptype == PolynomialType::Constant)
+                .unwrap_or_else(|| panic!("Fixed column {name} not found."));
+            assert_eq!(
+                values.len() as DegreeType,
+                self.degree,
+                "Override of fixed column {name} has the wrong number of values."
+            );
+            let column = &mut self.fixed_cols[poly_id];
+            column.values = Some(values);
+            column.window = None;
+        }
+        self
     }
 
-    fn external_witness(&self, row: DegreeType, column: &PolyID) -> Option<T> {
+    /// Applies the options, resolving the columns they reference by name.
+    /// Fixed column overrides have to be applied before.
+    pub fn with_options(mut self, options: WitgenOptions<'a, T>) -> Self {
+        let column_by_name = |name: &String| {
+            self.try_column_by_name(name)
+                .unwrap_or_else(|| panic!("Column {name} not found."))
+        };
+        let monotonic_columns = options
+            .monotonic_columns
+            .iter()
+            .map(|name| {
+                self.try_column_by_name(name)
+                    .unwrap_or_else(|| panic!("Monotonic column {name} not found."))
+            })
+            .collect();
+        let bit_budgets = options
+            .bit_budgets
+            .iter()
+            .map(|(columns, bits)| BitBudget {
+                columns: columns.iter().map(column_by_name).collect(),
+                bits: *bits,
+            })
+            .collect();
+        let input_validators = options
+            .input_validators
+            .iter()
+            .map(|(column, validator)| (column_by_name(column), *validator))
+            .collect();
+        let optional_identities = match options.optional_identity_policy {
+            OptionalIdentityPolicy::Skip => optional_identities(self.analyzed),
+            OptionalIdentityPolicy::Require => BTreeSet::new(),
+        };
+        let convergence_heatmap = options.convergence_heatmap.map(|_| {
+            Mutex::new(ConvergenceHeatmap::new(
+                self.witness_cols
+                    .values()
+                    .map(|column| column.poly.name.clone())
+                    .collect(),
+            ))
+        });
+        if let Some(row_window) = options.row_window {
+            assert!(
+                row_window >= 2,
+                "The row window must contain at least 2 rows."
+            );
+        }
+        match options.fixed_column_windows {
+            Some((source, window_size)) => {
+                self.materialize_fixed_columns_in_windows(source, window_size)
+            }
+            None => self.assert_fixed_columns_provided(),
+        }
+        Self {
+            options,
+            monotonic_columns,
+            bit_budgets,
+            input_validators,
+            optional_identities,
+            convergence_heatmap,
+            ..self
+        }
+    }
+
+    /// Panics with the names of the fixed columns whose values were not provided.
+    fn assert_fixed_columns_provided(&self) {
+        let missing = self
+            .fixed_cols
+            .values()
+            .filter(|column| column.values.is_none() && column.window.is_none())
+            .map(|column| column.name.as_str())
+            .collect::<Vec<_>>();
+        assert!(
+            missing.is_empty(),
+            "The values of the fixed columns {} were not provided. Fixed columns can only \
+            be computed while processing if fixed column windows are configured.",
+            missing.join(", ")
+        );
+    }
+
+    /// Materializes the fixed columns whose values were not provided upfront in windows
+    /// of the given size, computed by the given source. Old windows are discarded, so the
+    /// window size should be aligned with the number of rows processed at a time.
+    /// Such columns can only be accessed row by row, so they cannot be used e.g. in
+    /// lookups into fixed columns.
+    fn materialize_fixed_columns_in_windows(
+        &mut self,
+        source: &'a dyn FixedColumnSource<T>,
+        window_size: usize,
+    ) {
+        let degree = self.degree;
+        for column in self.fixed_cols.values_mut() {
+            if column.values.is_none() {
+                column.window = Some(FixedColumnWindow::new(
+                    source,
+                    window_size as DegreeType,
+                    degree,
+                ));
+            }
+        }
+    }
+
+    pub fn with_global_range_constraints(
+        self,
+        global_range_constraints: GlobalConstraints<T>,
+    ) -> Self {
+        assert!(
+            self.global_range_constraints
+                .witness_constraints
+                .values()
+                .chain(self.global_range_constraints.fixed_constraints.values())
+                .all(|c| c.is_none()),
+            "range constraints already set"
+        );
+
+        Self {
+            global_range_constraints,
+            ..self
+        }
+    }
+
+    pub fn global_range_constraints(&self) -> &GlobalConstraints<T> {
+        &self.global_range_constraints
+    }
+
+    fn witness_map_with<V: Clone>(&self, initial_value: V) -> WitnessColumnMap<V> {
+        WitnessColumnMap::new(initial_value, self.witness_cols.len())
+    }
+
+    fn column_name(&self, poly_id: &PolyID) -> &str {
+        match poly_id.ptype {
+            PolynomialType::Committed => &self.witness_cols[poly_id].poly.name,
+            PolynomialType::Constant => &self.fixed_cols[poly_id].name,
+            PolynomialType::Intermediate => unimplemented!(),
+        }
+    }
+
+    pub fn try_column_by_name(&self, name: &str) -> Option<PolyID> {
+        self.column_by_name.get(name).cloned()
+    }
+
+    /// Returns all identities (of all stages) that reference the given column,
+    /// in source order. References through intermediate columns are not considered.
+    pub fn identities_referencing(
+        &self,
+        poly_id: &PolyID,
+    ) -> Vec<&'a Identity<AlgebraicExpression<T>>> {
+        self.analyzed
+            .identities
+            .iter()
+            .filter(|identity| refs_in_identity(identity).contains(poly_id))
+            .collect()
+    }
+
+    fn external_witness(&self, row: DegreeType, column: &PolyID) -> Option<T> {
         let row = row % self.degree;
         self.witness_cols[column]
             .external_values
@@ -405,13 +1347,52 @@ impl<'a, T: FieldElement> FixedData<'a, T> {
 
 pub struct FixedColumn<'a, T> {
     name: String,
-    values: &'a Vec<T>,
+    /// The values, if they were materialized upfront.
+    values: Option<&'a Vec<T>>,
+    /// The values materialized in windows, for columns whose values were not provided upfront.
+    window: Option<FixedColumnWindow<'a, T>>,
 }
 
-impl<'a, T> FixedColumn<'a, T> {
-    pub fn new(name: &'a str, values: &'a Vec<T>) -> FixedColumn<'a, T> {
+impl<'a, T: Copy> FixedColumn<'a, T> {
+    pub fn new(name: &str, values: Option<&'a Vec<T>>) -> FixedColumn<'a, T> {
         let name = name.to_string();
-        FixedColumn { name, values }
+        FixedColumn {
+            name,
+            values,
+            window: None,
+        }
+    }
+
+    /// Returns all values of the column.
+    /// Panics if the column is not materialized upfront.
+    pub fn values(&self) -> &'a Vec<T> {
+        self.values.unwrap_or_else(|| {
+            panic!(
+                "Values of fixed column {} are not fully materialized.",
+                self.name
+            )
+        })
+    }
+
+    /// Returns all values of the column, if they are materialized upfront.
+    pub fn try_values(&self) -> Option<&'a Vec<T>> {
+        self.values
+    }
+
+    /// Returns the value in the given row, which must be smaller than the degree.
+    pub fn value(&self, row: DegreeType) -> T {
+        match (&self.values, &self.window) {
+            (Some(values), _) => values[row as usize],
+            (None, Some(window)) => window.value(&self.name, row),
+            (None, None) => panic!("Values of fixed column {} are not available.", self.name),
+        }
+    }
+
+    /// Returns the rows that are currently materialized, if the column is materialized in windows.
+    pub fn materialized_rows(&self) -> Option<Range<DegreeType>> {
+        self.window
+            .as_ref()
+            .map(|window| window.materialized_rows())
     }
 }
 
@@ -471,3 +1452,2435 @@ impl<'a, T> WitnessColumn<'a, T> {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use powdr_number::GoldilocksField;
+    use powdr_pil_analyzer::analyze_string;
+
+    use crate::constant_evaluator::generate;
+
+    use super::range_constraints::RangeConstraint;
+    use super::rows::{Row, RowUpdater, UnknownStrategy};
+    use super::*;
+
+    /// The analyzed PIL of a test and its fixed columns, from which witness generators
+    /// are created.
+    struct Fixture {
+        analyzed: Analyzed<GoldilocksField>,
+        constants: Vec<(String, Vec<GoldilocksField>)>,
+    }
+
+    impl Fixture {
+        fn new(src: &str) -> Self {
+            let analyzed = analyze_string(src);
+            let constants = generate(&analyzed);
+            Self {
+                analyzed,
+                constants,
+            }
+        }
+
+        /// Returns a witness generator for the fixture that does not expect any
+        /// prover queries.
+        fn generator(&self) -> WitnessGenerator<'_, '_, GoldilocksField> {
+            WitnessGenerator::new(&self.analyzed, &self.constants, &no_queries)
+        }
+
+        /// Re-validates the witness after overriding the cell (see [override_and_check]).
+        fn override_and_check(
+            &self,
+            witness: &[(String, Vec<GoldilocksField>)],
+            row: DegreeType,
+            column: &str,
+            value: u64,
+        ) -> Result<(), Vec<RowCheckFailure<GoldilocksField>>> {
+            override_and_check(
+                &self.analyzed,
+                &self.constants,
+                witness,
+                row,
+                column,
+                value.into(),
+            )
+        }
+    }
+
+    /// Like [unused_query_callback], but as a function, so that it can be borrowed
+    /// for as long as needed.
+    fn no_queries(_: &str) -> Result<Option<GoldilocksField>, String> {
+        unreachable!()
+    }
+
+    fn generate_witness(
+        src: &str,
+        instruction_replay: Option<&InstructionReplay>,
+    ) -> BTreeMap<String, Vec<GoldilocksField>> {
+        let fixture = Fixture::new(src);
+        let mut witness_generator = fixture.generator();
+        if let Some(instruction_replay) = instruction_replay {
+            witness_generator = witness_generator.with_instruction_replay(instruction_replay);
+        }
+        witness_generator.generate().into_iter().collect()
+    }
+
+    #[test]
+    fn instruction_replay_with_jump() {
+        let src = r#"
+            namespace main(8);
+                col fixed FIRST = [1] + [0]*;
+                col fixed LAST = [0]* + [1];
+                col witness pc, instr_inc, instr_jmp_to_start, x;
+
+                FIRST * x = 0;
+                (1 - LAST) * (x' - x - instr_inc) = 0;
+                (1 - LAST) * (pc' - instr_inc * (pc + 1)) = 0;
+        "#;
+        // 0: inc
+        // 1: inc
+        // 2: jmp 0
+        let instruction_replay = InstructionReplay {
+            pc: "main.pc".to_string(),
+            instruction_flags: vec![
+                "main.instr_inc".to_string(),
+                "main.instr_jmp_to_start".to_string(),
+            ],
+            program: vec![0, 0, 1],
+            executed_pcs: vec![0, 1, 2, 0, 1, 2, 0, 1],
+        };
+        let witness = generate_witness(src, Some(&instruction_replay));
+
+        let to_field = |values: &[u64]| {
+            values
+                .iter()
+                .map(|v| GoldilocksField::from(*v))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(witness["main.pc"], to_field(&[0, 1, 2, 0, 1, 2, 0, 1]));
+        assert_eq!(
+            witness["main.instr_jmp_to_start"],
+            to_field(&[0, 0, 1, 0, 0, 1, 0, 0])
+        );
+        assert_eq!(witness["main.x"], to_field(&[0, 1, 2, 2, 3, 4, 4, 5]));
+    }
+
+    #[test]
+    #[should_panic = "Witness generation failed."]
+    fn instruction_replay_pc_outside_program() {
+        let src = r#"
+            namespace main(8);
+                col witness pc, instr_inc, x;
+
+                pc' = pc + instr_inc;
+        "#;
+        let instruction_replay = InstructionReplay {
+            pc: "main.pc".to_string(),
+            instruction_flags: vec!["main.instr_inc".to_string()],
+            program: vec![0, 0, 0],
+            executed_pcs: vec![0, 1, 3],
+        };
+        generate_witness(src, Some(&instruction_replay));
+    }
+
+    /// `y` can only be solved from an identity that references the next row,
+    /// but it is needed by an identity that does not.
+    const NEXT_ROW_FEEDBACK: &str = r#"
+        namespace main(4);
+            col fixed FIRST = [1] + [0]*;
+            col fixed LAST = [0]* + [1];
+            col witness x, y, z;
+
+            FIRST * x = 0;
+            z = 2 * y;
+            (1 - LAST) * (x' - x - 1) = 0;
+            (1 - LAST) * (y - (x' - x)) = 0;
+    "#;
+
+    #[test]
+    fn next_row_policy_eager() {
+        let fixture = Fixture::new(NEXT_ROW_FEEDBACK);
+        let witness = fixture
+            .generator()
+            .with_options(WitgenOptions {
+                next_row_policy: NextRowPolicy::Eager,
+                ..Default::default()
+            })
+            .generate()
+            .into_iter()
+            .collect::<BTreeMap<_, _>>();
+        let z = witness["main.z"]
+            .iter()
+            .map(|v| v.to_degree())
+            .collect::<Vec<_>>();
+        assert_eq!(z, vec![2, 2, 2, 0]);
+    }
+
+    #[test]
+    #[should_panic = "Witness generation failed."]
+    fn next_row_policy_defer() {
+        // By the time `y` is known, the identity `z = 2 * y` is not processed anymore,
+        // so the row cannot be completed.
+        generate_witness(NEXT_ROW_FEEDBACK, None);
+    }
+
+    #[test]
+    fn plan_of_two_machines() {
+        let src = r#"
+            constant %N = 4;
+
+            namespace Add(%N);
+                col witness A, B, C;
+                A + B = C;
+
+            namespace Main(%N);
+                col fixed a(i) { i + 13 };
+                col fixed b(i) { i * 17 };
+                col witness c;
+                col fixed CALL = [1, 0]*;
+                (1 - CALL) * c = 0;
+                CALL {a, b, c} in {Add.A, Add.B, Add.C};
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let plan = plan_report(&analyzed);
+
+        assert_eq!(plan.fixed_column_count, 3);
+        assert_eq!(plan.witness_column_count, 4);
+        assert_eq!(plan.machines.len(), 2);
+
+        let main = &plan.machines[0];
+        assert_eq!(main.name, "Main Machine");
+        assert_eq!(main.degree, 4);
+        assert_eq!(main.witness_columns, vec!["Main.c".to_string()]);
+        assert_eq!(main.identities, vec![1, 2]);
+        assert!(main.connecting_identities.is_empty());
+
+        let add = &plan.machines[1];
+        assert_eq!(add.name, "Secondary machine 0: Add (BlockMachine)");
+        assert_eq!(add.witness_columns, vec!["Add.A", "Add.B", "Add.C"]);
+        assert_eq!(add.identities, vec![0]);
+        assert_eq!(add.connecting_identities, vec![2]);
+
+        assert_eq!(
+            plan.calls(),
+            vec![(main.name.as_str(), add.name.as_str(), 2)]
+        );
+    }
+
+    #[test]
+    fn local_and_global_identity_ids() {
+        let src = r#"
+            constant %N = 4;
+
+            namespace Add(%N);
+                col witness A, B, C;
+                A + B = C;
+
+            namespace Main(%N);
+                col fixed a(i) { i + 13 };
+                col fixed b(i) { i * 17 };
+                col witness c;
+                col fixed CALL = [1, 0]*;
+                (1 - CALL) * c = 0;
+                CALL {a, b, c} in {Add.A, Add.B, Add.C};
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let plan = plan_report(&analyzed);
+        let add = "Secondary machine 0: Add (BlockMachine)";
+
+        assert_eq!(plan.local_identity_id(0), Some((add, 0)));
+        assert_eq!(plan.local_identity_id(1), Some(("Main Machine", 0)));
+        assert_eq!(plan.local_identity_id(2), Some(("Main Machine", 1)));
+        assert_eq!(plan.local_identity_id(3), None);
+
+        for global_id in 0..3 {
+            let (machine, local_id) = plan.local_identity_id(global_id).unwrap();
+            assert_eq!(plan.global_identity_id(machine, local_id), Some(global_id));
+        }
+        assert_eq!(plan.global_identity_id(add, 1), None);
+        assert_eq!(plan.global_identity_id("Unknown", 0), None);
+    }
+
+    /// `a` counts up, `b` counts down.
+    const COUNTERS: &str = r#"
+        namespace main(4);
+            col fixed FIRST = [1] + [0]*;
+            col fixed LAST = [0]* + [1];
+            col witness a, b;
+
+            FIRST * a = 0;
+            (1 - LAST) * (a' - a - 1) = 0;
+            b = 3 - a;
+    "#;
+
+    #[test]
+    fn verify_monotonic_columns() {
+        let fixture = Fixture::new(COUNTERS);
+        let witness = fixture
+            .generator()
+            .with_options(WitgenOptions {
+                monotonic_columns: vec!["main.a".to_string()],
+                ..Default::default()
+            })
+            .generate();
+
+        assert_eq!(verify_monotonic(&witness, "main.a"), Ok(()));
+        assert_eq!(
+            verify_monotonic(&witness, "main.b"),
+            Err(MonotonicityViolation {
+                column: "main.b".to_string(),
+                row: 0,
+                value: 3.into(),
+                next_value: 2.into(),
+            })
+        );
+    }
+
+    #[test]
+    #[should_panic = "Witness generation failed."]
+    fn monotonic_column_checked_during_generation() {
+        let fixture = Fixture::new(COUNTERS);
+        fixture
+            .generator()
+            .with_options(WitgenOptions {
+                monotonic_columns: vec!["main.b".to_string()],
+                ..Default::default()
+            })
+            .generate();
+    }
+
+    const SUMS: &str = r#"
+        namespace main(4);
+            col fixed A = [100, 150, 200, 250];
+            col witness a, b, c;
+            a = A;
+            b = A;
+            c = a + b;
+    "#;
+
+    #[test]
+    fn sums_within_bit_budget() {
+        let fixture = Fixture::new(SUMS);
+        let witness = fixture
+            .generator()
+            .with_options(WitgenOptions {
+                bit_budgets: vec![(vec!["main.a".to_string(), "main.b".to_string()], 9)],
+                ..Default::default()
+            })
+            .generate();
+        assert_eq!(
+            witness[2].1,
+            [200, 300, 400, 500].map(GoldilocksField::from)
+        );
+    }
+
+    #[test]
+    #[should_panic = "Witness generation failed."]
+    fn sum_exceeding_bit_budget() {
+        let fixture = Fixture::new(SUMS);
+        fixture
+            .generator()
+            .with_options(WitgenOptions {
+                bit_budgets: vec![(vec!["main.a".to_string(), "main.b".to_string()], 8)],
+                ..Default::default()
+            })
+            .generate();
+    }
+
+    #[test]
+    fn compare_with_reference_witness() {
+        let fixture = Fixture::new(COUNTERS);
+        let witness = fixture.generator().generate();
+
+        let mut reference = vec![];
+        powdr_number::write_polys_stream(&mut reference, &witness).unwrap();
+        assert_eq!(
+            compare_with_reference(&witness, &mut reference.as_slice(), 10),
+            vec![]
+        );
+
+        let mut modified = witness.clone();
+        modified[1].1[2] = 7.into();
+        modified[1].1[3] = 8.into();
+        let mut reference = vec![];
+        powdr_number::write_polys_stream(&mut reference, &modified).unwrap();
+        assert_eq!(
+            compare_with_reference(&witness, &mut reference.as_slice(), 1),
+            vec![CellDifference {
+                column: "main.b".to_string(),
+                row: 2,
+                expected: Some(7.into()),
+                actual: Some(1.into()),
+            }]
+        );
+        assert_eq!(
+            compare_with_reference(&witness, &mut reference.as_slice(), 10).len(),
+            2
+        );
+    }
+
+    #[test]
+    fn override_cell() {
+        let fixture = Fixture::new(COUNTERS);
+        let witness = fixture.generator().generate();
+
+        let check = |row, column, value: u64| {
+            fixture
+                .override_and_check(&witness, row, column, value)
+                .map_err(|failures| {
+                    failures
+                        .into_iter()
+                        .map(|failure| (failure.identity_id, failure.row))
+                        .collect::<Vec<_>>()
+                })
+        };
+        assert_eq!(check(1, "main.a", 1), Ok(()));
+        // Breaks the counter identity on both row pairs and `b = 3 - a` on row 1.
+        assert_eq!(check(1, "main.a", 5), Err(vec![(1, 0), (1, 1), (2, 1)]));
+        // The counter identity does not apply to the last row pair.
+        assert_eq!(check(3, "main.b", 1), Err(vec![(2, 3)]));
+    }
+
+    #[test]
+    fn override_cell_wraps_around() {
+        let src = r#"
+            namespace main(4);
+                col witness a;
+                a' = 1 - a;
+        "#;
+        let fixture = Fixture::new(src);
+        let witness = vec![(
+            "main.a".to_string(),
+            [0, 1, 0, 1].map(GoldilocksField::from).to_vec(),
+        )];
+        let check = |row, value: u64| {
+            fixture
+                .override_and_check(&witness, row, "main.a", value)
+                .map_err(|failures| {
+                    failures
+                        .into_iter()
+                        .map(|failure| failure.row)
+                        .collect::<Vec<_>>()
+                })
+        };
+        assert_eq!(check(0, 0), Ok(()));
+        // The last row is followed by the first one.
+        assert_eq!(check(0, 1), Err(vec![3, 0]));
+        assert_eq!(check(3, 0), Err(vec![2, 3]));
+    }
+
+    #[test]
+    fn residuals_of_corrupted_witness() {
+        let fixture = Fixture::new(COUNTERS);
+        let mut witness = fixture.generator().generate();
+        assert!(
+            constraint_residuals(&fixture.analyzed, &fixture.constants, &witness, &CpuBackend)
+                .failures
+                .is_empty()
+        );
+
+        // Off by one in `a`, wildly off in `b`.
+        witness[0].1[1] = 2.into();
+        witness[1].1[3] = 100.into();
+        let report =
+            constraint_residuals(&fixture.analyzed, &fixture.constants, &witness, &CpuBackend);
+        let residuals = report
+            .failures
+            .iter()
+            .map(|failure| {
+                (
+                    failure.identity_id,
+                    failure.row,
+                    failure.residual.unwrap().try_into_i32().unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            residuals,
+            vec![(1, 0, 1), (1, 1, -1), (2, 1, 1), (2, 3, 100)]
+        );
+        assert_eq!(report.max_magnitude(), Some(100.into()));
+        assert_eq!(
+            report.to_string(),
+            "Identity 1 (Row 0): 1\nIdentity 1 (Row 1): -1\nIdentity 2 (Row 1): 1\nIdentity 2 (Row 3): 100\n"
+        );
+
+        // Overriding a cell reports the same residual.
+        let failures = fixture
+            .override_and_check(&witness, 3, "main.b", 4)
+            .unwrap_err();
+        assert_eq!(failures[0].residual, Some(4.into()));
+    }
+
+    #[test]
+    fn validate_identities_separately() {
+        let fixture = Fixture::new(COUNTERS);
+        let mut witness = fixture.generator().generate();
+        // Breaks the counter identity on the last row pair and `b = 3 - a` on the last row.
+        witness[0].1[3] = 4.into();
+
+        let results = validate_identities(&fixture.analyzed, &fixture.constants, &witness);
+        assert_eq!(
+            results
+                .iter()
+                .map(|result| (result.identity_id, result.holds(), &result.failing_rows[..]))
+                .collect::<Vec<_>>(),
+            vec![
+                (0, true, &[][..]),
+                (1, false, &[2][..]),
+                (2, false, &[3][..])
+            ]
+        );
+        assert_eq!(results[2].identity, "main.b = 3 - main.a;");
+    }
+
+    #[test]
+    fn row_window() {
+        let src = r#"
+            namespace main(1024);
+                col fixed FIRST = [1] + [0]*;
+                col fixed LAST = [0]* + [1];
+                col witness x, y, z;
+
+                FIRST * (x - 1) = 0;
+                FIRST * (y - 1) = 0;
+                (1 - LAST) * (x' - y) = 0;
+                (1 - LAST) * (y' - (x + y)) = 0;
+                z = x * y;
+        "#;
+        let fixture = Fixture::new(src);
+        let generate_with_window = |row_window: Option<usize>| {
+            fixture
+                .generator()
+                .with_options(WitgenOptions {
+                    row_window,
+                    ..Default::default()
+                })
+                .generate()
+        };
+
+        let witness = generate_with_window(Some(2));
+        assert_eq!(witness, generate_with_window(None));
+        let x = &witness[0].1;
+        assert_eq!(x[..6], [1, 1, 2, 3, 5, 8].map(GoldilocksField::from));
+    }
+
+    #[test]
+    fn histogram_of_lookup() {
+        let src = r#"
+            namespace main(8);
+                col fixed VALUES = [1, 2, 2, 3, 3, 3, 0, 0];
+                col fixed TABLE(i) { i };
+                col fixed SEL = [1, 1, 1, 1, 1, 1, 1, 0];
+                col witness w;
+
+                w = VALUES;
+                SEL { w } in { TABLE };
+        "#;
+        let fixture = Fixture::new(src);
+        let witness = fixture.generator().generate();
+
+        let histogram = lookup_histogram(&fixture.analyzed, &fixture.constants, &witness, 1)
+            .into_iter()
+            .map(|(tuple, count)| (tuple[0].to_degree(), count))
+            .collect::<Vec<_>>();
+        // The last row is not selected.
+        assert_eq!(
+            histogram,
+            vec![
+                (0, 1),
+                (1, 1),
+                (2, 2),
+                (3, 3),
+                (4, 0),
+                (5, 0),
+                (6, 0),
+                (7, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn closure_constraint() {
+        let src = r#"
+            namespace main(4);
+                col fixed FIRST = [1] + [0]*;
+                col fixed LAST = [0]* + [1];
+                col witness x, y;
+
+                FIRST * x = 0;
+                (1 - LAST) * (x' - x - 1) = 0;
+        "#;
+        let fixture = Fixture::new(src);
+        let closure_constraints = [ClosureConstraint::<GoldilocksField>::new(
+            "y = x * x + 1",
+            |row_pair| {
+                let x = row_pair.witness_reference("main.x").unwrap();
+                let y = row_pair.witness_reference("main.y").unwrap();
+                match row_pair.get_value(x) {
+                    Some(x) => {
+                        (AffineExpression::from_variable_id(y) - (x * x + 1.into()).into()).solve()
+                    }
+                    None => Ok(EvalValue::incomplete(IncompleteCause::DataNotYetAvailable)),
+                }
+            },
+        )];
+        let witness = fixture
+            .generator()
+            .with_options(WitgenOptions {
+                closure_constraints: &closure_constraints,
+                ..Default::default()
+            })
+            .generate()
+            .into_iter()
+            .collect::<BTreeMap<_, _>>();
+        let y = witness["main.y"]
+            .iter()
+            .map(|v| v.to_degree())
+            .collect::<Vec<_>>();
+        assert_eq!(y, vec![1, 2, 5, 10]);
+    }
+
+    #[test]
+    fn running_sum() {
+        let src = r#"
+            namespace main(8);
+                col fixed X(i) { i * i + 1 };
+                col witness x, sum, double_sum;
+                x = X;
+                double_sum = 2 * sum;
+        "#;
+        let fixture = Fixture::new(src);
+        let witness_with_block_size = |block_size| {
+            let closure_constraints = [running_aggregate::<GoldilocksField>(
+                "main.x",
+                "main.sum",
+                |sum, x| sum + x,
+                block_size,
+            )];
+            fixture
+                .generator()
+                .with_options(WitgenOptions {
+                    closure_constraints: &closure_constraints,
+                    ..Default::default()
+                })
+                .generate()
+                .into_iter()
+                .map(|(name, values)| {
+                    (
+                        name,
+                        values.iter().map(|v| v.to_degree()).collect::<Vec<_>>(),
+                    )
+                })
+                .collect::<BTreeMap<_, _>>()
+        };
+
+        let witness = witness_with_block_size(None);
+        let x = &witness["main.x"];
+        let expected = x
+            .iter()
+            .scan(0, |sum, x| {
+                *sum += x;
+                Some(*sum)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(witness["main.sum"], expected);
+        // Identities can reference the aggregate.
+        let doubled = expected.iter().map(|sum| 2 * sum).collect::<Vec<_>>();
+        assert_eq!(witness["main.double_sum"], doubled);
+
+        // The sum starts over in each block.
+        let witness = witness_with_block_size(Some(4));
+        let expected = x
+            .chunks(4)
+            .flat_map(|block| {
+                block.iter().scan(0, |sum, x| {
+                    *sum += x;
+                    Some(*sum)
+                })
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(witness["main.sum"], expected);
+        assert_eq!(expected[4], x[4]);
+    }
+
+    #[test]
+    fn tag_rows() {
+        let src = r#"
+            namespace main(8);
+                col fixed FLAG = [0, 1, 0, 0, 1, 1, 0, 0];
+                col witness flag;
+                flag = FLAG;
+        "#;
+        let fixture = Fixture::new(src);
+        let row_tagger = RowTagger::<GoldilocksField>::new(|row_pair| {
+            let flag = row_pair.witness_reference("main.flag").unwrap();
+            row_pair.get_value(flag) == Some(1.into())
+        });
+        fixture
+            .generator()
+            .with_options(WitgenOptions {
+                row_tagger: Some(&row_tagger),
+                ..Default::default()
+            })
+            .generate();
+        assert_eq!(
+            row_tagger.tagged_rows().into_iter().collect::<Vec<_>>(),
+            vec![1, 4, 5]
+        );
+    }
+
+    #[test]
+    fn generate_incrementally() {
+        let src = r#"
+            namespace main(8);
+                col fixed FIRST = [1] + [0]*;
+                col witness input, sum;
+                FIRST * (sum - input) = 0;
+                (1 - FIRST') * (sum' - sum - input') = 0;
+        "#;
+        let fixture = Fixture::new(src);
+        let input = (1..=8).map(GoldilocksField::from).collect::<Vec<_>>();
+
+        let external_witness_values = vec![("main.input".to_string(), input.clone())];
+        let batch_witness = fixture
+            .generator()
+            .with_external_witness_values(&external_witness_values)
+            .generate();
+
+        let batches = input
+            .chunks(4)
+            .map(|chunk| vec![("main.input".to_string(), chunk.to_vec())])
+            .collect::<Vec<_>>();
+        let mut appended_sums = vec![];
+        let witness = fixture.generator().generate_incrementally(batches, |rows| {
+            let sums = &rows.iter().find(|(name, _)| name == "main.sum").unwrap().1;
+            appended_sums.push(sums.iter().map(|v| v.to_degree()).collect::<Vec<_>>());
+        });
+        assert_eq!(appended_sums, vec![vec![1, 3, 6, 10], vec![15, 21, 28, 36]]);
+        assert_eq!(witness, batch_witness);
+    }
+
+    #[test]
+    fn compare_schedule_strategies() {
+        let src = r#"
+            namespace main(8);
+                col fixed FIRST = [1] + [0]*;
+                col witness a, b, c, d;
+                FIRST * (a - 1) = 0;
+                (1 - FIRST') * (a' - d) = 0;
+                d = c + b;
+                c = 2 * b;
+                b = a + 1;
+        "#;
+        let fixture = Fixture::new(src);
+        let witness = fixture
+            .generator()
+            .compare_schedule_strategies(ScheduleStrategy::InOrder, ScheduleStrategy::Shuffled(7));
+        assert_eq!(witness, fixture.generator().generate());
+    }
+
+    #[test]
+    fn dont_care_column() {
+        let src = r#"
+            namespace main(4);
+                col witness a, scratch;
+                a = 1;
+        "#;
+        let fixture = Fixture::new(src);
+        let witness = fixture
+            .generator()
+            .with_options(WitgenOptions {
+                dont_care_columns: vec![("main.scratch".to_string(), None)],
+                dont_care_value: 7.into(),
+                ..Default::default()
+            })
+            .generate();
+        assert_eq!(witness[0].1, vec![1.into(); 4]);
+        assert_eq!(witness[1].1, vec![7.into(); 4]);
+    }
+
+    #[test]
+    fn override_fixed_column() {
+        let src = r#"
+            namespace main(4);
+                col fixed X = [0, 1, 2, 3];
+                col fixed TABLE_IN = [0, 1, 2, 3];
+                col fixed TABLE_OUT = [0, 1, 4, 9];
+                col witness x, y;
+                x = X;
+                {x, y} in {TABLE_IN, TABLE_OUT};
+        "#;
+        let fixture = Fixture::new(src);
+        let overrides = vec![(
+            "main.TABLE_OUT".to_string(),
+            [0, 1, 8, 27]
+                .into_iter()
+                .map(GoldilocksField::from)
+                .collect(),
+        )];
+        let witness = fixture
+            .generator()
+            .with_fixed_column_overrides(&overrides)
+            .generate();
+        assert_eq!(witness[1].1, overrides[0].1);
+    }
+
+    #[test]
+    fn collect_public_outputs() {
+        let src = r#"
+            constant %N = 4;
+
+            namespace Add(%N);
+                col witness A, B, C;
+                A + B = C;
+                public first_sum = C(0);
+
+            namespace Main(%N);
+                col fixed a(i) { i + 13 };
+                col fixed b(i) { i * 17 };
+                col witness c;
+                col fixed CALL = [1, 0]*;
+                (1 - CALL) * c = 0;
+                CALL {a, b, c} in {Add.A, Add.B, Add.C};
+                public first_out = c(0);
+                public second_out = c(2);
+        "#;
+        let fixture = Fixture::new(src);
+        let witness = fixture.generator().generate();
+        let outputs =
+            plan_report(&fixture.analyzed).collect_public_outputs(&witness, &fixture.analyzed);
+
+        let key = |machine: &str, column: &str| (machine.to_string(), column.to_string());
+        assert_eq!(
+            outputs,
+            [
+                (key("Main Machine", "Main.c"), vec![13.into(), 49.into()]),
+                (
+                    key("Secondary machine 0: Add (BlockMachine)", "Add.C"),
+                    vec![13.into()]
+                ),
+            ]
+            .into_iter()
+            .collect()
+        );
+    }
+
+    #[test]
+    fn validate_selected_machines() {
+        let src = r#"
+            constant %N = 4;
+
+            namespace Add(%N);
+                col witness A, B, C;
+                A + B = C;
+
+            namespace Main(%N);
+                col fixed a(i) { i + 13 };
+                col fixed b(i) { i * 17 };
+                col witness c;
+                col fixed CALL = [1, 0]*;
+                (1 - CALL) * c = 0;
+                CALL {a, b, c} in {Add.A, Add.B, Add.C};
+        "#;
+        let fixture = Fixture::new(src);
+        let mut witness = fixture.generator().generate();
+
+        // Corrupt a cell of each machine.
+        for (name, values) in &mut witness {
+            match name.as_str() {
+                "Add.C" => values[0] += GoldilocksField::from(1),
+                "Main.c" => values[1] = 5.into(),
+                _ => {}
+            }
+        }
+        let failures = |machine: &str| {
+            machine_constraint_residuals(
+                &fixture.analyzed,
+                &fixture.constants,
+                &witness,
+                &CpuBackend,
+                |m| m.name.contains(machine),
+            )
+            .failures
+            .into_iter()
+            .map(|failure| (failure.identity_id, failure.row))
+            .collect::<Vec<_>>()
+        };
+        assert_eq!(failures("Main"), vec![(1, 1)]);
+        assert_eq!(failures("Add"), vec![(0, 0)]);
+        assert_eq!(failures(""), vec![(0, 0), (1, 1)]);
+    }
+
+    #[test]
+    fn degree_bound() {
+        let src = r#"
+            namespace main(4);
+                col witness a, b;
+                a = 2;
+                b = a * a * a;
+        "#;
+        let fixture = Fixture::new(src);
+        let witness = fixture
+            .generator()
+            .with_options(WitgenOptions {
+                max_constraint_degree: Some(3),
+                ..Default::default()
+            })
+            .generate();
+        assert_eq!(witness[1].1, vec![8.into(); 4]);
+
+        let violation = check_degree_bound(&fixture.analyzed, &witness, 2).unwrap_err();
+        assert_eq!(
+            violation,
+            DegreeBoundViolation::ConstraintDegree {
+                identity_id: 1,
+                identity: "main.b = main.a * main.a * main.a;".to_string(),
+                degree: 3,
+                max_degree: 2,
+            }
+        );
+    }
+
+    #[test]
+    #[should_panic = "Identity 1 has degree 3, but the maximum constraint degree is 2"]
+    fn degree_bound_exceeded() {
+        let src = r#"
+            namespace main(4);
+                col witness a, b;
+                a = 2;
+                b = a * a * a;
+        "#;
+        let fixture = Fixture::new(src);
+        fixture
+            .generator()
+            .with_options(WitgenOptions {
+                max_constraint_degree: Some(2),
+                ..Default::default()
+            })
+            .generate();
+    }
+
+    #[test]
+    fn query_determinism() {
+        let src = r#"
+            namespace std::prover(8);
+                enum Query {
+                    Input(int),
+                    None,
+                }
+
+            namespace main(8);
+                col fixed FIRST = [1] + [0]*;
+                col fixed LAST = [0]* + [1];
+                col witness input(i) query std::prover::Query::Input(i);
+                col witness sum;
+                FIRST * sum = 0;
+                (1 - LAST) * (sum' - sum - input) = 0;
+        "#;
+        let fixture = Fixture::new(src);
+        let query_callback = |query: &str| -> Result<Option<GoldilocksField>, String> {
+            let index = query
+                .strip_prefix("Input(")
+                .and_then(|query| query.strip_suffix(')'))
+                .ok_or_else(|| format!("Unsupported query: {query}"))?;
+            let index = index.parse::<u64>().map_err(|e| e.to_string())?;
+            Ok(Some((index * index).into()))
+        };
+        let scheduler = RandomDelays {
+            seed: 42,
+            max_delay: std::time::Duration::from_micros(200),
+        };
+        let witness = WitnessGenerator::new(&fixture.analyzed, &fixture.constants, &query_callback)
+            .check_query_determinism(20, &scheduler);
+        assert_eq!(
+            witness[1].1,
+            [0, 0, 1, 5, 14, 30, 55, 91]
+                .into_iter()
+                .map(GoldilocksField::from)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn hash_order_determinism() {
+        let src = r#"
+            namespace Add(8);
+                col witness A, B, C;
+                A + B = C;
+
+            namespace Main(8);
+                col fixed a(i) { i + 13 };
+                col fixed b(i) { i * 17 };
+                col witness c;
+                col fixed CALL = [1, 0]*;
+                (1 - CALL) * c = 0;
+                CALL {a, b, c} in {Add.A, Add.B, Add.C};
+        "#;
+        let fixture = Fixture::new(src);
+        let witness = fixture.generator().check_hash_order_determinism(8);
+        assert_eq!(witness, fixture.generator().generate());
+
+        // A constraint that picks the first element of a hash set is caught.
+        let src = r#"
+            namespace main(4);
+                col witness x;
+        "#;
+        let fixture = Fixture::new(src);
+        let closure_constraints = [ClosureConstraint::<GoldilocksField>::new(
+            "x = first element of a hash set",
+            |row_pair| {
+                let x = row_pair.witness_reference("main.x").unwrap();
+                let first = (0..16u64)
+                    .collect::<HashSet<_>>()
+                    .into_iter()
+                    .next()
+                    .unwrap();
+                (AffineExpression::from_variable_id(x) - GoldilocksField::from(first).into())
+                    .solve()
+            },
+        )];
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            fixture
+                .generator()
+                .with_options(WitgenOptions {
+                    closure_constraints: &closure_constraints,
+                    ..Default::default()
+                })
+                .check_hash_order_determinism(8)
+        }));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn compact_finalized_rows() {
+        let fixture = Fixture::new(COUNTERS);
+        let witness = fixture.generator().generate();
+        let compacted = fixture
+            .generator()
+            .with_options(WitgenOptions {
+                row_window: Some(2),
+                compact_finalized_rows: true,
+                ..Default::default()
+            })
+            .generate();
+        assert_eq!(compacted, witness);
+    }
+
+    #[test]
+    fn column_sink() {
+        let fixture = Fixture::new(COUNTERS);
+        let mut cells = vec![];
+        let column_sink = ColumnSink::new(|poly_id: PolyID, row, value: GoldilocksField| {
+            cells.push((poly_id.id, row, value.to_degree()))
+        });
+        let witness = fixture
+            .generator()
+            .with_options(WitgenOptions {
+                row_window: Some(2),
+                column_sink: Some(&column_sink),
+                ..Default::default()
+            })
+            .generate();
+        drop(column_sink);
+
+        // Row 0 is never finalized during processing, the other rows are finalized
+        // one by one, passing the values of `a` and `b` in order.
+        assert_eq!(
+            cells,
+            vec![
+                (0, 1, 1),
+                (1, 1, 2),
+                (0, 2, 2),
+                (1, 2, 1),
+                (0, 3, 3),
+                (1, 3, 0)
+            ]
+        );
+        for (column, row, value) in cells {
+            assert_eq!(witness[column as usize].1[row as usize].to_degree(), value);
+        }
+    }
+
+    #[test]
+    fn generate_single_block() {
+        // Decomposes a byte into bits, the low nibble in the first row of the block
+        // and the high nibble in the second (latch) row.
+        let src = r#"
+            constant %N = 8;
+
+            namespace Decompose(%N);
+                col fixed LATCH = [0, 1]*;
+                col witness VALUE, B0, B1, B2, B3;
+                B0 * (1 - B0) = 0;
+                B1 * (1 - B1) = 0;
+                B2 * (1 - B2) = 0;
+                B3 * (1 - B3) = 0;
+                (1 - LATCH) * VALUE = 0;
+                LATCH' * (VALUE' - 16 * (B0' + 2 * B1' + 4 * B2' + 8 * B3') - (B0 + 2 * B1 + 4 * B2 + 8 * B3)) = 0;
+
+            namespace Main(%N);
+                col fixed v(i) { i * 31 };
+                col fixed CALL = [1, 0]*;
+                CALL { v } in Decompose.LATCH { Decompose.VALUE };
+        "#;
+        let fixture = Fixture::new(src);
+        let block = fixture
+            .generator()
+            .generate_single_block(
+                "Secondary machine 0: Decompose (BlockMachine)",
+                &[("Decompose.VALUE".to_string(), 0xb7.into())],
+            )
+            .unwrap();
+
+        let block = block
+            .into_iter()
+            .map(|(name, values)| (name, values.iter().map(|v| v.to_degree()).collect()))
+            .collect::<Vec<(String, Vec<u64>)>>();
+        let column = |name: &str, values: [u64; 2]| (name.to_string(), values.to_vec());
+        assert_eq!(
+            block,
+            vec![
+                column("Decompose.VALUE", [0, 0xb7]),
+                column("Decompose.B0", [1, 1]),
+                column("Decompose.B1", [1, 1]),
+                column("Decompose.B2", [1, 0]),
+                column("Decompose.B3", [0, 1]),
+            ]
+        );
+    }
+
+    #[test]
+    fn column_alias() {
+        let src = r#"
+            namespace main(4);
+                col witness x, y;
+                let x_alias = x;
+                let x_alias_alias = x_alias;
+                y = x_alias_alias + 1;
+        "#;
+        let fixture = Fixture::new(src);
+        let external_witness_values = vec![(
+            "main.x_alias".to_string(),
+            vec![1.into(), 2.into(), 3.into(), 4.into()],
+        )];
+        let witness_generator = fixture
+            .generator()
+            .with_external_witness_values(&external_witness_values)
+            .with_options(WitgenOptions {
+                monotonic_columns: vec!["main.x_alias_alias".to_string()],
+                ..Default::default()
+            });
+
+        let fixed_data = witness_generator.fixed_data();
+        let x = fixed_data.try_column_by_name("main.x");
+        assert!(x.is_some());
+        assert_eq!(fixed_data.try_column_by_name("main.x_alias"), x);
+        assert_eq!(fixed_data.try_column_by_name("main.x_alias_alias"), x);
+
+        let witness = witness_generator
+            .generate()
+            .into_iter()
+            .map(|(name, values)| (name, values.iter().map(|v| v.to_degree()).collect()))
+            .collect::<Vec<(String, Vec<u64>)>>();
+        assert_eq!(
+            witness,
+            vec![
+                ("main.x".to_string(), vec![1, 2, 3, 4]),
+                ("main.y".to_string(), vec![2, 3, 4, 5]),
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic = "External witness values for column main.x provided more than once."]
+    fn column_alias_with_duplicate_external_values() {
+        let src = r#"
+            namespace main(4);
+                col witness x;
+                let x_alias = x;
+                x_alias = x;
+        "#;
+        let fixture = Fixture::new(src);
+        let values = vec![1.into(), 2.into(), 3.into(), 4.into()];
+        let external_witness_values = vec![
+            ("main.x".to_string(), values.clone()),
+            ("main.x_alias".to_string(), values),
+        ];
+        fixture
+            .generator()
+            .with_external_witness_values(&external_witness_values)
+            .generate();
+    }
+
+    #[test]
+    fn inverse_query_of_zero() {
+        let src = r#"
+            namespace std::prover(4);
+                enum Query {
+                    Inverse(fe),
+                    None,
+                }
+
+            namespace main(4);
+                let x_value: int -> fe = |i| if i == 1 { 3 } else { if i == 3 { 5 } else { 0 } };
+                col fixed X(i) { x_value(i) };
+                col witness inv(i) query std::prover::Query::Inverse(x_value(i));
+                col witness is_zero;
+                is_zero = 1 - X * inv;
+                is_zero * X = 0;
+        "#;
+        let fixture = Fixture::new(src);
+        let query_callback = inverse_query_callback(unused_query_callback(), 0.into());
+        let witness = WitnessGenerator::new(&fixture.analyzed, &fixture.constants, &query_callback)
+            .generate();
+
+        let inv_3 = GoldilocksField::from(1) / GoldilocksField::from(3);
+        let inv_5 = GoldilocksField::from(1) / GoldilocksField::from(5);
+        assert_eq!(
+            witness,
+            vec![
+                (
+                    "main.inv".to_string(),
+                    vec![0.into(), inv_3, 0.into(), inv_5]
+                ),
+                (
+                    "main.is_zero".to_string(),
+                    vec![1.into(), 0.into(), 1.into(), 0.into()]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn identities_referencing_column() {
+        let src = r#"
+            namespace Add(4);
+                col witness A, B, C;
+                A + B = C;
+
+            namespace Main(4);
+                col fixed a(i) { i + 13 };
+                col fixed b(i) { i * 17 };
+                col witness c;
+                col fixed CALL = [1, 0]*;
+                (1 - CALL) * c = 0;
+                CALL {a, b, c} in {Add.A, Add.B, Add.C};
+        "#;
+        let fixture = Fixture::new(src);
+        let fixed_data = FixedData::new(
+            &fixture.analyzed,
+            &fixture.constants,
+            &[],
+            Default::default(),
+            0,
+        );
+        let referencing = |column: &str| {
+            let poly_id = fixed_data.try_column_by_name(column).unwrap();
+            fixed_data
+                .identities_referencing(&poly_id)
+                .into_iter()
+                .map(|identity| identity.id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(referencing("Add.C"), vec![0, 2]);
+        assert_eq!(referencing("Main.c"), vec![1, 2]);
+        assert_eq!(referencing("Add.A"), vec![0, 2]);
+        assert_eq!(referencing("Main.a"), vec![2]);
+    }
+
+    #[test]
+    fn keyed_block_placement() {
+        let src = r#"
+            namespace Add(8);
+                col witness A, B, C;
+                A + B = C;
+
+            namespace Main(8);
+                col fixed a(i) { i + 13 };
+                col fixed b(i) { i * 17 };
+                col witness c;
+                col fixed CALL = [1, 0]*;
+                (1 - CALL) * c = 0;
+                CALL {a, b, c} in {Add.A, Add.B, Add.C};
+        "#;
+        let fixture = Fixture::new(src);
+        // Places the blocks by the value of `a`.
+        let placement = |_: &str, _: usize, key: &[Option<GoldilocksField>]| match key[0]
+            .unwrap()
+            .to_degree()
+        {
+            13 => 5,
+            15 => 0,
+            17 => 3,
+            19 => 1,
+            _ => unreachable!(),
+        };
+        let witness = fixture
+            .generator()
+            .with_options(WitgenOptions {
+                block_placement: Some(&placement),
+                ..Default::default()
+            })
+            .generate();
+
+        let column = |name: &str| {
+            witness
+                .iter()
+                .find(|(n, _)| n == name)
+                .unwrap()
+                .1
+                .iter()
+                .map(|v| v.to_degree())
+                .collect::<Vec<_>>()
+        };
+        // Unused blocks are filled with the first block, i.e. the one of the first call.
+        assert_eq!(column("Add.A"), vec![15, 19, 13, 17, 13, 13, 13, 13]);
+        assert_eq!(column("Add.B"), vec![34, 102, 0, 68, 0, 0, 0, 0]);
+        assert_eq!(column("Add.C"), vec![49, 121, 13, 85, 13, 13, 13, 13]);
+        assert_eq!(column("Main.c"), vec![13, 0, 49, 0, 85, 0, 121, 0]);
+    }
+
+    #[test]
+    fn failure_bundle() {
+        let src = r#"
+            namespace main(4);
+                col fixed FIRST = [1] + [0]*;
+                col fixed CHECK = [0, 0, 1, 0];
+                col witness x, y, z;
+                FIRST * y = 0;
+                y' = y + x;
+                z = y + 10;
+                CHECK * (z - 7) = 0;
+        "#;
+        let fixture = Fixture::new(src);
+        let inputs = vec![(
+            "main.x".to_string(),
+            vec![1.into(), 2.into(), 3.into(), 4.into()],
+        )];
+        let path = std::env::temp_dir().join(format!(
+            "powdr_witgen_failure_bundle_{}.txt",
+            std::process::id()
+        ));
+        let result = std::panic::catch_unwind(|| {
+            fixture
+                .generator()
+                .with_external_witness_values(&inputs)
+                .with_options(WitgenOptions {
+                    failure_bundle: Some(&path),
+                    ..Default::default()
+                })
+                .generate()
+        });
+        assert!(result.is_err());
+
+        let bundle = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines = bundle.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "row: 2");
+        assert!(lines[1].starts_with("error: Error in identity: main.CHECK * (main.z - 7) = 0;"));
+        let identities_hash = lines
+            .iter()
+            .find_map(|line| line.strip_prefix("identities_hash: "))
+            .unwrap();
+        assert_eq!(identities_hash.len(), 16);
+        let section = |header: &str| {
+            lines
+                .iter()
+                .skip_while(|line| !line.starts_with(header))
+                .skip(1)
+                .take_while(|line| line.starts_with("  "))
+                .map(|line| line.trim())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(section("inputs:"), vec!["main.x: 1, 2, 3, 4"]);
+        assert_eq!(
+            section("trace (from row 0):"),
+            vec![
+                "main.x: 1, 2, 3, 4",
+                "main.y: 0, 1, 3, ?",
+                "main.z: 10, 11, 13, ?"
+            ]
+        );
+    }
+
+    #[test]
+    fn failure_inspector() {
+        let src = r#"
+            namespace main(4);
+                col fixed CHECK = [0, 0, 1, 0];
+                col witness x, z;
+                z = x + 10;
+                CHECK * (z - 7) = 0;
+        "#;
+        let fixture = Fixture::new(src);
+        let inputs = vec![(
+            "main.x".to_string(),
+            vec![1.into(), 2.into(), 3.into(), 4.into()],
+        )];
+        let snapshots = Mutex::new(vec![]);
+        let inspect = |snapshot: &DebugSnapshot<GoldilocksField>| {
+            snapshots.lock().unwrap().push(snapshot.clone());
+        };
+        let result = std::panic::catch_unwind(|| {
+            fixture
+                .generator()
+                .with_external_witness_values(&inputs)
+                .with_options(WitgenOptions {
+                    failure_inspector: Some(&inspect),
+                    ..Default::default()
+                })
+                .generate()
+        });
+        assert!(result.is_err());
+
+        let snapshots = snapshots.into_inner().unwrap();
+        assert_eq!(snapshots.len(), 1);
+        let snapshot = &snapshots[0];
+        assert_eq!(snapshot.value("main.z", 1), Some(12.into()));
+        // The failing row, the next row is not processed yet.
+        assert_eq!(snapshot.value("main.z", 2), Some(13.into()));
+        assert!(!snapshot.is_known("main.z", 3));
+    }
+
+    /// Whether a cell is determined, without its value.
+    #[derive(Clone, Debug, PartialEq)]
+    struct Determined(bool);
+
+    impl<T: FieldElement> RowValue<T> for Determined {
+        fn fresh(external_value: Option<T>, _: Option<&RangeConstraint<T>>) -> Self {
+            Determined(external_value.is_some())
+        }
+
+        fn value(&self) -> Option<T> {
+            None
+        }
+
+        fn range_constraint(&self) -> Option<RangeConstraint<T>> {
+            None
+        }
+
+        fn update_with(&self, c: &Constraint<T>) -> Self {
+            assert!(!self.0, "Cell was already determined.");
+            Determined(matches!(c, Constraint::Assignment(_)))
+        }
+    }
+
+    #[test]
+    fn row_value_domain() {
+        let src = r#"
+            namespace main(4);
+                col witness x, y, z, w, v;
+                y = x + 1;
+                z = y * w;
+                v = 3 * y;
+        "#;
+        let fixture = Fixture::new(src);
+        let inputs = vec![(
+            "main.x".to_string(),
+            vec![1.into(), 2.into(), 3.into(), 4.into()],
+        )];
+        let fixed_data = FixedData::new(
+            &fixture.analyzed,
+            &fixture.constants,
+            &inputs,
+            Default::default(),
+            0,
+        );
+        let row_index = RowIndex::from_degree(0, fixed_data.degree);
+        let mut current = Row::<_, Determined>::fresh_with_domain(&fixed_data, row_index);
+        let mut next = current.clone();
+
+        // Determines which cells can be solved, by repeatedly looking for an affine identity
+        // with a single undetermined cell.
+        loop {
+            let row_pair = RowPair::from_single_row(
+                &current,
+                row_index,
+                &fixed_data,
+                UnknownStrategy::Unknown,
+            );
+            let solvable = fixture.analyzed.identities.iter().find_map(|identity| {
+                let affine = row_pair.evaluate(identity.expression_for_poly_id()).ok()?;
+                let undetermined = affine
+                    .nonzero_coefficients()
+                    .map(|(poly, _)| *poly)
+                    .filter(|poly| !current[&poly.poly_id].value.0)
+                    .collect::<Vec<_>>();
+                match undetermined[..] {
+                    [poly] => Some(poly),
+                    _ => None,
+                }
+            });
+            let Some(poly) = solvable else {
+                break;
+            };
+            RowUpdater::new(&mut current, &mut next, row_index)
+                .apply_update(poly, &Constraint::Assignment(0.into()));
+        }
+
+        let determined = current
+            .iter()
+            .filter(|(_, cell)| cell.value.0)
+            .map(|(_, cell)| cell.name)
+            .collect::<Vec<_>>();
+        assert_eq!(determined, vec!["main.x", "main.y", "main.v"]);
+    }
+
+    #[test]
+    fn key_value_table_lookup() {
+        let src = r#"
+            namespace main(8);
+                col fixed X(i) { i };
+                col fixed Y(i) { i };
+                col fixed a(i) { i % 4 };
+                col witness b;
+                {a, b} in {X, Y};
+        "#;
+        let fixture = Fixture::new(src);
+
+        // Maps the keys below 2 to `key + 4` instead of `key`.
+        struct Shifted;
+        impl KeyValueTable<GoldilocksField> for Shifted {
+            fn get(
+                &self,
+                input_columns: &[&str],
+                key: &[GoldilocksField],
+                output_columns: &[&str],
+            ) -> Option<Vec<GoldilocksField>> {
+                assert_eq!(input_columns, ["main.X"]);
+                assert_eq!(output_columns, ["main.Y"]);
+                (key[0].to_degree() < 2).then(|| vec![key[0] + 4.into()])
+            }
+        }
+
+        let witness = fixture
+            .generator()
+            .with_options(WitgenOptions {
+                key_value_table: Some((&Shifted, KeyValueMiss::FixedColumns)),
+                ..Default::default()
+            })
+            .generate();
+        let b = witness[0]
+            .1
+            .iter()
+            .map(|v| v.to_degree())
+            .collect::<Vec<_>>();
+        // Keys 2 and 3 are not in the table and resolved against the fixed columns.
+        assert_eq!(b, vec![4, 5, 2, 3, 4, 5, 2, 3]);
+
+        let result = std::panic::catch_unwind(|| {
+            fixture
+                .generator()
+                .with_options(WitgenOptions {
+                    key_value_table: Some((&Shifted, KeyValueMiss::Error)),
+                    ..Default::default()
+                })
+                .generate()
+        });
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn chrome_trace() {
+        let src = r#"
+            namespace Add(8);
+                col witness A, B, C;
+                A + B = C;
+
+            namespace Main(8);
+                col fixed a(i) { i + 13 };
+                col fixed b(i) { i * 17 };
+                col witness c;
+                col fixed CALL = [1, 0]*;
+                (1 - CALL) * c = 0;
+                CALL {a, b, c} in {Add.A, Add.B, Add.C};
+        "#;
+        let fixture = Fixture::new(src);
+        let path = std::env::temp_dir().join(format!(
+            "powdr_witgen_chrome_trace_{}.json",
+            std::process::id()
+        ));
+        fixture
+            .generator()
+            .with_options(WitgenOptions {
+                chrome_trace: Some(&path),
+                ..Default::default()
+            })
+            .generate();
+
+        let trace = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines = trace.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], r#"{"traceEvents":["#);
+        assert_eq!(*lines.last().unwrap(), "]}");
+        let events = lines[1..lines.len() - 1]
+            .iter()
+            .map(|line| {
+                let name = line.split('"').nth(3).unwrap();
+                let phase = line.split('"').nth(11).unwrap();
+                (name, phase)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(events[0], ("witgen (outer code)", "B"));
+        assert_eq!(events[1], ("Main Machine", "B"));
+        assert_eq!(events[events.len() - 1], ("witgen (outer code)", "E"));
+        // Each of the four calls to the block machine is a nested pair of events.
+        let add = "Secondary machine 0: Add (BlockMachine)";
+        let count = |event| events.iter().filter(|e| **e == event).count();
+        assert_eq!(count((add, "B")), 4);
+        assert_eq!(count((add, "E")), 4);
+    }
+
+    #[test]
+    fn convergence_heatmap() {
+        // Processed in this order, each identity can only be solved in the iteration
+        // after the previous one.
+        let src = r#"
+            namespace Main(4);
+                col witness x, y, z;
+                z = y + 1;
+                y = x + 1;
+                x = 3;
+        "#;
+        let fixture = Fixture::new(src);
+        let path = std::env::temp_dir().join(format!(
+            "powdr_witgen_convergence_heatmap_{}.csv",
+            std::process::id()
+        ));
+        fixture
+            .generator()
+            .with_options(WitgenOptions {
+                convergence_heatmap: Some(&path),
+                ..Default::default()
+            })
+            .generate();
+
+        let csv = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let grid = csv
+            .lines()
+            .map(|line| line.split(',').collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        // A header and one line per iteration, with the iteration and one count per column.
+        assert_eq!(grid.len(), 4);
+        assert!(grid.iter().all(|line| line.len() == 4));
+        assert_eq!(grid[0], ["iteration", "Main.x", "Main.y", "Main.z"]);
+        // In each of the 4 rows, `z` becomes known in the third iteration.
+        assert_eq!(grid[3], ["2", "0", "0", "4"]);
+    }
+
+    #[test]
+    fn pending_call_check() {
+        // The input `Sub.u` of the call to `Add` is never determined, but the calls to `Sub`
+        // complete nevertheless, so the calls to `Add` are dropped.
+        let src = r#"
+            namespace Add(8);
+                col witness A, B, C;
+                A + B = C;
+
+            namespace Sub(8);
+                col fixed LATCH = [1]*;
+                col witness x, y, u, w;
+                y = x + 1;
+                LATCH {x, u, w} in {Add.A, Add.B, Add.C};
+
+            namespace Main(8);
+                col fixed a(i) { i };
+                col witness r;
+                col fixed CALL = [1, 0]*;
+                (1 - CALL) * r = 0;
+                CALL {a, r} in Sub.LATCH {Sub.x, Sub.y};
+        "#;
+        let fixture = Fixture::new(src);
+
+        // Without the check, the dropped calls go unnoticed.
+        fixture.generator().generate();
+
+        let result = std::panic::catch_unwind(|| {
+            fixture
+                .generator()
+                .with_options(WitgenOptions {
+                    check_pending_calls: true,
+                    ..Default::default()
+                })
+                .generate()
+        });
+        let message = result.unwrap_err().downcast::<String>().unwrap();
+        let identity_id = fixture
+            .analyzed
+            .identities
+            .iter()
+            .find(|identity| identity.to_string().starts_with("Sub.LATCH"))
+            .unwrap()
+            .id;
+        // `Add` is only called while processing the first block of `Sub` (whose processing
+        // sequence skips the call later on), from the row pairs starting at rows 7 and 0.
+        let expected = [0, 7]
+            .iter()
+            .map(|row| {
+                format!(
+                    "  Secondary machine 0: Add (BlockMachine): identity {identity_id} called from row {row}"
+                )
+            })
+            .join("\n");
+        assert_eq!(
+            *message,
+            format!("Some calls to machines were never resolved:\n{expected}")
+        );
+    }
+
+    #[test]
+    fn scratchpad() {
+        let src = r#"
+            namespace main(4);
+                col fixed FIRST = [1] + [0]*;
+                col fixed LAST = [0]* + [1];
+                col witness x, y;
+
+                FIRST * x = 0;
+                (1 - LAST) * (x' - x - 1) = 0;
+        "#;
+        let fixture = Fixture::new(src);
+        // Sets `y` to the sum of `x` up to the current row, keeping the sums of all rows
+        // processed so far in the scratchpad.
+        let closure_constraints = [ClosureConstraint::<GoldilocksField>::with_scratchpad(
+            "y = running sum of x",
+            |row_pair, scratchpad| {
+                let x = row_pair.witness_reference("main.x").unwrap();
+                let y = row_pair.witness_reference("main.y").unwrap();
+                let Some(x) = row_pair.get_value(x) else {
+                    return Ok(EvalValue::incomplete(IncompleteCause::DataNotYetAvailable));
+                };
+                let row = DegreeType::from(row_pair.current_row_index) as usize;
+                let sum = scratchpad.with(|sums: &mut Vec<GoldilocksField>| {
+                    if sums.len() == row {
+                        sums.push(sums.last().copied().unwrap_or_default() + x);
+                    }
+                    sums[row]
+                });
+                (AffineExpression::from_variable_id(y) - sum.into()).solve()
+            },
+        )];
+        let scratchpad = Scratchpad::default();
+        let witness = fixture
+            .generator()
+            .with_options(WitgenOptions {
+                closure_constraints: &closure_constraints,
+                scratchpad: Some(&scratchpad),
+                ..Default::default()
+            })
+            .generate()
+            .into_iter()
+            .collect::<BTreeMap<_, _>>();
+        let y = witness["main.y"]
+            .iter()
+            .map(|v| v.to_degree())
+            .collect::<Vec<_>>();
+        assert_eq!(y, vec![0, 1, 3, 6]);
+        let sums = scratchpad.take::<Vec<GoldilocksField>>().unwrap();
+        assert_eq!(sums, [0, 1, 3, 6].map(GoldilocksField::from));
+    }
+
+    #[test]
+    fn reduce_counterexample() {
+        let src = r#"
+            namespace std::prover(4);
+                enum Query {
+                    Input(int),
+                    None,
+                }
+
+            namespace main(4);
+                col witness x(i) query std::prover::Query::Input(i);
+                col witness y;
+                y = 2 * x;
+        "#;
+        // Validation uses a stricter version of the program that only allows x in {0, 1, 2}.
+        let strict_src = format!("{src}\nx * (x - 1) * (x - 2) = 0;");
+        let fixture = Fixture::new(src);
+        let strict_analyzed = analyze_string::<GoldilocksField>(&strict_src);
+        // Answers `Input(i)` with the i-th input, or zero after the last input.
+        let inputs_callback = |inputs: Vec<GoldilocksField>| {
+            move |query: &str| -> Result<Option<GoldilocksField>, String> {
+                let index = query
+                    .strip_prefix("Input(")
+                    .and_then(|query| query.strip_suffix(')'))
+                    .ok_or_else(|| format!("Unsupported query: {query}"))?;
+                let index = index.parse::<usize>().map_err(|e| e.to_string())?;
+                Ok(Some(inputs.get(index).copied().unwrap_or_default()))
+            }
+        };
+        let validate = |witness: &[(String, Vec<GoldilocksField>)]| {
+            constraint_residuals(&strict_analyzed, &fixture.constants, witness, &CpuBackend)
+        };
+        let witness_generator = fixture.generator();
+
+        let inputs = [1, 5].map(GoldilocksField::from);
+        let counterexample = witness_generator
+            .reduce_counterexample(&inputs, inputs_callback, validate)
+            .unwrap();
+        assert_eq!(counterexample.inputs, vec![5.into()]);
+        assert_eq!(
+            counterexample.trace,
+            vec![
+                ("main.x".to_string(), vec![5.into(), 0.into()]),
+                ("main.y".to_string(), vec![10.into(), 0.into()]),
+            ]
+        );
+        assert_eq!(counterexample.report.failures.len(), 1);
+        assert_eq!(counterexample.report.failures[0].row, 0);
+
+        let inputs = [1, 2].map(GoldilocksField::from);
+        assert!(witness_generator
+            .reduce_counterexample(&inputs, inputs_callback, validate)
+            .is_none());
+    }
+
+    #[test]
+    fn range_constraints_from_callee() {
+        // The caller can only solve `Z = x + 8 * y` in the calling row once it knows that
+        // x and y have three bits each, which it learns from the range constraints of
+        // `Bits.a` and `Bits.b`.
+        let src = r#"
+            namespace Bits(8);
+                col fixed LATCH = [1]*;
+                col fixed BITS3(i) { i % 8 };
+                col witness a, b;
+                {a} in {BITS3};
+                {b} in {BITS3};
+
+            namespace Main(8);
+                col fixed CALL = [0, 0, 1] + [0]*;
+                col witness x, y;
+                CALL * (x + 8 * y - 29) = 0;
+                (1 - CALL) * x = 0;
+                (1 - CALL) * y = 0;
+                CALL {x, y} in Bits.LATCH {Bits.a, Bits.b};
+        "#;
+        let fixture = Fixture::new(src);
+        let witness = fixture
+            .generator()
+            .generate()
+            .into_iter()
+            .collect::<BTreeMap<_, _>>();
+        let column = |values: [u64; 8]| values.map(GoldilocksField::from).to_vec();
+        assert_eq!(witness["Main.x"], column([0, 0, 5, 0, 0, 0, 0, 0]));
+        assert_eq!(witness["Main.y"], column([0, 0, 3, 0, 0, 0, 0, 0]));
+        assert_eq!(witness["Bits.a"][0], 5.into());
+        assert_eq!(witness["Bits.b"][0], 3.into());
+    }
+
+    #[test]
+    fn agreement_with_fixed_column() {
+        // `x` is initialized from `BOOT` in the first four rows, and counts up afterwards.
+        let src = r#"
+            namespace main(8);
+                col fixed IS_BOOT = [1, 1, 1, 1] + [0]*;
+                col fixed BOOT = [2, 3, 5, 7] + [0]*;
+                col fixed WRONG_BOOT = [2, 3, 5, 8] + [0]*;
+                col witness x;
+                IS_BOOT * (x - BOOT) = 0;
+                (1 - IS_BOOT') * (x' - x - 1) = 0;
+        "#;
+        let fixture = Fixture::new(src);
+        let witness = fixture.generator().generate();
+
+        assert_eq!(
+            verify_agrees_with_fixed(&witness, &fixture.constants, "main.x", "main.BOOT", 0..4),
+            vec![]
+        );
+        let mismatches = verify_agrees_with_fixed(
+            &witness,
+            &fixture.constants,
+            "main.x",
+            "main.WRONG_BOOT",
+            0..4,
+        );
+        assert_eq!(
+            mismatches
+                .iter()
+                .map(|mismatch| mismatch.to_string())
+                .collect::<Vec<_>>(),
+            vec!["main.x (Row 3) is 7, but main.WRONG_BOOT is 8"]
+        );
+    }
+
+    #[test]
+    fn identity_budget() {
+        let src = r#"
+            namespace Add(8);
+                col witness A, B, C;
+                A + B = C;
+
+            namespace Main(8);
+                col fixed a(i) { i + 13 };
+                col fixed b(i) { i * 17 };
+                col witness c;
+                col fixed CALL = [1, 0]*;
+                (1 - CALL) * c = 0;
+                CALL {a, b, c} in {Add.A, Add.B, Add.C};
+        "#;
+        let fixture = Fixture::new(src);
+
+        let witness = fixture
+            .generator()
+            .with_options(WitgenOptions {
+                identity_budget: Some(1000),
+                ..Default::default()
+            })
+            .generate()
+            .into_iter()
+            .collect::<BTreeMap<_, _>>();
+        assert_eq!(witness["Main.c"][2], (15 + 34).into());
+
+        let result = std::panic::catch_unwind(|| {
+            fixture
+                .generator()
+                .with_options(WitgenOptions {
+                    identity_budget: Some(10),
+                    ..Default::default()
+                })
+                .generate()
+        });
+        let message = result.unwrap_err().downcast::<String>().unwrap();
+        assert_eq!(
+            *message,
+            "Identity processing budget exceeded for machine Main Machine"
+        );
+    }
+
+    #[test]
+    fn tie_breaking_seed() {
+        let src = r#"
+            namespace main(8);
+                col fixed FREE = [0, 1]*;
+                col witness a, b;
+                a = 1;
+                (1 - FREE) * (b - a) = 0;
+        "#;
+        let fixture = Fixture::new(src);
+        let run = |seed| {
+            fixture
+                .generator()
+                .with_options(WitgenOptions {
+                    dont_care_columns: vec![("main.b".to_string(), Some("main.FREE".to_string()))],
+                    tie_breaking_seed: Some(seed),
+                    ..Default::default()
+                })
+                .generate()
+        };
+
+        let witness = run(1);
+        assert_eq!(witness, run(1));
+        let other_witness = run(2);
+        assert_ne!(witness, other_witness);
+        for witness in [witness, other_witness] {
+            assert_eq!(witness[0].1, vec![1.into(); 8]);
+            let b = &witness[1].1;
+            // Only the "don't care" cells are chosen, and they are all different.
+            assert!(b.iter().step_by(2).all(|v| *v == 1.into()));
+            assert!(b.iter().skip(1).step_by(2).all_unique());
+        }
+    }
+
+    #[test]
+    fn input_canonicalization() {
+        let src = |operation: &str| {
+            format!(
+                r#"
+            namespace Op(8);
+                col witness A, B, C;
+                {operation};
+
+            namespace Main(8);
+                col fixed a = [3, 5, 4, 4, 0, 0, 0, 0];
+                col fixed b = [5, 3, 4, 4, 0, 0, 0, 0];
+                col witness c;
+                col fixed CALL = [1, 1, 0, 0, 0, 0, 0, 0];
+                (1 - CALL) * c = 0;
+                CALL {{a, b, c}} in {{Op.A, Op.B, Op.C}};
+        "#
+            )
+        };
+        // Sorts the two arguments of the operation.
+        let canonicalization = |_: &str, values: &[Option<GoldilocksField>]| {
+            let mut values = values.to_vec();
+            values[..2].sort();
+            values
+        };
+        let run = |operation: &str, canonicalize: bool| {
+            Fixture::new(&src(operation))
+                .generator()
+                .with_options(WitgenOptions {
+                    input_canonicalization: canonicalize
+                        .then_some(&canonicalization as &dyn InputCanonicalization<_>),
+                    ..Default::default()
+                })
+                .generate()
+                .into_iter()
+                .collect::<BTreeMap<_, _>>()
+        };
+
+        // `add(5, 3)` uses the outputs of `add(3, 5)`, but still gets its own block.
+        let witness = run("A + B = C", true);
+        assert_eq!(witness["Main.c"][..2], [8.into(), 8.into()]);
+        assert_eq!(witness["Op.A"][..2], [3.into(), 5.into()]);
+        assert_eq!(witness["Op.C"][..2], [8.into(), 8.into()]);
+
+        // For a non-commutative operation, the cached outputs are wrong,
+        // which is detected when computing the block.
+        let witness = run("A - B = C", false);
+        assert_eq!(witness["Main.c"][..2], [(-2).into(), 2.into()]);
+        let result = std::panic::catch_unwind(|| run("A - B = C", true));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn block_delegate() {
+        let src = r#"
+            namespace Op(8);
+                col witness A, B, C;
+                A + B = C;
+
+            namespace Main(8);
+                col fixed a = [3, 5, 4, 0, 0, 0, 0, 0];
+                col fixed b = [5, 2, 4, 0, 0, 0, 0, 0];
+                col witness c;
+                col fixed CALL = [1, 1, 1, 0, 0, 0, 0, 0];
+                (1 - CALL) * c = 0;
+                CALL {a, b, c} in {Op.A, Op.B, Op.C};
+        "#;
+        let fixture = Fixture::new(src);
+        let run = |result: fn(GoldilocksField, GoldilocksField) -> GoldilocksField| {
+            let delegate = move |machine: &str, values: &[Option<GoldilocksField>]| {
+                assert_eq!(machine, "Secondary machine 0: Op (BlockMachine)");
+                let (a, b) = (values[0]?, values[1]?);
+                // Leaves the call with equal inputs to the solver.
+                (a != b).then(|| {
+                    vec![
+                        ("Op.A".to_string(), vec![a]),
+                        ("Op.B".to_string(), vec![b]),
+                        ("Op.C".to_string(), vec![result(a, b)]),
+                    ]
+                })
+            };
+            fixture
+                .generator()
+                .with_options(WitgenOptions {
+                    block_delegate: Some(&delegate),
+                    ..Default::default()
+                })
+                .generate()
+                .into_iter()
+                .collect::<BTreeMap<_, _>>()
+        };
+
+        let witness = run(|a, b| a + b);
+        assert_eq!(witness["Main.c"][..3], [8.into(), 7.into(), 8.into()]);
+        assert_eq!(witness["Op.C"][..3], [8.into(), 7.into(), 8.into()]);
+
+        // A wrong block is rejected by the validation.
+        let result = std::panic::catch_unwind(|| run(|a, b| a * b));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn sparsity_of_columns() {
+        let src = r#"
+            namespace main(1024);
+                col fixed F(i) { if i < 24 { i + 1 } else { 0 } };
+                col witness w, v;
+                w = F;
+                v = 1 - w;
+        "#;
+        let fixture = Fixture::new(src);
+        let witness = fixture.generator().generate();
+        let sparsity = column_sparsity(&fixture.analyzed, &witness)
+            .into_iter()
+            .map(|(poly_id, ratio)| (poly_id.id, ratio))
+            .collect::<Vec<_>>();
+        // `v` is only zero in the row where `w` is 1.
+        assert_eq!(sparsity, vec![(0, 1000.0 / 1024.0), (1, 1.0 / 1024.0)]);
+    }
+
+    #[test]
+    fn optional_identities() {
+        let src = r#"
+            namespace main(4);
+                col fixed OPTIONAL = [1]*;
+                col witness x, y, z;
+                x = 2;
+                y = x + 1;
+                // Contradicts the identities above, so it is skipped.
+                OPTIONAL * (y - 5) = 0;
+                // Determines `z`, which is not constrained otherwise.
+                OPTIONAL * (z - 7) = 0;
+        "#;
+        let fixture = Fixture::new(src);
+        let witness = fixture
+            .generator()
+            .generate()
+            .into_iter()
+            .collect::<BTreeMap<_, _>>();
+        assert_eq!(witness["main.y"], vec![3.into(); 4]);
+        assert_eq!(witness["main.z"], vec![7.into(); 4]);
+
+        let result = std::panic::catch_unwind(|| {
+            fixture
+                .generator()
+                .with_options(WitgenOptions {
+                    optional_identity_policy: OptionalIdentityPolicy::Require,
+                    ..Default::default()
+                })
+                .generate()
+        });
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn queries_of_several_columns() {
+        let src = r#"
+            namespace std::prover(8);
+                enum Query {
+                    Input(int),
+                    None,
+                }
+
+            namespace main(8);
+                col witness a(i) query std::prover::Query::Input(i);
+                col witness b(i) query std::prover::Query::Input(i + 8);
+                col witness c(i) query std::prover::Query::Input(i + 16);
+                col witness d(i) query std::prover::Query::Input(i + 24);
+                col witness sum;
+                sum = a + b + c + d;
+        "#;
+        let fixture = Fixture::new(src);
+        let query_callback = |query: &str| -> Result<Option<GoldilocksField>, String> {
+            let index = query
+                .strip_prefix("Input(")
+                .and_then(|query| query.strip_suffix(')'))
+                .ok_or_else(|| format!("Unsupported query: {query}"))?;
+            let index = index.parse::<u64>().map_err(|e| e.to_string())?;
+            Ok(Some((index * index).into()))
+        };
+        let generate_with = |parallel_queries| {
+            WitnessGenerator::new(&fixture.analyzed, &fixture.constants, &query_callback)
+                .with_options(WitgenOptions {
+                    parallel_queries,
+                    ..Default::default()
+                })
+                .generate()
+        };
+        let witness = generate_with(false);
+        assert_eq!(generate_with(true), witness);
+        for (i, (name, values)) in witness.iter().take(4).enumerate() {
+            let expected = (0..8)
+                .map(|row| ((row + 8 * i as u64).pow(2)).into())
+                .collect::<Vec<_>>();
+            assert_eq!(values, &expected, "{name}");
+        }
+        assert_eq!(witness[4].1[1], (1 + 81 + 289 + 625).into());
+    }
+
+    #[test]
+    fn fingerprint() {
+        let src = r#"
+            namespace main(8);
+                col fixed X(i) { i };
+                col witness x, y;
+                x = X;
+                y = x + 1;
+        "#;
+        let fingerprint = pil_fingerprint(&analyze_string::<GoldilocksField>(src));
+        assert_eq!(
+            pil_fingerprint(&analyze_string::<GoldilocksField>(src)),
+            fingerprint
+        );
+        let changed = src.replace("y = x + 1;", "y = x + 2;");
+        assert_ne!(
+            pil_fingerprint(&analyze_string::<GoldilocksField>(&changed)),
+            fingerprint
+        );
+        let resized = src.replace("main(8)", "main(16)");
+        assert_ne!(
+            pil_fingerprint(&analyze_string::<GoldilocksField>(&resized)),
+            fingerprint
+        );
+    }
+
+    #[test]
+    fn move_instructions() {
+        let src = r#"
+            namespace main(8);
+                col fixed X(i) { i + 1 };
+                col fixed MOV_A_B = [0, 1, 0, 0, 1, 0, 0, 0];
+                col fixed MOV_B_A = [0, 0, 1, 0, 0, 0, 1, 0];
+                col witness instr_mov_a_b, instr_mov_b_a, a, b;
+                instr_mov_a_b = MOV_A_B;
+                instr_mov_b_a = MOV_B_A;
+                // The registers are loaded from X unless they are the target of a move.
+                (1 - instr_mov_b_a) * (a - X) = 0;
+                (1 - instr_mov_a_b) * (b - 10 * X) = 0;
+        "#;
+        let fixture = Fixture::new(src);
+        let closure_constraints = [instruction_copies::<GoldilocksField>(&[
+            ("main.instr_mov_a_b", "main.a", "main.b"),
+            ("main.instr_mov_b_a", "main.b", "main.a"),
+        ])];
+        let witness = fixture
+            .generator()
+            .with_options(WitgenOptions {
+                closure_constraints: &closure_constraints,
+                ..Default::default()
+            })
+            .generate()
+            .into_iter()
+            .collect::<BTreeMap<_, _>>();
+        let to_degrees = |column: &str| {
+            witness[column]
+                .iter()
+                .map(|v| v.to_degree())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(to_degrees("main.a"), vec![1, 2, 30, 4, 5, 6, 70, 8]);
+        assert_eq!(to_degrees("main.b"), vec![10, 2, 30, 40, 5, 60, 70, 80]);
+    }
+
+    #[test]
+    fn selector_summary_column() {
+        let src = r#"
+            namespace main(8);
+                col fixed A = [1, 0, 1, 0, 0, 1, 0, 0];
+                col fixed B = [0, 0, 1, 1, 0, 0, 0, 0];
+                col witness instr_a, instr_b, is_active;
+                instr_a = A;
+                instr_b = B;
+        "#;
+        let fixture = Fixture::new(src);
+        let closure_constraints = [selector_summary::<GoldilocksField>(
+            "main.is_active",
+            &["main.instr_a", "main.instr_b"],
+        )];
+        let witness = fixture
+            .generator()
+            .with_options(WitgenOptions {
+                closure_constraints: &closure_constraints,
+                ..Default::default()
+            })
+            .generate()
+            .into_iter()
+            .collect::<BTreeMap<_, _>>();
+        let is_active = witness["main.is_active"]
+            .iter()
+            .map(|v| v.to_degree())
+            .collect::<Vec<_>>();
+        // Both selectors are set in row 2.
+        assert_eq!(is_active, vec![1, 0, 1, 1, 0, 1, 0, 0]);
+    }
+
+    #[test]
+    fn instantiate_template() {
+        let src = r#"
+            namespace main(8);
+                col fixed FIRST = [1] + [0]*;
+                col witness step, x;
+                // `step` is a constant of the template, given in the first row.
+                (1 - FIRST') * (step' - step) = 0;
+                FIRST * (x - step) = 0;
+                (1 - FIRST') * (x' - x - step) = 0;
+        "#;
+        let fixture = Fixture::new(src);
+        let template = fixture
+            .generator()
+            .generate_template(vec![(("main.step".to_string(), 0), "step".to_string())])
+            .unwrap();
+        assert_eq!(
+            template.placeholders,
+            BTreeMap::from([("step".to_string(), vec![("main.step".to_string(), 0)])])
+        );
+
+        let instantiate = |step: u64| {
+            fixture
+                .generator()
+                .instantiate_template(
+                    &template,
+                    &BTreeMap::from([("step".to_string(), step.into())]),
+                )
+                .unwrap()
+                .into_iter()
+                .map(|(name, values)| {
+                    (
+                        name,
+                        values.iter().map(|v| v.to_degree()).collect::<Vec<_>>(),
+                    )
+                })
+                .collect::<BTreeMap<_, _>>()
+        };
+        for step in [3, 5] {
+            let witness = instantiate(step);
+            assert_eq!(witness["main.step"], vec![step; 8]);
+            assert_eq!(
+                witness["main.x"],
+                (1..=8).map(|i| i * step).collect::<Vec<_>>()
+            );
+        }
+
+        let error = fixture
+            .generator()
+            .instantiate_template(&template, &BTreeMap::new())
+            .unwrap_err();
+        assert_eq!(error.to_string(), "No value given for placeholder step.");
+
+        // A placeholder cannot be determined by the constraints.
+        let error = Fixture::new("namespace main(4); col witness a; a = 1;")
+            .generator()
+            .generate_template(vec![(("main.a".to_string(), 2), "a".to_string())])
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Placeholder a (main.a in row 2) is determined to be 1."
+        );
+    }
+
+    const OUTER_ASSIGNMENTS_SRC: &str = r#"
+        constant %N = 4;
+
+        namespace Add(%N);
+            col witness A, B, C;
+            A + B = C;
+
+        namespace Main(%N);
+            col fixed a(i) { i + 13 };
+            col fixed b(i) { i * 17 };
+            col witness c;
+            col fixed CALL = [1, 0]*;
+            (1 - CALL) * c = 0;
+            CALL {a, b, c} in {Add.A, Add.B, Add.C};
+    "#;
+
+    #[derive(Default)]
+    struct RecordOuterAssignments(Mutex<Vec<(PolyID, GoldilocksField, DegreeType)>>);
+
+    impl OuterAssignmentObserver<GoldilocksField> for RecordOuterAssignments {
+        fn on_outer_assignment(
+            &self,
+            poly: PolyID,
+            value: GoldilocksField,
+            row: DegreeType,
+        ) -> Result<(), String> {
+            self.0.lock().unwrap().push((poly, value, row));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn observe_outer_assignments() {
+        let fixture = Fixture::new(OUTER_ASSIGNMENTS_SRC);
+        let record = RecordOuterAssignments::default();
+        let witness = fixture
+            .generator()
+            .with_options(WitgenOptions {
+                outer_assignment_observer: Some(&record),
+                ..Default::default()
+            })
+            .generate();
+        assert_eq!(witness[3].0, "Main.c");
+        let c = PolyID {
+            id: 3,
+            ptype: PolynomialType::Committed,
+        };
+
+        let mut assignments = record.0.into_inner().unwrap();
+        assignments.sort_by_key(|(_, _, row)| *row);
+        assert_eq!(assignments, vec![(c, 13.into(), 0), (c, 49.into(), 2)]);
+    }
+
+    #[test]
+    fn reject_outer_assignment() {
+        let fixture = Fixture::new(OUTER_ASSIGNMENTS_SRC);
+        let reject_large = |_: PolyID, value: GoldilocksField, _: DegreeType| {
+            if value.to_degree() > 20 {
+                Err("too large".to_string())
+            } else {
+                Ok(())
+            }
+        };
+        let path = std::env::temp_dir().join(format!(
+            "powdr_witgen_rejected_outer_assignment_{}.txt",
+            std::process::id()
+        ));
+        let result = std::panic::catch_unwind(|| {
+            fixture
+                .generator()
+                .with_options(WitgenOptions {
+                    outer_assignment_observer: Some(&reject_large),
+                    failure_bundle: Some(&path),
+                    ..Default::default()
+                })
+                .generate()
+        });
+        assert!(result.is_err());
+
+        let bundle = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines = bundle.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "row: 2");
+        assert!(lines
+            .iter()
+            .any(|line| line
+                .contains("Outer assignment Main.c = 49 (Row 2) was rejected: too large")));
+    }
+
+    #[test]
+    fn generate_with_provenance() {
+        let src = r#"
+            constant %N = 4;
+
+            namespace Add(%N);
+                col witness A, B, C;
+                A + B = C;
+
+            namespace Main(%N);
+                col fixed a(i) { i + 13 };
+                col fixed b(i) { i * 17 };
+                col witness c, d;
+                col fixed CALL = [1, 0]*;
+                (1 - CALL) * c = 0;
+                d = c + 1;
+                CALL {a, b, c} in {Add.A, Add.B, Add.C};
+        "#;
+        let fixture = Fixture::new(src);
+        let (witness, provenance) = fixture.generator().generate_with_provenance();
+        assert_eq!(witness, fixture.generator().generate());
+
+        let id = |index: usize| fixture.analyzed.identities[index].id;
+        let provenance_of = |column: &str, row| provenance.get(&(column.to_string(), row)).copied();
+        // In the main machine, `c` is determined by the lookup in calling rows and by
+        // `(1 - CALL) * c = 0` in the others.
+        assert_eq!(
+            provenance_of("Main.c", 0),
+            Some(Provenance::Identity(id(3)))
+        );
+        assert_eq!(
+            provenance_of("Main.c", 1),
+            Some(Provenance::Identity(id(1)))
+        );
+        assert_eq!(
+            provenance_of("Main.d", 3),
+            Some(Provenance::Identity(id(2)))
+        );
+        // In the block machine, the inputs come from the outer query and the output is
+        // solved from `A + B = C`, also in the first block, which is merged with the next one.
+        for row in 0..2 {
+            assert_eq!(provenance_of("Add.A", row), Some(Provenance::OuterQuery));
+            assert_eq!(
+                provenance_of("Add.C", row),
+                Some(Provenance::Identity(id(0)))
+            );
+        }
+        // Unused blocks are filled in after solving.
+        assert_eq!(provenance_of("Add.A", 2), None);
+    }
+}
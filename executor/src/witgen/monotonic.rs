@@ -0,0 +1,61 @@
+use std::fmt::{self, Display};
+
+use powdr_number::{DegreeType, FieldElement};
+
+/// A pair of consecutive rows in which a column that should be monotonically
+/// non-decreasing decreases.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MonotonicityViolation<T> {
+    pub column: String,
+    /// The first row of the pair.
+    pub row: DegreeType,
+    pub value: T,
+    pub next_value: T,
+}
+
+impl<T: FieldElement> MonotonicityViolation<T> {
+    /// Returns a violation if `next_value` is smaller than `value` (compared as integers).
+    pub fn check(column: &str, row: DegreeType, value: T, next_value: T) -> Option<Self> {
+        (next_value.to_integer() < value.to_integer()).then(|| Self {
+            column: column.to_string(),
+            row,
+            value,
+            next_value,
+        })
+    }
+}
+
+impl<T: Display> Display for MonotonicityViolation<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Column {} is not monotonic: it decreases from {} in row {} to {} in row {}.",
+            self.column,
+            self.value,
+            self.row,
+            self.next_value,
+            self.row + 1
+        )
+    }
+}
+
+/// Checks that the values of the given column in a generated witness are
+/// monotonically non-decreasing, reporting the first pair of rows in which they decrease.
+/// Panics if the witness does not contain the column.
+pub fn verify_monotonic<T: FieldElement>(
+    witness: &[(String, Vec<T>)],
+    column: &str,
+) -> Result<(), MonotonicityViolation<T>> {
+    let (_, values) = witness
+        .iter()
+        .find(|(name, _)| name == column)
+        .unwrap_or_else(|| panic!("Column {column} not found in the witness."));
+    values
+        .iter()
+        .zip(values.iter().skip(1))
+        .enumerate()
+        .find_map(|(row, (value, next_value))| {
+            MonotonicityViolation::check(column, row as DegreeType, *value, *next_value)
+        })
+        .map_or(Ok(()), Err)
+}
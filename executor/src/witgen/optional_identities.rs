@@ -0,0 +1,53 @@
+use std::collections::BTreeSet;
+
+use powdr_ast::analyzed::{
+    AlgebraicBinaryOperation, AlgebraicBinaryOperator, AlgebraicExpression as Expression, Analyzed,
+    IdentityKind, PolynomialType,
+};
+use powdr_number::FieldElement;
+
+/// The name of the fixed column that tags optional identities.
+const OPTIONAL_COLUMN: &str = "OPTIONAL";
+
+/// Returns the IDs of the identities tagged as optional in the PIL. An identity is optional
+/// if it is a polynomial identity of the form `OPTIONAL * e = 0`, where `OPTIONAL` is
+/// a fixed column of that name in any namespace (usually `[1]*`).
+pub fn optional_identities<T: FieldElement>(analyzed: &Analyzed<T>) -> BTreeSet<u64> {
+    analyzed
+        .identities
+        .iter()
+        .filter(|identity| identity.kind == IdentityKind::Polynomial)
+        .filter(|identity| {
+            let mut expression = identity.expression_for_poly_id();
+            // `OPTIONAL * e = 0` is represented as `OPTIONAL * e - 0`.
+            if let Expression::BinaryOperation(AlgebraicBinaryOperation {
+                left,
+                op: AlgebraicBinaryOperator::Sub,
+                right,
+            }) = expression
+            {
+                if let Expression::Number(n) = right.as_ref() {
+                    if n.is_zero() {
+                        expression = left;
+                    }
+                }
+            }
+            let Expression::BinaryOperation(AlgebraicBinaryOperation {
+                left,
+                op: AlgebraicBinaryOperator::Mul,
+                ..
+            }) = expression
+            else {
+                return false;
+            };
+            match left.as_ref() {
+                Expression::Reference(poly) => {
+                    poly.poly_id.ptype == PolynomialType::Constant
+                        && poly.name.rsplit('.').next() == Some(OPTIONAL_COLUMN)
+                }
+                _ => false,
+            }
+        })
+        .map(|identity| identity.id)
+        .collect()
+}
@@ -0,0 +1,188 @@
+use std::path::Path;
+
+use powdr_number::{DegreeType, FieldElement};
+
+use super::column_sink::WriteCells;
+use super::{
+    BlockDelegate, BlockPlacement, ClosureConstraint, ConflictPolicy, CpuBackend, FailureInspector,
+    FieldBackend, FixedColumnSource, InputCanonicalization, InputValidator, KeyValueMiss,
+    KeyValueTable, LastRowPolicy, NextRowPolicy, OptionalIdentityPolicy, OuterAssignmentObserver,
+    RowTagger, ScheduleStrategy, Scratchpad,
+};
+
+/// Options that control how the witness is generated, in contrast to [super::FixedData],
+/// which describes the constraint system. They are passed once, with
+/// [super::WitnessGenerator::with_options], and are available to all machines.
+/// Columns are referenced by name.
+#[derive(Clone)]
+pub struct WitgenOptions<'a, T: FieldElement> {
+    /// Records how each cell was determined, which is reported when an identity fails
+    /// and returned by [super::WitnessGenerator::generate_with_provenance].
+    /// This costs additional memory per cell.
+    pub track_provenance: bool,
+    /// Counts the cells determined in each column.
+    pub count_progress: bool,
+    /// Measures the latency of processing each identity.
+    pub measure_identity_latency: bool,
+    /// When VM machines process identities that reference the next row.
+    pub next_row_policy: NextRowPolicy,
+    /// The backend that performs the field arithmetic of evaluating expressions on rows.
+    pub field_backend: &'a dyn FieldBackend<T>,
+    /// Witness columns that are checked to be monotonically non-decreasing during
+    /// generation. Generation fails at the first row in which one of them decreases.
+    /// Only rows of the main machine are checked; see [super::verify_monotonic] for a
+    /// check of the generated witness.
+    pub monotonic_columns: Vec<String>,
+    /// If set, the rows of VM machines are finalized as soon as they are older than the
+    /// given number of rows, so that only a small window of rows is kept in full during
+    /// generation. The window includes the current and the next row, so it must be at
+    /// least 2. Loop detection is disabled for windows that are too small to detect loops.
+    pub row_window: Option<usize>,
+    /// Compacts finalized rows in which all cells are known, so that only their values are
+    /// kept, which saves memory for large machines but costs some time when finalizing.
+    pub compact_finalized_rows: bool,
+    /// Constraints processed in the main machine, in addition to the identities.
+    pub closure_constraints: &'a [ClosureConstraint<'a, T>],
+    /// Bit budgets for groups of witness columns: In identities referencing one of the
+    /// columns of a group, all intermediate sums, evaluated over the integers, have to be
+    /// smaller than `2^bits` in absolute value, otherwise witness generation fails.
+    pub bit_budgets: Vec<(Vec<String>, usize)>,
+    /// If set, the values of fixed columns that are not provided upfront are computed by
+    /// the given source and materialized in windows of the given size. The window size
+    /// should match [WitgenOptions::row_window].
+    pub fixed_column_windows: Option<(&'a dyn FixedColumnSource<T>, usize)>,
+    /// If set, tags the rows of the main machine that satisfy the predicate of the tagger.
+    /// The tagged rows can be retrieved from the tagger after generation.
+    pub row_tagger: Option<&'a RowTagger<'a, T>>,
+    /// If set, the cells of rows are passed to the sink as soon as they are finalized.
+    /// Only has an effect together with [WitgenOptions::row_window].
+    pub column_sink: Option<&'a dyn WriteCells<T>>,
+    /// If set, the blocks of block machines are placed at the indices chosen by the
+    /// strategy, instead of in the order of the calls.
+    pub block_placement: Option<&'a dyn BlockPlacement<T>>,
+    /// If set, the outputs of calls to block machines are cached by their inputs, mapped
+    /// to a canonical form by the given function.
+    pub input_canonicalization: Option<&'a dyn InputCanonicalization<T>>,
+    /// Validators of the values the query callback returns for the given witness columns.
+    /// Rejected values fail witness generation with the cell and the value.
+    pub input_validators: Vec<(String, &'a dyn InputValidator<T>)>,
+    /// If set, observes the values machines assign to cells of their callers while
+    /// processing outer queries, and can reject them.
+    pub outer_assignment_observer: Option<&'a dyn OuterAssignmentObserver<T>>,
+    /// If set, fills the blocks of block machines instead of solving them.
+    pub block_delegate: Option<&'a dyn BlockDelegate<T>>,
+    /// If set and witness generation fails, the errors, the failing row, the inputs and
+    /// the partial trace of the failing machine are written to the file before panicking.
+    pub failure_bundle: Option<&'a Path>,
+    /// If set and witness generation fails in a VM machine, the inspector is called with a
+    /// snapshot of the state of the machine before panicking.
+    pub failure_inspector: Option<&'a dyn FailureInspector<T>>,
+    /// If set, a [super::ConvergenceHeatmap] of how many cells of each witness column
+    /// became known in each fixpoint iteration is written as CSV to the file after generation.
+    pub convergence_heatmap: Option<&'a Path>,
+    /// If set, lookups into fixed columns are resolved against the external table.
+    /// Lookups that are not found in the table are handled according to the [KeyValueMiss].
+    pub key_value_table: Option<(&'a dyn KeyValueTable<T>, KeyValueMiss)>,
+    /// How updates that conflict with the current state of a cell are handled.
+    pub conflict_policy: ConflictPolicy,
+    /// How identities tagged as optional in the PIL (`OPTIONAL * e = 0`, where `OPTIONAL`
+    /// is a fixed column) are handled if they cannot be solved.
+    pub optional_identity_policy: OptionalIdentityPolicy,
+    /// Checks every assignment to a witness column against the global range constraint
+    /// of the column at the time of the assignment (regardless of the [ConflictPolicy]),
+    /// failing with the row and the value on a violation.
+    pub eager_range_checks: bool,
+    /// If set, limits the number of identities each machine processes over the whole
+    /// witness generation, so that a single machine cannot take up an unbounded amount of
+    /// work. Generation fails as soon as a machine exceeds the budget.
+    pub identity_budget: Option<u64>,
+    /// Which row is used as the next row of the last row.
+    pub last_row_policy: LastRowPolicy,
+    /// The order in which VM machines process the identities of a row.
+    pub schedule_strategy: ScheduleStrategy,
+    /// Runs the prover queries of the columns of a row in parallel. The results are
+    /// combined in the order of the columns, so the witness is the same.
+    /// Enabled by default with the `parallel-queries` feature.
+    pub parallel_queries: bool,
+    /// If set, the scratchpad holds the auxiliary state of the run (it is cleared at the
+    /// start of [super::WitnessGenerator::generate]), so that query callbacks can share it
+    /// with closure constraints and the final state can be inspected afterwards.
+    pub scratchpad: Option<&'a Scratchpad>,
+    /// After generation, checks that every call to a block machine that was answered as
+    /// incomplete was completed later on, and panics with a report of the unresolved
+    /// calls otherwise.
+    pub check_pending_calls: bool,
+    /// If set, the time spent in each machine is written as a timeline in the Chrome
+    /// tracing format (see `chrome://tracing`) to the file after generation.
+    pub chrome_trace: Option<&'a Path>,
+    /// Witness columns whose cells are "don't care", with an optional selector (a fixed
+    /// column): Cells that are never written are silently set to
+    /// [WitgenOptions::dont_care_value] instead of being reported, in rows where the
+    /// selector is nonzero. Only applies to VM machines.
+    pub dont_care_columns: Vec<(String, Option<String>)>,
+    /// The value of "don't care" cells that were never written.
+    pub dont_care_value: T,
+    /// If set, the values of "don't care" cells that were never written are chosen
+    /// pseudo-randomly, determined by the seed, instead of using
+    /// [WitgenOptions::dont_care_value]. This makes it possible to check that the witness
+    /// is valid for different choices, while keeping each run reproducible.
+    pub tie_breaking_seed: Option<u64>,
+    /// Copy constraints between pairs of witness cells, given by column name and row.
+    /// Rows with copy-constrained cells are only finalized once all cells they are
+    /// copy-constrained to are known, so the constraints should be local to the row
+    /// window (see [WitgenOptions::row_window]).
+    pub copy_constraints: Vec<((String, DegreeType), (String, DegreeType))>,
+    /// Adds the copy constraints given by the connect identities of the PIL (see
+    /// [super::copy_constraints_from_connect_identities] for the encoding). The same
+    /// restrictions as for [WitgenOptions::copy_constraints] apply.
+    pub copy_constraints_from_pil: bool,
+    /// If set, checks after generation that the witness is compatible with a backend that
+    /// supports constraints up to the given degree (see [super::check_degree_bound]),
+    /// panics otherwise.
+    pub max_constraint_degree: Option<usize>,
+}
+
+impl<'a, T: FieldElement> Default for WitgenOptions<'a, T> {
+    fn default() -> Self {
+        Self {
+            track_provenance: false,
+            count_progress: false,
+            measure_identity_latency: false,
+            next_row_policy: NextRowPolicy::default(),
+            field_backend: &CpuBackend,
+            monotonic_columns: vec![],
+            row_window: None,
+            compact_finalized_rows: false,
+            closure_constraints: &[],
+            bit_budgets: vec![],
+            fixed_column_windows: None,
+            row_tagger: None,
+            column_sink: None,
+            block_placement: None,
+            input_canonicalization: None,
+            input_validators: vec![],
+            outer_assignment_observer: None,
+            block_delegate: None,
+            failure_bundle: None,
+            failure_inspector: None,
+            convergence_heatmap: None,
+            key_value_table: None,
+            conflict_policy: ConflictPolicy::default(),
+            optional_identity_policy: OptionalIdentityPolicy::default(),
+            eager_range_checks: false,
+            identity_budget: None,
+            last_row_policy: LastRowPolicy::default(),
+            schedule_strategy: ScheduleStrategy::default(),
+            parallel_queries: cfg!(feature = "parallel-queries"),
+            scratchpad: None,
+            check_pending_calls: false,
+            chrome_trace: None,
+            dont_care_columns: vec![],
+            dont_care_value: T::zero(),
+            tie_breaking_seed: None,
+            copy_constraints: vec![],
+            copy_constraints_from_pil: false,
+            max_constraint_degree: None,
+        }
+    }
+}
@@ -0,0 +1,30 @@
+use powdr_ast::analyzed::PolyID;
+use powdr_number::DegreeType;
+
+/// Observes the values machines assign to cells of their callers while processing outer
+/// queries (see [super::WitgenOptions::outer_assignment_observer]), e.g. to log them while
+/// debugging. It is implemented for closures with the signature of
+/// [OuterAssignmentObserver::on_outer_assignment].
+pub trait OuterAssignmentObserver<T>: Send + Sync {
+    /// Called for each value a machine assigns to a cell of its caller, with the column,
+    /// the value and the (global) row of the caller, before the assignment is returned.
+    /// Returning an error rejects the assignment and fails the outer query.
+    /// Accepts all assignments by default.
+    fn on_outer_assignment(
+        &self,
+        _poly: PolyID,
+        _value: T,
+        _row: DegreeType,
+    ) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+impl<T, F> OuterAssignmentObserver<T> for F
+where
+    F: Fn(PolyID, T, DegreeType) -> Result<(), String> + Send + Sync,
+{
+    fn on_outer_assignment(&self, poly: PolyID, value: T, row: DegreeType) -> Result<(), String> {
+        self(poly, value, row)
+    }
+}
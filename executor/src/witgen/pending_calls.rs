@@ -0,0 +1,25 @@
+use std::fmt::{self, Display};
+
+use powdr_number::DegreeType;
+
+/// A call to a machine that was answered as incomplete and never completed afterwards,
+/// i.e. the caller finished without the call being resolved.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PendingCall {
+    /// The name of the called machine.
+    pub machine: String,
+    /// The ID of the connecting identity.
+    pub identity_id: u64,
+    /// The (global) row of the caller.
+    pub caller_row: DegreeType,
+}
+
+impl Display for PendingCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: identity {} called from row {}",
+            self.machine, self.identity_id, self.caller_row
+        )
+    }
+}
@@ -0,0 +1,14 @@
+use powdr_ast::analyzed::Analyzed;
+use powdr_number::FieldElement;
+
+/// Returns a fingerprint of the analyzed PIL, i.e. of its degree, column declarations and
+/// definitions and identities, e.g. to check that a stored witness or checkpoint was
+/// generated for the same PIL. It is computed from the printed PIL using FNV-1a, so it
+/// is stable across runs and platforms (unlike the std hashers), but it changes as soon
+/// as a single identity changes.
+pub fn pil_fingerprint<T: FieldElement>(analyzed: &Analyzed<T>) -> u64 {
+    let canonical = format!("degree {:?}\n{analyzed}", analyzed.degree);
+    canonical.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
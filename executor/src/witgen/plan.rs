@@ -0,0 +1,182 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::{self, Display};
+
+use itertools::Itertools;
+use powdr_ast::analyzed::{AlgebraicExpression as Expression, Analyzed, Identity, PolyID};
+use powdr_number::{DegreeType, FieldElement};
+
+use crate::constant_evaluator;
+
+use super::machines::machine_extractor::{split_out_machines, ExtractionOutput};
+use super::{global_constraints, identities_for_stage, FixedData};
+
+/// A description of what witness generation will do for a given PIL,
+/// computed without generating any witness.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WitgenPlan {
+    pub fixed_column_count: usize,
+    pub witness_column_count: usize,
+    /// The main machine first, followed by all secondary machines in the order
+    /// in which they were extracted.
+    pub machines: Vec<MachinePlan>,
+}
+
+/// The parts of a single machine, as detected by the machine extractor.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MachinePlan {
+    /// The name of the machine (which includes the detected machine type).
+    pub name: String,
+    pub degree: DegreeType,
+    /// The names of the witness columns of the machine, sorted.
+    pub witness_columns: Vec<String>,
+    /// The IDs of the identities processed by the machine itself.
+    pub identities: Vec<u64>,
+    /// The IDs of the identities through which the machine is called.
+    pub connecting_identities: Vec<u64>,
+}
+
+impl MachinePlan {
+    pub fn new<T: FieldElement>(
+        name: String,
+        fixed_data: &FixedData<T>,
+        witnesses: &HashSet<PolyID>,
+        identities: &[&Identity<Expression<T>>],
+        connecting_identities: impl Iterator<Item = u64>,
+    ) -> Self {
+        Self {
+            name,
+            degree: fixed_data.degree,
+            witness_columns: witnesses
+                .iter()
+                .map(|poly_id| fixed_data.column_name(poly_id).to_string())
+                .sorted()
+                .collect(),
+            identities: identities.iter().map(|identity| identity.id).collect(),
+            connecting_identities: connecting_identities.collect(),
+        }
+    }
+}
+
+impl WitgenPlan {
+    /// Returns the call graph as a list of (caller, callee, connecting identity ID) tuples.
+    pub fn calls(&self) -> Vec<(&str, &str, u64)> {
+        self.machines
+            .iter()
+            .flat_map(|callee| {
+                callee.connecting_identities.iter().map(move |id| {
+                    let caller = self
+                        .machines
+                        .iter()
+                        .find(|caller| caller.identities.contains(id))
+                        .map(|caller| caller.name.as_str())
+                        .unwrap_or("<unknown>");
+                    (caller, callee.name.as_str(), *id)
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the (globally unique) ID of the identity at position `local_id` among the
+    /// identities processed by the given machine.
+    pub fn global_identity_id(&self, machine: &str, local_id: usize) -> Option<u64> {
+        self.machines
+            .iter()
+            .find(|m| m.name == machine)?
+            .identities
+            .get(local_id)
+            .copied()
+    }
+
+    /// Returns the name of the machine processing the identity with the given ID and the
+    /// position of the identity among the identities of that machine.
+    /// This is the inverse of [WitgenPlan::global_identity_id].
+    pub fn local_identity_id(&self, global_id: u64) -> Option<(&str, usize)> {
+        self.machines.iter().find_map(|machine| {
+            let local_id = machine.identities.iter().position(|id| *id == global_id)?;
+            Some((machine.name.as_str(), local_id))
+        })
+    }
+
+    /// Gathers the values of the public declarations of a complete witness (all machines
+    /// have finished), grouped by the machine owning the referenced column.
+    /// @returns a map from (machine name, column name) to the values of the publics
+    /// referencing that column, in source order.
+    pub fn collect_public_outputs<T: FieldElement>(
+        &self,
+        witness: &[(String, Vec<T>)],
+        pil: &Analyzed<T>,
+    ) -> BTreeMap<(String, String), Vec<T>> {
+        let witness = witness
+            .iter()
+            .map(|(name, col)| (name.as_str(), col))
+            .collect::<BTreeMap<_, _>>();
+        let mut outputs: BTreeMap<_, Vec<T>> = BTreeMap::new();
+        for (_, public_declaration) in pil.public_declarations_in_source_order() {
+            let poly_name = public_declaration.referenced_poly_name();
+            let machine = self
+                .machines
+                .iter()
+                .find(|machine| machine.witness_columns.contains(&poly_name))
+                .map(|machine| machine.name.as_str())
+                .unwrap_or("<unknown>");
+            let value = witness[poly_name.as_str()][public_declaration.index as usize];
+            outputs
+                .entry((machine.to_string(), poly_name))
+                .or_default()
+                .push(value);
+        }
+        outputs
+    }
+}
+
+impl Display for WitgenPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Witgen plan: {} fixed columns, {} witness columns, {} machines",
+            self.fixed_column_count,
+            self.witness_column_count,
+            self.machines.len()
+        )?;
+        for machine in &self.machines {
+            writeln!(f, "  {} (degree {})", machine.name, machine.degree)?;
+            writeln!(f, "    witnesses: {}", machine.witness_columns.join(", "))?;
+            writeln!(f, "    identities: {:?}", machine.identities)?;
+        }
+        writeln!(f, "Calls:")?;
+        for (caller, callee, id) in self.calls() {
+            writeln!(f, "  {caller} -> {callee} (identity {id})")?;
+        }
+        Ok(())
+    }
+}
+
+/// Computes the [WitgenPlan] of the first stage for a given PIL.
+/// This evaluates the fixed columns (which are needed to detect machines),
+/// but does not generate any witness.
+pub fn plan_report<T: FieldElement>(analyzed: &Analyzed<T>) -> WitgenPlan {
+    let fixed_col_values = constant_evaluator::generate(analyzed);
+    let fixed = FixedData::new(analyzed, &fixed_col_values, &[], Default::default(), 0);
+    let identities = identities_for_stage(analyzed, 0);
+    let (fixed, retained_identities) =
+        global_constraints::set_global_constraints(fixed, &identities);
+    let ExtractionOutput {
+        machine_plans,
+        base_identities,
+        base_witnesses,
+        ..
+    } = split_out_machines(&fixed, retained_identities);
+
+    let main_machine = MachinePlan::new(
+        "Main Machine".to_string(),
+        &fixed,
+        &base_witnesses,
+        &base_identities,
+        std::iter::empty(),
+    );
+    WitgenPlan {
+        fixed_column_count: fixed.fixed_cols.len(),
+        witness_column_count: fixed.witness_cols.len(),
+        machines: std::iter::once(main_machine).chain(machine_plans).collect(),
+    }
+}
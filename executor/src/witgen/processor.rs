@@ -1,25 +1,49 @@
-use std::collections::{BTreeMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::ops::Range;
+use std::time::{Duration, Instant};
 
+use itertools::Itertools;
 use powdr_ast::analyzed::PolynomialType;
 use powdr_ast::analyzed::{
-    AlgebraicExpression as Expression, AlgebraicReference, Identity, PolyID,
+    AlgebraicExpression as Expression, AlgebraicReference, Identity, IdentityKind, PolyID,
 };
+use powdr_ast::parsed::visitor::ExpressionVisitable;
 use powdr_number::{DegreeType, FieldElement};
+use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
 
 use crate::witgen::{query_processor::QueryProcessor, util::try_to_simple_poly, Constraint};
 
 use super::{
     affine_expression::AffineExpression,
+    closure_constraint::ClosureConstraint,
+    column_group::ColumnGroup,
     data_structures::{
         column_map::WitnessColumnMap, copy_constraints::CopyConstraints,
         finalizable_data::FinalizableData,
     },
-    identity_processor::IdentityProcessor,
+    debug_snapshot::{DebugSnapshot, OuterQuerySnapshot},
+    expression_evaluator::ExpressionEvaluator,
+    identity_budget::IdentityBudget,
+    identity_processor::{process_polynomial_identity, IdentityProcessor},
+    latency_histogram::LatencyHistogram,
+    log_scope::LogScope,
+    range_constraints::RangeConstraint,
+    residuals::identity_residual,
     rows::{CellValue, Row, RowIndex, RowPair, RowUpdater, UnknownStrategy},
-    Constraints, EvalError, EvalValue, FixedData, IncompleteCause, MutableState, QueryCallback,
+    stall::{stall_cause, StallCause},
+    symbolic_evaluator::SymbolicEvaluator,
+    witness_snapshot::{SerializedRows, SnapshotReader, SnapshotWriter},
+    Constraints, EvalError, EvalResult, EvalValue, FixedData, IdentityFailure, MutableState,
+    QueryCallback,
 };
 
+/// The maximal number of values of a single cell that [Processor::enumerate_assignments]
+/// splits into cases.
+const MAX_CASE_SPLIT_VALUES: u64 = 256;
+
 type Left<'a, T> = Vec<AffineExpression<&'a AlgebraicReference, T>>;
+/// A cell, identified by its global row and column.
+type Cell = (DegreeType, PolyID);
 
 /// Data needed to handle an outer query.
 #[derive(Clone)]
@@ -51,11 +75,129 @@ impl<'a, 'b, T: FieldElement> OuterQuery<'a, 'b, T> {
         }
     }
 
+    /// Returns whether all values on the left side and the left selector are known.
     pub fn is_complete(&self) -> bool {
-        self.left.iter().all(|l| l.is_constant())
+        self.selector_known() && self.left.iter().all(|l| l.is_constant())
+    }
+
+    /// Returns whether the selector on the left side of the connecting identity is known
+    /// in the caller's rows, which is trivially the case if there is no selector.
+    pub fn selector_known(&self) -> bool {
+        match &self.connecting_identity.left.selector {
+            Some(selector) => self
+                .caller_rows
+                .evaluate(selector)
+                .is_ok_and(|selector| selector.is_constant()),
+            None => true,
+        }
+    }
+}
+
+/// Statistics collected by a [Processor] while processing identities.
+#[derive(Clone, Debug, Default)]
+pub struct ProcessorStats {
+    /// For each identity ID, the number of times processing it made progress without
+    /// completing it. Identities with high counts need many passes to converge and
+    /// might benefit from a better solver.
+    pub partial_progress: BTreeMap<u64, usize>,
+    /// The maximal number of rows that were in progress (i.e. not finalized) at the same time.
+    pub peak_rows_in_progress: usize,
+    /// The maximal estimated number of bytes used by the rows at the same time.
+    pub peak_bytes: usize,
+    /// The number of times processing a polynomial identity was skipped, because it did not
+    /// make progress the last time and no cell has been updated since.
+    pub evaluation_cache_hits: usize,
+    /// The number of times a polynomial identity had to be evaluated.
+    pub evaluation_cache_misses: usize,
+    /// For each identity ID, the latencies of processing it, to find rows on which it is
+    /// slow to evaluate. Only measured if enabled in [FixedData].
+    pub identity_latencies: BTreeMap<u64, LatencyHistogram>,
+}
+
+impl ProcessorStats {
+    /// Returns the ID of the identity with the highest maximal processing latency,
+    /// together with that latency.
+    pub fn slowest_identity(&self) -> Option<(u64, Duration)> {
+        self.identity_latencies
+            .iter()
+            .map(|(id, latencies)| (*id, latencies.max()))
+            .max_by_key(|(_, max)| *max)
     }
 }
 
+/// Records how the value of a cell was determined.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Provenance {
+    /// Solved from the identity with the given ID.
+    Identity(u64),
+    /// Answered by a prover query.
+    Query,
+    /// Set from an input of the outer query or a streamed input.
+    Input,
+    /// Determined while processing the outer query.
+    OuterQuery,
+    /// Propagated along a copy constraint.
+    CopyConstraint,
+    /// Set directly by the machine, e.g. a default selector value or an instruction replay.
+    Machine,
+    /// Overridden by [Processor::override_and_check].
+    Override,
+    /// Solved from the closure constraint with the given index.
+    ClosureConstraint(usize),
+}
+
+/// Determines which row is used as the "next" row when processing the last row.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LastRowPolicy {
+    /// The next row is the first row, i.e. the trace wraps around.
+    #[default]
+    Cyclic,
+    /// All witness cells of the next row are zero. Fixed columns still wrap around.
+    Zero,
+    /// Identities that reference the next row must not be processed on the last row.
+    /// Prover queries are processed without a next row, so they must not reference it.
+    Error,
+}
+
+/// Determines how updates that conflict with the current state of a cell are handled.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Updating a known cell or assigning a value outside of the cell's range constraint
+    /// is an error.
+    #[default]
+    Strict,
+    /// Like [ConflictPolicy::Strict], but updates of a known cell that are consistent with
+    /// its value (e.g. assigning the same value again) are ignored.
+    AllowEqual,
+    /// Like [ConflictPolicy::Strict], but an assignment replaces the range constraint of
+    /// the cell, even if the value is not allowed by it.
+    OverrideRangeWithValue,
+}
+
+/// Determines how optional identities (tagged in the PIL, see
+/// [super::optional_identities::optional_identities]) are handled.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OptionalIdentityPolicy {
+    /// Optional identities are processed like all others, but if processing one fails
+    /// (e.g. because it contradicts known values), it is skipped on that row and treated
+    /// as complete, i.e. it neither blocks the row nor results in an error.
+    #[default]
+    Skip,
+    /// Optional identities are required like all others.
+    Require,
+}
+
+/// An identity that does not hold on a row pair after a cell was overridden.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RowCheckFailure<T> {
+    pub identity_id: u64,
+    /// The (global) index of the first row of the row pair.
+    pub row: DegreeType,
+    pub message: String,
+    /// The value of `left - right` for polynomial identities whose cells are all known.
+    pub residual: Option<T>,
+}
+
 pub struct IdentityResult {
     /// Whether any progress was made by processing the identity
     pub progress: bool,
@@ -63,6 +205,14 @@ pub struct IdentityResult {
     pub is_complete: bool,
 }
 
+/// The result of [Processor::process_identities_batched].
+pub struct BatchedIdentityResult {
+    /// Whether any progress was made and whether all identities are complete.
+    pub aggregate: IdentityResult,
+    /// For each identity of the batch, whether it is complete.
+    pub is_complete: Vec<bool>,
+}
+
 /// A basic processor that holds a set of rows and knows how to process identities and queries
 /// on any given row.
 /// The lifetimes mean the following:
@@ -84,11 +234,85 @@ pub struct Processor<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> {
     is_relevant_witness: WitnessColumnMap<bool>,
     /// Relevant witness columns that have a prover query function attached.
     prover_query_witnesses: Vec<PolyID>,
-    /// The outer query, if any. If there is none, processing an outer query will fail.
-    outer_query: Option<OuterQuery<'a, 'c, T>>,
+    /// The outer queries, usually at most one. A machine can be called via several
+    /// connecting identities at once, which then share the inputs and are processed together.
+    /// If there is none, processing an outer query will fail.
+    outer_queries: Vec<OuterQuery<'a, 'c, T>>,
     inputs: Vec<(PolyID, T)>,
     previously_set_inputs: BTreeMap<PolyID, usize>,
-    copy_constraints: CopyConstraints<(PolyID, RowIndex)>,
+    copy_constraints: &'a CopyConstraints<(PolyID, RowIndex)>,
+    /// For each (global) row, the number of its copy-constrained cells that are not known yet.
+    /// Rows are only finalized once this drops to zero.
+    pending_copy_constraints: BTreeMap<DegreeType, usize>,
+    /// Rows (local index) that should have been finalized, but still have pending
+    /// copy constraints.
+    deferred_finalization: BTreeSet<usize>,
+    stats: ProcessorStats,
+    /// For each (global row, column), how its value was determined.
+    /// Only recorded if enabled in [FixedData], to save memory.
+    provenance: Option<BTreeMap<(DegreeType, PolyID), Provenance>>,
+    /// For each (global row, column) solved from an identity or copy constraint, the cells
+    /// that were known at that point and that the value was derived from.
+    /// Recorded together with [Processor::provenance].
+    dependencies: Option<BTreeMap<Cell, Vec<Cell>>>,
+    /// For each column, the number of its cells that were determined.
+    /// Only counted if enabled in [FixedData].
+    progress_counts: Option<BTreeMap<PolyID, usize>>,
+    /// Incremented whenever a cell is updated.
+    data_version: u64,
+    /// For each (row, polynomial identity ID) that did not make progress, the value of
+    /// [Processor::data_version] at that point.
+    evaluation_cache: HashMap<(usize, u64), u64>,
+    /// The next row of the last row, if it is not the first row (see [LastRowPolicy::Zero]).
+    zero_row: Option<Row<'a, T>>,
+    /// The budget of the machine, spent for every processed identity.
+    identity_budget: Option<&'c IdentityBudget>,
+    /// The fixpoint iteration that cells becoming known are recorded for,
+    /// if a convergence heatmap is enabled in [FixedData].
+    convergence_iteration: usize,
+    /// The state overwritten since [Processor::begin_transaction], if a transaction is open.
+    transaction: Option<Transaction<'a, T>>,
+    /// The machine the log messages are prefixed with.
+    log_scope: LogScope<'c>,
+}
+
+/// The state overwritten during a transaction (see [Processor::begin_transaction]),
+/// recorded when a cell is first updated, so that only the touched cells are copied.
+#[derive(Default)]
+struct Transaction<'a, T: FieldElement> {
+    /// The previous values of the updated cells, by local row index, together with their
+    /// provenance and dependencies (if tracked).
+    cells: BTreeMap<(usize, PolyID), RecordedCell<T>>,
+    /// The previous numbers of pending copy constraints of the affected (global) rows.
+    pending_copy_constraints: BTreeMap<DegreeType, Option<usize>>,
+    /// The previous left sides of the outer queries, if any of them was assigned to.
+    outer_query_left: Option<Vec<Left<'a, T>>>,
+}
+
+/// The state of a cell recorded by a [Transaction].
+struct RecordedCell<T: FieldElement> {
+    value: CellValue<T>,
+    provenance: Option<Provenance>,
+    dependencies: Option<Vec<Cell>>,
+}
+
+/// Solves `right = value` for the single column referenced by `right`, e.g. `x` for
+/// `x + 1`, to derive an input of the callee from a right expression of a connecting
+/// identity. Returns None if `right` is not affine in a single column of the current row.
+fn solve_for_input<T: FieldElement>(right: &Expression<T>, value: T) -> Option<(PolyID, T)> {
+    if let Some(poly) = try_to_simple_poly(right) {
+        return Some((poly.poly_id, value));
+    }
+    let right = ExpressionEvaluator::new(SymbolicEvaluator)
+        .evaluate(right)
+        .ok()?;
+    let solution = (right - value.into()).solve().ok()?;
+    match &solution.constraints[..] {
+        [(poly, Constraint::Assignment(value))] if poly.is_witness() && !poly.next => {
+            Some((poly.poly_id, *value))
+        }
+        _ => None,
+    }
 }
 
 impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> Processor<'a, 'b, 'c, T, Q> {
@@ -112,6 +336,39 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> Processor<'a, 'b, 'c, T,
             .map(|(poly_id, _)| poly_id)
             .collect();
 
+        let zero_row = (fixed_data.options.last_row_policy == LastRowPolicy::Zero).then(|| {
+            let mut row = Row::fresh(fixed_data, row_offset);
+            for cell in row.values_mut() {
+                cell.value = CellValue::Known(T::zero());
+            }
+            row
+        });
+
+        let mut pending_copy_constraints = BTreeMap::new();
+        for (poly_id, row) in fixed_data.copy_constraints.cells() {
+            let local_index = row.to_local(&row_offset);
+            if !witness_cols.contains(&poly_id) {
+                continue;
+            }
+            // Guaranteed by `check_copy_constraints_within_machines`.
+            debug_assert!({
+                let (next_poly_id, _) = fixed_data.copy_constraints.next((poly_id, row));
+                next_poly_id.ptype != PolynomialType::Committed
+                    || witness_cols.contains(&next_poly_id)
+            });
+            let is_known = local_index < data.len() && data[local_index][&poly_id].value.is_known();
+            if !is_known {
+                *pending_copy_constraints
+                    .entry(DegreeType::from(row))
+                    .or_default() += 1;
+            }
+        }
+
+        let stats = ProcessorStats {
+            peak_rows_in_progress: data.rows_in_progress(),
+            peak_bytes: data.estimated_bytes(),
+            ..Default::default()
+        };
         Self {
             row_offset,
             data,
@@ -120,11 +377,38 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> Processor<'a, 'b, 'c, T,
             witness_cols,
             is_relevant_witness,
             prover_query_witnesses,
-            outer_query: None,
+            outer_queries: vec![],
             inputs: Vec::new(),
             previously_set_inputs: BTreeMap::new(),
-            // TODO(#1333): Get copy constraints from PIL.
-            copy_constraints: Default::default(),
+            copy_constraints: &fixed_data.copy_constraints,
+            pending_copy_constraints,
+            deferred_finalization: BTreeSet::new(),
+            stats,
+            provenance: fixed_data.options.track_provenance.then(BTreeMap::new),
+            dependencies: fixed_data.options.track_provenance.then(BTreeMap::new),
+            progress_counts: fixed_data.options.count_progress.then(BTreeMap::new),
+            data_version: 0,
+            evaluation_cache: HashMap::new(),
+            zero_row,
+            identity_budget: None,
+            convergence_iteration: 0,
+            transaction: None,
+            log_scope: LogScope::default(),
+        }
+    }
+
+    pub fn with_identity_budget(self, identity_budget: Option<&'c IdentityBudget>) -> Self {
+        Self {
+            identity_budget,
+            ..self
+        }
+    }
+
+    /// Prefixes the log messages of the processor with the name of the given machine.
+    pub fn with_log_scope(self, machine: &'c str) -> Self {
+        Self {
+            log_scope: LogScope::new(machine),
+            ..self
         }
     }
 
@@ -132,197 +416,1131 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> Processor<'a, 'b, 'c, T,
         self,
         outer_query: OuterQuery<'a, 'c, T>,
     ) -> Processor<'a, 'b, 'c, T, Q> {
-        log::trace!("  Extracting inputs:");
+        self.with_outer_queries(vec![outer_query])
+    }
+
+    /// Like [Processor::with_outer_query], but for several outer queries to this machine
+    /// (via different connecting identities) that are answered together. The inputs of all
+    /// of them are extracted, and the outer query is only finished once all are complete.
+    /// All connecting identities must have the same selector on the right side, which
+    /// determines the latch of the block.
+    pub fn with_outer_queries(
+        self,
+        outer_queries: Vec<OuterQuery<'a, 'c, T>>,
+    ) -> Processor<'a, 'b, 'c, T, Q> {
+        assert!(
+            outer_queries
+                .iter()
+                .map(|outer_query| &outer_query.connecting_identity.right.selector)
+                .all_equal(),
+            "Outer queries answered together must have the same latch."
+        );
+        log::trace!("{}  Extracting inputs:", self.log_scope);
         let mut inputs = vec![];
-        for (l, r) in outer_query
-            .left
-            .iter()
-            .zip(&outer_query.connecting_identity.right.expressions)
-        {
-            if let Some(right_poly) = try_to_simple_poly(r).map(|p| p.poly_id) {
+        for outer_query in &outer_queries {
+            for (l, r) in outer_query
+                .left
+                .iter()
+                .zip(&outer_query.connecting_identity.right.expressions)
+            {
                 if let Some(l) = l.constant_value() {
-                    log::trace!("    {} = {}", r, l);
-                    inputs.push((right_poly, l));
+                    if let Some(input) = solve_for_input(r, l) {
+                        log::trace!("{}    {r} = {l}", self.log_scope);
+                        inputs.push(input);
+                    }
                 }
             }
         }
         Processor {
-            outer_query: Some(outer_query),
+            outer_queries,
             inputs,
             ..self
         }
     }
 
+    /// Sets the fixpoint iteration (counted from the start of the current fixpoint loop)
+    /// for the convergence heatmap.
+    pub fn set_convergence_iteration(&mut self, iteration: usize) {
+        self.convergence_iteration = iteration;
+    }
+
+    /// Starts recording the updates to the cells, so that they can be undone with
+    /// [Processor::rollback], e.g. to try a candidate assignment (see
+    /// [Processor::enumerate_assignments]).
+    /// Rows must not be replaced or finalized while the transaction is open,
+    /// and statistics like [Processor::progress_stats] are not reverted.
+    pub fn begin_transaction(&mut self) {
+        assert!(
+            self.transaction.is_none(),
+            "Nested transactions are not supported."
+        );
+        self.transaction = Some(Transaction::default());
+    }
+
+    /// Ends the current transaction and restores all cells updated since it started
+    /// to their previous values.
+    pub fn rollback(&mut self) {
+        let transaction = self.transaction.take().expect("No open transaction.");
+        for ((row_index, poly_id), recorded) in transaction.cells {
+            let row = DegreeType::from(self.global_index(row_index));
+            if let Some(provenance) = &mut self.provenance {
+                match recorded.provenance {
+                    Some(previous) => provenance.insert((row, poly_id), previous),
+                    None => provenance.remove(&(row, poly_id)),
+                };
+            }
+            if let Some(dependencies) = &mut self.dependencies {
+                match recorded.dependencies {
+                    Some(previous) => dependencies.insert((row, poly_id), previous),
+                    None => dependencies.remove(&(row, poly_id)),
+                };
+            }
+            self.data[row_index][&poly_id].value = recorded.value;
+        }
+        if let Some(outer_query_left) = transaction.outer_query_left {
+            for (outer_query, left) in self.outer_queries.iter_mut().zip(outer_query_left) {
+                outer_query.left = left;
+            }
+        }
+        for (row, pending) in transaction.pending_copy_constraints {
+            match pending {
+                Some(pending) => self.pending_copy_constraints.insert(row, pending),
+                None => self.pending_copy_constraints.remove(&row),
+            };
+        }
+        self.data_version += 1;
+    }
+
+    /// Records the current value of a cell in the open transaction, if any,
+    /// unless it was already recorded.
+    fn record_for_transaction(&mut self, row_index: usize, poly_id: PolyID) {
+        if self.transaction.is_none() {
+            return;
+        }
+        let row = DegreeType::from(self.global_index(row_index));
+        if let Some(transaction) = &mut self.transaction {
+            transaction
+                .cells
+                .entry((row_index, poly_id))
+                .or_insert_with(|| RecordedCell {
+                    value: self.data[row_index][&poly_id].value.clone(),
+                    provenance: self
+                        .provenance
+                        .as_ref()
+                        .and_then(|provenance| provenance.get(&(row, poly_id)).copied()),
+                    dependencies: self
+                        .dependencies
+                        .as_ref()
+                        .and_then(|dependencies| dependencies.get(&(row, poly_id)).cloned()),
+                });
+        }
+    }
+
+    /// Records the left sides of the outer queries in the open transaction, if any,
+    /// unless they were already recorded.
+    fn record_outer_queries_for_transaction(&mut self) {
+        if let Some(transaction) = &mut self.transaction {
+            transaction.outer_query_left.get_or_insert_with(|| {
+                self.outer_queries
+                    .iter()
+                    .map(|outer_query| outer_query.left.clone())
+                    .collect()
+            });
+        }
+    }
+
     pub fn finished_outer_query(&self) -> bool {
-        self.outer_query
-            .as_ref()
-            .map(|outer_query| outer_query.is_complete())
-            .unwrap_or(true)
+        self.outer_queries
+            .iter()
+            .all(|outer_query| outer_query.is_complete())
+    }
+
+    /// Returns the rows, together with the provenance of their cells (if tracked).
+    pub fn finish(mut self) -> FinalizableData<'a, T> {
+        if let Some(provenance) = self.provenance.take() {
+            let degree = self.fixed_data.degree;
+            let row_offset = DegreeType::from(self.row_offset);
+            for ((row, poly_id), provenance) in provenance {
+                // The first local row of a global row; it appears again every `degree` rows.
+                let first = ((row + degree - row_offset) % degree) as usize;
+                for i in (first..self.data.len()).step_by(degree as usize) {
+                    self.data.set_provenance(i, poly_id, provenance);
+                }
+            }
+        }
+        self.data
+    }
+
+    pub fn data(&self) -> &FinalizableData<'a, T> {
+        &self.data
+    }
+
+    /// Serializes the rows and the row offset, e.g. to continue after a crash with
+    /// [Processor::restore_from_snapshot].
+    /// Only the rows are part of the snapshot, not the rest of the state of the processor,
+    /// i.e. its outer queries, provenance, statistics and open transactions. Processing
+    /// continues as before if the snapshot is restored into a processor that was
+    /// constructed in the same way and has no provenance or open transaction.
+    pub fn witness_snapshot(&self) -> SerializedRows {
+        let mut writer = SnapshotWriter::default();
+        writer.write_u64(self.row_offset.into());
+        writer.write_u64(self.fixed_data.witness_cols.len() as u64);
+        self.data.write_snapshot(&mut writer);
+        writer.finish()
+    }
+
+    /// Replaces the rows and the row offset by the ones of a snapshot taken with
+    /// [Processor::witness_snapshot] by a processor of the same machine.
+    pub fn restore_from_snapshot(&mut self, snapshot: &SerializedRows) -> Result<(), String> {
+        let mut reader = SnapshotReader::new(snapshot);
+        let row_offset = reader.read_u64()?;
+        let witness_count = reader.read_u64()?;
+        if witness_count != self.fixed_data.witness_cols.len() as u64 {
+            return Err(format!(
+                "Witness snapshot has {witness_count} witness columns, but there are {}.",
+                self.fixed_data.witness_cols.len()
+            ));
+        }
+        let data = FinalizableData::read_snapshot(&mut reader, self.fixed_data)?;
+        reader.finish()?;
+        self.row_offset = RowIndex::from_degree(row_offset, self.fixed_data.degree);
+        self.data = data;
+        self.data_version += 1;
+        self.evaluation_cache.clear();
+        Ok(())
+    }
+
+    /// Returns a read-only copy of the current state, for inspection while debugging.
+    pub fn debug_snapshot(&self) -> DebugSnapshot<T> {
+        DebugSnapshot {
+            row_offset: DegreeType::from(self.row_offset),
+            columns: self
+                .data
+                .known_columns()
+                .map(|(poly_id, values)| {
+                    (self.fixed_data.column_name(&poly_id).to_string(), values)
+                })
+                .collect(),
+            outer_queries: self
+                .outer_queries
+                .iter()
+                .map(|outer_query| OuterQuerySnapshot {
+                    identity: outer_query.connecting_identity.to_string(),
+                    left: outer_query
+                        .left
+                        .iter()
+                        .map(|l| l.constant_value())
+                        .collect(),
+                })
+                .collect(),
+            inputs: self
+                .inputs
+                .iter()
+                .map(|(poly_id, value)| (self.fixed_data.column_name(poly_id).to_string(), *value))
+                .collect(),
+        }
     }
 
-    pub fn finish(self) -> FinalizableData<'a, T> {
+    /// Returns the cells of the witness columns of this machine that are still unknown,
+    /// e.g. to diagnose incomplete witness generation. Cells that are only range-constrained
+    /// are included if `include_range_constrained` is set.
+    pub fn unsolved_cells(&self, include_range_constrained: bool) -> Vec<(PolyID, RowIndex)> {
         self.data
+            .unknown_cells(include_range_constrained)
+            .filter(|(_, poly_id)| self.is_relevant_witness[poly_id])
+            .map(|(i, poly_id)| (poly_id, self.global_index(i)))
+            .collect()
+    }
+
+    /// Returns the fraction of the cells of the witness columns of this machine that are
+    /// known, e.g. to log the progress of long runs. It is 1 if there are no such cells.
+    pub fn column_solved_ratio(&self) -> f64 {
+        let relevant_columns = self.is_relevant_witness.values().filter(|r| **r).count();
+        let total = self.data.len() * relevant_columns;
+        if total == 0 {
+            return 1.0;
+        }
+        let unknown = self
+            .data
+            .unknown_cells(true)
+            .filter(|(_, poly_id)| self.is_relevant_witness[poly_id])
+            .count();
+        (total - unknown) as f64 / total as f64
+    }
+
+    /// Returns, for each column, the number of its cells that were determined so far,
+    /// e.g. to find the identities that do most of the work. Columns without any
+    /// determined cells are omitted. Empty if progress counting is disabled.
+    pub fn progress_stats(&self) -> BTreeMap<PolyID, usize> {
+        self.progress_counts.clone().unwrap_or_default()
+    }
+
+    pub fn stats(&self) -> &ProcessorStats {
+        &self.stats
+    }
+
+    /// Returns how the value (or range constraint) of a given cell was determined,
+    /// if provenance tracking is enabled and the cell is known or range-constrained.
+    pub fn provenance(&self, row_index: usize, poly_id: &PolyID) -> Option<Provenance> {
+        let row = DegreeType::from(self.global_index(row_index));
+        self.provenance.as_ref()?.get(&(row, *poly_id)).copied()
+    }
+
+    /// Returns all cells the value of the given cell was (transitively) derived from.
+    /// The set is empty if provenance tracking is disabled or if the value did not depend
+    /// on other cells, e.g. because it was provided by a query.
+    pub fn cell_dependencies(
+        &self,
+        row_index: usize,
+        poly_id: &PolyID,
+    ) -> HashSet<(PolyID, RowIndex)> {
+        let Some(dependencies) = &self.dependencies else {
+            return HashSet::new();
+        };
+        let mut result = HashSet::new();
+        let mut queue = vec![(DegreeType::from(self.global_index(row_index)), *poly_id)];
+        while let Some(cell) = queue.pop() {
+            for &(row, poly_id) in dependencies.get(&cell).into_iter().flatten() {
+                let row_index = RowIndex::from_degree(row, self.fixed_data.degree);
+                if result.insert((poly_id, row_index)) {
+                    queue.push((row, poly_id));
+                }
+            }
+        }
+        result
+    }
+
+    /// Returns the input cells that the value of the given cell was (transitively) derived
+    /// from, sorted. Input cells are cells whose values were not solved from identities,
+    /// e.g. answered by a query, set from an input or the outer query, or provided
+    /// externally. Inputs that are not on any path to the given cell are excluded.
+    /// The list is empty if provenance tracking is disabled.
+    pub fn minimal_inputs_for(
+        &self,
+        row_index: usize,
+        poly_id: &PolyID,
+    ) -> Vec<(PolyID, RowIndex)> {
+        let Some(provenance) = &self.provenance else {
+            return vec![];
+        };
+        self.cell_dependencies(row_index, poly_id)
+            .into_iter()
+            .filter(|(poly_id, row)| {
+                matches!(
+                    provenance.get(&(DegreeType::from(*row), *poly_id)),
+                    None | Some(
+                        Provenance::Query
+                            | Provenance::Input
+                            | Provenance::OuterQuery
+                            | Provenance::Override
+                    )
+                )
+            })
+            .sorted()
+            .collect()
+    }
+
+    /// Groups the cells whose provenance is known into levels by the order in which they
+    /// can be determined: Level 0 contains the cells that were not derived from other
+    /// cells (e.g. inputs), level k the cells derived from cells of lower levels, at least
+    /// one of them on level k - 1. The cells of a level can be solved in parallel.
+    /// The list is empty if provenance tracking is disabled.
+    pub fn fill_levels(&self) -> Vec<Vec<(PolyID, RowIndex)>> {
+        let (Some(provenance), Some(dependencies)) = (&self.provenance, &self.dependencies) else {
+            return vec![];
+        };
+        let mut levels: BTreeMap<Cell, usize> = BTreeMap::new();
+        for &cell in provenance.keys() {
+            let mut stack = vec![cell];
+            while let Some(&cell) = stack.last() {
+                if levels.contains_key(&cell) {
+                    stack.pop();
+                    continue;
+                }
+                let cell_dependencies = dependencies.get(&cell).map_or(&[][..], |d| &d[..]);
+                let pending = cell_dependencies
+                    .iter()
+                    .filter(|d| !levels.contains_key(d))
+                    .copied()
+                    .collect::<Vec<_>>();
+                if pending.is_empty() {
+                    let level = cell_dependencies
+                        .iter()
+                        .map(|d| levels[d] + 1)
+                        .max()
+                        .unwrap_or(0);
+                    levels.insert(cell, level);
+                    stack.pop();
+                } else {
+                    stack.extend(pending);
+                }
+            }
+        }
+        let mut result = vec![vec![]; levels.values().max().map_or(0, |max| max + 1)];
+        for ((row, poly_id), level) in levels {
+            result[level].push((poly_id, RowIndex::from_degree(row, self.fixed_data.degree)));
+        }
+        for level in &mut result {
+            level.sort();
+        }
+        result
+    }
+
+    /// Renders how each known cell of the given row was determined, together with the
+    /// inputs needed to determine it, or returns None if provenance tracking is disabled.
+    fn render_provenance(&self, row_index: usize) -> Option<String> {
+        self.provenance.as_ref()?;
+        Some(
+            self.witness_cols
+                .iter()
+                .sorted()
+                .filter_map(|poly_id| {
+                    self.provenance(row_index, poly_id).map(|provenance| {
+                        let dependency_count = self.cell_dependencies(row_index, poly_id).len();
+                        let inputs = self
+                            .minimal_inputs_for(row_index, poly_id)
+                            .into_iter()
+                            .map(|(poly_id, row)| {
+                                format!("{} (row {row})", self.fixed_data.column_name(&poly_id))
+                            })
+                            .collect::<Vec<_>>();
+                        let inputs = match inputs.is_empty() {
+                            true => String::new(),
+                            false => format!(", inputs: {}", inputs.join(", ")),
+                        };
+                        format!(
+                            "    {}: {provenance:?} (derived from {dependency_count} cells{inputs})",
+                            self.fixed_data.column_name(poly_id)
+                        )
+                    })
+                })
+                .join("\n"),
+        )
+    }
+
+    /// Returns the witness cells of this machine referenced by the identity that are
+    /// known in the given row pair, or None if provenance tracking is disabled.
+    fn known_cells_of_identity(
+        &self,
+        row_pair: &RowPair<'_, 'a, T>,
+        identity: &'a Identity<Expression<T>>,
+    ) -> Option<Vec<Cell>> {
+        self.dependencies.as_ref()?;
+        let mut cells = Vec::new();
+        identity.pre_visit_expressions(&mut |expr| {
+            if let Expression::Reference(poly) = expr {
+                if self.witness_cols.contains(&poly.poly_id) && row_pair.get_value(poly).is_some() {
+                    let row = DegreeType::from(row_pair.current_row_index + poly.next as usize);
+                    cells.push((row, poly.poly_id));
+                }
+            }
+        });
+        cells.sort();
+        cells.dedup();
+        Some(cells)
+    }
+
+    /// Records that the cells assigned by the updates were derived from the given cells.
+    fn record_dependencies(
+        &mut self,
+        row_index: usize,
+        updates: &EvalValue<&'a AlgebraicReference, T>,
+        inputs: Vec<Cell>,
+    ) {
+        if self.dependencies.is_none() {
+            return;
+        }
+        for (poly, c) in &updates.constraints {
+            if let Constraint::Assignment(_) = c {
+                if self.witness_cols.contains(&poly.poly_id) {
+                    let row = DegreeType::from(self.global_index(row_index + poly.next as usize));
+                    let dependencies = self.dependencies.as_mut().unwrap();
+                    dependencies.insert((row, poly.poly_id), inputs.clone());
+                }
+            }
+        }
     }
 
     pub fn latch_value(&self, row_index: usize) -> Option<bool> {
+        self.latch()
+            .and_then(|latch| self.evaluate_latch(latch, row_index))
+    }
+
+    /// Like [Processor::latch_value], but for all rows of the given range of local row
+    /// indices, e.g. to locate block boundaries.
+    pub fn latch_values(&self, rows: Range<usize>) -> Vec<Option<bool>> {
+        let Some(latch) = self.latch() else {
+            return vec![None; rows.len()];
+        };
+        rows.map(|row_index| self.evaluate_latch(latch, row_index))
+            .collect()
+    }
+
+    /// The selector of the right side of the connecting identities of the outer queries,
+    /// which is the same for all of them (see [Processor::with_outer_queries]).
+    fn latch(&self) -> Option<&'a Expression<T>> {
+        self.outer_queries
+            .first()
+            .and_then(|outer_query| outer_query.connecting_identity.right.selector.as_ref())
+    }
+
+    fn evaluate_latch(&self, latch: &Expression<T>, row_index: usize) -> Option<bool> {
         let row_pair = RowPair::from_single_row(
             &self.data[row_index],
-            self.row_offset + row_index as u64,
+            self.global_index(row_index),
             self.fixed_data,
             UnknownStrategy::Unknown,
         );
-        self.outer_query
-            .as_ref()
-            .and_then(|outer_query| outer_query.connecting_identity.right.selector.as_ref())
-            .and_then(|latch| row_pair.evaluate(latch).ok())
+        row_pair
+            .evaluate(latch)
+            .ok()
             .and_then(|l| l.constant_value())
             .map(|l| l.is_one())
     }
 
+    /// Runs the prover queries of the columns of the row, in parallel if enabled in the
+    /// [super::WitgenOptions]. Once a query fails, no further queries are started, but in
+    /// parallel, the error reported is not necessarily the one of the first failing column.
     pub fn process_queries(&mut self, row_index: usize) -> Result<bool, EvalError<T>> {
-        let mut query_processor =
-            QueryProcessor::new(self.fixed_data, self.mutable_state.query_callback);
-        let global_row_index = self.row_offset + row_index as u64;
-        let row_pair = RowPair::new(
-            &self.data[row_index],
-            &self.data[row_index + 1],
-            global_row_index,
+        let fixed_data = self.fixed_data;
+        let query_callback = &*self.mutable_state.query_callback;
+        let row_pair = Self::row_pair(
+            &self.data,
+            self.zero_row.as_ref(),
             self.fixed_data,
+            self.global_index(row_index),
+            row_index,
             UnknownStrategy::Unknown,
         );
+        let process_query = |poly_id| {
+            QueryProcessor::new(fixed_data, query_callback)
+                .process_query(&row_pair, poly_id)
+                .transpose()
+        };
+        let results = match fixed_data.options.parallel_queries {
+            true => self
+                .prover_query_witnesses
+                .par_iter()
+                .map(process_query)
+                .collect::<Result<Vec<_>, _>>(),
+            false => self
+                .prover_query_witnesses
+                .iter()
+                .map(process_query)
+                .collect::<Result<Vec<_>, _>>(),
+        }?;
         let mut updates = EvalValue::complete(vec![]);
-        for poly_id in &self.prover_query_witnesses {
-            if let Some(r) = query_processor.process_query(&row_pair, poly_id) {
-                updates.combine(r?);
-            }
+        for r in results.into_iter().flatten() {
+            updates.combine(r);
         }
-        Ok(self.apply_updates(row_index, &updates, || "queries".to_string()))
+        self.apply_updates(row_index, &updates, Provenance::Query, || {
+            "queries".to_string()
+        })
     }
 
-    /// Given a row and identity index, computes any updates and applies them.
-    /// @returns the `IdentityResult`.
-    pub fn process_identity(
+    /// Evaluates a closure constraint on the given row and applies the updates,
+    /// in the same way as [Processor::process_identity].
+    pub fn process_closure_constraint(
         &mut self,
         row_index: usize,
-        identity: &'a Identity<Expression<T>>,
+        index: usize,
+        constraint: &ClosureConstraint<'_, T>,
         unknown_strategy: UnknownStrategy,
     ) -> Result<IdentityResult, EvalError<T>> {
-        // Create row pair
-        let global_row_index = self.row_offset + row_index as u64;
         let row_pair = RowPair::new(
             &self.data[row_index],
             &self.data[row_index + 1],
-            global_row_index,
+            self.global_index(row_index),
             self.fixed_data,
             unknown_strategy,
         );
-
-        // Compute updates
-        let mut identity_processor = IdentityProcessor::new(self.fixed_data, self.mutable_state);
-        let updates = identity_processor
-            .process_identity(identity, &row_pair)
+        let updates = constraint
+            .evaluate(&row_pair, self.mutable_state.scratchpad)
             .map_err(|e| -> EvalError<T> {
-                let mut error = format!(
-                    r"Error in identity: {identity}
-Known values in current row (local: {row_index}, global {global_row_index}):
-{}
-",
-                    self.data[row_index].render_values(false, Some(self.witness_cols))
-                );
-                if identity.contains_next_ref() {
-                    error += &format!(
-                        "Known values in next row (local: {}, global {}):\n{}\n",
-                        row_index + 1,
-                        global_row_index + 1,
-                        self.data[row_index + 1].render_values(false, Some(self.witness_cols))
-                    );
-                }
-                error += &format!("   => Error: {e}");
-                error.into()
+                format!("Error in closure constraint {}: {e}", constraint.name).into()
             })?;
 
-        if unknown_strategy == UnknownStrategy::Zero {
-            assert!(updates.constraints.is_empty());
-            assert!(!updates.side_effect);
+        if unknown_strategy != UnknownStrategy::Unknown {
             return Ok(IdentityResult {
                 progress: false,
                 is_complete: false,
             });
         }
 
+        let progress = self.apply_updates(
+            row_index,
+            &updates,
+            Provenance::ClosureConstraint(index),
+            || constraint.name.clone(),
+        )? || updates.side_effect;
         Ok(IdentityResult {
-            progress: self.apply_updates(row_index, &updates, || identity.to_string())
-                || updates.side_effect,
+            progress,
             is_complete: updates.is_complete(),
         })
     }
 
-    pub fn process_outer_query(
+    /// Returns the global row index of the given local row index.
+    ///
+    /// # Panics
+    /// In debug builds, panics if the global row index overflows or is beyond the next row
+    /// of the last row (see [RowIndex::is_within_degree]), instead of silently wrapping
+    /// around to a different row.
+    fn global_index(&self, row_index: usize) -> RowIndex {
+        debug_assert!(
+            self.row_offset
+                .checked_add(row_index)
+                .is_some_and(|row| row.is_within_degree()),
+            "Local row {row_index} with row offset {} is out of bounds for degree {}.",
+            DegreeType::from(self.row_offset),
+            self.fixed_data.degree
+        );
+        self.row_offset + row_index
+    }
+
+    /// Given a row and identity index, computes any updates and applies them.
+    /// @returns the `IdentityResult`.
+    pub fn process_identity(
         &mut self,
         row_index: usize,
-    ) -> Result<(bool, Constraints<&'a AlgebraicReference, T>), EvalError<T>> {
-        let mut progress = false;
-        let right = &self.outer_query.as_ref().unwrap().connecting_identity.right;
-        if let Some(selector) = right.selector.as_ref() {
-            progress |= self
-                .set_value(row_index, selector, T::one(), || {
-                    "Set selector to 1".to_string()
-                })
-                .unwrap_or(false);
+        identity: &'a Identity<Expression<T>>,
+        unknown_strategy: UnknownStrategy,
+    ) -> Result<IdentityResult, EvalError<T>> {
+        if !self.fixed_data.options.measure_identity_latency {
+            return self.process_identity_inner(row_index, identity, unknown_strategy);
         }
+        let start = Instant::now();
+        let result = self.process_identity_inner(row_index, identity, unknown_strategy);
+        self.stats
+            .identity_latencies
+            .entry(identity.id)
+            .or_default()
+            .record(start.elapsed());
+        result
+    }
 
-        let outer_query = self
-            .outer_query
-            .as_ref()
-            .expect("Asked to process outer query, but it was not set!");
+    fn process_identity_inner(
+        &mut self,
+        row_index: usize,
+        identity: &'a Identity<Expression<T>>,
+        unknown_strategy: UnknownStrategy,
+    ) -> Result<IdentityResult, EvalError<T>> {
+        if let Some(identity_budget) = self.identity_budget {
+            identity_budget.spend(1)?;
+        }
+        if self.is_cached(row_index, identity, unknown_strategy) {
+            return Ok(IdentityResult {
+                progress: false,
+                is_complete: false,
+            });
+        }
+        let data_version = self.data_version;
 
-        let row_pair = RowPair::new(
-            &self.data[row_index],
-            &self.data[row_index + 1],
-            self.row_offset + row_index as u64,
+        // Create row pair
+        self.check_next_row_reference(row_index, identity)?;
+        let row_pair = Self::row_pair(
+            &self.data,
+            self.zero_row.as_ref(),
             self.fixed_data,
-            UnknownStrategy::Unknown,
+            self.global_index(row_index),
+            row_index,
+            unknown_strategy,
         );
 
+        let known_cells = self.known_cells_of_identity(&row_pair, identity);
+
+        // Compute updates
         let mut identity_processor = IdentityProcessor::new(self.fixed_data, self.mutable_state);
-        let updates = identity_processor
-            .process_link(outer_query, &row_pair)
-            .map_err(|e| {
-                log::warn!("Error in outer query: {e}");
-                log::warn!("Some of the following entries could not be matched:");
-                for (l, r) in outer_query.left.iter().zip(right.expressions.iter()) {
-                    if let Ok(r) = row_pair.evaluate(r) {
-                        log::warn!("  => {} = {}", l, r);
-                    }
-                }
-                e
-            })?;
+        let result = identity_processor.process_identity(identity, &row_pair);
+        let Some(updates) = self.identity_updates(row_index, identity, result)? else {
+            return Ok(IdentityResult {
+                progress: false,
+                is_complete: true,
+            });
+        };
 
-        progress |= self.apply_updates(row_index, &updates, || "outer query".to_string());
+        if unknown_strategy != UnknownStrategy::Unknown {
+            assert!(updates.constraints.is_empty());
+            assert!(!updates.side_effect);
+            return Ok(IdentityResult {
+                progress: false,
+                is_complete: false,
+            });
+        }
 
-        let outer_assignments = updates
-            .constraints
-            .into_iter()
-            .filter(|(poly, update)| match update {
-                Constraint::Assignment(_) => !self.is_relevant_witness[&poly.poly_id],
-                // Range constraints are currently not communicated between callee and caller.
-                Constraint::RangeConstraint(_) => false,
-            })
-            .collect::<Vec<_>>();
+        let progress = self.apply_updates(
+            row_index,
+            &updates,
+            Provenance::Identity(identity.id),
+            || identity.to_string(),
+        )? || updates.side_effect;
+        let is_complete = self.record_processed_identity(
+            row_index,
+            identity,
+            &updates,
+            known_cells,
+            progress,
+            data_version,
+        )?;
 
-        Ok((progress, outer_assignments))
+        Ok(IdentityResult {
+            progress,
+            is_complete,
+        })
     }
 
-    /// Sets the inputs to the values given in [VmProcessor::inputs] if they are not already set.
-    /// Typically, inputs will have a constraint of the form: `((1 - instr__reset) * (_input' - _input)) = 0;`
-    /// So, once the value of `_input` is set, this function will do nothing until the next reset instruction.
-    /// However, if `_input` does become unconstrained, we need to undo all changes we've done so far.
-    /// For this reason, we keep track of all changes we've done to inputs in [Processor::previously_set_inputs].
-    pub fn set_inputs_if_unset(&mut self, row_index: usize) -> bool {
-        let mut input_updates = EvalValue::complete(vec![]);
-        for (poly_id, value) in self.inputs.iter() {
-            match &self.data[row_index][poly_id].value {
-                CellValue::Known(_) => {}
+    /// Polynomial identities do not make progress if they did not last time
+    /// and nothing has changed since, so the result can be cached.
+    fn is_cacheable(identity: &Identity<Expression<T>>, unknown_strategy: UnknownStrategy) -> bool {
+        unknown_strategy == UnknownStrategy::Unknown && identity.kind == IdentityKind::Polynomial
+    }
+
+    /// Returns whether processing the identity on the given row is known not to make
+    /// progress (see [Processor::is_cacheable]).
+    fn is_cached(
+        &mut self,
+        row_index: usize,
+        identity: &Identity<Expression<T>>,
+        unknown_strategy: UnknownStrategy,
+    ) -> bool {
+        if !Self::is_cacheable(identity, unknown_strategy) {
+            return false;
+        }
+        let is_cached =
+            self.evaluation_cache.get(&(row_index, identity.id)) == Some(&self.data_version);
+        match is_cached {
+            true => self.stats.evaluation_cache_hits += 1,
+            false => self.stats.evaluation_cache_misses += 1,
+        }
+        is_cached
+    }
+
+    /// Returns the updates of processing the identity on the given row, or None if it
+    /// failed, but the identity is optional and can be skipped.
+    fn identity_updates(
+        &self,
+        row_index: usize,
+        identity: &'a Identity<Expression<T>>,
+        result: EvalResult<'a, T>,
+    ) -> Result<Option<EvalValue<&'a AlgebraicReference, T>>, EvalError<T>> {
+        match result {
+            Ok(updates) => Ok(Some(updates)),
+            Err(e) if self.fixed_data.optional_identities.contains(&identity.id) => {
+                log::debug!(
+                    "{}Skipping optional identity {identity} on row {}: {e}",
+                    self.log_scope,
+                    self.global_index(row_index)
+                );
+                Ok(None)
+            }
+            Err(e) => Err(self.identity_error(row_index, identity, e)),
+        }
+    }
+
+    /// Records the dependencies and statistics of an identity whose updates were applied
+    /// to the given row, checks its bit budgets if it is complete, and caches that it did
+    /// not make progress for the given data version. Returns whether it is complete.
+    fn record_processed_identity(
+        &mut self,
+        row_index: usize,
+        identity: &'a Identity<Expression<T>>,
+        updates: &EvalValue<&'a AlgebraicReference, T>,
+        known_cells: Option<Vec<Cell>>,
+        progress: bool,
+        data_version: u64,
+    ) -> Result<bool, EvalError<T>> {
+        if let Some(known_cells) = known_cells {
+            self.record_dependencies(row_index, updates, known_cells);
+        }
+        let is_complete = updates.is_complete();
+        if progress && !is_complete {
+            *self.stats.partial_progress.entry(identity.id).or_default() += 1;
+        }
+        if is_complete {
+            self.check_bit_budgets(row_index, identity, UnknownStrategy::Unknown)?;
+        }
+        if Self::is_cacheable(identity, UnknownStrategy::Unknown) && !progress && !is_complete {
+            self.evaluation_cache
+                .insert((row_index, identity.id), data_version);
+        }
+        Ok(is_complete)
+    }
+
+    /// Computes the updates that [Processor::process_identity] would apply for the given
+    /// identity and row, without applying them.
+    /// Only polynomial identities can be previewed, because processing a lookup might
+    /// change the state of other machines.
+    pub fn preview_identity(
+        &self,
+        row_index: usize,
+        identity: &'a Identity<Expression<T>>,
+        unknown_strategy: UnknownStrategy,
+    ) -> Result<EvalValue<&'a AlgebraicReference, T>, EvalError<T>> {
+        if identity.kind != IdentityKind::Polynomial {
+            return Err(format!("Only polynomial identities can be previewed: {identity}").into());
+        }
+        self.check_next_row_reference(row_index, identity)?;
+        let row_pair = Self::row_pair(
+            &self.data,
+            self.zero_row.as_ref(),
+            self.fixed_data,
+            self.global_index(row_index),
+            row_index,
+            unknown_strategy,
+        );
+        process_polynomial_identity(identity, &row_pair)
+            .map_err(|e| self.identity_error(row_index, identity, e))
+    }
+
+    /// Fails if the identity references the next row on the last row, but the
+    /// [LastRowPolicy] does not allow it.
+    fn check_next_row_reference(
+        &self,
+        row_index: usize,
+        identity: &Identity<Expression<T>>,
+    ) -> Result<(), EvalError<T>> {
+        if self.is_last_row(row_index)
+            && self.fixed_data.options.last_row_policy == LastRowPolicy::Error
+            && identity.contains_next_ref()
+        {
+            let global_row_index = self.global_index(row_index);
+            return Err(format!(
+                "Identity {identity} references the next row, which is not allowed on the last row ({global_row_index})."
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Evaluates all given identities on the same row pair and applies their updates
+    /// afterwards, so that the row pair and the identity processor are only constructed
+    /// once. In contrast to calling [Processor::process_identity] for each identity,
+    /// the identities do not see the updates of the other identities of the batch.
+    pub fn process_identities_batched(
+        &mut self,
+        row_index: usize,
+        identities: &[&'a Identity<Expression<T>>],
+        unknown_strategy: UnknownStrategy,
+    ) -> Result<BatchedIdentityResult, EvalError<T>> {
+        if let Some(identity_budget) = self.identity_budget {
+            identity_budget.spend(identities.len() as u64)?;
+        }
+        let global_row_index = self.global_index(row_index);
+        let data_version = self.data_version;
+        let mut to_process = vec![];
+        for (i, identity) in identities.iter().enumerate() {
+            self.check_next_row_reference(row_index, identity)?;
+            if !self.is_cached(row_index, identity, unknown_strategy) {
+                to_process.push(i);
+            }
+        }
+
+        // Evaluate all identities on the same row pair.
+        let row_pair = Self::row_pair(
+            &self.data,
+            self.zero_row.as_ref(),
+            self.fixed_data,
+            global_row_index,
+            row_index,
+            unknown_strategy,
+        );
+        let known_cells = to_process
+            .iter()
+            .map(|i| self.known_cells_of_identity(&row_pair, identities[*i]))
+            .collect::<Vec<_>>();
+        let mut identity_processor = IdentityProcessor::new(self.fixed_data, self.mutable_state);
+        let mut results = Vec::with_capacity(to_process.len());
+        for i in &to_process {
+            let identity = identities[*i];
+            let result = identity_processor.process_identity(identity, &row_pair);
+            let failed =
+                result.is_err() && !self.fixed_data.optional_identities.contains(&identity.id);
+            results.push(result);
+            if failed {
+                break;
+            }
+        }
+        // Skipped optional identities are complete without updates.
+        let results = to_process
+            .iter()
+            .zip(results)
+            .map(|(i, result)| {
+                Ok(self
+                    .identity_updates(row_index, identities[*i], result)?
+                    .unwrap_or_else(|| EvalValue::complete(vec![])))
+            })
+            .collect::<Result<Vec<_>, EvalError<T>>>()?;
+
+        let mut is_complete = vec![false; identities.len()];
+        if unknown_strategy != UnknownStrategy::Unknown {
+            for updates in &results {
+                assert!(updates.constraints.is_empty());
+                assert!(!updates.side_effect);
+            }
+            return Ok(BatchedIdentityResult {
+                aggregate: IdentityResult {
+                    progress: false,
+                    is_complete: false,
+                },
+                is_complete,
+            });
+        }
+
+        // Merge the updates, so that each cell is updated at most once per kind of
+        // constraint: Range constraints are combined, equal assignments are deduplicated.
+        let mut range_constraints = BTreeMap::new();
+        let mut assignments = BTreeMap::new();
+        for (result_index, updates) in results.iter().enumerate() {
+            for (poly, c) in &updates.constraints {
+                let key = (poly.poly_id, poly.next);
+                match c {
+                    Constraint::Assignment(value) => match assignments.get(&key) {
+                        None => {
+                            assignments.insert(key, (result_index, *poly, *value));
+                        }
+                        Some((_, _, existing)) if existing == value => {}
+                        Some((other_index, _, existing)) => {
+                            return Err(format!(
+                                "Conflicting updates for {poly} (Row {}): {existing} (from {}) and {value} (from {})",
+                                global_row_index + poly.next as usize,
+                                identities[to_process[*other_index]],
+                                identities[to_process[result_index]],
+                            )
+                            .into());
+                        }
+                    },
+                    Constraint::RangeConstraint(range_constraint) => {
+                        let (_, _, combined) = range_constraints
+                            .entry(key)
+                            .or_insert_with(|| (result_index, *poly, range_constraint.clone()));
+                        *combined = combined.conjunction(range_constraint);
+                    }
+                }
+            }
+        }
+
+        // Apply the merged updates, range constraints first.
+        let mut progress = vec![false; results.len()];
+        let updates = range_constraints
+            .into_values()
+            .map(|(result_index, poly, rc)| (result_index, poly, Constraint::RangeConstraint(rc)))
+            .chain(
+                assignments
+                    .into_values()
+                    .map(|(result_index, poly, value)| {
+                        (result_index, poly, Constraint::Assignment(value))
+                    }),
+            )
+            .collect::<Vec<_>>();
+        if !updates.is_empty() {
+            log::trace!(
+                "{}    Updates from a batch of {} identities",
+                self.log_scope,
+                results.len()
+            );
+        }
+        for (result_index, poly, c) in updates {
+            let identity = identities[to_process[result_index]];
+            progress[result_index] |= self.apply_constraint(
+                row_index,
+                poly,
+                &c,
+                Provenance::Identity(identity.id),
+                &|| identity.to_string(),
+            )?;
+        }
+
+        for (result_index, (updates, known_cells)) in results.iter().zip(known_cells).enumerate() {
+            let identity = identities[to_process[result_index]];
+            let progress = progress[result_index] || updates.side_effect;
+            is_complete[to_process[result_index]] = self.record_processed_identity(
+                row_index,
+                identity,
+                updates,
+                known_cells,
+                progress,
+                data_version,
+            )?;
+        }
+
+        let any_progress = progress.iter().any(|p| *p) || results.iter().any(|u| u.side_effect);
+        Ok(BatchedIdentityResult {
+            aggregate: IdentityResult {
+                progress: any_progress,
+                is_complete: is_complete.iter().all(|c| *c),
+            },
+            is_complete,
+        })
+    }
+
+    /// Adds the known values of the rows to an error that occurred while processing
+    /// the given identity.
+    fn identity_error(
+        &self,
+        row_index: usize,
+        identity: &Identity<Expression<T>>,
+        e: EvalError<T>,
+    ) -> EvalError<T> {
+        if let EvalError::BudgetExceeded(_) = e {
+            // Passed on unchanged, so that it can be reported as such.
+            return e;
+        }
+        EvalError::IdentityFailed(Box::new(IdentityFailure {
+            identity_id: identity.id,
+            identity: identity.to_string(),
+            local_row: row_index,
+            global_row: self.global_index(row_index).into(),
+            current_row: self.data[row_index].known_values(self.witness_cols),
+            next_row: identity
+                .contains_next_ref()
+                .then(|| self.data[row_index + 1].known_values(self.witness_cols)),
+            provenance: self.render_provenance(row_index),
+            cause: e,
+        }))
+    }
+
+    /// Checks the intermediate sums of a complete identity against the bit budgets
+    /// of the column groups it references.
+    fn check_bit_budgets(
+        &self,
+        row_index: usize,
+        identity: &'a Identity<Expression<T>>,
+        unknown_strategy: UnknownStrategy,
+    ) -> Result<(), EvalError<T>> {
+        let global_row_index = self.global_index(row_index);
+        let row_pair = RowPair::new(
+            &self.data[row_index],
+            &self.data[row_index + 1],
+            global_row_index,
+            self.fixed_data,
+            unknown_strategy,
+        );
+        self.fixed_data
+            .bit_budgets
+            .iter()
+            .filter(|budget| budget.applies_to(identity))
+            .try_for_each(|budget| budget.check(identity, &row_pair))
+            .map_err(|e| {
+                format!("Error in identity: {identity} (Row {global_row_index})\n   => {e}").into()
+            })
+    }
+
+    pub fn process_outer_query(
+        &mut self,
+        row_index: usize,
+    ) -> Result<(bool, Constraints<&'a AlgebraicReference, T>), EvalError<T>> {
+        assert!(
+            !self.outer_queries.is_empty(),
+            "Asked to process outer query, but it was not set!"
+        );
+        let mut progress = false;
+        let selectors = self
+            .outer_queries
+            .iter()
+            .filter_map(|outer_query| outer_query.connecting_identity.right.selector.as_ref())
+            .collect::<Vec<_>>();
+        for selector in selectors {
+            progress |= self
+                .set_value(
+                    row_index,
+                    selector,
+                    T::one(),
+                    Provenance::OuterQuery,
+                    || "Set selector to 1".to_string(),
+                )
+                .unwrap_or(false);
+        }
+
+        let row_pair = RowPair::new(
+            &self.data[row_index],
+            &self.data[row_index + 1],
+            self.global_index(row_index),
+            self.fixed_data,
+            UnknownStrategy::Unknown,
+        );
+
+        let mut identity_processor = IdentityProcessor::new(self.fixed_data, self.mutable_state);
+        let mut updates = EvalValue::complete(vec![]);
+        for outer_query in &self.outer_queries {
+            let right = &outer_query.connecting_identity.right;
+            updates.combine(
+                identity_processor
+                    .process_link(outer_query, &row_pair)
+                    .map_err(|e| {
+                        log::warn!("{}Error in outer query: {e}", self.log_scope);
+                        log::warn!(
+                            "{}Some of the following entries could not be matched:",
+                            self.log_scope
+                        );
+                        for (l, r) in outer_query.left.iter().zip(right.expressions.iter()) {
+                            if let Ok(r) = row_pair.evaluate(r) {
+                                log::warn!("{}  => {l} = {r}", self.log_scope);
+                            }
+                        }
+                        e
+                    })?,
+            );
+        }
+        // The same cell can be determined by several outer queries.
+        let mut constraints = vec![];
+        for constraint in std::mem::take(&mut updates.constraints) {
+            if !constraints.contains(&constraint) {
+                constraints.push(constraint);
+            }
+        }
+        updates.constraints = constraints;
+
+        progress |= self.apply_updates(row_index, &updates, Provenance::OuterQuery, || {
+            "outer query".to_string()
+        })?;
+
+        // Updates to columns of other machines can only come from the caller's side
+        // of the connecting identity, so they are exactly the updates for the caller.
+        let outer_assignments = updates
+            .constraints
+            .into_iter()
+            .filter(|(poly, _)| !self.is_relevant_witness[&poly.poly_id])
+            .collect::<Vec<_>>();
+
+        if let Some(observer) = self.fixed_data.options.outer_assignment_observer {
+            // All outer queries share the caller's rows.
+            let caller_row = self.outer_queries[0].caller_rows.current_row_index;
+            for (poly, c) in &outer_assignments {
+                if let Constraint::Assignment(value) = c {
+                    let row = DegreeType::from(caller_row + poly.next as usize);
+                    observer
+                        .on_outer_assignment(poly.poly_id, *value, row)
+                        .map_err(|reason| EvalError::OuterAssignmentRejected {
+                            column: poly.name.clone(),
+                            row,
+                            value: *value,
+                            reason,
+                        })?;
+                }
+            }
+        }
+
+        Ok((progress, outer_assignments))
+    }
+
+    /// Sets the inputs to the values given in [VmProcessor::inputs] if they are not already set.
+    /// Typically, inputs will have a constraint of the form: `((1 - instr__reset) * (_input' - _input)) = 0;`
+    /// So, once the value of `_input` is set, this function will do nothing until the next reset instruction.
+    /// However, if `_input` does become unconstrained, we need to undo all changes we've done so far.
+    /// For this reason, we keep track of all changes we've done to inputs in [Processor::previously_set_inputs].
+    /// Fails if an input is already known to have a different value.
+    pub fn set_inputs_if_unset(&mut self, row_index: usize) -> Result<bool, EvalError<T>> {
+        let mut input_updates = EvalValue::complete(vec![]);
+        for (poly_id, value) in self.inputs.iter() {
+            match &self.data[row_index][poly_id].value {
+                CellValue::Known(existing) if existing != value => {
+                    return Err(format!(
+                        "Input {} (Row {}) is {value}, but it is already known to be {existing}.",
+                        self.fixed_data.column_name(poly_id),
+                        self.global_index(row_index),
+                    )
+                    .into());
+                }
+                CellValue::Known(_) => {}
                 CellValue::RangeConstraint(_) | CellValue::Unknown => {
                     input_updates.combine(EvalValue::complete(vec![(
                         &self.fixed_data.witness_cols[poly_id].poly,
@@ -336,18 +1554,122 @@ Known values in current row (local: {row_index}, global {global_row_index}):
             let poly_id = poly.poly_id;
             if let Some(start_row) = self.previously_set_inputs.remove(&poly_id) {
                 log::trace!(
-                    "    Resetting previously set inputs for column: {}",
+                    "{}    Resetting previously set inputs for column: {}",
+                    self.log_scope,
                     self.fixed_data.column_name(&poly_id)
                 );
                 for row_index in start_row..row_index {
+                    self.record_for_transaction(row_index, poly_id);
+                    if self.data[row_index][&poly_id].value.is_known() {
+                        self.restore_pending_copy_constraint(row_index, poly_id);
+                    }
                     self.data[row_index][&poly_id].value = CellValue::Unknown;
+                    self.data_version += 1;
+                    let row = DegreeType::from(self.global_index(row_index));
+                    if let Some(provenance) = &mut self.provenance {
+                        provenance.remove(&(row, poly_id));
+                    }
+                    if let Some(dependencies) = &mut self.dependencies {
+                        dependencies.remove(&(row, poly_id));
+                    }
                 }
             }
         }
         for (poly, _) in &input_updates.constraints {
             self.previously_set_inputs.insert(poly.poly_id, row_index);
         }
-        self.apply_updates(row_index, &input_updates, || "inputs".to_string())
+        self.apply_updates(row_index, &input_updates, Provenance::Input, || {
+            "inputs".to_string()
+        })
+    }
+
+    /// Counts the given cell as a pending copy constraint of its row again, if it is
+    /// copy-constrained to other cells, because it is about to become unknown.
+    fn restore_pending_copy_constraint(&mut self, row_index: usize, poly_id: PolyID) {
+        let row = self.global_index(row_index);
+        if self.copy_constraints.next((poly_id, row)) == (poly_id, row) {
+            return;
+        }
+        let row = DegreeType::from(row);
+        if let Some(transaction) = &mut self.transaction {
+            transaction
+                .pending_copy_constraints
+                .entry(row)
+                .or_insert_with(|| self.pending_copy_constraints.get(&row).copied());
+        }
+        *self.pending_copy_constraints.entry(row).or_default() += 1;
+    }
+
+    /// Sets the given cells of a row, unless they are already known.
+    /// Fails if a cell is already known to have a different value.
+    pub fn set_cells_if_unset(
+        &mut self,
+        row_index: usize,
+        values: &[(PolyID, T)],
+        provenance: Provenance,
+        source_name: impl Fn() -> String,
+    ) -> Result<bool, EvalError<T>> {
+        let mut updates = EvalValue::complete(vec![]);
+        for (poly_id, value) in values {
+            match &self.data[row_index][poly_id].value {
+                CellValue::Known(known) if known != value => {
+                    return Err(format!(
+                        "Cannot set {} to {value} in row {}: it is already known to be {known}.",
+                        self.fixed_data.column_name(poly_id),
+                        self.global_index(row_index),
+                    )
+                    .into());
+                }
+                CellValue::Known(_) => {}
+                CellValue::RangeConstraint(_) | CellValue::Unknown => {
+                    updates.combine(EvalValue::complete(vec![(
+                        &self.fixed_data.witness_cols[poly_id].poly,
+                        Constraint::Assignment(*value),
+                    )]));
+                }
+            }
+        }
+        self.apply_updates(row_index, &updates, provenance, source_name)
+    }
+
+    /// Returns the values of all columns of the group in the given row,
+    /// or None if any of them is not known yet.
+    pub fn read_group(&self, row_index: usize, group: &ColumnGroup) -> Option<Vec<T>> {
+        let row = &self.data[row_index];
+        group
+            .columns
+            .iter()
+            .map(|poly_id| match row[poly_id].value {
+                CellValue::Known(value) => Some(value),
+                CellValue::RangeConstraint(_) | CellValue::Unknown => None,
+            })
+            .collect()
+    }
+
+    /// Sets all columns of the group in the given row to the given values (in the order
+    /// of the group's columns). Fails without changing the row if any of the columns is
+    /// already known to have a different value.
+    pub fn write_group(
+        &mut self,
+        row_index: usize,
+        group: &ColumnGroup,
+        values: &[T],
+        provenance: Provenance,
+        source_name: impl Fn() -> String,
+    ) -> Result<bool, EvalError<T>> {
+        assert_eq!(
+            group.columns.len(),
+            values.len(),
+            "Wrong number of values for column group {}",
+            group.name
+        );
+        let values = group
+            .columns
+            .iter()
+            .cloned()
+            .zip(values.iter().cloned())
+            .collect::<Vec<_>>();
+        self.set_cells_if_unset(row_index, &values, provenance, source_name)
     }
 
     /// Sets the value of a given expression, in a given row.
@@ -356,57 +1678,209 @@ Known values in current row (local: {row_index}, global {global_row_index}):
         row_index: usize,
         expression: &'a Expression<T>,
         value: T,
+        provenance: Provenance,
         name: impl Fn() -> String,
-    ) -> Result<bool, IncompleteCause<&'a AlgebraicReference>> {
+    ) -> Result<bool, EvalError<T>> {
         let row_pair = RowPair::new(
             &self.data[row_index],
             &self.data[row_index + 1],
-            self.row_offset + row_index as u64,
+            self.global_index(row_index),
             self.fixed_data,
             UnknownStrategy::Unknown,
         );
-        let affine_expression = row_pair.evaluate(expression)?;
+        let set_value_failed = |cause| EvalError::SetValueFailed {
+            expression: expression.to_string(),
+            row: DegreeType::from(self.global_index(row_index)),
+            value,
+            cause: Box::new(cause),
+        };
+        let affine_expression = row_pair.evaluate(expression).map_err(|cause| {
+            set_value_failed(format!("Could not evaluate expression: {cause:?}").into())
+        })?;
         let updates = (affine_expression - value.into())
             .solve_with_range_constraints(&row_pair)
-            .unwrap();
-        Ok(self.apply_updates(row_index, &updates, name))
+            .map_err(set_value_failed)?;
+        self.apply_updates(row_index, &updates, provenance, name)
     }
 
     fn apply_updates(
         &mut self,
         row_index: usize,
         updates: &EvalValue<&'a AlgebraicReference, T>,
+        provenance: Provenance,
         source_name: impl Fn() -> String,
-    ) -> bool {
+    ) -> Result<bool, EvalError<T>> {
         if updates.constraints.is_empty() {
-            return false;
+            return Ok(false);
         }
 
-        log::trace!("    Updates from: {}", source_name());
+        log::trace!("{}    Updates from: {}", self.log_scope, source_name());
 
         let mut progress = false;
         for (poly, c) in &updates.constraints {
-            if self.witness_cols.contains(&poly.poly_id) {
-                // Build RowUpdater
-                // (a bit complicated, because we need two mutable
-                // references to elements of the same vector)
-                let (current, next) = self.data.mutable_row_pair(row_index);
-                let mut row_updater =
-                    RowUpdater::new(current, next, self.row_offset + row_index as u64);
-                row_updater.apply_update(poly, c);
-                progress = true;
-                self.propagate_along_copy_constraints(row_index, poly, c);
-            } else if let Constraint::Assignment(v) = c {
-                let left = &mut self.outer_query.as_mut().unwrap().left;
-                log::trace!("      => {} (outer) = {}", poly, v);
-                for l in left.iter_mut() {
+            progress |= self.apply_constraint(row_index, poly, c, provenance, &source_name)?;
+        }
+
+        Ok(progress)
+    }
+
+    /// Applies a single update to a cell of this machine or to the outer query.
+    /// Returns whether any progress was made.
+    fn apply_constraint(
+        &mut self,
+        row_index: usize,
+        poly: &'a AlgebraicReference,
+        c: &Constraint<T>,
+        provenance: Provenance,
+        source_name: &impl Fn() -> String,
+    ) -> Result<bool, EvalError<T>> {
+        if self.witness_cols.contains(&poly.poly_id) {
+            self.check_global_range_constraint(row_index, poly, c, source_name)?;
+            if !self.check_conflict(row_index, poly, c, source_name)? {
+                return Ok(false);
+            }
+            self.record_for_transaction(row_index + poly.next as usize, poly.poly_id);
+            // Build RowUpdater
+            // (a bit complicated, because we need two mutable
+            // references to elements of the same vector)
+            let global_row_index = self.global_index(row_index);
+            let (current, next) = self.data.mutable_row_pair(row_index);
+            let mut row_updater = RowUpdater::new(current, next, global_row_index);
+            row_updater.apply_update(poly, c);
+            self.data_version += 1;
+            if let (Some(counts), Constraint::Assignment(_)) = (&mut self.progress_counts, c) {
+                *counts.entry(poly.poly_id).or_default() += 1;
+            }
+            if let (Some(heatmap), Constraint::Assignment(_)) =
+                (&self.fixed_data.convergence_heatmap, c)
+            {
+                heatmap
+                    .lock()
+                    .unwrap()
+                    .record(self.convergence_iteration, &poly.poly_id);
+            }
+            let row = DegreeType::from(self.global_index(row_index + poly.next as usize));
+            if let Some(provenance_map) = &mut self.provenance {
+                provenance_map.insert((row, poly.poly_id), provenance);
+            }
+            self.propagate_along_copy_constraints(row_index, poly, c)?;
+            Ok(true)
+        } else if let Constraint::Assignment(v) = c {
+            assert!(!self.outer_queries.is_empty());
+            log::trace!("{}      => {poly} (outer) = {v}", self.log_scope);
+            self.record_outer_queries_for_transaction();
+            for outer_query in &mut self.outer_queries {
+                for l in outer_query.left.iter_mut() {
                     l.assign(poly, *v);
                 }
-                progress = true;
-            };
+            }
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Checks whether the update conflicts with the current value or range constraint of
+    /// the cell, resolving the conflict according to the [ConflictPolicy].
+    /// Returns whether the update should be applied.
+    fn check_conflict(
+        &self,
+        row_index: usize,
+        poly: &AlgebraicReference,
+        constraint: &Constraint<T>,
+        source_name: &impl Fn() -> String,
+    ) -> Result<bool, EvalError<T>> {
+        let cell_row_index = row_index + poly.next as usize;
+        let policy = self.fixed_data.options.conflict_policy;
+        let existing = match &self.data[cell_row_index][&poly.poly_id].value {
+            CellValue::Unknown => return Ok(true),
+            CellValue::Known(value) => {
+                let is_equal = match constraint {
+                    Constraint::Assignment(new_value) => new_value == value,
+                    Constraint::RangeConstraint(range_constraint) => {
+                        range_constraint.allows_value(*value)
+                    }
+                };
+                if policy == ConflictPolicy::AllowEqual && is_equal {
+                    return Ok(false);
+                }
+                return Err(format!(
+                    "Conflicting update for {} (Row {}):\n    {}{constraint} (from {})\n    but the value was already set to {value}{}",
+                    poly.name,
+                    self.global_index(cell_row_index),
+                    poly.name,
+                    source_name(),
+                    self.render_existing_source(cell_row_index, &poly.poly_id),
+                )
+                .into());
+            }
+            CellValue::RangeConstraint(existing) => existing,
+        };
+        let is_contradiction = match constraint {
+            Constraint::Assignment(_) if policy == ConflictPolicy::OverrideRangeWithValue => false,
+            Constraint::Assignment(value) => !existing.allows_value(*value),
+            Constraint::RangeConstraint(range_constraint) => existing.is_disjoint(range_constraint),
+        };
+        if !is_contradiction {
+            // Range constraints (e.g. reported by a callee again) are only applied if they
+            // narrow the existing range constraint.
+            return Ok(match constraint {
+                Constraint::Assignment(_) => true,
+                Constraint::RangeConstraint(range_constraint) => {
+                    range_constraint.conjunction(existing) != *existing
+                }
+            });
+        }
+        Err(format!(
+            "Contradicting range constraints for {} (Row {}):\n    {}{constraint} (from {})\n    is incompatible with the range constraint {existing}{}",
+            poly.name,
+            self.global_index(cell_row_index),
+            poly.name,
+            source_name(),
+            self.render_existing_source(cell_row_index, &poly.poly_id),
+        )
+        .into())
+    }
+
+    /// If eager range checks are enabled, checks that an assignment is allowed by the
+    /// global range constraint of the column.
+    fn check_global_range_constraint(
+        &self,
+        row_index: usize,
+        poly: &AlgebraicReference,
+        constraint: &Constraint<T>,
+        source_name: &impl Fn() -> String,
+    ) -> Result<(), EvalError<T>> {
+        if !self.fixed_data.options.eager_range_checks {
+            return Ok(());
+        }
+        let Constraint::Assignment(value) = constraint else {
+            return Ok(());
+        };
+        let Some(range_constraint) =
+            &self.fixed_data.global_range_constraints.witness_constraints[&poly.poly_id]
+        else {
+            return Ok(());
+        };
+        if range_constraint.allows_value(*value) {
+            return Ok(());
         }
+        Err(format!(
+            "Range check failed for {} (Row {}):\n    {} = {value} (from {})\n    is outside of the range constraint {range_constraint} of the column",
+            poly.name,
+            self.global_index(row_index + poly.next as usize),
+            poly.name,
+            source_name(),
+        )
+        .into())
+    }
 
-        progress
+    fn render_existing_source(&self, row_index: usize, poly_id: &PolyID) -> String {
+        let source = self
+            .provenance(row_index, poly_id)
+            .map(|provenance| format!("{provenance:?}"))
+            .unwrap_or_else(|| "global range constraints or an untracked update".to_string());
+        format!(" (from {source})")
     }
 
     fn propagate_along_copy_constraints(
@@ -414,53 +1888,227 @@ Known values in current row (local: {row_index}, global {global_row_index}):
         row_index: usize,
         poly: &AlgebraicReference,
         constraint: &Constraint<T>,
-    ) {
+    ) -> Result<(), EvalError<T>> {
         if self.copy_constraints.is_empty() {
-            return;
+            return Ok(());
         }
-        if let Constraint::Assignment(v) = constraint {
-            // If we do an assignment, propagate the value to any other cell that is
-            // copy-constrained to the current cell.
-            let row = self.row_offset + row_index + poly.next as usize;
-
-            // Have to materialize the other cells to please the borrow checker...
-            let others = self
-                .copy_constraints
-                .iter_equivalence_class((poly.poly_id, row))
-                .skip(1)
-                .collect::<Vec<_>>();
-            for (other_poly, other_row) in others {
-                if other_poly.ptype != PolynomialType::Committed {
-                    unimplemented!(
-                        "Copy constraints to fixed columns are not yet supported (#1335)!"
+        let row = RowIndex::from_degree(
+            DegreeType::from(self.global_index(row_index + poly.next as usize)),
+            self.fixed_data.degree,
+        );
+        // Have to materialize the other cells to please the borrow checker...
+        let others = self
+            .copy_constraints
+            .iter_equivalence_class((poly.poly_id, row))
+            .skip(1)
+            .collect::<Vec<_>>();
+        match constraint {
+            Constraint::Assignment(v) => {
+                if let Some(transaction) = &mut self.transaction {
+                    let row = DegreeType::from(row);
+                    transaction
+                        .pending_copy_constraints
+                        .entry(row)
+                        .or_insert_with(|| self.pending_copy_constraints.get(&row).copied());
+                }
+                // If we do an assignment, propagate the value to any other cell that is
+                // copy-constrained to the current cell.
+                if let Some(pending) = self
+                    .pending_copy_constraints
+                    .get_mut(&DegreeType::from(row))
+                    .filter(|_| {
+                        self.copy_constraints.next((poly.poly_id, row)) != (poly.poly_id, row)
+                    })
+                {
+                    *pending -= 1;
+                    if *pending == 0 {
+                        self.pending_copy_constraints.remove(&DegreeType::from(row));
+                    }
+                }
+
+                for (other_poly, other_row) in others {
+                    if other_poly.ptype == PolynomialType::Constant {
+                        // Fixed cells cannot be assigned, so only check that they match.
+                        let fixed_value = self.fixed_data.fixed_cols[&other_poly]
+                            .value(DegreeType::from(other_row));
+                        if fixed_value != *v {
+                            return Err(EvalError::FixedCopyConstraintViolated {
+                                column: self.fixed_data.column_name(&other_poly).to_string(),
+                                row: DegreeType::from(other_row),
+                                fixed_value,
+                                value: *v,
+                            });
+                        }
+                        continue;
+                    }
+                    let expression = &self.fixed_data.witness_cols[&other_poly].expr;
+                    let local_index = other_row.to_local(&self.row_offset);
+                    // Cells that are already known were not derived from this cell.
+                    let is_known = self.data[local_index][&other_poly].value.is_known();
+                    if let Some(dependencies) = self.dependencies.as_mut().filter(|_| !is_known) {
+                        dependencies.insert(
+                            (DegreeType::from(other_row), other_poly),
+                            vec![(DegreeType::from(row), poly.poly_id)],
+                        );
+                    }
+                    let result = self.set_value(
+                        local_index,
+                        expression,
+                        *v,
+                        Provenance::CopyConstraint,
+                        || {
+                            format!(
+                                "Copy constraint: {} (Row {}) -> {} (Row {})",
+                                self.fixed_data.column_name(&poly.poly_id),
+                                row,
+                                self.fixed_data.column_name(&other_poly),
+                                other_row
+                            )
+                        },
                     );
+                    match result {
+                        Ok(_) => {}
+                        // Already reported for the (innermost) equivalence class.
+                        Err(
+                            e @ (EvalError::CopyConstraintConflict { .. }
+                            | EvalError::FixedCopyConstraintViolated { .. }),
+                        ) => return Err(e),
+                        Err(e) => {
+                            return Err(EvalError::CopyConstraintConflict {
+                                value: *v,
+                                class: self.render_equivalence_class((poly.poly_id, row)),
+                                cause: Box::new(e),
+                            })
+                        }
+                    }
+                }
+            }
+            Constraint::RangeConstraint(_) => {
+                // Propagate the range constraint of the current cell (i.e. the conjunction
+                // with the update) to all cells that are copy-constrained to it.
+                let CellValue::RangeConstraint(range_constraint) = self.data
+                    [row_index + poly.next as usize][&poly.poly_id]
+                    .value
+                    .clone()
+                else {
+                    return Ok(());
+                };
+                for (other_poly, other_row) in others {
+                    if other_poly.ptype != PolynomialType::Committed {
+                        continue;
+                    }
+                    let local_index = other_row.to_local(&self.row_offset);
+                    self.intersect_range_constraint(local_index, &other_poly, &range_constraint);
                 }
-                let expression = &self.fixed_data.witness_cols[&other_poly].expr;
-                let local_index = other_row.to_local(&self.row_offset);
-                self.set_value(local_index, expression, *v, || {
-                    format!(
-                        "Copy constraint: {} (Row {}) -> {} (Row {})",
-                        self.fixed_data.column_name(&poly.poly_id),
-                        row,
-                        self.fixed_data.column_name(&other_poly),
-                        other_row
-                    )
-                })
-                .unwrap();
             }
         }
+        Ok(())
+    }
+
+    /// Returns the cells (column, global row) of the equivalence class of the given cell
+    /// with their values, if known.
+    fn render_equivalence_class(
+        &self,
+        cell: (PolyID, RowIndex),
+    ) -> Vec<(String, DegreeType, Option<T>)> {
+        self.copy_constraints
+            .iter_equivalence_class(cell)
+            .map(|(poly_id, row)| {
+                let value = match poly_id.ptype {
+                    PolynomialType::Committed => {
+                        match self.data[row.to_local(&self.row_offset)][&poly_id].value {
+                            CellValue::Known(value) => Some(value),
+                            _ => None,
+                        }
+                    }
+                    _ => Some(self.fixed_data.fixed_cols[&poly_id].value(DegreeType::from(row))),
+                };
+                (
+                    self.fixed_data.column_name(&poly_id).to_string(),
+                    DegreeType::from(row),
+                    value,
+                )
+            })
+            .collect()
+    }
+
+    /// Intersects the range constraint of a cell with the given range constraint.
+    /// Known cells and cells whose range constraint does not change are left untouched,
+    /// which makes the propagation along copy constraints terminate.
+    fn intersect_range_constraint(
+        &mut self,
+        row_index: usize,
+        poly_id: &PolyID,
+        range_constraint: &RangeConstraint<T>,
+    ) {
+        let global_row_index = self.global_index(row_index);
+        let new = match &self.data[row_index][poly_id].value {
+            CellValue::Known(_) => return,
+            CellValue::RangeConstraint(existing) => {
+                let new = existing.conjunction(range_constraint);
+                if new == *existing {
+                    return;
+                }
+                new
+            }
+            CellValue::Unknown => range_constraint.clone(),
+        };
+        self.record_for_transaction(row_index, *poly_id);
+        let cell = &mut self.data[row_index][poly_id];
+        log::trace!(
+            "{}      => {} (Row {global_row_index}) {new} (copy constraint)",
+            self.log_scope,
+            cell.name
+        );
+        cell.value = CellValue::RangeConstraint(new);
+        self.data_version += 1;
     }
 
     pub fn len(&self) -> usize {
         self.data.len()
     }
 
+    /// Finalizes the given rows. Rows with pending copy constraints (i.e. cells that are
+    /// copy-constrained to cells that are not known yet) are only finalized once the
+    /// copy constraints are resolved, in one of the next calls.
+    /// If a [super::ColumnSink] is configured, the cells of the finalized rows are passed to it.
+    /// With [super::WitgenOptions::compact_finalized_rows], finalized rows in which all
+    /// cells are known are compacted to save memory.
     pub fn finalize_range(&mut self, range: impl Iterator<Item = usize>) {
-        assert!(
-            self.copy_constraints.is_empty(),
-            "Machines with copy constraints should not be finalized while being processed."
-        );
-        self.data.finalize_range(range);
+        let mut rows = self
+            .deferred_finalization
+            .iter()
+            .copied()
+            .filter(|i| !self.has_pending_copy_constraints(*i))
+            .collect::<Vec<_>>();
+        for i in &rows {
+            self.deferred_finalization.remove(i);
+        }
+        for i in range {
+            if self.has_pending_copy_constraints(i) {
+                self.deferred_finalization.insert(i);
+            } else {
+                rows.push(i);
+            }
+        }
+        match self.fixed_data.options.column_sink {
+            Some(column_sink) => column_sink.write_cells(&mut |write| {
+                let row_offset = self.row_offset;
+                self.data
+                    .finalize_range_into(rows.iter().copied(), |poly_id, i, value| {
+                        write(poly_id, DegreeType::from(row_offset + i), value)
+                    })
+            }),
+            None => self.data.finalize_range(rows.iter().copied()),
+        }
+        if self.fixed_data.options.compact_finalized_rows {
+            self.data.compact_known_rows(rows.into_iter());
+        }
+    }
+
+    fn has_pending_copy_constraints(&self, row_index: usize) -> bool {
+        self.pending_copy_constraints
+            .contains_key(&DegreeType::from(self.global_index(row_index)))
     }
 
     pub fn row(&self, i: usize) -> &Row<'a, T> {
@@ -468,20 +2116,365 @@ Known values in current row (local: {row_index}, global {global_row_index}):
     }
 
     pub fn has_outer_query(&self) -> bool {
-        self.outer_query.is_some()
+        !self.outer_queries.is_empty()
     }
 
     /// Sets the ith row, extending the data if necessary.
     pub fn set_row(&mut self, i: usize, row: Row<'a, T>) {
+        self.data_version += 1;
         if i < self.data.len() {
             self.data[i] = row;
         } else {
             assert_eq!(i, self.data.len());
             self.data.push(row);
+            self.stats.peak_rows_in_progress = self
+                .stats
+                .peak_rows_in_progress
+                .max(self.data.rows_in_progress());
+            self.stats.peak_bytes = self.stats.peak_bytes.max(self.data.estimated_bytes());
         }
     }
 
-    /// Checks whether a given identity is satisfied on a proposed row.
+    /// Returns whether the given row is the last row of the trace.
+    fn is_last_row(&self, row_index: usize) -> bool {
+        DegreeType::from(self.global_index(row_index)) == self.fixed_data.degree - 1
+    }
+
+    /// Creates the row pair of the given row and its next row, which is determined
+    /// by the [LastRowPolicy] on the last row.
+    /// Only borrows the rows, so that the mutable state can be borrowed at the same time.
+    fn row_pair<'r>(
+        data: &'r FinalizableData<'a, T>,
+        zero_row: Option<&'r Row<'a, T>>,
+        fixed_data: &'a FixedData<'a, T>,
+        global_row_index: RowIndex,
+        row_index: usize,
+        unknown_strategy: UnknownStrategy,
+    ) -> RowPair<'r, 'a, T> {
+        let next = if DegreeType::from(global_row_index) == fixed_data.degree - 1 {
+            match fixed_data.options.last_row_policy {
+                LastRowPolicy::Cyclic => Some(&data[row_index + 1]),
+                LastRowPolicy::Zero => zero_row,
+                LastRowPolicy::Error => None,
+            }
+        } else {
+            Some(&data[row_index + 1])
+        };
+        match next {
+            Some(next) => RowPair::new(
+                &data[row_index],
+                next,
+                global_row_index,
+                fixed_data,
+                unknown_strategy,
+            ),
+            None => RowPair::from_single_row(
+                &data[row_index],
+                global_row_index,
+                fixed_data,
+                unknown_strategy,
+            ),
+        }
+    }
+
+    /// Passes the given row (and the next row) to the row tagger, if there is one.
+    pub fn tag_row(&self, row_index: usize) {
+        if let Some(row_tagger) = self.fixed_data.options.row_tagger {
+            let row_pair = RowPair::new(
+                &self.data[row_index],
+                &self.data[row_index + 1],
+                self.global_index(row_index),
+                self.fixed_data,
+                UnknownStrategy::Unknown,
+            );
+            row_tagger.tag(&row_pair);
+        }
+    }
+
+    /// Evaluates each of the given polynomial identities on the given row (and the next row),
+    /// substituting the currently known values, and returns the resulting affine expressions,
+    /// which have to be zero. Identities that are not affine on this row (e.g. because they
+    /// contain products of unknown cells) and identities of other kinds are skipped.
+    pub fn row_affine_system(
+        &self,
+        row_index: usize,
+        identities: &[&'a Identity<Expression<T>>],
+    ) -> Vec<AffineExpression<&'a AlgebraicReference, T>> {
+        let row_pair = RowPair::new(
+            &self.data[row_index],
+            &self.data[row_index + 1],
+            self.global_index(row_index),
+            self.fixed_data,
+            UnknownStrategy::Unknown,
+        );
+        identities
+            .iter()
+            .filter(|identity| identity.kind == IdentityKind::Polynomial)
+            .filter_map(|identity| row_pair.evaluate(identity.expression_for_poly_id()).ok())
+            .collect()
+    }
+
+    /// Determines why the given polynomial identities do not make progress on the given row,
+    /// considering those that are affine and have at least two unknown cells.
+    /// Returns None if there are no such identities.
+    pub fn stall_cause(
+        &self,
+        row_index: usize,
+        identities: &[&'a Identity<Expression<T>>],
+    ) -> Option<StallCause> {
+        let row_pair = RowPair::new(
+            &self.data[row_index],
+            &self.data[row_index + 1],
+            self.global_index(row_index),
+            self.fixed_data,
+            UnknownStrategy::Unknown,
+        );
+        let stalled = identities
+            .iter()
+            .filter(|identity| identity.kind == IdentityKind::Polynomial)
+            .filter_map(|identity| {
+                let expression = row_pair.evaluate(identity.expression_for_poly_id()).ok()?;
+                let cells = expression
+                    .nonzero_coefficients()
+                    .map(|(poly, _)| poly.to_string())
+                    .collect::<Vec<_>>();
+                (cells.len() >= 2).then_some((identity.id, cells))
+            })
+            .collect::<Vec<_>>();
+        (!stalled.is_empty()).then(|| stall_cause(&stalled))
+    }
+
+    /// Enumerates up to `limit` distinct complete assignments of the witness cells of the
+    /// given row that are consistent with the given identities.
+    /// After propagating as much as possible, the first unknown cell is split into cases
+    /// according to its range constraint. Branches with unknown cells that are not
+    /// range-constrained to a few values are dropped.
+    /// Every case is tried in a transaction that is rolled back afterwards. Identities that
+    /// call other machines should not be passed, because the side effects of such calls
+    /// are not undone.
+    pub fn enumerate_assignments(
+        &mut self,
+        row_index: usize,
+        identities: &[&'a Identity<Expression<T>>],
+        limit: usize,
+    ) -> Vec<Vec<(PolyID, T)>> {
+        let witness_cols = self
+            .witness_cols
+            .iter()
+            .sorted()
+            .cloned()
+            .collect::<Vec<_>>();
+        let mut assignments = vec![];
+        // The cases still to be tried, as values of split cells, in depth-first order.
+        let mut cases: Vec<Vec<(PolyID, T)>> = vec![vec![]];
+        while let Some(case) = cases.pop() {
+            if assignments.len() >= limit {
+                break;
+            }
+            self.begin_transaction();
+            if self.propagate_case(row_index, identities, &case) {
+                match witness_cols
+                    .iter()
+                    .find(|poly_id| !self.data[row_index][poly_id].value.is_known())
+                {
+                    None => assignments.push(
+                        witness_cols
+                            .iter()
+                            .map(|poly_id| {
+                                (
+                                    *poly_id,
+                                    self.data[row_index][poly_id].value.unwrap_or_default(),
+                                )
+                            })
+                            .collect(),
+                    ),
+                    Some(poly_id) => {
+                        if let CellValue::RangeConstraint(range_constraint) =
+                            &self.data[row_index][poly_id].value
+                        {
+                            if let Some(values) =
+                                range_constraint.allowed_values(MAX_CASE_SPLIT_VALUES)
+                            {
+                                let values = values.collect::<Vec<_>>();
+                                // Reversed, so that the smallest value is tried first.
+                                cases.extend(values.into_iter().rev().map(|value| {
+                                    let mut case = case.clone();
+                                    case.push((*poly_id, value));
+                                    case
+                                }));
+                            }
+                        }
+                    }
+                }
+            }
+            self.rollback();
+        }
+        assignments
+    }
+
+    /// Sets the split cells of a case of [Processor::enumerate_assignments] and propagates
+    /// their values through the given identities until no more progress is made.
+    /// Returns false if the case is inconsistent.
+    fn propagate_case(
+        &mut self,
+        row_index: usize,
+        identities: &[&'a Identity<Expression<T>>],
+        case: &[(PolyID, T)],
+    ) -> bool {
+        let mut split_cells = case.iter();
+        loop {
+            let mut progress = false;
+            for identity in identities {
+                match self.process_identity(row_index, identity, UnknownStrategy::Unknown) {
+                    Ok(result) => progress |= result.progress,
+                    Err(_) => return false,
+                }
+            }
+            if progress {
+                continue;
+            }
+            // The cells are split in the same order as they became the first unknown cell.
+            let Some((poly_id, value)) = split_cells.next() else {
+                return true;
+            };
+            self.record_for_transaction(row_index, *poly_id);
+            self.data[row_index][poly_id].value = CellValue::Known(*value);
+            self.data_version += 1;
+        }
+    }
+
+    /// Sets the given cell to a new value (regardless of its previous value) and checks
+    /// the given identities on all row pairs that contain the cell.
+    /// If the processor holds all rows of the trace, the last row is followed by the first
+    /// one, so identities that reference the next row are also checked on that row pair.
+    /// The new value is kept, even if some identities do not hold anymore.
+    /// Identities that still have unknown values are not reported.
+    pub fn override_and_check(
+        &mut self,
+        row_index: usize,
+        poly_id: &PolyID,
+        value: T,
+        identities: &[&'a Identity<Expression<T>>],
+    ) -> Result<(), Vec<RowCheckFailure<T>>> {
+        self.record_for_transaction(row_index, *poly_id);
+        self.data[row_index][poly_id].value = CellValue::Known(value);
+        self.data_version += 1;
+        let row = DegreeType::from(self.global_index(row_index));
+        if let Some(provenance) = &mut self.provenance {
+            provenance.insert((row, *poly_id), Provenance::Override);
+        }
+        if let Some(dependencies) = &mut self.dependencies {
+            dependencies.remove(&(row, *poly_id));
+        }
+
+        let wraps_around = self.data.len() as DegreeType == self.fixed_data.degree;
+        let next_row_index = |row_index: usize| match row_index + 1 {
+            next if next < self.data.len() => Some(next),
+            _ if wraps_around => Some(0),
+            _ => None,
+        };
+        let mut failures = vec![];
+        for identity in identities {
+            let has_next_reference = identity.contains_next_ref();
+            // With a next reference, the cell is also part of the previous row pair.
+            let mut first_rows = vec![row_index];
+            if has_next_reference {
+                match row_index.checked_sub(1) {
+                    Some(previous) => first_rows.insert(0, previous),
+                    None if wraps_around && self.data.len() > 1 => {
+                        first_rows.insert(0, self.data.len() - 1)
+                    }
+                    None => {}
+                }
+            }
+            for row_index in first_rows {
+                let global_row_index = self.global_index(row_index);
+                let row_pair = match (has_next_reference, next_row_index(row_index)) {
+                    (_, Some(next_row_index)) => RowPair::new(
+                        &self.data[row_index],
+                        &self.data[next_row_index],
+                        global_row_index,
+                        self.fixed_data,
+                        UnknownStrategy::Unknown,
+                    ),
+                    (false, None) => RowPair::from_single_row(
+                        &self.data[row_index],
+                        global_row_index,
+                        self.fixed_data,
+                        UnknownStrategy::Unknown,
+                    ),
+                    // There is no next row to check against.
+                    (true, None) => continue,
+                };
+                let mut identity_processor =
+                    IdentityProcessor::new(self.fixed_data, self.mutable_state);
+                if let Err(e) = identity_processor.process_identity(identity, &row_pair) {
+                    failures.push(RowCheckFailure {
+                        identity_id: identity.id,
+                        row: global_row_index.into(),
+                        message: e.to_string(),
+                        residual: identity_residual(identity, &row_pair),
+                    });
+                }
+            }
+        }
+
+        match failures.is_empty() {
+            true => Ok(()),
+            false => Err(failures),
+        }
+    }
+
+    /// Checks the identity on all rows (or row pairs, if it references the next row) using
+    /// [Processor::check_row_pair] and returns the (global) rows on which it does not hold.
+    pub fn failing_rows(&mut self, identity: &'a Identity<Expression<T>>) -> Vec<DegreeType> {
+        let has_next_reference = identity.contains_next_ref();
+        let first_row = usize::from(has_next_reference);
+        let mut failing_rows = vec![];
+        for row_index in first_row..self.data.len() {
+            let row = self.data[row_index].clone();
+            if !self.check_row_pair(
+                row_index,
+                &row,
+                identity,
+                has_next_reference,
+                UnknownStrategy::Zero,
+            ) {
+                let first_row_of_pair = row_index - first_row;
+                failing_rows.push(DegreeType::from(self.global_index(first_row_of_pair)));
+            }
+        }
+        failing_rows
+    }
+
+    /// Returns the IDs of the given identities that are likely redundant, i.e. implied by the
+    /// others: No cell was determined by them (according to the provenance) and they hold
+    /// on all rows (see [Processor::failing_rows]). This is a heuristic, since the
+    /// identities might still be needed for other inputs, but it helps to simplify PIL.
+    ///
+    /// # Panics
+    /// Panics if provenance tracking is disabled.
+    pub fn redundant_identities(&mut self, identities: &[&'a Identity<Expression<T>>]) -> Vec<u64> {
+        let solving_identities = self
+            .provenance
+            .as_ref()
+            .expect("Detecting redundant identities requires provenance tracking.")
+            .values()
+            .filter_map(|provenance| match provenance {
+                Provenance::Identity(id) => Some(*id),
+                _ => None,
+            })
+            .collect::<HashSet<_>>();
+        identities
+            .iter()
+            .filter(|identity| !solving_identities.contains(&identity.id))
+            .filter(|identity| self.failing_rows(identity).is_empty())
+            .map(|identity| identity.id)
+            .collect()
+    }
+
+    /// Checks whether a given identity is satisfied on a proposed row, evaluating unknown
+    /// cells according to `unknown_strategy` (which should not be [UnknownStrategy::Unknown]).
     pub fn check_row_pair(
         &mut self,
         row_index: usize,
@@ -489,7 +2482,12 @@ Known values in current row (local: {row_index}, global {global_row_index}):
         identity: &'a Identity<Expression<T>>,
         // This could be computed from the identity, but should be pre-computed for performance reasons.
         has_next_reference: bool,
+        unknown_strategy: UnknownStrategy,
     ) -> bool {
+        let global_row_index = self.global_index(row_index);
+        let previous_global_row_index = row_index
+            .checked_sub(1)
+            .map(|previous| self.global_index(previous));
         let mut identity_processor = IdentityProcessor::new(self.fixed_data, self.mutable_state);
         let row_pair = match has_next_reference {
             // Check whether identities with a reference to the next row are satisfied
@@ -499,9 +2497,9 @@ Known values in current row (local: {row_index}, global {global_row_index}):
                 RowPair::new(
                     &self.data[row_index - 1],
                     proposed_row,
-                    self.row_offset + (row_index - 1) as DegreeType,
+                    previous_global_row_index.unwrap(),
                     self.fixed_data,
-                    UnknownStrategy::Zero,
+                    unknown_strategy,
                 )
             }
             // Check whether identities without a reference to the next row are satisfied
@@ -509,9 +2507,9 @@ Known values in current row (local: {row_index}, global {global_row_index}):
             // Because we never access the next row, we can use [RowPair::from_single_row] here.
             false => RowPair::from_single_row(
                 proposed_row,
-                self.row_offset + row_index as DegreeType,
+                global_row_index,
                 self.fixed_data,
-                UnknownStrategy::Zero,
+                unknown_strategy,
             ),
         };
 
@@ -519,9 +2517,11 @@ Known values in current row (local: {row_index}, global {global_row_index}):
             .process_identity(identity, &row_pair)
             .is_err()
         {
-            log::debug!("Previous {:?}", &self.data[row_index - 1]);
-            log::debug!("Proposed {:?}", proposed_row);
-            log::debug!("Failed on identity: {}", identity);
+            if row_index > 0 {
+                log::debug!("{}Previous {:?}", self.log_scope, &self.data[row_index - 1]);
+            }
+            log::debug!("{}Proposed {proposed_row:?}", self.log_scope);
+            log::debug!("{}Failed on identity: {identity}", self.log_scope);
 
             return false;
         }
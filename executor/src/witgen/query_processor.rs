@@ -3,7 +3,7 @@ use std::sync::Arc;
 use powdr_ast::analyzed::Challenge;
 use powdr_ast::analyzed::{AlgebraicReference, Expression, PolyID, PolynomialType};
 use powdr_ast::parsed::types::Type;
-use powdr_number::{BigInt, FieldElement};
+use powdr_number::{BigInt, DegreeType, FieldElement};
 use powdr_pil_analyzer::evaluator::{self, Definitions, EvalError, SymbolLookup, Value};
 
 use super::{rows::RowPair, Constraint, EvalResult, EvalValue, FixedData, IncompleteCause};
@@ -11,13 +11,13 @@ use super::{rows::RowPair, Constraint, EvalResult, EvalValue, FixedData, Incompl
 /// Computes value updates that result from a query.
 pub struct QueryProcessor<'a, 'b, T: FieldElement, QueryCallback: Send + Sync> {
     fixed_data: &'a FixedData<'a, T>,
-    query_callback: &'b mut QueryCallback,
+    query_callback: &'b QueryCallback,
 }
 
 impl<'a, 'b, T: FieldElement, QueryCallback: super::QueryCallback<T>>
     QueryProcessor<'a, 'b, T, QueryCallback>
 {
-    pub fn new(fixed_data: &'a FixedData<'a, T>, query_callback: &'b mut QueryCallback) -> Self {
+    pub fn new(fixed_data: &'a FixedData<'a, T>, query_callback: &'b QueryCallback) -> Self {
         Self {
             fixed_data,
             query_callback,
@@ -66,6 +66,19 @@ impl<'a, 'b, T: FieldElement, QueryCallback: super::QueryCallback<T>>
             if let Some(value) =
                 (self.query_callback)(&query_str).map_err(super::EvalError::ProverQueryError)?
             {
+                let is_valid = self
+                    .fixed_data
+                    .input_validators
+                    .iter()
+                    .filter(|(poly_id, _)| *poly_id == poly.poly_id)
+                    .all(|(_, validator)| validator(value));
+                if !is_valid {
+                    return Err(super::EvalError::InvalidInput {
+                        column: poly.name.clone(),
+                        row: DegreeType::from(rows.current_row_index),
+                        value,
+                    });
+                }
                 EvalValue::complete(vec![(poly, Constraint::Assignment(value))])
             } else {
                 EvalValue::incomplete(IncompleteCause::NoQueryAnswer(
@@ -144,9 +157,8 @@ impl<'a, T: FieldElement> SymbolLookup<'a, T> for Symbols<'a, T> {
                 .get_value(poly_ref)
                 .ok_or(EvalError::DataNotAvailable)?,
             PolynomialType::Constant => {
-                let values = self.fixed_data.fixed_cols[&poly_ref.poly_id].values;
                 let row = self.rows.current_row_index + if poly_ref.next { 1 } else { 0 };
-                values[usize::from(row)]
+                self.fixed_data.fixed_cols[&poly_ref.poly_id].value(DegreeType::from(row))
             }
         })
         .into())
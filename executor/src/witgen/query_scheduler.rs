@@ -0,0 +1,56 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use super::QueryCallback;
+
+/// Determines the timing of query callbacks when checking that witness generation does
+/// not depend on it (see [super::WitnessGenerator::check_query_determinism]).
+pub trait QueryScheduler: Send + Sync {
+    /// Returns how long to delay the `query_index`th query callback of the given run.
+    fn delay(&self, run: usize, query_index: usize) -> Duration;
+}
+
+/// Delays each query callback by a pseudo-random duration below `max_delay`, which is
+/// determined by the seed and differs from run to run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RandomDelays {
+    pub seed: u64,
+    pub max_delay: Duration,
+}
+
+impl QueryScheduler for RandomDelays {
+    fn delay(&self, run: usize, query_index: usize) -> Duration {
+        // SplitMix64, seeded with the run and the query index.
+        let mut z = self.seed
+            ^ (run as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+            ^ (query_index as u64).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        let max_nanos = self.max_delay.as_nanos().max(1) as u64;
+        Duration::from_nanos(z % max_nanos)
+    }
+}
+
+/// Wraps a query callback such that each invocation runs on a separate thread,
+/// after the delay chosen by the scheduler.
+pub fn scheduled_query_callback<'a, T: Send>(
+    query_callback: &'a dyn QueryCallback<T>,
+    scheduler: &'a dyn QueryScheduler,
+    run: usize,
+) -> impl QueryCallback<T> + 'a {
+    let query_count = AtomicUsize::new(0);
+    move |query: &str| {
+        let query_index = query_count.fetch_add(1, Ordering::Relaxed);
+        let delay = scheduler.delay(run, query_index);
+        std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    std::thread::sleep(delay);
+                    query_callback(query)
+                })
+                .join()
+                .unwrap()
+        })
+    }
+}
@@ -49,6 +49,12 @@ impl<T: FieldElement> RangeConstraint<T> {
         }
     }
 
+    /// Constraint with the given mask and range (see [RangeConstraint::mask] and
+    /// [RangeConstraint::range]), e.g. to restore a constraint exactly as it was.
+    pub fn from_mask_and_range(mask: T::Integer, min: T, max: T) -> Self {
+        Self { mask, min, max }
+    }
+
     /// Constraint that allows values from min to max.
     /// If min <= max, this means min <= x && x <= max.
     /// If min > max, this means min <= x || x <= max.
@@ -82,6 +88,34 @@ impl<T: FieldElement> RangeConstraint<T> {
         range_width(self.min, self.max)
     }
 
+    /// Returns whether the constraint allows the given value.
+    pub fn allows_value(&self, value: T) -> bool {
+        let in_range = if self.min <= self.max {
+            self.min <= value && value <= self.max
+        } else {
+            self.min <= value || value <= self.max
+        };
+        in_range && value.to_integer() & self.mask == value.to_integer()
+    }
+
+    /// Returns whether there is definitely no value allowed by both constraints.
+    /// Only the ranges are compared, the masks are ignored.
+    pub fn is_disjoint(&self, other: &Self) -> bool {
+        interval_intersection((self.min, self.max), (other.min, other.max)).is_none()
+    }
+
+    /// Returns the values allowed by the constraint, or None if (an upper bound for)
+    /// their number is larger than `max_count`.
+    pub fn allowed_values(&self, max_count: u64) -> Option<impl Iterator<Item = T> + '_> {
+        let width = self.range_width();
+        (width <= max_count.into()).then(move || {
+            (0..max_count)
+                .take_while(move |k| T::Integer::from(*k) < width)
+                .map(move |k| self.min + T::from(k))
+                .filter(move |v| v.to_integer() & self.mask == v.to_integer())
+        })
+    }
+
     /// The range constraint of the sum of two expressions.
     pub fn combine_sum(&self, other: &Self) -> Self {
         // TODO we could use "add_with_carry" to see if this created an overflow.
@@ -272,6 +306,32 @@ mod test {
         );
     }
 
+    #[test]
+    fn allows_value() {
+        let rc = RCg::from_range(3.into(), 9.into());
+        assert!(rc.allows_value(3.into()));
+        assert!(rc.allows_value(9.into()));
+        assert!(!rc.allows_value(2.into()));
+        assert!(!rc.allows_value(10.into()));
+        // Wrapping range
+        let rc = RCg::from_range(9.into(), 3.into());
+        assert!(rc.allows_value(10.into()));
+        assert!(rc.allows_value(2.into()));
+        assert!(!rc.allows_value(5.into()));
+        // Mask
+        let rc = RCg::from_mask(0xf0u32);
+        assert!(rc.allows_value(0x30.into()));
+        assert!(!rc.allows_value(0x31.into()));
+    }
+
+    #[test]
+    fn is_disjoint() {
+        let rc = RCg::from_range(3.into(), 9.into());
+        assert!(rc.is_disjoint(&RCg::from_range(10.into(), 20.into())));
+        assert!(!rc.is_disjoint(&RCg::from_range(9.into(), 20.into())));
+        assert!(!rc.is_disjoint(&RCg::from_range(20.into(), 4.into())));
+    }
+
     #[test]
     fn from_range() {
         assert_eq!(
@@ -0,0 +1,81 @@
+use std::fmt::{self, Display};
+use std::io::Read;
+
+use powdr_number::{read_polys_file, DegreeType, FieldElement};
+
+/// A cell in which a generated witness differs from a reference witness.
+/// A value of `None` means that the row does not exist in the respective witness.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CellDifference<T> {
+    pub column: String,
+    pub row: DegreeType,
+    pub expected: Option<T>,
+    pub actual: Option<T>,
+}
+
+impl<T: Display> Display for CellDifference<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let render = |value: &Option<T>| match value {
+            Some(value) => value.to_string(),
+            None => "<missing>".to_string(),
+        };
+        write!(
+            f,
+            "{} (Row {}): expected {}, got {}",
+            self.column,
+            self.row,
+            render(&self.expected),
+            render(&self.actual)
+        )
+    }
+}
+
+/// Compares a generated witness against a reference witness in the format written by
+/// [powdr_number::write_polys_file], which is assumed to contain the same columns in the
+/// same order. Returns the first `max_differences` differing cells, in row-major order,
+/// i.e. an empty list if the witnesses match.
+pub fn compare_with_reference<T: FieldElement>(
+    witness: &[(String, Vec<T>)],
+    reference: &mut impl Read,
+    max_differences: usize,
+) -> Vec<CellDifference<T>> {
+    if witness.is_empty() {
+        return vec![];
+    }
+    let column_names = witness
+        .iter()
+        .map(|(name, _)| name.clone())
+        .collect::<Vec<_>>();
+    let (reference, _) = read_polys_file::<T>(reference, &column_names);
+    compare_witnesses(witness, &reference, max_differences)
+}
+
+/// Compares a witness against a reference witness with the same columns in the same order.
+/// Returns the first `max_differences` differing cells, in row-major order.
+pub(super) fn compare_witnesses<T: FieldElement>(
+    witness: &[(String, Vec<T>)],
+    reference: &[(String, Vec<T>)],
+    max_differences: usize,
+) -> Vec<CellDifference<T>> {
+    let degree = witness
+        .iter()
+        .chain(reference)
+        .map(|(_, values)| values.len())
+        .max()
+        .unwrap_or_default();
+
+    (0..degree)
+        .flat_map(|row| (0..witness.len()).map(move |column| (row, column)))
+        .filter_map(|(row, column)| {
+            let expected = reference[column].1.get(row).copied();
+            let actual = witness[column].1.get(row).copied();
+            (expected != actual).then(|| CellDifference {
+                column: witness[column].0.clone(),
+                row: row as DegreeType,
+                expected,
+                actual,
+            })
+        })
+        .take(max_differences)
+        .collect()
+}
@@ -0,0 +1,200 @@
+use std::collections::BTreeSet;
+use std::fmt::{self, Display};
+
+use powdr_ast::analyzed::{
+    AlgebraicExpression as Expression, Analyzed, Identity, IdentityKind, PolynomialType,
+};
+use powdr_number::{DegreeType, FieldElement};
+
+use super::machines::machine_extractor::refs_in_identity;
+use super::plan::{plan_report, MachinePlan};
+use super::processor::RowCheckFailure;
+use super::rows::{Row, RowIndex, RowPair, UnknownStrategy};
+use super::{identities_for_stage, FieldBackend, FixedData, WitgenOptions};
+
+/// The residuals of the polynomial identities that do not hold on a witness,
+/// in the order of rows and then identities.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResidualReport<T> {
+    pub failures: Vec<RowCheckFailure<T>>,
+}
+
+impl<T: FieldElement> ResidualReport<T> {
+    /// Returns the largest magnitude of a residual (see [residual_magnitude]).
+    pub fn max_magnitude(&self) -> Option<T> {
+        self.failures
+            .iter()
+            .filter_map(|failure| failure.residual)
+            .map(residual_magnitude)
+            .max_by_key(|magnitude| magnitude.to_arbitrary_integer())
+    }
+}
+
+impl<T: FieldElement> Display for ResidualReport<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for failure in &self.failures {
+            write!(
+                f,
+                "Identity {} (Row {}): ",
+                failure.identity_id, failure.row
+            )?;
+            match failure.residual {
+                Some(residual) if residual.is_in_lower_half() => writeln!(f, "{residual}")?,
+                Some(residual) => writeln!(f, "-{}", -residual)?,
+                None => writeln!(f, "{}", failure.message)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the distance of a residual to zero, i.e. negative values (in relation to 0
+/// in the field) are negated.
+pub fn residual_magnitude<T: FieldElement>(residual: T) -> T {
+    if residual.is_in_lower_half() {
+        residual
+    } else {
+        -residual
+    }
+}
+
+/// Evaluates the polynomial identities on all row pairs of a complete witness and
+/// reports the residual (left - right) of each identity that does not hold.
+/// Lookups and permutations are not checked.
+/// The identities are evaluated in one batch by [FieldBackend::identity_residuals].
+pub fn constraint_residuals<T: FieldElement>(
+    analyzed: &Analyzed<T>,
+    fixed_col_values: &[(String, Vec<T>)],
+    witness: &[(String, Vec<T>)],
+    field_backend: &dyn FieldBackend<T>,
+) -> ResidualReport<T> {
+    let fixed_data = FixedData::new(analyzed, fixed_col_values, witness, Default::default(), 0)
+        .with_options(WitgenOptions {
+            field_backend,
+            ..Default::default()
+        });
+    let identities = identities_for_stage(analyzed, 0)
+        .into_iter()
+        .filter(|identity| identity.kind == IdentityKind::Polynomial)
+        .collect::<Vec<_>>();
+    residuals_of_identities(&fixed_data, &identities)
+}
+
+/// Like [constraint_residuals], but only checks the identities of the machines
+/// (as detected by [plan_report]) selected by `machine_filter`, e.g. to validate
+/// the part of the witness covered by a partial proof.
+/// Identities that are not processed by any machine (e.g. because they were turned into
+/// global range constraints) are checked if they only reference witness columns of the
+/// selected machines.
+pub fn machine_constraint_residuals<T: FieldElement>(
+    analyzed: &Analyzed<T>,
+    fixed_col_values: &[(String, Vec<T>)],
+    witness: &[(String, Vec<T>)],
+    field_backend: &dyn FieldBackend<T>,
+    machine_filter: impl Fn(&MachinePlan) -> bool,
+) -> ResidualReport<T> {
+    let fixed_data = FixedData::new(analyzed, fixed_col_values, witness, Default::default(), 0)
+        .with_options(WitgenOptions {
+            field_backend,
+            ..Default::default()
+        });
+    let plan = plan_report(analyzed);
+    let machine_identities = plan
+        .machines
+        .iter()
+        .flat_map(|machine| &machine.identities)
+        .collect::<BTreeSet<_>>();
+    let selected_machines = plan
+        .machines
+        .iter()
+        .filter(|machine| machine_filter(machine))
+        .collect::<Vec<_>>();
+    let selected_identities = selected_machines
+        .iter()
+        .flat_map(|machine| &machine.identities)
+        .collect::<BTreeSet<_>>();
+    let selected_witnesses = selected_machines
+        .iter()
+        .flat_map(|machine| &machine.witness_columns)
+        .map(|name| name.as_str())
+        .collect::<BTreeSet<_>>();
+
+    let identities = identities_for_stage(analyzed, 0)
+        .into_iter()
+        .filter(|identity| identity.kind == IdentityKind::Polynomial)
+        .filter(|identity| {
+            if machine_identities.contains(&identity.id) {
+                return selected_identities.contains(&identity.id);
+            }
+            let witnesses = refs_in_identity(identity)
+                .into_iter()
+                .filter(|poly_id| poly_id.ptype == PolynomialType::Committed)
+                .collect::<Vec<_>>();
+            !witnesses.is_empty()
+                && witnesses
+                    .iter()
+                    .all(|poly_id| selected_witnesses.contains(fixed_data.column_name(poly_id)))
+        })
+        .collect::<Vec<_>>();
+    residuals_of_identities(&fixed_data, &identities)
+}
+
+fn residuals_of_identities<T: FieldElement>(
+    fixed_data: &FixedData<T>,
+    identities: &[Identity<Expression<T>>],
+) -> ResidualReport<T> {
+    let rows = (0..fixed_data.degree)
+        .map(|i| Row::fresh(fixed_data, RowIndex::from_degree(i, fixed_data.degree)))
+        .collect::<Vec<_>>();
+
+    let row_pairs = (0..rows.len())
+        .map(|row_index| {
+            RowPair::new(
+                &rows[row_index],
+                &rows[(row_index + 1) % rows.len()],
+                RowIndex::from_degree(row_index as DegreeType, fixed_data.degree),
+                fixed_data,
+                UnknownStrategy::Unknown,
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let failures = fixed_data
+        .options
+        .field_backend
+        .identity_residuals(identities, &row_pairs)
+        .into_iter()
+        .enumerate()
+        .flat_map(|(row_index, residuals)| {
+            identities
+                .iter()
+                .zip(residuals)
+                .filter_map(|(identity, residual)| {
+                    let residual = residual?;
+                    (!residual.is_zero()).then(|| RowCheckFailure {
+                        identity_id: identity.id,
+                        row: row_index as DegreeType,
+                        message: format!("{identity} does not hold."),
+                        residual: Some(residual),
+                    })
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    ResidualReport { failures }
+}
+
+/// Returns the value of `left - right` of a polynomial identity on the row pair,
+/// or None if the identity is not a polynomial identity or references unknown cells.
+pub(super) fn identity_residual<T: FieldElement>(
+    identity: &Identity<Expression<T>>,
+    row_pair: &RowPair<'_, '_, T>,
+) -> Option<T> {
+    if identity.kind != IdentityKind::Polynomial {
+        return None;
+    }
+    row_pair
+        .evaluate(identity.expression_for_poly_id())
+        .ok()?
+        .constant_value()
+}
@@ -0,0 +1,43 @@
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+
+use powdr_number::{DegreeType, FieldElement};
+
+use super::rows::RowPair;
+
+type TagPredicate<'b, T> = dyn for<'row, 'a> Fn(&RowPair<'row, 'a, T>) -> bool + Send + Sync + 'b;
+
+/// Tags "interesting" rows of the main machine during witness generation, e.g. rows in
+/// which a certain instruction is executed, so that they can be found quickly in a large
+/// trace. The predicate is called on each completed row (together with the next row)
+/// and the rows for which it returns true can be retrieved after generation.
+pub struct RowTagger<'b, T: FieldElement> {
+    predicate: Box<TagPredicate<'b, T>>,
+    tagged_rows: Mutex<BTreeSet<DegreeType>>,
+}
+
+impl<'b, T: FieldElement> RowTagger<'b, T> {
+    pub fn new(
+        predicate: impl for<'row, 'a> Fn(&RowPair<'row, 'a, T>) -> bool + Send + Sync + 'b,
+    ) -> Self {
+        Self {
+            predicate: Box::new(predicate),
+            tagged_rows: Default::default(),
+        }
+    }
+
+    /// Evaluates the predicate on the row pair and tags the current row if it holds.
+    pub fn tag(&self, row_pair: &RowPair<'_, '_, T>) {
+        if (self.predicate)(row_pair) {
+            self.tagged_rows
+                .lock()
+                .unwrap()
+                .insert(row_pair.current_row_index.into());
+        }
+    }
+
+    /// Returns the (global) indices of the tagged rows.
+    pub fn tagged_rows(&self) -> BTreeSet<DegreeType> {
+        self.tagged_rows.lock().unwrap().clone()
+    }
+}
@@ -1,11 +1,14 @@
 use std::{
     collections::HashSet,
     fmt::Debug,
+    marker::PhantomData,
     ops::{Add, Sub},
 };
 
 use itertools::Itertools;
-use powdr_ast::analyzed::{AlgebraicExpression as Expression, AlgebraicReference, PolyID};
+use powdr_ast::analyzed::{
+    AlgebraicExpression as Expression, AlgebraicReference, PolyID, PolynomialType,
+};
 use powdr_number::{DegreeType, FieldElement};
 
 use crate::witgen::Constraint;
@@ -13,17 +16,17 @@ use crate::witgen::Constraint;
 use super::{
     affine_expression::{AffineExpression, AffineResult},
     data_structures::column_map::WitnessColumnMap,
-    expression_evaluator::ExpressionEvaluator,
+    expression_evaluator::{EvaluationCache, ExpressionEvaluator},
     global_constraints::RangeConstraintSet,
     range_constraints::RangeConstraint,
     symbolic_witness_evaluator::{SymbolicWitnessEvaluator, WitnessColumnEvaluator},
-    FixedData,
+    FixedData, TieBreaker,
 };
 
 /// A small wrapper around a row index, which knows the total number of rows.
 /// When converted to DegreeType or usize, it will be reduced modulo the number of rows
 /// (handling negative indices as well).
-#[derive(Clone, Copy, PartialOrd, PartialEq, Eq, Ord)]
+#[derive(Clone, Copy, Debug, PartialOrd, PartialEq, Eq, Ord, Hash)]
 pub struct RowIndex {
     index: i64,
     num_rows: DegreeType,
@@ -59,6 +62,22 @@ impl RowIndex {
         }
     }
 
+    /// Adds `rhs` to the index, returning None on overflow.
+    pub fn checked_add(self, rhs: usize) -> Option<RowIndex> {
+        Some(RowIndex {
+            index: self.index.checked_add(i64::try_from(rhs).ok()?)?,
+            num_rows: self.num_rows,
+        })
+    }
+
+    /// Returns whether the index wraps around at most to the first row after the last row
+    /// (i.e. `-num_rows < index <= num_rows`), as happens when accessing the next row of
+    /// the last row. Indices further out point to rows of a different cycle.
+    pub fn is_within_degree(&self) -> bool {
+        let num_rows = i64::try_from(self.num_rows).unwrap();
+        -num_rows < self.index && self.index <= num_rows
+    }
+
     /// Compute the current row index as usize, assuming index 0 is the given row offset.
     pub fn to_local(self, row_offset: &RowIndex) -> usize {
         let row_index = DegreeType::from(self);
@@ -145,7 +164,7 @@ impl<T: FieldElement> CellValue<T> {
             (CellValue::RangeConstraint(current), Constraint::RangeConstraint(c)) => {
                 let new = c.conjunction(current);
                 assert!(new != *current, "Range constraint was already set");
-                log::trace!("         (the conjunction is {})", new);
+                log::trace!("         (the conjunction is {new})");
                 CellValue::RangeConstraint(new)
             }
             (CellValue::Unknown, Constraint::RangeConstraint(c)) => {
@@ -155,6 +174,54 @@ impl<T: FieldElement> CellValue<T> {
     }
 }
 
+/// The value of a cell of a [Row]. Rows and [RowPair]s are generic over it, so that
+/// alternative value domains (e.g. for abstract interpretation of PIL) can reuse the
+/// evaluation of identities. Witness generation uses [CellValue].
+pub trait RowValue<T: FieldElement>: Clone {
+    /// Returns the value of a cell in a fresh row, given its externally provided value
+    /// and its global range constraint.
+    fn fresh(external_value: Option<T>, range_constraint: Option<&RangeConstraint<T>>) -> Self;
+
+    /// Returns the concrete value of the cell, if it is known.
+    fn value(&self) -> Option<T>;
+
+    fn range_constraint(&self) -> Option<RangeConstraint<T>>;
+
+    /// Returns the cell value after applying the update.
+    ///
+    /// # Panics
+    /// Panics if the update is not an improvement.
+    fn update_with(&self, c: &Constraint<T>) -> Self;
+}
+
+impl<T: FieldElement> RowValue<T> for CellValue<T> {
+    fn fresh(external_value: Option<T>, range_constraint: Option<&RangeConstraint<T>>) -> Self {
+        match (external_value, range_constraint) {
+            (Some(external_value), _) => CellValue::Known(external_value),
+            (None, Some(range_constraint)) => CellValue::RangeConstraint(range_constraint.clone()),
+            (None, None) => CellValue::Unknown,
+        }
+    }
+
+    fn value(&self) -> Option<T> {
+        match self {
+            CellValue::Known(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    fn range_constraint(&self) -> Option<RangeConstraint<T>> {
+        match self {
+            CellValue::RangeConstraint(c) => Some(c.clone()),
+            _ => None,
+        }
+    }
+
+    fn update_with(&self, c: &Constraint<T>) -> Self {
+        CellValue::update_with(self, c)
+    }
+}
+
 impl<T: FieldElement> From<CellValue<T>> for Option<T> {
     fn from(val: CellValue<T>) -> Self {
         match val {
@@ -166,13 +233,22 @@ impl<T: FieldElement> From<CellValue<T>> for Option<T> {
 
 /// A single cell, holding an optional value and range constraint.
 #[derive(Clone)]
-pub struct Cell<'a, T: FieldElement> {
+pub struct Cell<'a, T: FieldElement, V = CellValue<T>> {
     /// The column name, for debugging purposes.
     pub name: &'a str,
-    pub value: CellValue<T>,
+    pub value: V,
+    _marker: PhantomData<T>,
 }
 
-impl<'a, T: FieldElement> Cell<'a, T> {
+impl<'a, T: FieldElement, V: RowValue<T>> Cell<'a, T, V> {
+    pub fn new(name: &'a str, value: V) -> Self {
+        Self {
+            name,
+            value,
+            _marker: PhantomData,
+        }
+    }
+
     /// Applies the new range constraint or new value to this cell.
     ///
     /// # Panics
@@ -196,7 +272,7 @@ impl<T: FieldElement> Debug for Cell<'_, T> {
 }
 
 /// A row of cells, indexed by polynomial ID.
-pub type Row<'a, T> = WitnessColumnMap<Cell<'a, T>>;
+pub type Row<'a, T, V = CellValue<T>> = WitnessColumnMap<Cell<'a, T, V>>;
 
 impl<T: FieldElement> Debug for Row<'_, T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -204,9 +280,9 @@ impl<T: FieldElement> Debug for Row<'_, T> {
     }
 }
 
-impl<'a, T: FieldElement> Row<'a, T> {
-    /// Creates a "fresh" row, i.e., one that is empty but initialized with the global range constraints.
-    pub fn fresh(fixed_data: &'a FixedData<'a, T>, row: RowIndex) -> Row<'a, T> {
+impl<'a, T: FieldElement, V: RowValue<T>> Row<'a, T, V> {
+    /// Like [Row::fresh], but for an alternative value domain.
+    pub fn fresh_with_domain(fixed_data: &'a FixedData<'a, T>, row: RowIndex) -> Row<'a, T, V> {
         WitnessColumnMap::from(
             fixed_data
                 .global_range_constraints()
@@ -214,20 +290,21 @@ impl<'a, T: FieldElement> Row<'a, T> {
                 .iter()
                 .map(|(poly_id, range_constraint)| {
                     let name = fixed_data.column_name(&poly_id);
-                    let value = match (
+                    let value = V::fresh(
                         fixed_data.external_witness(row.into(), &poly_id),
                         range_constraint.as_ref(),
-                    ) {
-                        (Some(external_witness), _) => CellValue::Known(external_witness),
-                        (None, Some(range_constraint)) => {
-                            CellValue::RangeConstraint(range_constraint.clone())
-                        }
-                        (None, None) => CellValue::Unknown,
-                    };
-                    Cell { name, value }
+                    );
+                    Cell::new(name, value)
                 }),
         )
     }
+}
+
+impl<'a, T: FieldElement> Row<'a, T> {
+    /// Creates a "fresh" row, i.e., one that is empty but initialized with the global range constraints.
+    pub fn fresh(fixed_data: &'a FixedData<'a, T>, row: RowIndex) -> Row<'a, T> {
+        Self::fresh_with_domain(fixed_data, row)
+    }
 
     /// Builds a string representing the current row
     pub fn render(&self, title: &str, include_unknown: bool, cols: &HashSet<PolyID>) -> String {
@@ -264,6 +341,20 @@ impl<'a, T: FieldElement> Row<'a, T> {
             .map(|(_, cell)| format!("    {cell:?}"))
             .join("\n")
     }
+
+    /// Returns the names and values of the known cells of the given columns, in the
+    /// order of [Self::render_values].
+    pub fn known_values(&self, cols: &HashSet<PolyID>) -> Vec<(String, T)> {
+        self.iter()
+            .filter(|(col, _)| cols.contains(col))
+            .filter_map(|(i, cell)| match cell.value {
+                CellValue::Known(v) => Some((v.is_zero(), i, cell.name, v)),
+                _ => None,
+            })
+            .sorted_by_key(|(is_zero, i, _, _)| (*is_zero, *i))
+            .map(|(_, _, name, value)| (name.to_string(), value))
+            .collect()
+    }
 }
 
 impl<T: FieldElement> From<Row<'_, T>> for WitnessColumnMap<T> {
@@ -277,16 +368,16 @@ impl<T: FieldElement> From<Row<'_, T>> for WitnessColumnMap<T> {
 }
 
 /// A pair of mutable row references which knows how to apply updates.
-pub struct RowUpdater<'row, 'a, T: FieldElement> {
-    current: &'row mut Row<'a, T>,
-    next: &'row mut Row<'a, T>,
+pub struct RowUpdater<'row, 'a, T: FieldElement, V = CellValue<T>> {
+    current: &'row mut Row<'a, T, V>,
+    next: &'row mut Row<'a, T, V>,
     current_row_index: RowIndex,
 }
 
-impl<'row, 'a, T: FieldElement> RowUpdater<'row, 'a, T> {
+impl<'row, 'a, T: FieldElement, V: RowValue<T>> RowUpdater<'row, 'a, T, V> {
     pub fn new(
-        current: &'row mut Row<'a, T>,
-        next: &'row mut Row<'a, T>,
+        current: &'row mut Row<'a, T, V>,
+        next: &'row mut Row<'a, T, V>,
         current_row_index: RowIndex,
     ) -> Self {
         Self {
@@ -318,7 +409,7 @@ impl<'row, 'a, T: FieldElement> RowUpdater<'row, 'a, T> {
         self.get_cell_mut(poly).apply_update(c);
     }
 
-    fn get_cell_mut<'b>(&'b mut self, poly: &AlgebraicReference) -> &'b mut Cell<'a, T> {
+    fn get_cell_mut<'b>(&'b mut self, poly: &AlgebraicReference) -> &'b mut Cell<'a, T, V> {
         match poly.next {
             false => &mut self.current[&poly.poly_id],
             true => &mut self.next[&poly.poly_id],
@@ -339,22 +430,27 @@ pub enum UnknownStrategy {
     Zero,
     /// If a value is unknown, leave it unknown.
     Unknown,
+    /// If a value is unknown, evaluate it to a pseudo-random value determined by the seed,
+    /// the column and the row (see [TieBreaker]). Identities that hold with
+    /// [UnknownStrategy::Zero] but not with random values reveal columns that are not
+    /// determined by the identities.
+    Random(u64),
 }
 
 /// A pair of row references which knows which value / range constraint
 /// to return for a given [AlgebraicReference].
-pub struct RowPair<'row, 'a, T: FieldElement> {
-    pub current: &'row Row<'a, T>,
-    pub next: Option<&'row Row<'a, T>>,
+pub struct RowPair<'row, 'a, T: FieldElement, V = CellValue<T>> {
+    pub current: &'row Row<'a, T, V>,
+    pub next: Option<&'row Row<'a, T, V>>,
     pub current_row_index: RowIndex,
     fixed_data: &'a FixedData<'a, T>,
     unknown_strategy: UnknownStrategy,
 }
-impl<'row, 'a, T: FieldElement> RowPair<'row, 'a, T> {
+impl<'row, 'a, T: FieldElement, V: RowValue<T>> RowPair<'row, 'a, T, V> {
     /// Creates a new row pair.
     pub fn new(
-        current: &'row Row<'a, T>,
-        next: &'row Row<'a, T>,
+        current: &'row Row<'a, T, V>,
+        next: &'row Row<'a, T, V>,
         current_row_index: RowIndex,
         fixed_data: &'a FixedData<'a, T>,
         unknown_strategy: UnknownStrategy,
@@ -370,7 +466,7 @@ impl<'row, 'a, T: FieldElement> RowPair<'row, 'a, T> {
 
     /// Creates a new row pair from a single row, setting the next row to None.
     pub fn from_single_row(
-        current: &'row Row<'a, T>,
+        current: &'row Row<'a, T, V>,
         current_row_index: RowIndex,
         fixed_data: &'a FixedData<'a, T>,
         unknown_strategy: UnknownStrategy,
@@ -389,7 +485,7 @@ impl<'row, 'a, T: FieldElement> RowPair<'row, 'a, T> {
     /// # Panics
     /// Panics if the next row is accessed but the row pair has been constructed with
     /// [RowPair::from_single_row].
-    fn get_cell(&self, poly: &AlgebraicReference) -> &Cell<T> {
+    fn get_cell(&self, poly: &AlgebraicReference) -> &Cell<T, V> {
         match (poly.next, self.next.as_ref()) {
             (false, _) => &self.current[&poly.poly_id],
             (true, Some(next)) => &next[&poly.poly_id],
@@ -397,12 +493,24 @@ impl<'row, 'a, T: FieldElement> RowPair<'row, 'a, T> {
         }
     }
 
+    /// Returns the reference to the current row of the witness column with the given name.
+    pub fn witness_reference(&self, name: &str) -> Option<&'a AlgebraicReference> {
+        self.fixed_data
+            .try_column_by_name(name)
+            .filter(|poly_id| poly_id.ptype == PolynomialType::Committed)
+            .map(|poly_id| &self.fixed_data.witness_cols[&poly_id].poly)
+    }
+
     pub fn get_value(&self, poly: &AlgebraicReference) -> Option<T> {
-        match self.get_cell(poly).value {
-            CellValue::Known(value) => Some(value),
-            _ => match self.unknown_strategy {
+        match self.get_cell(poly).value.value() {
+            Some(value) => Some(value),
+            None => match self.unknown_strategy {
                 UnknownStrategy::Zero => Some(T::zero()),
                 UnknownStrategy::Unknown => None,
+                UnknownStrategy::Random(seed) => {
+                    let row = DegreeType::from(self.current_row_index + poly.next as DegreeType);
+                    Some(TieBreaker::new(seed).choose(&poly.poly_id, row))
+                }
             },
         }
     }
@@ -416,11 +524,29 @@ impl<'row, 'a, T: FieldElement> RowPair<'row, 'a, T> {
             self.current_row_index.into(),
             self,
         ))
+        .with_backend(self.fixed_data.options.field_backend)
         .evaluate(expr)
     }
+
+    /// Like [RowPair::evaluate], but reuses the results of sub-expressions that were already
+    /// evaluated with the same cache (see [ExpressionEvaluator::evaluate_cached]).
+    /// The cache must not be used with other row pairs or after the rows changed.
+    pub fn evaluate_cached<'b>(
+        &self,
+        expr: &'b Expression<T>,
+        cache: &mut EvaluationCache<'b, T>,
+    ) -> AffineResult<&'b AlgebraicReference, T> {
+        ExpressionEvaluator::new(SymbolicWitnessEvaluator::new(
+            self.fixed_data,
+            self.current_row_index.into(),
+            self,
+        ))
+        .with_backend(self.fixed_data.options.field_backend)
+        .evaluate_cached(expr, cache)
+    }
 }
 
-impl<T: FieldElement> WitnessColumnEvaluator<T> for RowPair<'_, '_, T> {
+impl<T: FieldElement, V: RowValue<T>> WitnessColumnEvaluator<T> for RowPair<'_, '_, T, V> {
     fn value<'b>(&self, poly: &'b AlgebraicReference) -> AffineResult<&'b AlgebraicReference, T> {
         Ok(match self.get_value(poly) {
             Some(v) => v.into(),
@@ -429,11 +555,10 @@ impl<T: FieldElement> WitnessColumnEvaluator<T> for RowPair<'_, '_, T> {
     }
 }
 
-impl<T: FieldElement> RangeConstraintSet<&AlgebraicReference, T> for RowPair<'_, '_, T> {
+impl<T: FieldElement, V: RowValue<T>> RangeConstraintSet<&AlgebraicReference, T>
+    for RowPair<'_, '_, T, V>
+{
     fn range_constraint(&self, poly: &AlgebraicReference) -> Option<RangeConstraint<T>> {
-        match self.get_cell(poly).value {
-            CellValue::RangeConstraint(ref c) => Some(c.clone()),
-            _ => None,
-        }
+        self.get_cell(poly).value.range_constraint()
     }
 }
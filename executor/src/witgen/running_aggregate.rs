@@ -0,0 +1,64 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use powdr_number::{DegreeType, FieldElement};
+
+use super::{AffineExpression, ClosureConstraint, EvalError, EvalValue, IncompleteCause};
+
+/// Returns a closure constraint that fills the witness column `target` with the running
+/// aggregate of the witness column `source`, i.e. `target` is `source` in the first row
+/// of each block and `fold(target of the previous row, source)` in all other rows.
+/// Blocks have `block_size` rows (the whole column if None), so block-local columns
+/// start over at each block. Since the values are computed during witness generation,
+/// identities can reference `target` like any other column.
+///
+/// The aggregate of the previous row is kept by the constraint, so it relies on the rows
+/// being processed in order, as in the main machine.
+pub fn running_aggregate<'b, T: FieldElement>(
+    source: &str,
+    target: &str,
+    fold: impl Fn(T, T) -> T + Send + Sync + 'b,
+    block_size: Option<usize>,
+) -> ClosureConstraint<'b, T> {
+    let (source, target) = (source.to_string(), target.to_string());
+    let name = format!("{target} = running aggregate of {source}");
+    // The aggregates of the most recently processed rows.
+    let aggregates = Mutex::new(BTreeMap::<DegreeType, T>::new());
+    ClosureConstraint::new(name, move |row_pair| {
+        let source_ref = row_pair
+            .witness_reference(&source)
+            .ok_or_else(|| format!("Witness column {source} not found."))?;
+        let target_ref = row_pair
+            .witness_reference(&target)
+            .ok_or_else(|| format!("Witness column {target} not found."))?;
+        let Some(value) = row_pair.get_value(source_ref) else {
+            return Ok(EvalValue::incomplete(IncompleteCause::DataNotYetAvailable));
+        };
+        let row = DegreeType::from(row_pair.current_row_index);
+        let is_block_start = match block_size {
+            Some(block_size) => row % block_size as DegreeType == 0,
+            None => row == 0,
+        };
+        let mut aggregates = aggregates.lock().unwrap();
+        let aggregate = if is_block_start {
+            value
+        } else {
+            match aggregates.get(&(row - 1)) {
+                Some(previous) => fold(*previous, value),
+                None => return Ok(EvalValue::incomplete(IncompleteCause::DataNotYetAvailable)),
+            }
+        };
+        aggregates.insert(row, aggregate);
+        aggregates.retain(|r, _| *r + 1 >= row);
+        drop(aggregates);
+
+        // The target might have been determined by an identity already.
+        match row_pair.get_value(target_ref) {
+            Some(existing) if existing == aggregate => Ok(EvalValue::complete(vec![])),
+            Some(existing) => Err(EvalError::ConstraintUnsatisfiable(format!(
+                "{target} is {existing}, but the running aggregate is {aggregate}"
+            ))),
+            None => (AffineExpression::from_variable_id(target_ref) - aggregate.into()).solve(),
+        }
+    })
+}
@@ -0,0 +1,41 @@
+use std::any::Any;
+use std::sync::Mutex;
+
+/// Auxiliary state of a run of witness generation that is not a column, e.g. the running
+/// state of a hash function. It holds a single value of a type chosen by the user, which
+/// is created on first access and reset at the start of each run.
+///
+/// The scratchpad is carried by [super::MutableState] and passed to closure constraints
+/// created with [super::ClosureConstraint::with_scratchpad]. Query callbacks can access it
+/// by capturing the reference passed in [super::WitgenOptions::scratchpad].
+#[derive(Default)]
+pub struct Scratchpad {
+    value: Mutex<Option<Box<dyn Any + Send>>>,
+}
+
+impl Scratchpad {
+    /// Calls `f` on the value of the scratchpad, initializing it with the default value
+    /// of `S` if it is empty.
+    /// Panics if the scratchpad holds a value of a different type.
+    pub fn with<S: Any + Send + Default, R>(&self, f: impl FnOnce(&mut S) -> R) -> R {
+        let mut value = self.value.lock().unwrap();
+        let value = value.get_or_insert_with(|| Box::<S>::default());
+        f(value
+            .downcast_mut()
+            .expect("The scratchpad holds a value of a different type."))
+    }
+
+    /// Removes the value from the scratchpad and returns it, if any.
+    /// Panics if the scratchpad holds a value of a different type.
+    pub fn take<S: Any>(&self) -> Option<S> {
+        self.value.lock().unwrap().take().map(|value| {
+            *value
+                .downcast()
+                .expect("The scratchpad holds a value of a different type.")
+        })
+    }
+
+    pub(crate) fn clear(&self) {
+        *self.value.lock().unwrap() = None;
+    }
+}
@@ -0,0 +1,46 @@
+use powdr_number::FieldElement;
+
+use super::{AffineExpression, ClosureConstraint, EvalError, EvalValue, IncompleteCause};
+
+/// Returns a closure constraint that fills the witness column `target` with a summary of
+/// the witness columns `selectors` (e.g. the instruction flags and call selectors of a
+/// machine): `target` is 1 in rows where any selector is non-zero and 0 on rows where all
+/// of them are zero, i.e. on padding. Many proving systems use such a column as the
+/// `is_active` flag. Overlapping selectors are combined with OR, so `target` is boolean.
+pub fn selector_summary<'b, T: FieldElement>(
+    target: &str,
+    selectors: &[&str],
+) -> ClosureConstraint<'b, T> {
+    let target = target.to_string();
+    let selectors = selectors.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    let name = format!("{target} = OR of {}", selectors.join(", "));
+    ClosureConstraint::<T>::new(name, move |row_pair| {
+        let target_ref = row_pair
+            .witness_reference(&target)
+            .ok_or_else(|| format!("Witness column {target} not found."))?;
+        let mut all_known = true;
+        let mut is_active = false;
+        for selector in &selectors {
+            let selector_ref = row_pair
+                .witness_reference(selector)
+                .ok_or_else(|| format!("Witness column {selector} not found."))?;
+            match row_pair.get_value(selector_ref) {
+                Some(value) => is_active |= !value.is_zero(),
+                None => all_known = false,
+            }
+        }
+        if !is_active && !all_known {
+            return Ok(EvalValue::incomplete(IncompleteCause::DataNotYetAvailable));
+        }
+        let summary = if is_active { T::one() } else { T::zero() };
+
+        // The target might have been determined by an identity already.
+        match row_pair.get_value(target_ref) {
+            Some(existing) if existing == summary => Ok(EvalValue::complete(vec![])),
+            Some(existing) => Err(EvalError::ConstraintUnsatisfiable(format!(
+                "{target} is {existing}, but the summary of the selectors is {summary}"
+            ))),
+            None => (AffineExpression::from_variable_id(target_ref) - summary.into()).solve(),
+        }
+    })
+}
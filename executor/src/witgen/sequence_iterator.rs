@@ -104,6 +104,11 @@ impl DefaultSequenceIterator {
         self.progress_in_current_round = false;
     }
 
+    /// Returns the number of the current round on the current row delta.
+    pub fn round(&self) -> usize {
+        self.current_round_count
+    }
+
     pub fn report_progress(&mut self, progress_in_last_step: bool) {
         assert!(
             self.cur_action_index != -1,
@@ -184,6 +189,15 @@ impl ProcessingSequenceIterator {
         }
     }
 
+    /// Returns the number of the current round on the current row delta.
+    /// Cached sequences do not repeat steps, so they only have a single round.
+    pub fn round(&self) -> usize {
+        match self {
+            Self::Default(it) => it.round(),
+            Self::Cached(_) | Self::Incomplete => 0,
+        }
+    }
+
     pub fn has_steps(&self) -> bool {
         match self {
             Self::Default(_) | Self::Cached(_) => true,
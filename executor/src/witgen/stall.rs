@@ -0,0 +1,136 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Display};
+
+use itertools::Itertools;
+
+/// The reason why processing the identities of a row stopped making progress although
+/// some of them are still incomplete.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StallCause {
+    /// The identities wait on each other: Each identity references the cell following it
+    /// in the list, which is also referenced by the next identity (the last cell by the
+    /// first identity), so none of them can be solved before another one.
+    Cycle {
+        identities: Vec<u64>,
+        cells: Vec<String>,
+    },
+    /// The identities do not wait on each other, so values for (some of) the given cells
+    /// have to be provided from elsewhere.
+    MissingInputs { cells: Vec<String> },
+}
+
+impl Display for StallCause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StallCause::Cycle { identities, cells } => {
+                write!(f, "Identities wait on each other: ")?;
+                for (identity, cell) in identities.iter().zip(cells) {
+                    write!(f, "identity {identity} -> {cell} -> ")?;
+                }
+                write!(f, "identity {}", identities[0])
+            }
+            StallCause::MissingInputs { cells } => {
+                write!(f, "Missing inputs among: {}", cells.iter().join(", "))
+            }
+        }
+    }
+}
+
+/// Determines why the given identities (given by their IDs and unknown cells, each
+/// having at least two unknown cells) cannot make progress.
+pub fn stall_cause(stalled: &[(u64, Vec<String>)]) -> StallCause {
+    match find_cycle(stalled) {
+        Some((identities, cells)) => StallCause::Cycle { identities, cells },
+        None => StallCause::MissingInputs {
+            cells: stalled
+                .iter()
+                .flat_map(|(_, cells)| cells.iter().cloned())
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect(),
+        },
+    }
+}
+
+/// A node of the (bipartite) graph of identities and the cells they reference.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Node<'s> {
+    Identity(usize),
+    Cell(&'s str),
+}
+
+/// Finds a cycle in the graph that connects each identity to its unknown cells.
+/// Returns the identities and cells of the cycle in alternating order.
+fn find_cycle(stalled: &[(u64, Vec<String>)]) -> Option<(Vec<u64>, Vec<String>)> {
+    let mut edges: BTreeMap<Node, Vec<Node>> = BTreeMap::new();
+    for (index, (_, cells)) in stalled.iter().enumerate() {
+        for cell in cells {
+            edges
+                .entry(Node::Identity(index))
+                .or_default()
+                .push(Node::Cell(cell));
+            edges
+                .entry(Node::Cell(cell))
+                .or_default()
+                .push(Node::Identity(index));
+        }
+    }
+
+    let mut visited = BTreeSet::new();
+    for start in (0..stalled.len()).map(Node::Identity) {
+        if visited.contains(&start) {
+            continue;
+        }
+        let mut path = vec![start];
+        if let Some(cycle) = find_cycle_from(&edges, &mut visited, &mut path) {
+            let identities = cycle
+                .iter()
+                .filter_map(|node| match node {
+                    Node::Identity(index) => Some(stalled[*index].0),
+                    Node::Cell(_) => None,
+                })
+                .collect();
+            let cells = cycle
+                .iter()
+                .filter_map(|node| match node {
+                    Node::Cell(cell) => Some(cell.to_string()),
+                    Node::Identity(_) => None,
+                })
+                .collect();
+            return Some((identities, cells));
+        }
+    }
+    None
+}
+
+/// Depth-first search for a cycle, starting from the last node of the path.
+/// Returns the nodes of the cycle, starting with an identity.
+fn find_cycle_from<'s>(
+    edges: &BTreeMap<Node<'s>, Vec<Node<'s>>>,
+    visited: &mut BTreeSet<Node<'s>>,
+    path: &mut Vec<Node<'s>>,
+) -> Option<Vec<Node<'s>>> {
+    let node = *path.last().unwrap();
+    visited.insert(node);
+    let parent = path.len().checked_sub(2).map(|i| path[i]);
+    for next in &edges[&node] {
+        if Some(*next) == parent {
+            continue;
+        }
+        if let Some(position) = path.iter().position(|n| n == next) {
+            let mut cycle = path[position..].to_vec();
+            if let Node::Cell(_) = cycle[0] {
+                cycle.rotate_left(1);
+            }
+            return Some(cycle);
+        }
+        if !visited.contains(next) {
+            path.push(*next);
+            if let Some(cycle) = find_cycle_from(edges, visited, path) {
+                return Some(cycle);
+            }
+            path.pop();
+        }
+    }
+    None
+}
@@ -0,0 +1,32 @@
+use powdr_ast::analyzed::PolyID;
+use powdr_number::{DegreeType, FieldElement};
+
+/// Chooses values for cells that may take any value (e.g. "don't care" cells, see
+/// [super::WitgenOptions::dont_care_columns]), pseudo-randomly but determined by
+/// the seed. In contrast to blinding, the choice is reproducible: Runs with the same seed
+/// result in the same witness, while other seeds can be used to check that nothing
+/// depends on the choice.
+///
+/// The tie breaker is carried by [super::MutableState].
+pub struct TieBreaker {
+    seed: u64,
+}
+
+impl TieBreaker {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    /// Returns the value of the cell of the given column in the given row.
+    /// It only depends on the seed and the cell, not on the order of the calls.
+    pub fn choose<T: FieldElement>(&self, column: &PolyID, row: DegreeType) -> T {
+        // SplitMix64, seeded with the seed and the cell.
+        let mut z = self
+            .seed
+            .wrapping_add(column.id.wrapping_mul(0x9e37_79b9_7f4a_7c15))
+            .wrapping_add(row.wrapping_mul(0xd1b5_4a32_d192_ed03));
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        T::from(z ^ (z >> 31))
+    }
+}
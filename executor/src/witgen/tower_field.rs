@@ -0,0 +1,137 @@
+use powdr_number::{FieldElement, GoldilocksField, LargeInt};
+
+/// Number of bits packed into one word of a bit plane.
+const WORD_BITS: usize = 128;
+
+/// A field whose elements can be decomposed into bits and embedded into a binary tower field.
+pub trait TowerFieldDecomposition: FieldElement {
+    /// The tower level (log2 of the bit width) of the smallest binary tower field
+    /// that can hold any element of this field.
+    const TOWER_LEVEL: usize;
+}
+
+impl TowerFieldDecomposition for GoldilocksField {
+    const TOWER_LEVEL: usize = 6;
+}
+
+/// A witness column in the bit-sliced layout expected by tower-field proving systems.
+/// Each value is stored in the binary tower field of level `tower_level`, i.e. using
+/// `2^tower_level` bits. Bit `i` of all values is stored in `bit_planes[i]`, where bit
+/// `r % 128` of word `r / 128` belongs to row `r`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BitSlicedColumn {
+    pub name: String,
+    pub tower_level: usize,
+    /// The number of rows.
+    pub len: usize,
+    pub bit_planes: Vec<Vec<u128>>,
+}
+
+/// Converts the columns of a generated witness into the bit-sliced layout.
+/// Each column uses the smallest tower level that can hold all of its values.
+pub fn to_bit_sliced<T: TowerFieldDecomposition>(
+    witness: &[(String, Vec<T>)],
+) -> Vec<BitSlicedColumn> {
+    witness
+        .iter()
+        .map(|(name, values)| {
+            let max_bits = values
+                .iter()
+                .map(|value| value.to_integer().num_bits())
+                .max()
+                .unwrap_or_default();
+            let tower_level = (0..=T::TOWER_LEVEL)
+                .find(|level| max_bits <= 1 << level)
+                .unwrap();
+            let bit_planes = (0..1 << tower_level)
+                .map(|bit| {
+                    let mut plane = vec![0u128; values.len().div_ceil(WORD_BITS)];
+                    for (row, value) in values.iter().enumerate() {
+                        if ((value.to_integer() >> bit) & T::Integer::one()).is_one() {
+                            plane[row / WORD_BITS] |= 1 << (row % WORD_BITS);
+                        }
+                    }
+                    plane
+                })
+                .collect();
+            BitSlicedColumn {
+                name: name.clone(),
+                tower_level,
+                len: values.len(),
+                bit_planes,
+            }
+        })
+        .collect()
+}
+
+/// Converts columns in the bit-sliced layout back into witness columns.
+/// Panics if a column uses a tower level that is too large for the field.
+pub fn from_bit_sliced<T: TowerFieldDecomposition>(
+    columns: &[BitSlicedColumn],
+) -> Vec<(String, Vec<T>)> {
+    columns
+        .iter()
+        .map(|column| {
+            assert!(
+                column.tower_level <= T::TOWER_LEVEL,
+                "Column {} uses tower level {}, but the field only supports up to {}.",
+                column.name,
+                column.tower_level,
+                T::TOWER_LEVEL
+            );
+            let values = (0..column.len)
+                .map(|row| {
+                    let integer = column.bit_planes.iter().enumerate().fold(
+                        T::Integer::from(0),
+                        |acc, (bit, plane)| {
+                            if (plane[row / WORD_BITS] >> (row % WORD_BITS)) & 1 == 1 {
+                                acc | (T::Integer::one() << bit)
+                            } else {
+                                acc
+                            }
+                        },
+                    );
+                    T::from(integer)
+                })
+                .collect();
+            (column.name.clone(), values)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let column = (0..300u64)
+            .map(|i| GoldilocksField::from(i * 0x1234_5678_9abc))
+            .collect::<Vec<_>>();
+        let bits = (0..300u64)
+            .map(|i| GoldilocksField::from(i % 3 == 0))
+            .collect::<Vec<_>>();
+        let witness = vec![
+            ("main.x".to_string(), column),
+            ("main.bit".to_string(), bits),
+            (
+                "main.minus_one".to_string(),
+                vec![-GoldilocksField::from(1); 5],
+            ),
+        ];
+
+        let sliced = to_bit_sliced(&witness);
+        assert_eq!(
+            sliced
+                .iter()
+                .map(|column| (column.tower_level, column.bit_planes.len()))
+                .collect::<Vec<_>>(),
+            vec![(6, 64), (0, 1), (6, 64)]
+        );
+        // 300 rows need 3 words per bit plane.
+        assert!(sliced[1].bit_planes.iter().all(|plane| plane.len() == 3));
+        assert_eq!(sliced[1].bit_planes[0][0] & 0b1111, 0b1001);
+
+        assert_eq!(from_bit_sliced::<GoldilocksField>(&sliced), witness);
+    }
+}
@@ -13,25 +13,92 @@ use crate::witgen::identity_processor::{self};
 use crate::witgen::IncompleteCause;
 
 use super::data_structures::finalizable_data::FinalizableData;
-use super::processor::{OuterQuery, Processor};
+use super::failure_bundle::FailureBundle;
+use super::identity_budget::IdentityBudget;
+use super::instruction_replay::ResolvedInstructionReplay;
+use super::monotonic::MonotonicityViolation;
+use super::processor::{OuterQuery, Processor, Provenance};
 
-use super::rows::{Row, RowIndex, UnknownStrategy};
-use super::{Constraints, EvalError, EvalValue, FixedData, MutableState, QueryCallback};
+use super::rows::{CellValue, Row, RowIndex, UnknownStrategy};
+use super::{
+    ColumnGroup, Constraints, EvalError, EvalStatus, EvalValue, FixedData, MutableState,
+    QueryCallback,
+};
 
 /// Maximal period checked during loop detection.
 const MAX_PERIOD: usize = 4;
 
 const REPORT_FREQUENCY: u64 = 1_000;
 
-/// A list of identities with a flag whether it is complete.
+/// Determines when identities that reference the next row are processed in a VM.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NextRowPolicy {
+    /// Only process identities that reference the next row once the identities that
+    /// do not reference it have been processed until no more progress is made.
+    #[default]
+    Defer,
+    /// Process all identities together, so that values solved from identities that
+    /// reference the next row can still be used by the other identities of the current row.
+    Eager,
+}
+
+/// Determines the order in which a VM processes the identities of a row.
+/// The solved values do not depend on the order, only the amount of work does.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ScheduleStrategy {
+    /// Process the identities in source order.
+    #[default]
+    InOrder,
+    /// Process the identities in a pseudo-random order that is determined by the seed
+    /// and differs from row to row.
+    Shuffled(u64),
+}
+
+impl ScheduleStrategy {
+    fn schedule<I>(&self, items: &mut [I], row_index: DegreeType) {
+        if let ScheduleStrategy::Shuffled(seed) = self {
+            // Fisher-Yates shuffle, using SplitMix64 as the random number generator.
+            let mut state = seed ^ row_index.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+            let mut next_random = || {
+                state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+                let mut z = state;
+                z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+                z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+                z ^ (z >> 31)
+            };
+            for i in (1..items.len()).rev() {
+                let j = (next_random() % (i as u64 + 1)) as usize;
+                items.swap(i, j);
+            }
+        }
+    }
+}
+
+/// A list of identities (and closure constraints) with a flag whether it is complete.
 struct CompletableIdentities<'a, T: FieldElement> {
     identities_with_complete: Vec<(&'a Identity<Expression<T>>, bool)>,
+    closure_constraints_with_complete: Vec<bool>,
 }
 
 impl<'a, T: FieldElement> CompletableIdentities<'a, T> {
     fn new(identities: impl Iterator<Item = &'a Identity<Expression<T>>>) -> Self {
         Self {
             identities_with_complete: identities.map(|identity| (identity, false)).collect(),
+            closure_constraints_with_complete: vec![],
+        }
+    }
+
+    /// Reorders the identities according to the schedule strategy.
+    fn scheduled(mut self, strategy: ScheduleStrategy, row_index: DegreeType) -> Self {
+        strategy.schedule(&mut self.identities_with_complete, row_index);
+        self
+    }
+
+    /// Also processes the closure constraints of [FixedData].
+    fn with_closure_constraints(self, count: usize) -> Self {
+        Self {
+            closure_constraints_with_complete: vec![false; count],
+            ..self
         }
     }
 
@@ -59,6 +126,8 @@ pub struct VmProcessor<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> {
     last_report_time: Instant,
     processor: Processor<'a, 'b, 'c, T, Q>,
     progress_bar: ProgressBar,
+    /// If set, determines the program counter and instruction flags of the rows it covers.
+    instruction_replay: Option<&'a ResolvedInstructionReplay<'a>>,
 }
 
 impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T, Q> {
@@ -93,6 +162,7 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
             last_report_time: Instant::now(),
             processor,
             progress_bar,
+            instruction_replay: None,
         }
     }
 
@@ -101,6 +171,27 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
         Self { processor, ..self }
     }
 
+    pub fn with_identity_budget(self, identity_budget: Option<&'c IdentityBudget>) -> Self {
+        let processor = self.processor.with_identity_budget(identity_budget);
+        Self { processor, ..self }
+    }
+
+    /// Prefixes the log messages of the processor with the name of the given machine.
+    pub fn with_log_scope(self, machine: &'c str) -> Self {
+        let processor = self.processor.with_log_scope(machine);
+        Self { processor, ..self }
+    }
+
+    pub fn with_instruction_replay(
+        self,
+        instruction_replay: &'a ResolvedInstructionReplay<'a>,
+    ) -> Self {
+        Self {
+            instruction_replay: Some(instruction_replay),
+            ..self
+        }
+    }
+
     pub fn finish(self) -> FinalizableData<'a, T> {
         self.processor.finish()
     }
@@ -128,12 +219,15 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
         };
         let rows_left = self.fixed_data.degree - self.row_offset + 1;
         let mut finalize_start = 1;
+        // Loop detection needs to look at the last few rows.
+        let loop_detection_enabled =
+            !matches!(self.fixed_data.options.row_window, Some(window) if window < 2 * MAX_PERIOD);
         for row_index in 0..rows_left {
             if is_main_run {
                 self.maybe_log_performance(row_index);
             }
 
-            if (row_index + 1) % 10000 == 0 {
+            if self.fixed_data.options.row_window.is_none() && (row_index + 1) % 10000 == 0 {
                 // Periodically make sure most rows are finalized.
                 // Row 0 and the last MAX_PERIOD rows might be needed later, so they are not finalized.
                 let finalize_end = row_index as usize - MAX_PERIOD;
@@ -148,7 +242,14 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
             }
 
             // Check if we are in a loop.
-            if looping_period.is_none() && row_index % 100 == 0 && row_index > 0 {
+            // Rows covered by an instruction replay are never proposed, because the
+            // replayed control flow does not have to repeat.
+            if looping_period.is_none()
+                && loop_detection_enabled
+                && row_index % 100 == 0
+                && row_index > 0
+                && !self.is_replayed(row_index)
+            {
                 looping_period = self.rows_are_repeating(row_index);
                 if let Some(p) = looping_period {
                     log::log!(
@@ -197,6 +298,15 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
                     return EvalValue::incomplete(IncompleteCause::UnknownLatch);
                 }
             };
+
+            if let Some(window) = self.fixed_data.options.row_window {
+                // Row 0 and the last `window` rows (including the next row) stay in progress.
+                let finalize_end = (row_index as usize + 2).saturating_sub(window);
+                if finalize_end > finalize_start {
+                    self.processor.finalize_range(finalize_start..finalize_end);
+                    finalize_start = finalize_end;
+                }
+            }
         }
 
         assert_eq!(
@@ -206,6 +316,12 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
 
         if is_main_run {
             self.progress_bar.finish();
+            self.report_partial_progress();
+            self.report_identity_latencies();
+            self.report_progress_counts();
+            self.report_fill_levels();
+            self.report_redundant_identities();
+            self.report_memory_usage();
         }
 
         EvalValue::complete(outer_assignments)
@@ -230,6 +346,13 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
         })
     }
 
+    /// Returns whether the given row is covered by the instruction replay, if any.
+    fn is_replayed(&self, row_index: DegreeType) -> bool {
+        self.instruction_replay
+            .map(|replay| replay.covers_row(row_index + self.row_offset))
+            .unwrap_or(false)
+    }
+
     fn ensure_has_next_row(&mut self, row_index: DegreeType) {
         assert!(self.processor.len() as DegreeType > row_index);
         if row_index == self.processor.len() as DegreeType - 1 {
@@ -249,19 +372,59 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
             row_index + self.row_offset
         );
 
+        if let Some(replay) = self.instruction_replay {
+            let values = match replay.row_values(row_index + self.row_offset) {
+                Ok(values) => values,
+                Err(e) => self.report_failure_and_panic_unsatisfiable(row_index, vec![e]),
+            };
+            if let Some(values) = values {
+                if let Err(e) = self.processor.write_group(
+                    row_index as usize,
+                    replay.columns(),
+                    &values,
+                    Provenance::Machine,
+                    || "instruction replay".to_string(),
+                ) {
+                    self.report_failure_and_panic_unsatisfiable(row_index, vec![e]);
+                }
+            }
+        }
+
         log::trace!("  Going over all identities until no more progress is made");
         // First, go over identities that don't reference the next row,
         // Second, propagate values to the next row by going over identities that do reference the next row.
-        let mut identities_without_next_ref =
-            CompletableIdentities::new(self.identities_without_next_ref.iter().cloned());
-        let mut identities_with_next_ref =
-            CompletableIdentities::new(self.identities_with_next_ref.iter().cloned());
+        // With `NextRowPolicy::Eager`, all identities are already processed in the first step.
+        let (first_identities, second_identities) = match self.fixed_data.options.next_row_policy {
+            NextRowPolicy::Defer => (
+                CompletableIdentities::new(self.identities_without_next_ref.iter().cloned()),
+                CompletableIdentities::new(self.identities_with_next_ref.iter().cloned()),
+            ),
+            NextRowPolicy::Eager => (
+                CompletableIdentities::new(
+                    self.identities_without_next_ref
+                        .iter()
+                        .chain(&self.identities_with_next_ref)
+                        .cloned(),
+                ),
+                CompletableIdentities::new(std::iter::empty()),
+            ),
+        };
+        // Closure constraints only refer to the current row of the main machine.
+        let closure_constraint_count = match self.processor.has_outer_query() {
+            true => 0,
+            false => self.fixed_data.options.closure_constraints.len(),
+        };
+        let strategy = self.fixed_data.options.schedule_strategy;
+        let mut first_identities = first_identities
+            .scheduled(strategy, row_index)
+            .with_closure_constraints(closure_constraint_count);
+        let mut second_identities = second_identities.scheduled(strategy, row_index);
         let outer_assignments = self
-            .loop_until_no_progress(row_index, &mut identities_without_next_ref)
+            .loop_until_no_progress(row_index, &mut first_identities)
             .and_then(|outer_assignments| {
                 Ok(outer_assignments
                     .into_iter()
-                    .chain(self.loop_until_no_progress(row_index, &mut identities_with_next_ref)?)
+                    .chain(self.loop_until_no_progress(row_index, &mut second_identities)?)
                     .collect::<Vec<_>>())
             })
             .map_err(|e| self.report_failure_and_panic_unsatisfiable(row_index, e))
@@ -275,20 +438,18 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
             log::trace!(
                 "  Checking that remaining identities hold when unknown values are set to 0"
             );
-            self.process_identities(
-                row_index,
-                &mut identities_without_next_ref,
-                UnknownStrategy::Zero,
-            )
-            .and_then(|_| {
-                self.process_identities(
-                    row_index,
-                    &mut identities_with_next_ref,
-                    UnknownStrategy::Zero,
-                )
-            })
-            .map_err(|e| self.report_failure_and_panic_under_constrained(row_index, e))
-            .unwrap();
+            self.process_identities(row_index, &mut first_identities, UnknownStrategy::Zero)
+                .and_then(|_| {
+                    self.process_identities(
+                        row_index,
+                        &mut second_identities,
+                        UnknownStrategy::Zero,
+                    )
+                })
+                .map_err(|e| self.report_failure_and_panic_under_constrained(row_index, e))
+                .unwrap();
+            self.check_monotonic_columns(row_index);
+            self.processor.tag_row(row_index as usize);
         }
 
         log::trace!(
@@ -323,7 +484,8 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
             .filter(|(_, (ident, _))| ident.kind == IdentityKind::Plookup)
             .max_by_key(|(_, (ident, _))| ident.left.expressions.len())
             .map(|(i, _)| i);
-        loop {
+        for iteration in 0.. {
+            self.processor.set_convergence_iteration(iteration);
             let mut progress = false;
             if let Some(pc_lookup_index) = pc_lookup_index {
                 let (identity, is_complete) =
@@ -347,7 +509,10 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
                 outer_assignments.extend(new_outer_assignments);
             }
 
-            progress |= self.processor.set_inputs_if_unset(row_index);
+            progress |= self
+                .processor
+                .set_inputs_if_unset(row_index)
+                .map_err(|e| vec![e])?;
             progress |= self
                 .processor
                 .process_queries(row_index)
@@ -377,13 +542,66 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
         let mut progress = false;
         let mut errors = vec![];
 
+        // The polynomial identities are evaluated on the same row pair and their updates
+        // are applied at once, the others one by one. This is not done if there are optional
+        // identities, because whether they are skipped depends on the order of the updates.
+        // If the batch fails, its identities are processed one by one to report the error.
+        let is_batched = |identity: &Identity<Expression<T>>| {
+            unknown_strategy == UnknownStrategy::Unknown
+                && self.fixed_data.optional_identities.is_empty()
+                && identity.kind == IdentityKind::Polynomial
+        };
+        let (batch, mut batch_complete): (Vec<_>, Vec<_>) = identities
+            .iter_mut()
+            .filter(|(identity, is_complete)| !**is_complete && is_batched(identity))
+            .unzip();
+        let batch_succeeded = !batch.is_empty()
+            && match self.processor.process_identities_batched(
+                row_index as usize,
+                &batch,
+                unknown_strategy,
+            ) {
+                Ok(result) => {
+                    progress |= result.aggregate.progress;
+                    for (is_complete, complete) in batch_complete.iter_mut().zip(result.is_complete)
+                    {
+                        **is_complete = complete;
+                    }
+                    true
+                }
+                Err(_) => false,
+            };
         for (identity, is_complete) in identities.iter_mut() {
+            if batch_succeeded && is_batched(identity) {
+                continue;
+            }
             match self.process_identity(row_index, identity, is_complete, unknown_strategy) {
                 Ok(Some(result)) => progress |= result,
                 Ok(None) => (),
                 Err(e) => errors.push(e),
             }
         }
+        for (index, is_complete) in identities
+            .closure_constraints_with_complete
+            .iter_mut()
+            .enumerate()
+        {
+            if *is_complete {
+                continue;
+            }
+            match self.processor.process_closure_constraint(
+                row_index as usize,
+                index,
+                &self.fixed_data.options.closure_constraints[index],
+                unknown_strategy,
+            ) {
+                Ok(result) => {
+                    *is_complete = result.is_complete;
+                    progress |= result.progress;
+                }
+                Err(e) => errors.push(e),
+            }
+        }
 
         if errors.is_empty() {
             Ok(progress)
@@ -413,7 +631,7 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
             identity.kind,
             IdentityKind::Plookup | IdentityKind::Permutation
         );
-        if is_machine_call && unknown_strategy == UnknownStrategy::Zero {
+        if is_machine_call && unknown_strategy != UnknownStrategy::Unknown {
             // The fact that we got to the point where we assume 0 for unknown cells, but this identity
             // is still not complete, means that either the inputs or the machine is under-constrained.
             return Err(format!(
@@ -428,11 +646,62 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
         Ok(Some(result.progress))
     }
 
+    /// Checks that the monotonic columns of this machine do not decrease from the previous
+    /// row to the given row. Values that are not known are not checked.
+    fn check_monotonic_columns(&self, row_index: DegreeType) {
+        if row_index == 0 {
+            return;
+        }
+        let row_index = row_index as usize;
+        let violations = self
+            .fixed_data
+            .monotonic_columns
+            .iter()
+            .filter(|poly_id| self.witnesses.contains(poly_id))
+            .filter_map(|poly_id| {
+                match (
+                    &self.processor.row(row_index - 1)[poly_id].value,
+                    &self.processor.row(row_index)[poly_id].value,
+                ) {
+                    (CellValue::Known(value), CellValue::Known(next_value)) => {
+                        MonotonicityViolation::check(
+                            self.fixed_data.column_name(poly_id),
+                            self.row_offset + row_index as DegreeType - 1,
+                            *value,
+                            *next_value,
+                        )
+                    }
+                    _ => None,
+                }
+            })
+            .collect::<Vec<_>>();
+        if !violations.is_empty() {
+            log::error!(
+                "\nError: Row {} failed.\n",
+                self.row_offset + row_index as DegreeType
+            );
+            log::error!(
+                "Errors:\n{}\n",
+                violations
+                    .iter()
+                    .map(|v| indent(v.to_string(), 1))
+                    .join("\n")
+            );
+            panic!("Witness generation failed.");
+        }
+    }
+
     fn report_failure_and_panic_unsatisfiable(
         &self,
         row_index: DegreeType,
         failures: Vec<EvalError<T>>,
     ) -> ! {
+        if let Some(e) = failures
+            .iter()
+            .find(|e| matches!(e, EvalError::BudgetExceeded(_)))
+        {
+            panic!("{e}");
+        }
         log::error!(
             "\nError: Row {} failed. Set RUST_LOG=debug for more information.\n",
             row_index + self.row_offset
@@ -455,16 +724,20 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
                 &self.witnesses
             )
         );
+        self.report_latch_values(row_index);
+        self.report_column_groups(row_index);
         log::debug!("Set RUST_LOG=trace to understand why these values were chosen.");
         log::error!(
             "Errors:\n{}\n",
             failures.iter().map(|r| indent(r.to_string(), 1)).join("\n")
         );
+        self.write_failure_bundle(row_index as DegreeType, &failures);
+        self.inspect_failure();
         panic!("Witness generation failed.");
     }
 
     fn report_failure_and_panic_under_constrained(
-        &self,
+        &mut self,
         row_index: DegreeType,
         failures: Vec<EvalError<T>>,
     ) -> ! {
@@ -491,24 +764,205 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
                 &self.witnesses
             )
         );
+        self.report_unsolved_cells();
+        self.report_latch_values(row_index);
+        self.report_column_groups(row_index);
         log::debug!("\nSet RUST_LOG=trace to understand why these values were (not) chosen.");
         log::debug!(
             "Assuming zero for unknown values, the following identities fail:\n{}\n",
             failures.iter().map(|r| indent(r.to_string(), 1)).join("\n")
         );
+        if log::log_enabled!(log::Level::Debug) {
+            self.report_affine_system(row_index);
+            self.report_incomplete_causes(row_index);
+            self.report_example_assignments(row_index);
+        }
+        self.write_failure_bundle(row_index as DegreeType, &failures);
+        self.inspect_failure();
         panic!("Witness generation failed.");
     }
 
+    /// Logs the cells of this machine that are still unknown, i.e. those that were
+    /// assumed to be zero. Cells that are only range-constrained are not listed.
+    fn report_unsolved_cells(&self) {
+        let unsolved = self.processor.unsolved_cells(false);
+        if unsolved.is_empty() {
+            return;
+        }
+        log::debug!(
+            "{} cells are still unknown ({:.2}% of the cells are known), e.g.: {}",
+            unsolved.len(),
+            self.processor.column_solved_ratio() * 100.0,
+            unsolved
+                .iter()
+                .take(20)
+                .map(|(poly_id, row)| format!(
+                    "{} (row {row})",
+                    self.fixed_data.column_name(poly_id)
+                ))
+                .join(", ")
+        );
+    }
+
+    /// Logs the values of the latch up to the next row, if this machine answers an
+    /// outer query, to see whether the end of the block was reached.
+    fn report_latch_values(&self, row_index: usize) {
+        if !self.processor.has_outer_query() {
+            return;
+        }
+        log::debug!(
+            "Latch values up to the next row: {}",
+            self.processor
+                .latch_values(0..row_index + 2)
+                .into_iter()
+                .map(|latch| match latch {
+                    Some(true) => "1",
+                    Some(false) => "0",
+                    None => "?",
+                })
+                .join("")
+        );
+    }
+
+    /// Logs the values of the column groups of this machine on the current row, which
+    /// are easier to read as a unit than spread over the rendered row.
+    fn report_column_groups(&self, row_index: usize) {
+        let groups = ColumnGroup::all(self.fixed_data)
+            .into_iter()
+            .filter(|group| group.columns.iter().all(|c| self.witnesses.contains(c)))
+            .map(|group| match self.processor.read_group(row_index, &group) {
+                Some(values) => format!("  {} = [{}]", group.name, values.iter().join(", ")),
+                None => format!("  {} is not fully known", group.name),
+            })
+            .collect::<Vec<_>>();
+        if !groups.is_empty() {
+            log::debug!("Column groups on the current row:\n{}", groups.join("\n"));
+        }
+    }
+
+    /// Passes a snapshot of the state to the failure inspector, if set.
+    fn inspect_failure(&self) {
+        if let Some(inspector) = self.fixed_data.options.failure_inspector {
+            inspector(&self.processor.debug_snapshot());
+        }
+    }
+
+    /// Writes a [FailureBundle] for the failing row, if enabled.
+    fn write_failure_bundle(&self, row_index: DegreeType, failures: &[EvalError<T>]) {
+        if let Some(path) = self.fixed_data.options.failure_bundle {
+            FailureBundle::new(
+                self.fixed_data,
+                self.row_offset + row_index,
+                failures.iter().map(|e| e.to_string()).collect(),
+                self.row_offset,
+                self.processor.data(),
+            )
+            .write(path);
+        }
+    }
+
+    /// Logs the polynomial identities that are affine on the current row, with the known
+    /// values substituted, and why they do not make progress.
+    fn report_affine_system(&self, row_index: usize) {
+        let identities = self
+            .identities_with_next_ref
+            .iter()
+            .chain(&self.identities_without_next_ref)
+            .cloned()
+            .collect::<Vec<_>>();
+        if let Some(cause) = self.processor.stall_cause(row_index, &identities) {
+            log::debug!("{cause}");
+        }
+        let system = self.processor.row_affine_system(row_index, &identities);
+        log::debug!(
+            "Affine form of the polynomial identities on the current row:\n{}",
+            system
+                .iter()
+                .filter(|expr| !expr.is_constant())
+                .map(|expr| format!("  {expr} = 0"))
+                .join("\n")
+        );
+    }
+
+    /// Logs why the polynomial identities on the current row did not determine their
+    /// unknown cells, by previewing what processing them would do.
+    fn report_incomplete_causes(&self, row_index: usize) {
+        let causes = self
+            .identities_with_next_ref
+            .iter()
+            .chain(&self.identities_without_next_ref)
+            .filter(|identity| identity.kind == IdentityKind::Polynomial)
+            .filter_map(|identity| {
+                match self
+                    .processor
+                    .preview_identity(row_index, identity, UnknownStrategy::Unknown)
+                {
+                    Ok(EvalValue {
+                        status: EvalStatus::Incomplete(cause),
+                        ..
+                    }) => Some(format!("  {identity}: {cause:?}")),
+                    _ => None,
+                }
+            })
+            .collect::<Vec<_>>();
+        if !causes.is_empty() {
+            log::debug!(
+                "Incomplete polynomial identities on the current row:\n{}",
+                causes.join("\n")
+            );
+        }
+    }
+
+    /// Logs a few complete assignments of the row that satisfy the polynomial identities
+    /// without a next reference, to illustrate which columns are not determined.
+    fn report_example_assignments(&mut self, row_index: usize) {
+        let identities = self
+            .identities_without_next_ref
+            .iter()
+            .filter(|identity| identity.kind == IdentityKind::Polynomial)
+            .cloned()
+            .collect::<Vec<_>>();
+        let assignments = self
+            .processor
+            .enumerate_assignments(row_index, &identities, 4);
+        if assignments.is_empty() {
+            return;
+        }
+        log::debug!("Some assignments of the current row that satisfy the polynomial identities:");
+        for assignment in assignments {
+            log::debug!(
+                "  {}",
+                assignment
+                    .iter()
+                    .map(|(poly_id, value)| format!(
+                        "{} = {value}",
+                        self.fixed_data.column_name(poly_id)
+                    ))
+                    .join(", ")
+            );
+        }
+    }
+
     /// Verifies the proposed values for the next row.
     /// TODO this is bad for machines because we might introduce rows in the machine that are then
     /// not used.
     fn try_proposed_row(&mut self, row_index: DegreeType, proposed_row: Row<'a, T>) -> bool {
         let constraints_valid = self.identities_with_next_ref.iter().all(|i| {
-            self.processor
-                .check_row_pair(row_index as usize, &proposed_row, i, true)
+            self.processor.check_row_pair(
+                row_index as usize,
+                &proposed_row,
+                i,
+                true,
+                UnknownStrategy::Zero,
+            )
         }) && self.identities_without_next_ref.iter().all(|i| {
-            self.processor
-                .check_row_pair(row_index as usize, &proposed_row, i, false)
+            self.processor.check_row_pair(
+                row_index as usize,
+                &proposed_row,
+                i,
+                false,
+                UnknownStrategy::Zero,
+            )
         });
 
         if constraints_valid {
@@ -523,6 +977,136 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
         constraints_valid
     }
 
+    /// Logs the peak memory usage of the rows and how effective the evaluation cache was.
+    fn report_memory_usage(&self) {
+        let stats = self.processor.stats();
+        log::debug!(
+            "Peak memory usage: {} rows in progress, approximately {} bytes.",
+            stats.peak_rows_in_progress,
+            stats.peak_bytes
+        );
+        log::debug!(
+            "Evaluation cache: {} hits, {} misses.",
+            stats.evaluation_cache_hits,
+            stats.evaluation_cache_misses
+        );
+    }
+
+    /// Logs the processing latency percentiles of the identities, slowest first,
+    /// if they were measured.
+    fn report_identity_latencies(&self) {
+        let stats = self.processor.stats();
+        let Some((slowest, max)) = stats.slowest_identity() else {
+            return;
+        };
+        log::debug!("Slowest identity: {slowest} (up to {max:?} per row)");
+        let mut latencies = stats.identity_latencies.iter().collect::<Vec<_>>();
+        latencies.sort_by_key(|(_, latencies)| std::cmp::Reverse(latencies.max()));
+        log::debug!("Identity latencies (count, p50, p99, max):");
+        for (id, latencies) in latencies.into_iter().take(10) {
+            if let Some(identity) = self
+                .identities_with_next_ref
+                .iter()
+                .chain(&self.identities_without_next_ref)
+                .find(|identity| identity.id == *id)
+            {
+                log::debug!(
+                    "  {:>8} {:>10?} {:>10?} {:>10?}: {identity}",
+                    latencies.count(),
+                    latencies.p50(),
+                    latencies.p99(),
+                    latencies.max()
+                );
+            }
+        }
+    }
+
+    /// Logs the number of cells determined in each column, most first, if counted.
+    fn report_progress_counts(&self) {
+        let mut progress_counts = self
+            .processor
+            .progress_stats()
+            .into_iter()
+            .collect::<Vec<_>>();
+        if progress_counts.is_empty() {
+            return;
+        }
+        progress_counts.sort_by(|(_, a), (_, b)| b.cmp(a));
+        log::debug!("Cells determined per column:");
+        for (poly_id, count) in progress_counts {
+            log::debug!("  {count:>8}: {}", self.fixed_data.column_name(&poly_id));
+        }
+    }
+
+    /// Logs how many of the cells could be solved in parallel, i.e. the sizes of the levels
+    /// of the order in which they can be determined, if provenance is tracked.
+    fn report_fill_levels(&self) {
+        if !log::log_enabled!(log::Level::Debug) {
+            return;
+        }
+        let levels = self.processor.fill_levels();
+        if levels.is_empty() {
+            return;
+        }
+        log::debug!(
+            "The cells can be determined in {} levels, with up to {} cells per level.",
+            levels.len(),
+            levels.iter().map(|level| level.len()).max().unwrap()
+        );
+    }
+
+    /// Logs the identities that are likely implied by the others, if provenance is tracked
+    /// (see [Processor::redundant_identities]).
+    fn report_redundant_identities(&mut self) {
+        if !self.fixed_data.options.track_provenance || !log::log_enabled!(log::Level::Debug) {
+            return;
+        }
+        let identities = self
+            .identities_with_next_ref
+            .iter()
+            .chain(&self.identities_without_next_ref)
+            .copied()
+            .collect::<Vec<_>>();
+        let redundant = self.processor.redundant_identities(&identities);
+        if redundant.is_empty() {
+            return;
+        }
+        log::debug!(
+            "Likely redundant identities (they did not determine any cell and hold on all rows):\n{}",
+            identities
+                .iter()
+                .filter(|identity| redundant.contains(&identity.id))
+                .map(|identity| format!("  {identity}"))
+                .join("\n")
+        );
+    }
+
+    /// Logs the identities that most often made progress without being completed,
+    /// i.e. those that needed the most passes to converge.
+    fn report_partial_progress(&self) {
+        let mut partial_progress = self
+            .processor
+            .stats()
+            .partial_progress
+            .iter()
+            .collect::<Vec<_>>();
+        if partial_progress.is_empty() {
+            return;
+        }
+        partial_progress.sort_by(|(_, a), (_, b)| b.cmp(a));
+        log::debug!("Identities with the most partial progress:");
+        for (id, count) in partial_progress.into_iter().take(10) {
+            if let Some(identity) = self
+                .identities_with_next_ref
+                .iter()
+                .chain(&self.identities_without_next_ref)
+                .find(|identity| identity.id == *id)
+            {
+                log::debug!("  {count:>8}: {identity}");
+            }
+        }
+    }
+
     fn maybe_log_performance(&mut self, row_index: DegreeType) {
         if row_index >= self.last_report + REPORT_FREQUENCY {
             let duration = self.last_report_time.elapsed();
@@ -550,3 +1134,486 @@ impl<'a, 'b, 'c, T: FieldElement, Q: QueryCallback<T>> VmProcessor<'a, 'b, 'c, T
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use powdr_ast::analyzed::{IdentityKind, PolyID, PolynomialType};
+    use powdr_number::GoldilocksField;
+    use powdr_pil_analyzer::analyze_string;
+
+    use crate::{
+        constant_evaluator::generate,
+        witgen::{
+            data_structures::{
+                copy_constraints::CopyConstraints, finalizable_data::FinalizableData,
+            },
+            identity_processor::Machines,
+            machines::FixedLookup,
+            processor::OuterQuery,
+            rows::{CellValue, Row, RowIndex, RowPair, UnknownStrategy},
+            unused_query_callback, FixedData, MutableState, Scratchpad,
+        },
+    };
+
+    use super::{VmProcessor, MAX_PERIOD};
+
+    #[test]
+    fn peak_memory_is_bounded() {
+        let src = r#"
+            namespace main(32768);
+                col fixed FIRST = [1] + [0]*;
+                col fixed LAST = [0]* + [1];
+                col witness x;
+
+                FIRST * x = 0;
+                (1 - LAST) * (x' - x - 1) = 0;
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let constants = generate(&analyzed);
+        let fixed_data = FixedData::new(&analyzed, &constants, &[], Default::default(), 0);
+
+        let mut fixed_lookup = FixedLookup::new(fixed_data.global_range_constraints().clone());
+        let mut query_callback = unused_query_callback();
+        let mut mutable_state = MutableState {
+            fixed_lookup: &mut fixed_lookup,
+            machines: Machines::from([].iter_mut()),
+            query_callback: &mut query_callback,
+            scratchpad: &Scratchpad::default(),
+            tie_breaker: None,
+        };
+        let row_offset = RowIndex::from_degree(0, fixed_data.degree);
+        let witnesses = [PolyID {
+            id: 0,
+            ptype: PolynomialType::Committed,
+        }]
+        .into_iter()
+        .collect();
+        let data = FinalizableData::with_initial_rows_in_progress(
+            &witnesses,
+            [Row::fresh(&fixed_data, row_offset)].into_iter(),
+        );
+        let identities = analyzed.identities.iter().collect::<Vec<_>>();
+        let mut processor = VmProcessor::new(
+            row_offset,
+            &fixed_data,
+            &identities,
+            &witnesses,
+            data,
+            &mut mutable_state,
+        );
+        processor.run(false);
+
+        // Rows are finalized every 10000 rows, except for the first and the last few.
+        let stats = processor.processor.stats();
+        assert!(stats.peak_rows_in_progress >= 10_000);
+        assert!(stats.peak_rows_in_progress <= 10_000 + MAX_PERIOD + 2);
+
+        let all_rows_in_progress = FinalizableData::with_initial_rows_in_progress(
+            &witnesses,
+            (0..=fixed_data.degree)
+                .map(|i| Row::fresh(&fixed_data, RowIndex::from_degree(i, fixed_data.degree))),
+        );
+        assert!(stats.peak_bytes < all_rows_in_progress.estimated_bytes());
+    }
+
+    #[test]
+    fn conflicting_input() {
+        let src = r#"
+            namespace Main(4);
+                col witness a, b;
+            namespace Sub(4);
+                col fixed LATCH = [0, 1]*;
+                col witness _input, out;
+                (1 - LATCH) * (_input - 3) = 0;
+                out = _input + 1;
+                {Main.a, Main.b} in Sub.LATCH {Sub._input, Sub.out};
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let constants = generate(&analyzed);
+        let fixed_data = FixedData::new(&analyzed, &constants, &[], Default::default(), 0);
+        let row_offset = RowIndex::from_degree(0, fixed_data.degree);
+
+        // The caller passes 5 as input.
+        let mut caller_row = Row::fresh(&fixed_data, row_offset);
+        caller_row[&fixed_data.try_column_by_name("Main.a").unwrap()].value =
+            CellValue::Known(5.into());
+        let caller_next_row = Row::fresh(&fixed_data, row_offset + 1);
+        let caller_rows = RowPair::new(
+            &caller_row,
+            &caller_next_row,
+            row_offset,
+            &fixed_data,
+            UnknownStrategy::Unknown,
+        );
+        let (connecting_identities, identities): (Vec<_>, Vec<_>) = analyzed
+            .identities
+            .iter()
+            .partition(|identity| identity.kind == IdentityKind::Plookup);
+
+        let mut fixed_lookup = FixedLookup::new(fixed_data.global_range_constraints().clone());
+        let mut query_callback = unused_query_callback();
+        let mut mutable_state = MutableState {
+            fixed_lookup: &mut fixed_lookup,
+            machines: Machines::from([].iter_mut()),
+            query_callback: &mut query_callback,
+            scratchpad: &Scratchpad::default(),
+            tie_breaker: None,
+        };
+        let witnesses = ["Sub._input", "Sub.out"]
+            .into_iter()
+            .map(|name| fixed_data.try_column_by_name(name).unwrap())
+            .collect();
+        let data = FinalizableData::with_initial_rows_in_progress(
+            &witnesses,
+            (0..2).map(|i| Row::fresh(&fixed_data, row_offset + i)),
+        );
+        let mut processor = VmProcessor::new(
+            row_offset,
+            &fixed_data,
+            &identities,
+            &witnesses,
+            data,
+            &mut mutable_state,
+        )
+        .with_outer_query(OuterQuery::new(&caller_rows, connecting_identities[0]));
+
+        // The trace requires the input to be 3 in the first row.
+        processor
+            .processor
+            .process_identity(0, identities[0], UnknownStrategy::Unknown)
+            .unwrap();
+        let error = processor
+            .processor
+            .set_inputs_if_unset(0)
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            error,
+            "Input Sub._input (Row 0) is 5, but it is already known to be 3."
+        );
+    }
+
+    #[test]
+    fn reset_input_restores_copy_constraints() {
+        let src = r#"
+            namespace Main(4);
+                col witness a, b;
+            namespace Sub(4);
+                col fixed LATCH = [0, 1]*;
+                col witness _input, out, copy;
+                {Main.a, Main.b} in Sub.LATCH {Sub._input, Sub.out};
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let constants = generate(&analyzed);
+        let fixed_data = FixedData::new(&analyzed, &constants, &[], Default::default(), 0);
+        let row_offset = RowIndex::from_degree(0, fixed_data.degree);
+        let input = fixed_data.try_column_by_name("Sub._input").unwrap();
+        let copy = fixed_data.try_column_by_name("Sub.copy").unwrap();
+        // The input in the first row is copied to the next row.
+        let copy_constraints =
+            CopyConstraints::new(&[((input, row_offset), (copy, row_offset + 1))]);
+        let fixed_data = fixed_data.with_copy_constraints(copy_constraints);
+
+        let mut caller_row = Row::fresh(&fixed_data, row_offset);
+        caller_row[&fixed_data.try_column_by_name("Main.a").unwrap()].value =
+            CellValue::Known(5.into());
+        let caller_next_row = Row::fresh(&fixed_data, row_offset + 1);
+        let caller_rows = RowPair::new(
+            &caller_row,
+            &caller_next_row,
+            row_offset,
+            &fixed_data,
+            UnknownStrategy::Unknown,
+        );
+        let connecting_identity = &analyzed.identities[0];
+
+        let mut fixed_lookup = FixedLookup::new(fixed_data.global_range_constraints().clone());
+        let mut query_callback = unused_query_callback();
+        let mut mutable_state = MutableState {
+            fixed_lookup: &mut fixed_lookup,
+            machines: Machines::from([].iter_mut()),
+            query_callback: &mut query_callback,
+            scratchpad: &Scratchpad::default(),
+            tie_breaker: None,
+        };
+        let witnesses = ["Sub._input", "Sub.out", "Sub.copy"]
+            .into_iter()
+            .map(|name| fixed_data.try_column_by_name(name).unwrap())
+            .collect();
+        let data = FinalizableData::with_initial_rows_in_progress(
+            &witnesses,
+            (0..4).map(|i| Row::fresh(&fixed_data, row_offset + i)),
+        );
+        let mut processor = VmProcessor::new(
+            row_offset,
+            &fixed_data,
+            &[],
+            &witnesses,
+            data,
+            &mut mutable_state,
+        )
+        .with_outer_query(OuterQuery::new(&caller_rows, connecting_identity));
+
+        // Setting the input resolves the copy constraint of the first row...
+        assert!(processor.processor.set_inputs_if_unset(0).unwrap());
+        // ...but the input is not constrained in the third row, so it is reset.
+        assert!(processor.processor.set_inputs_if_unset(2).unwrap());
+        assert!(!processor.processor.row(0)[&input].value.is_known());
+
+        // The first row cannot be finalized until the input is known again.
+        processor.processor.finalize_range(0..1);
+        assert_eq!(processor.processor.data().rows_in_progress(), 4);
+        assert!(processor.processor.set_inputs_if_unset(0).unwrap());
+        processor.processor.finalize_range(1..2);
+        assert_eq!(processor.processor.data().rows_in_progress(), 2);
+    }
+
+    #[test]
+    fn input_from_affine_right_expression() {
+        let src = r#"
+            namespace Main(4);
+                col witness a, b;
+            namespace Sub(4);
+                col fixed LATCH = [0, 1]*;
+                col witness _input, out;
+                out = 2 * _input;
+                {Main.a, Main.b} in Sub.LATCH {Sub._input + 1, Sub.out};
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let constants = generate(&analyzed);
+        let fixed_data = FixedData::new(&analyzed, &constants, &[], Default::default(), 0);
+        let row_offset = RowIndex::from_degree(0, fixed_data.degree);
+
+        // The caller passes 5 as the first argument, so the input has to be 4.
+        let mut caller_row = Row::fresh(&fixed_data, row_offset);
+        caller_row[&fixed_data.try_column_by_name("Main.a").unwrap()].value =
+            CellValue::Known(5.into());
+        let caller_next_row = Row::fresh(&fixed_data, row_offset + 1);
+        let caller_rows = RowPair::new(
+            &caller_row,
+            &caller_next_row,
+            row_offset,
+            &fixed_data,
+            UnknownStrategy::Unknown,
+        );
+        let connecting_identity = analyzed
+            .identities
+            .iter()
+            .find(|identity| identity.kind == IdentityKind::Plookup)
+            .unwrap();
+
+        let mut fixed_lookup = FixedLookup::new(fixed_data.global_range_constraints().clone());
+        let mut query_callback = unused_query_callback();
+        let mut mutable_state = MutableState {
+            fixed_lookup: &mut fixed_lookup,
+            machines: Machines::from([].iter_mut()),
+            query_callback: &mut query_callback,
+            scratchpad: &Scratchpad::default(),
+            tie_breaker: None,
+        };
+        let input = fixed_data.try_column_by_name("Sub._input").unwrap();
+        let witnesses = [input, fixed_data.try_column_by_name("Sub.out").unwrap()]
+            .into_iter()
+            .collect();
+        let data = FinalizableData::with_initial_rows_in_progress(
+            &witnesses,
+            (0..2).map(|i| Row::fresh(&fixed_data, row_offset + i)),
+        );
+        let mut processor = VmProcessor::new(
+            row_offset,
+            &fixed_data,
+            &[],
+            &witnesses,
+            data,
+            &mut mutable_state,
+        )
+        .with_outer_query(OuterQuery::new(&caller_rows, connecting_identity));
+
+        assert!(processor.processor.set_inputs_if_unset(0).unwrap());
+        assert_eq!(
+            processor.processor.data()[0][&input].value,
+            CellValue::Known(4.into())
+        );
+    }
+
+    #[test]
+    fn latch_values() {
+        let src = r#"
+            namespace Main(8);
+                col witness a, b;
+            namespace Sub(8);
+                col fixed LATCH = [0, 0, 0, 1]*;
+                col witness x, y;
+                y = x + 1;
+                {Main.a, Main.b} in Sub.LATCH {Sub.x, Sub.y};
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let constants = generate(&analyzed);
+        let fixed_data = FixedData::new(&analyzed, &constants, &[], Default::default(), 0);
+        let row_offset = RowIndex::from_degree(0, fixed_data.degree);
+        let caller_row = Row::fresh(&fixed_data, row_offset);
+        let caller_next_row = Row::fresh(&fixed_data, row_offset + 1);
+        let caller_rows = RowPair::new(
+            &caller_row,
+            &caller_next_row,
+            row_offset,
+            &fixed_data,
+            UnknownStrategy::Unknown,
+        );
+        let connecting_identity = analyzed
+            .identities
+            .iter()
+            .find(|identity| identity.kind == IdentityKind::Plookup)
+            .unwrap();
+
+        let mut fixed_lookup = FixedLookup::new(fixed_data.global_range_constraints().clone());
+        let mut query_callback = unused_query_callback();
+        let mut mutable_state = MutableState {
+            fixed_lookup: &mut fixed_lookup,
+            machines: Machines::from([].iter_mut()),
+            query_callback: &mut query_callback,
+            scratchpad: &Scratchpad::default(),
+            tie_breaker: None,
+        };
+        let witnesses = ["Sub.x", "Sub.y"]
+            .into_iter()
+            .map(|name| fixed_data.try_column_by_name(name).unwrap())
+            .collect();
+        let data = FinalizableData::with_initial_rows_in_progress(
+            &witnesses,
+            (0..8).map(|i| Row::fresh(&fixed_data, row_offset + i)),
+        );
+        let processor = VmProcessor::new(
+            row_offset,
+            &fixed_data,
+            &[],
+            &witnesses,
+            data,
+            &mut mutable_state,
+        );
+        // Without an outer query, there is no latch.
+        assert_eq!(processor.processor.latch_values(0..3), vec![None; 3]);
+
+        let processor =
+            processor.with_outer_query(OuterQuery::new(&caller_rows, connecting_identity));
+        let latches = processor.processor.latch_values(0..8);
+        assert_eq!(
+            latches,
+            [false, false, false, true, false, false, false, true].map(Some)
+        );
+        assert_eq!(latches[5], processor.processor.latch_value(5));
+        assert_eq!(
+            processor.processor.latch_values(2..4),
+            [Some(false), Some(true)]
+        );
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "Local row 2 with row offset 7 is out of bounds for degree 8.")]
+    fn global_row_index_beyond_degree() {
+        let src = r#"
+            namespace Main(8);
+                col witness x;
+                x = 1;
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let constants = generate(&analyzed);
+        let fixed_data = FixedData::new(&analyzed, &constants, &[], Default::default(), 0);
+        let row_offset = RowIndex::from_degree(fixed_data.degree - 1, fixed_data.degree);
+
+        let mut fixed_lookup = FixedLookup::new(fixed_data.global_range_constraints().clone());
+        let mut query_callback = unused_query_callback();
+        let mut mutable_state = MutableState {
+            fixed_lookup: &mut fixed_lookup,
+            machines: Machines::from([].iter_mut()),
+            query_callback: &mut query_callback,
+            scratchpad: &Scratchpad::default(),
+            tie_breaker: None,
+        };
+        let witnesses = [fixed_data.try_column_by_name("Main.x").unwrap()]
+            .into_iter()
+            .collect();
+        let data = FinalizableData::with_initial_rows_in_progress(
+            &witnesses,
+            (0..4).map(|i| Row::fresh(&fixed_data, row_offset + i)),
+        );
+        let identities = analyzed.identities.iter().collect::<Vec<_>>();
+        let mut processor = VmProcessor::new(
+            row_offset,
+            &fixed_data,
+            &identities,
+            &witnesses,
+            data,
+            &mut mutable_state,
+        );
+        // The next row of the last row is the first row.
+        for row_index in 0..2 {
+            processor
+                .processor
+                .process_identity(row_index, identities[0], UnknownStrategy::Unknown)
+                .unwrap();
+        }
+        // Row 9 would wrap around to row 1.
+        let _ = processor
+            .processor
+            .process_identity(2, identities[0], UnknownStrategy::Unknown);
+    }
+
+    #[test]
+    fn outer_query_with_unknown_selector() {
+        let src = r#"
+            namespace Main(4);
+                col witness sel, a, b;
+            namespace Sub(4);
+                col fixed LATCH = [0, 1]*;
+                col witness A, B;
+                A = B;
+                Main.sel {Main.a, Main.b} in Sub.LATCH {Sub.A, Sub.B};
+                {Main.a, Main.b} in Sub.LATCH {Sub.A, Sub.B};
+        "#;
+        let analyzed = analyze_string::<GoldilocksField>(src);
+        let constants = generate(&analyzed);
+        let fixed_data = FixedData::new(&analyzed, &constants, &[], Default::default(), 0);
+        let row_offset = RowIndex::from_degree(0, fixed_data.degree);
+        let next_row = Row::fresh(&fixed_data, row_offset + 1);
+        let connecting_identities = analyzed
+            .identities
+            .iter()
+            .filter(|identity| identity.kind == IdentityKind::Plookup)
+            .collect::<Vec<_>>();
+
+        // The arguments are known, but the selector is not solved yet.
+        let mut row = Row::fresh(&fixed_data, row_offset);
+        for (name, value) in [("Main.a", 7), ("Main.b", 7)] {
+            row[&fixed_data.try_column_by_name(name).unwrap()].value =
+                CellValue::Known(value.into());
+        }
+        let rows = RowPair::new(
+            &row,
+            &next_row,
+            row_offset,
+            &fixed_data,
+            UnknownStrategy::Unknown,
+        );
+        let outer_query = OuterQuery::new(&rows, connecting_identities[0]);
+        assert!(!outer_query.selector_known());
+        assert!(!outer_query.is_complete());
+        // Without a selector, the arguments are all that is needed.
+        let outer_query = OuterQuery::new(&rows, connecting_identities[1]);
+        assert!(outer_query.selector_known());
+        assert!(outer_query.is_complete());
+
+        row[&fixed_data.try_column_by_name("Main.sel").unwrap()].value = CellValue::Known(1.into());
+        let rows = RowPair::new(
+            &row,
+            &next_row,
+            row_offset,
+            &fixed_data,
+            UnknownStrategy::Unknown,
+        );
+        let outer_query = OuterQuery::new(&rows, connecting_identities[0]);
+        assert!(outer_query.selector_known());
+        assert!(outer_query.is_complete());
+    }
+}
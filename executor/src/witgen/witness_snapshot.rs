@@ -0,0 +1,150 @@
+use bit_vec::BitVec;
+use powdr_number::{FieldElement, LargeInt};
+
+use super::range_constraints::RangeConstraint;
+use super::rows::CellValue;
+
+/// The rows of a processor in a compact binary form, written by
+/// `Processor::witness_snapshot` (e.g. to checkpoint long-running witness generation)
+/// and read by `Processor::restore_from_snapshot`. Unlike the final witness, it includes
+/// the range constraints of unknown cells, so that the cells are restored exactly.
+/// The rest of the state of the processor is not included.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SerializedRows {
+    bytes: Vec<u8>,
+}
+
+impl SerializedRows {
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        Self { bytes }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+/// Writes the values of a [SerializedRows], all integers in little endian.
+#[derive(Default)]
+pub struct SnapshotWriter {
+    bytes: Vec<u8>,
+}
+
+impl SnapshotWriter {
+    pub fn write_u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.bytes.extend(value.to_le_bytes());
+    }
+
+    pub fn write_field<T: FieldElement>(&mut self, value: T) {
+        self.bytes.extend(value.to_bytes_le());
+    }
+
+    pub fn write_bits(&mut self, bits: &BitVec) {
+        self.bytes.extend(bits.to_bytes());
+    }
+
+    pub fn write_cell_value<T: FieldElement>(&mut self, value: &CellValue<T>) {
+        match value {
+            CellValue::Unknown => self.write_u8(0),
+            CellValue::Known(value) => {
+                self.write_u8(1);
+                self.write_field(*value);
+            }
+            CellValue::RangeConstraint(range_constraint) => {
+                self.write_u8(2);
+                let mask = *range_constraint.mask();
+                for i in 0..integer_limbs::<T>() {
+                    let limb = (mask >> (64 * i)) & T::Integer::from(u64::MAX);
+                    self.write_u64(limb.try_into_u64().unwrap());
+                }
+                let (min, max) = range_constraint.range();
+                self.write_field(min);
+                self.write_field(max);
+            }
+        }
+    }
+
+    pub fn finish(self) -> SerializedRows {
+        SerializedRows { bytes: self.bytes }
+    }
+}
+
+/// Reads the values of a [SerializedRows] in the order they were written by a
+/// [SnapshotWriter].
+pub struct SnapshotReader<'s> {
+    bytes: &'s [u8],
+}
+
+impl<'s> SnapshotReader<'s> {
+    pub fn new(snapshot: &'s SerializedRows) -> Self {
+        Self {
+            bytes: &snapshot.bytes,
+        }
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<&'s [u8], String> {
+        if self.bytes.len() < len {
+            return Err("Unexpected end of witness snapshot.".to_string());
+        }
+        let (bytes, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(bytes)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, String> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.read_bytes(8)?.try_into().unwrap()))
+    }
+
+    pub fn read_field<T: FieldElement>(&mut self) -> Result<T, String> {
+        let len = T::zero().to_bytes_le().len();
+        Ok(T::from_bytes_le(self.read_bytes(len)?))
+    }
+
+    pub fn read_bits(&mut self, len: usize) -> Result<BitVec, String> {
+        let mut bits = BitVec::from_bytes(self.read_bytes(len.div_ceil(8))?);
+        bits.truncate(len);
+        Ok(bits)
+    }
+
+    pub fn read_cell_value<T: FieldElement>(&mut self) -> Result<CellValue<T>, String> {
+        match self.read_u8()? {
+            0 => Ok(CellValue::Unknown),
+            1 => Ok(CellValue::Known(self.read_field()?)),
+            2 => {
+                let mut mask = T::Integer::from(0);
+                for i in 0..integer_limbs::<T>() {
+                    mask |= T::Integer::from(self.read_u64()?) << (64 * i);
+                }
+                let min = self.read_field()?;
+                let max = self.read_field()?;
+                Ok(CellValue::RangeConstraint(
+                    RangeConstraint::from_mask_and_range(mask, min, max),
+                ))
+            }
+            tag => Err(format!("Invalid cell tag {tag} in witness snapshot.")),
+        }
+    }
+
+    /// Checks that all bytes have been read.
+    pub fn finish(self) -> Result<(), String> {
+        match self.bytes.len() {
+            0 => Ok(()),
+            len => Err(format!(
+                "{len} unexpected bytes at the end of witness snapshot."
+            )),
+        }
+    }
+}
+
+/// The number of 64 bit limbs of [FieldElement::Integer].
+fn integer_limbs<T: FieldElement>() -> usize {
+    T::Integer::NUM_BITS.div_ceil(64)
+}
@@ -0,0 +1,127 @@
+use std::collections::{BTreeMap, HashSet};
+
+use itertools::Itertools;
+use powdr_ast::analyzed::{AlgebraicExpression as Expression, Identity, PolyID};
+use powdr_number::{DegreeType, FieldElement};
+
+use super::{
+    data_structures::finalizable_data::FinalizableData,
+    processor::{LastRowPolicy, Processor, Provenance},
+    rows::{CellValue, Row, RowIndex, UnknownStrategy},
+    EvalError, FixedData, MutableState, QueryCallback, SerializedRows,
+};
+
+/// A witness of the main machine in which some cells are placeholders that are only
+/// filled in later, e.g. the constants of a machine that is instantiated several times
+/// (see [super::WitnessGenerator::generate_template]).
+#[derive(Clone, Debug)]
+pub struct WitnessTemplate {
+    /// The placeholder cells (column name and row) of each tag.
+    pub placeholders: BTreeMap<String, Vec<(String, DegreeType)>>,
+    /// The rows of the main machine, solved as far as possible without the placeholders.
+    pub(crate) rows: SerializedRows,
+}
+
+/// Solves all rows of the main machine, starting from the given rows (fresh ones if None),
+/// after setting the given cells (column, row and value).
+/// @returns the solved rows and the values of all witness cells, None where unknown.
+#[allow(clippy::type_complexity)]
+pub(crate) fn solve_main_machine<'a, 'b, T: FieldElement, Q: QueryCallback<T>>(
+    mutable_state: &mut MutableState<'a, 'b, T, Q>,
+    identities: &[&'a Identity<Expression<T>>],
+    fixed_data: &'a FixedData<'a, T>,
+    witness_cols: &HashSet<PolyID>,
+    rows: Option<&SerializedRows>,
+    cells: &[(PolyID, DegreeType, T)],
+) -> Result<(SerializedRows, BTreeMap<PolyID, Vec<Option<T>>>), EvalError<T>> {
+    let degree = fixed_data.degree as usize;
+    let row_offset = RowIndex::from_degree(0, fixed_data.degree);
+    // The row after the last row is stored separately. Updating a row requires its next
+    // row to be present, so there is one more row after that.
+    let data = FinalizableData::with_initial_rows_in_progress(
+        witness_cols,
+        (0..degree + 2).map(|i| Row::fresh(fixed_data, row_offset + i)),
+    );
+    let mut processor = Processor::new(row_offset, data, mutable_state, fixed_data, witness_cols);
+    if let Some(rows) = rows {
+        processor.restore_from_snapshot(rows)?;
+    }
+    for (poly_id, row, value) in cells {
+        processor.set_cells_if_unset(
+            *row as usize,
+            &[(*poly_id, *value)],
+            Provenance::Input,
+            || format!("placeholder {}", fixed_data.column_name(poly_id)),
+        )?;
+    }
+    let sorted_witness_cols = witness_cols.iter().sorted().copied().collect_vec();
+    solve_rows(
+        &mut processor,
+        identities,
+        &sorted_witness_cols,
+        degree,
+        fixed_data.options.last_row_policy == LastRowPolicy::Cyclic,
+    )?;
+
+    let values = sorted_witness_cols
+        .into_iter()
+        .map(|poly_id| {
+            let values = (0..degree)
+                .map(|row| processor.row(row)[&poly_id].value.clone().into())
+                .collect();
+            (poly_id, values)
+        })
+        .collect();
+    Ok((processor.witness_snapshot(), values))
+}
+
+/// Solves the rows `0..degree` of the processor until no more progress is made.
+/// The processor holds an additional row after the last row, which is kept in sync with
+/// the first row if `cyclic` is set. Rows are skipped if they and their next row are
+/// already known, so that instantiating a template only solves the cells that depend on
+/// the placeholders.
+fn solve_rows<'a, T: FieldElement, Q: QueryCallback<T>>(
+    processor: &mut Processor<'a, '_, '_, T, Q>,
+    identities: &[&'a Identity<Expression<T>>],
+    witness_cols: &[PolyID],
+    degree: usize,
+    cyclic: bool,
+) -> Result<(), EvalError<T>> {
+    let known_values = |processor: &Processor<'a, '_, '_, T, Q>, row_index: usize| {
+        witness_cols
+            .iter()
+            .filter_map(|poly_id| match processor.row(row_index)[poly_id].value {
+                CellValue::Known(value) => Some((*poly_id, value)),
+                _ => None,
+            })
+            .collect_vec()
+    };
+    loop {
+        let mut progress = false;
+        for row_index in 0..degree {
+            if known_values(processor, row_index).len() == witness_cols.len()
+                && known_values(processor, row_index + 1).len() == witness_cols.len()
+            {
+                continue;
+            }
+            for identity in identities {
+                progress |= processor
+                    .process_identity(row_index, identity, UnknownStrategy::Unknown)?
+                    .progress;
+            }
+            progress |= processor.process_queries(row_index)?;
+        }
+        if cyclic {
+            for (from, to) in [(0, degree), (degree, 0)] {
+                let values = known_values(processor, from);
+                progress |=
+                    processor.set_cells_if_unset(to, &values, Provenance::Machine, || {
+                        "first row after the last row".to_string()
+                    })?;
+            }
+        }
+        if !progress {
+            return Ok(());
+        }
+    }
+}
@@ -0,0 +1,72 @@
+//! Checks the log messages of witness generation with a logger of its own, which needs
+//! a separate test binary because only one logger can be installed per process.
+
+use std::sync::Mutex;
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use powdr_executor::{
+    constant_evaluator::generate,
+    witgen::{unused_query_callback, WitnessGenerator},
+};
+use powdr_number::GoldilocksField;
+use powdr_pil_analyzer::analyze_string;
+
+/// Records the target and the message of every log message.
+struct RecordingLogger(Mutex<Vec<(String, String)>>);
+
+impl Log for RecordingLogger {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        self.0
+            .lock()
+            .unwrap()
+            .push((record.target().to_string(), record.args().to_string()));
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: RecordingLogger = RecordingLogger(Mutex::new(vec![]));
+
+#[test]
+fn processor_messages_are_scoped_by_machine() {
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(LevelFilter::Trace);
+
+    let src = r#"
+        namespace Sub(8);
+            col fixed LATCH = [1]*;
+            col witness x, y;
+            y = x + 1;
+
+        namespace Main(8);
+            col fixed a(i) { i };
+            col witness r;
+            col fixed CALL = [1, 0]*;
+            (1 - CALL) * r = 0;
+            CALL {a, r} in Sub.LATCH {Sub.x, Sub.y};
+    "#;
+    let analyzed = analyze_string::<GoldilocksField>(src);
+    let constants = generate(&analyzed);
+    let query_callback = unused_query_callback();
+    WitnessGenerator::new(&analyzed, &constants, &query_callback).generate();
+    assert!(log::log_enabled!(Level::Trace));
+
+    let messages = LOGGER.0.lock().unwrap();
+    let prefix = "[Secondary machine 0: Sub (BlockMachine)] ";
+    let scoped = messages
+        .iter()
+        .filter(|(_, message)| message.starts_with(prefix))
+        .collect::<Vec<_>>();
+    assert!(scoped
+        .iter()
+        .any(|(_, message)| message.contains("Updates from:")));
+    // The messages keep the target of the module that logs them, so that per-module
+    // log filters still apply.
+    assert!(scoped
+        .iter()
+        .all(|(target, _)| target == "powdr_executor::witgen::processor"));
+}
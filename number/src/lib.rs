@@ -11,7 +11,7 @@ mod traits;
 
 pub use serialize::{
     buffered_write_file, read_polys_csv_file, read_polys_file, write_polys_csv_file,
-    write_polys_file, CsvRenderMode,
+    write_polys_file, write_polys_stream, CsvRenderMode,
 };
 
 pub use bn254::Bn254Field;
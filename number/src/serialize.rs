@@ -113,7 +113,7 @@ pub fn write_polys_file<F: FieldElement>(
     Ok(())
 }
 
-fn write_polys_stream<T: FieldElement>(
+pub fn write_polys_stream<T: FieldElement>(
     file: &mut impl Write,
     polys: &[(String, Vec<T>)],
 ) -> Result<(), io::Error> {
@@ -14,6 +14,10 @@ enum Query {
     Hint(fe),
     /// Query a prover input element by index and data id.
     DataIdentifier(int, int),
+    /// Generate the inverse of a field element to fill a witness column with.
+    /// By convention, the inverse of zero is a designated value (usually zero), so the
+    /// column should be accompanied by an "is zero" flag `is_zero = 1 - x * inv`.
+    Inverse(fe),
     /// This value is not (additionally) constrained by the query.
     None,
 }